@@ -0,0 +1,199 @@
+use crate::checkpoint;
+use crate::events::{Event, Observer, SystemState};
+use crate::validation::ConfigError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::process::Command;
+use std::str::FromStr;
+
+/// Measurement of the simulated system an alert watches.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Metric {
+    /// Number of requests waiting in the queues at the end of the tick.
+    QueueLen,
+    /// Number of workers busy at the end of the tick.
+    BusyWorkers,
+    /// Number of requests rejected during the tick.
+    Rejections,
+}
+
+/// Condition that fires an alert, as `metric>threshold` or `metric<threshold`, e.g. `queue_len>500`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertCondition {
+    metric: Metric,
+    above: bool,
+    threshold: f64,
+}
+
+impl FromStr for AlertCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (split, above) = match (s.find('>'), s.find('<')) {
+            (Some(index), None) => (index, true),
+            (None, Some(index)) => (index, false),
+            _ => {
+                return Err(format!(
+                    "Expected alert as metric>threshold or metric<threshold, got '{}'",
+                    s
+                ))
+            }
+        };
+        let metric = match s[..split].trim() {
+            "queue_len" => Metric::QueueLen,
+            "busy_workers" => Metric::BusyWorkers,
+            "rejections" => Metric::Rejections,
+            other => {
+                return Err(format!(
+                    "Unknown alert metric '{}', expected queue_len, busy_workers or rejections",
+                    other
+                ))
+            }
+        };
+        let threshold = s[split + 1..]
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid threshold in alert '{}'", s))?;
+        Ok(AlertCondition {
+            metric,
+            above,
+            threshold,
+        })
+    }
+}
+
+impl AlertCondition {
+    /// Value of the watched metric at the end of a tick, during which `rejections` requests were rejected.
+    pub fn value(&self, state: &SystemState, rejections: u64) -> f64 {
+        match self.metric {
+            Metric::QueueLen => state.queue_len as f64,
+            Metric::BusyWorkers => state.busy_workers as f64,
+            Metric::Rejections => rejections as f64,
+        }
+    }
+
+    pub fn holds(&self, value: f64) -> bool {
+        if self.above {
+            value > self.threshold
+        } else {
+            value < self.threshold
+        }
+    }
+}
+
+impl fmt::Display for AlertCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metric = match self.metric {
+            Metric::QueueLen => "queue_len",
+            Metric::BusyWorkers => "busy_workers",
+            Metric::Rejections => "rejections",
+        };
+        let comparison = if self.above { '>' } else { '<' };
+        write!(f, "{}{}{}", metric, comparison, self.threshold)
+    }
+}
+
+/// State of a single alert.
+#[derive(Serialize, Deserialize)]
+struct Alert {
+    condition: AlertCondition,
+    /// Last tick the condition held at.
+    last_held: Option<u64>,
+    first_tick: Option<u64>,
+    times_fired: u64,
+}
+
+/// Watches the simulated system for the configured conditions. An alert fires whenever its condition starts
+/// to hold, running the alert command (if there is one) to let external tools capture the moment.
+#[derive(Serialize, Deserialize)]
+pub struct Alerts {
+    alerts: Vec<Alert>,
+    command: Option<String>,
+    /// How long the condition has to be absent for the alert to fire again, so that a metric hovering
+    /// around the threshold doesn't fire it on every swing.
+    cooldown_ticks: u64,
+    rejections: u64,
+}
+
+impl Alerts {
+    pub fn new(
+        conditions: &[AlertCondition],
+        command: Option<String>,
+        cooldown_ticks: u64,
+    ) -> Alerts {
+        Alerts {
+            alerts: conditions
+                .iter()
+                .map(|condition| Alert {
+                    condition: condition.clone(),
+                    last_held: None,
+                    first_tick: None,
+                    times_fired: 0,
+                })
+                .collect(),
+            command,
+            cooldown_ticks,
+            rejections: 0,
+        }
+    }
+}
+
+impl Observer for Alerts {
+    fn on_event(&mut self, _tick: u64, event: &Event) {
+        if let Event::Rejected { .. } | Event::Shed { .. } = event {
+            self.rejections += 1;
+        }
+    }
+
+    fn on_tick_end(&mut self, tick: u64, state: &SystemState) {
+        for alert in self.alerts.iter_mut() {
+            let value = alert.condition.value(state, self.rejections);
+            if !alert.condition.holds(value) {
+                continue;
+            }
+            let resolved = alert
+                .last_held
+                .is_none_or(|last_held| tick - last_held > self.cooldown_ticks);
+            alert.last_held = Some(tick);
+            if resolved {
+                alert.first_tick.get_or_insert(tick);
+                alert.times_fired += 1;
+                if let Some(command) = self.command.as_ref() {
+                    // The simulation waits for the command, so that whatever it captures matches the tick.
+                    Command::new("sh")
+                        .arg("-c")
+                        .arg(command)
+                        .env("ALERT_CONDITION", alert.condition.to_string())
+                        .env("ALERT_TICK", tick.to_string())
+                        .env("ALERT_VALUE", value.to_string())
+                        .status()
+                        .unwrap_or_else(|e| {
+                            ConfigError::io("run alert command", command, e).exit()
+                        });
+                }
+            }
+        }
+        self.rejections = 0;
+    }
+
+    fn print_summary(&self) {
+        for alert in self.alerts.iter() {
+            match alert.first_tick {
+                Some(tick) => println!(
+                    "Alert {}: first fired at tick {}, fired {} times",
+                    alert.condition, tick, alert.times_fired
+                ),
+                None => println!("Alert {}: never fired", alert.condition),
+            }
+        }
+    }
+
+    fn checkpoint(&mut self) -> Result<Value, String> {
+        checkpoint::save(self)
+    }
+
+    fn resume(&mut self, state: Value) -> Result<(), String> {
+        checkpoint::restore(self, state)
+    }
+}
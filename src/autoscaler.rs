@@ -0,0 +1,84 @@
+use crate::worker::Worker;
+use crate::Opt;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+/// A simple reactive autoscaler. Whenever the queue grows past a threshold, it tries to add one more worker,
+/// up to the configured maximum. Not every scale up attempt is successful - just like in real life, a new
+/// worker may fail to start altogether, or take much longer than usual to start taking requests.
+#[derive(Serialize, Deserialize)]
+pub struct Autoscaler {
+    max_workers: usize,
+    queue_threshold: usize,
+    cooldown_ticks: u32,
+    startup_ticks: u32,
+    start_failure_probability: f64,
+    slow_start_probability: f64,
+    slow_start_multiplier: u32,
+    ticks_since_last_attempt: u32,
+    workers_added: u32,
+    workers_failed_to_start: u32,
+    workers_started_slow: u32,
+    rng: ChaCha12Rng,
+}
+
+impl Autoscaler {
+    pub fn new(opt: &Opt, seed: u64) -> Autoscaler {
+        Autoscaler {
+            max_workers: opt.autoscale_max_workers as usize,
+            queue_threshold: opt.autoscale_queue_threshold,
+            cooldown_ticks: opt.autoscale_cooldown,
+            startup_ticks: opt.worker_startup_ticks,
+            start_failure_probability: opt.worker_start_failure_probability,
+            slow_start_probability: opt.worker_slow_start_probability,
+            slow_start_multiplier: opt.worker_slow_start_multiplier,
+            ticks_since_last_attempt: opt.autoscale_cooldown,
+            workers_added: 0,
+            workers_failed_to_start: 0,
+            workers_started_slow: 0,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Spends one tick. If the queue is long enough and we are not in a cooldown period, attempts to add
+    /// a worker to the pool.
+    pub fn tick(&mut self, queue_len: usize, workers: &mut Vec<Worker>) {
+        if self.ticks_since_last_attempt < self.cooldown_ticks {
+            self.ticks_since_last_attempt += 1;
+            return;
+        }
+        if queue_len < self.queue_threshold || workers.len() >= self.max_workers {
+            return;
+        }
+
+        self.ticks_since_last_attempt = 0;
+        if self.rng.gen_bool(self.start_failure_probability) {
+            // The capacity we asked for never materializes. We will only find out about it after the
+            // cooldown, when the queue is still long.
+            self.workers_failed_to_start += 1;
+            return;
+        }
+
+        let mut startup_ticks = self.startup_ticks;
+        if self.rng.gen_bool(self.slow_start_probability) {
+            self.workers_started_slow += 1;
+            startup_ticks = startup_ticks.saturating_mul(self.slow_start_multiplier);
+        }
+
+        self.workers_added += 1;
+        workers.push(Worker::starting(startup_ticks));
+    }
+
+    /// Prints what the autoscaler did, naming its pool if specified.
+    pub fn print_summary(&self, pool: Option<&str>) {
+        println!(
+            "Autoscaler{} added {} workers ({} started slowly), {} failed to start.",
+            pool.map(|name| format!(" of pool {}", name))
+                .unwrap_or_default(),
+            self.workers_added,
+            self.workers_started_slow,
+            self.workers_failed_to_start
+        );
+    }
+}
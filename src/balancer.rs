@@ -0,0 +1,398 @@
+use crate::pool::Pool;
+use crate::simulation::{self, Summary};
+use crate::utilization;
+use crate::Opt;
+use rand::seq::index;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// Points each queue has on the consistent hashing ring, so that the keys are spread evenly over the queues.
+const VIRTUAL_NODES: usize = 100;
+
+/// How the load balancer picks the queue a request goes to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Routing {
+    /// Join the shortest queue: the one with the fewest requests waiting, preferring the ones with fewer busy
+    /// workers on a tie. Needs to know the length of every queue, which a real load balancer rarely does.
+    ShortestQueue,
+    /// The shortest of the specified number of queues picked at random, the same way as the shortest queue.
+    /// With just two choices, most of the benefit of joining the shortest queue, without knowing all of them.
+    Choices(usize),
+    /// Each queue in turn, regardless of its load.
+    RoundRobin,
+    /// A queue picked at random, regardless of its load.
+    Random,
+    /// The queue with the fewest requests outstanding, waiting or being processed. Unlike the length of the
+    /// queues, a load balancer knows this from the requests it sent and the responses it got back.
+    LeastConnections,
+    /// Sticky routing by the key of the request, consistently hashed onto the queues, so that all the requests
+    /// with the same key go to the same queue. Hot keys overload their queue however idle the others are.
+    ConsistentHash,
+}
+
+impl FromStr for Routing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "jsq" => Ok(Routing::ShortestQueue),
+            None if s == "round-robin" => Ok(Routing::RoundRobin),
+            None if s == "random" => Ok(Routing::Random),
+            None if s == "least-connections" => Ok(Routing::LeastConnections),
+            None if s == "hash" => Ok(Routing::ConsistentHash),
+            Some(("choices", choices)) => match choices.parse::<usize>() {
+                Ok(choices) if choices > 0 => Ok(Routing::Choices(choices)),
+                _ => Err(format!(
+                    "Invalid number of choices '{}', expected a positive integer",
+                    choices
+                )),
+            },
+            _ => Err(format!(
+                "Unknown routing '{}', expected jsq, choices:D, round-robin, random, least-connections or hash",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Routing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Routing::ShortestQueue => write!(f, "jsq"),
+            Routing::Choices(choices) => write!(f, "choices:{}", choices),
+            Routing::RoundRobin => write!(f, "round-robin"),
+            Routing::Random => write!(f, "random"),
+            Routing::LeastConnections => write!(f, "least-connections"),
+            Routing::ConsistentHash => write!(f, "hash"),
+        }
+    }
+}
+
+/// Load balancer in front of independent queues, each with its own share of the workers, routing every
+/// request to one of them. Unlike a single shared queue, a request can wait behind a slow one while a worker
+/// of another queue sits idle.
+#[derive(Serialize, Deserialize)]
+pub struct Balancer {
+    routing: Routing,
+    /// Queue the round robin goes to next.
+    next: usize,
+    /// Requests routed to each queue after the warm-up, and how many of them failed in the queue.
+    routed: Vec<u64>,
+    failed: Vec<u64>,
+    ring: Option<Ring>,
+}
+
+/// Consistent hashing ring of the queues.
+#[derive(Serialize, Deserialize)]
+struct Ring {
+    /// Points of the queues on the ring, by their hash. A key goes to the queue of the first point at or after
+    /// its own hash, wrapping around.
+    points: Vec<(u64, usize)>,
+}
+
+impl Ring {
+    fn new(queues: usize) -> Ring {
+        let mut points: Vec<(u64, usize)> = (0..queues)
+            .flat_map(|queue| (0..VIRTUAL_NODES).map(move |node| (hash((queue, node)), queue)))
+            .collect();
+        points.sort_unstable();
+        Ring { points }
+    }
+
+    /// Queue of a request with the specified key.
+    fn route(&self, key: u64) -> usize {
+        let key = hash(key);
+        let point = self.points.partition_point(|&(h, _)| h < key);
+        self.points[point % self.points.len()].1
+    }
+}
+
+fn hash<T: Hash>(value: T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Balancer {
+    /// Load balancer in front of the specified number of queues.
+    pub fn new(routing: Routing, queues: usize) -> Balancer {
+        Balancer {
+            routing,
+            next: 0,
+            routed: vec![0; queues],
+            failed: vec![0; queues],
+            ring: match routing {
+                Routing::ConsistentHash => Some(Ring::new(queues)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Picks the queue for a request with the specified key, counting it if `measured`. Ties are broken at
+    /// random, so that the first queues don't get more than their share.
+    pub fn route<R: Rng>(
+        &mut self,
+        pools: &[Pool],
+        key: u64,
+        measured: bool,
+        rng: &mut R,
+    ) -> usize {
+        let pool = match self.routing {
+            Routing::ShortestQueue => shortest(pools, (0..pools.len()).collect(), rng),
+            Routing::Choices(choices) => {
+                let sampled = index::sample(rng, pools.len(), choices).into_vec();
+                shortest(pools, sampled, rng)
+            }
+            Routing::RoundRobin => {
+                let pool = self.next;
+                self.next = (self.next + 1) % pools.len();
+                pool
+            }
+            Routing::Random => rng.gen_range(0..pools.len()),
+            Routing::LeastConnections => least_loaded(
+                pools,
+                (0..pools.len()).collect(),
+                |p| (p.queue_len() + p.busy_workers(), 0),
+                rng,
+            ),
+            Routing::ConsistentHash => self.ring.as_ref().unwrap().route(key),
+        };
+        if measured {
+            self.routed[pool] += 1;
+        }
+        pool
+    }
+
+    /// A request routed to the specified queue failed there: rejected, shed, abandoned or timed out.
+    pub fn failed(&mut self, pool: usize, measured: bool) {
+        if measured {
+            self.failed[pool] += 1;
+        }
+    }
+
+    /// Prints the share of the requests each queue got, their failure rate, and the utilization of its workers.
+    pub fn print_summary(&self, pools: &[Pool]) {
+        let total: u64 = self.routed.iter().sum();
+        for (index, pool) in pools.iter().enumerate() {
+            let routed = self.routed[index];
+            println!(
+                "Queue {}: {} requests ({:.2}%), failure rate: {:.2}%, utilization: {:.2}%",
+                pool.name(),
+                routed,
+                routed as f64 / total.max(1) as f64 * 100.0,
+                self.failed[index] as f64 / routed.max(1) as f64 * 100.0,
+                utilization::aggregate(&pool.worker_times()) * 100.0
+            );
+        }
+    }
+}
+
+/// The shortest of the specified queues: the one with the fewest requests waiting, then with the fewest busy
+/// workers, then picked at random.
+fn shortest<R: Rng>(pools: &[Pool], queues: Vec<usize>, rng: &mut R) -> usize {
+    least_loaded(pools, queues, |p| (p.queue_len(), p.busy_workers()), rng)
+}
+
+/// The least loaded of the specified queues, by the specified measure of the load, picked at random among the
+/// equally loaded ones.
+fn least_loaded<R: Rng>(
+    pools: &[Pool],
+    queues: Vec<usize>,
+    load: impl Fn(&Pool) -> (usize, usize),
+    rng: &mut R,
+) -> usize {
+    let least = queues.iter().map(|&i| load(&pools[i])).min().unwrap();
+    let candidates: Vec<usize> = queues
+        .into_iter()
+        .filter(|&i| load(&pools[i]) == least)
+        .collect();
+    candidates[rng.gen_range(0..candidates.len())]
+}
+
+/// Compares the outcome of the independent queues with that of a single queue shared by all of their workers,
+/// with a queue size of all of theirs. Reruns the simulation with the shared queue.
+pub fn print_comparison(opt: &Opt, summary: &Summary) {
+    let mut shared = opt.clone();
+    shared.queues = None;
+    shared.batching_report = false;
+    shared.explain = false;
+    shared.queue_distribution = false;
+    shared.littles_law = false;
+    shared.telemetry = false;
+    shared.tui = false;
+    shared.metrics_address = None;
+    shared.speed = None;
+    shared.time_series = None;
+    shared.snapshots = None;
+    shared.event_log = None;
+    shared.chrome_trace = None;
+    shared.checkpoint_every = None;
+    shared.resume = false;
+    shared.html_report = None;
+    shared.alerts.clear();
+    shared.service_time_caps.clear();
+    let shared_summary = simulation::run(&shared);
+
+    println!(
+        "{} queues ({}): failure rate {:.2}%, latency: mean={:.2} {}",
+        opt.queues.unwrap_or(1),
+        opt.routing,
+        summary.stats.failure_rate(),
+        summary.stats.latency.mean(),
+        summary.stats.latency.format_percentiles()
+    );
+    println!(
+        "Single shared queue: failure rate {:.2}%, latency: mean={:.2} {}",
+        shared_summary.stats.failure_rate(),
+        shared_summary.stats.latency.mean(),
+        shared_summary.stats.latency.format_percentiles()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::PoolSpec;
+    use crate::queue::{self, Discipline};
+    use crate::request::Request;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha12Rng;
+
+    /// Pools of a single worker each, with the specified numbers of requests in them: the first one being
+    /// processed, and the others waiting in the queue.
+    fn pools(requests: &[usize]) -> Vec<Pool> {
+        requests
+            .iter()
+            .enumerate()
+            .map(|(index, &requests)| {
+                let spec = PoolSpec {
+                    name: index.to_string(),
+                    workers: 1,
+                    queue_size: 10,
+                    classes: Vec::new(),
+                };
+                let queue = queue::new(Discipline::Fifo, 10, ChaCha12Rng::seed_from_u64(0));
+                let mut pool = Pool::new(&spec, queue, false, false, None, None, None);
+                for _ in 0..requests {
+                    assert!(pool.admit(Request::new(10, 100, 0, 0)));
+                }
+                pool
+            })
+            .collect()
+    }
+
+    fn routes(routing: Routing, pools: &[Pool], requests: usize) -> Vec<usize> {
+        let mut balancer = Balancer::new(routing, pools.len());
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        (0..requests)
+            .map(|key| balancer.route(pools, key as u64, true, &mut rng))
+            .collect()
+    }
+
+    #[test]
+    fn routings_parse_back_from_their_names() {
+        for name in [
+            "jsq",
+            "choices:2",
+            "round-robin",
+            "random",
+            "least-connections",
+            "hash",
+        ] {
+            assert_eq!(name.parse::<Routing>().unwrap().to_string(), name);
+        }
+        assert!("choices:0".parse::<Routing>().is_err());
+        assert!("shortest".parse::<Routing>().is_err());
+    }
+
+    #[test]
+    fn round_robin_takes_the_queues_in_turn() {
+        assert_eq!(
+            routes(Routing::RoundRobin, &pools(&[5, 0, 3]), 7),
+            [0, 1, 2, 0, 1, 2, 0]
+        );
+    }
+
+    #[test]
+    fn shortest_queue_breaks_ties_by_the_busy_workers_then_at_random() {
+        // Queues of 2, 0, 0 and 0 requests, the second with its worker busy.
+        let pools = pools(&[3, 1, 0, 0]);
+        let routed = routes(Routing::ShortestQueue, &pools, 100);
+        assert!(routed.iter().all(|&pool| pool == 2 || pool == 3));
+        assert!(routed.contains(&2) && routed.contains(&3));
+    }
+
+    #[test]
+    fn choices_of_every_queue_join_the_shortest_one() {
+        let pools = pools(&[3, 2, 4, 1, 3]);
+        assert!(routes(Routing::Choices(5), &pools, 100)
+            .iter()
+            .all(|&pool| pool == 3));
+        // With two choices, the longest queue never gets a request, since the other choice is always shorter.
+        assert!(!routes(Routing::Choices(2), &pools, 100).contains(&2));
+    }
+
+    #[test]
+    fn least_connections_counts_the_requests_being_processed() {
+        // Same queue length, but the last one has no request being processed.
+        let pools = pools(&[1, 2, 1, 0]);
+        assert!(routes(Routing::LeastConnections, &pools, 100)
+            .iter()
+            .all(|&pool| pool == 3));
+    }
+
+    #[test]
+    fn consistent_hashing_sends_a_key_to_the_same_queue_every_time() {
+        let idle = pools(&[0; 4]);
+        let first = routes(Routing::ConsistentHash, &idle, 1000);
+        assert_eq!(first, routes(Routing::ConsistentHash, &idle, 1000));
+        // Regardless of how loaded the queues are.
+        assert_eq!(
+            first,
+            routes(Routing::ConsistentHash, &pools(&[5, 0, 9, 2]), 1000)
+        );
+    }
+
+    #[test]
+    fn consistent_hashing_spreads_the_keys_evenly() {
+        let routed = routes(Routing::ConsistentHash, &pools(&[0; 4]), 10000);
+        for pool in 0..4 {
+            let share = routed.iter().filter(|&&p| p == pool).count() as f64 / 10000.0;
+            assert!((0.15..0.35).contains(&share), "queue {}: {}", pool, share);
+        }
+    }
+
+    #[test]
+    fn consistent_hashing_only_moves_keys_to_an_added_queue() {
+        let before = Ring::new(4);
+        let after = Ring::new(5);
+        let mut moved = 0;
+        for key in 0..10000 {
+            let (from, to) = (before.route(key), after.route(key));
+            if from != to {
+                assert_eq!(to, 4, "key {} moved from {} to {}", key, from, to);
+                moved += 1;
+            }
+        }
+        // About a fifth of the keys, the new queue's share.
+        assert!((1000..3000).contains(&moved), "{}", moved);
+    }
+
+    #[test]
+    fn only_measured_requests_are_counted() {
+        let pools = pools(&[0, 0]);
+        let mut balancer = Balancer::new(Routing::RoundRobin, 2);
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        for measured in [false, true, true, false, true] {
+            balancer.route(&pools, 0, measured, &mut rng);
+        }
+        balancer.failed(1, true);
+        balancer.failed(1, false);
+        assert_eq!(balancer.routed, [2, 1]);
+        assert_eq!(balancer.failed, [0, 1]);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::simulation::{self, Summary};
+use crate::Opt;
+
+/// Share of the latency attributable to tick batching above which the tick resolution is worth refining.
+const ARTIFACT_WARNING_PERCENT: f64 = 5.0;
+
+/// Reports how much of the measured latency is an artifact of the requests arriving during a tick all arriving
+/// at its start. Reruns the simulation with the other arrival model, and compares the results.
+pub fn print_report(opt: &Opt, summary: &Summary) {
+    let mut other = opt.clone();
+    other.sub_tick_arrivals = !opt.sub_tick_arrivals;
+    other.batching_report = false;
+    other.explain = false;
+    other.queue_distribution = false;
+    other.littles_law = false;
+    other.telemetry = false;
+    other.tui = false;
+    other.metrics_address = None;
+    other.speed = None;
+    other.time_series = None;
+    other.snapshots = None;
+    other.event_log = None;
+    other.chrome_trace = None;
+    other.checkpoint_every = None;
+    other.resume = false;
+    other.alerts.clear();
+    let other_summary = simulation::run(&other);
+    let (batched, spread) = if opt.sub_tick_arrivals {
+        (&other_summary, summary)
+    } else {
+        (summary, &other_summary)
+    };
+
+    let batched_latency = batched.stats.latency.mean();
+    let spread_latency = spread.stats.latency.mean();
+    let artifact = if batched_latency > 0.0 {
+        (batched_latency - spread_latency) / batched_latency * 100.0
+    } else {
+        0.0
+    };
+    println!(
+        "Tick batching: mean latency {:.2} with arrivals at the start of the tick, {:.2} with arrivals spread \
+         over the tick ({:.1}% of the latency is a batching artifact)",
+        batched_latency, spread_latency, artifact
+    );
+    println!(
+        "Tick batching: failure rate {:.2}% with arrivals at the start of the tick, {:.2}% with arrivals spread \
+         over the tick",
+        batched.stats.failure_rate(),
+        spread.stats.failure_rate()
+    );
+    if artifact > ARTIFACT_WARNING_PERCENT {
+        println!(
+            "Tick batching: the tick is coarse compared to the request latency, consider scaling the latency, \
+             timeout and simulation time up (and the arrival rate down) for a finer resolution"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Cache in front of the workers. A hit is answered by the cache itself in a small fixed time, without
+/// taking a worker, so the workers only see the misses. A cold cache, at the start of the run or after a
+/// flush, sends all of the load to the workers, which may not cope with it at the hit ratio they are sized for.
+#[derive(Serialize, Deserialize)]
+pub struct Cache {
+    model: Model,
+    /// Ticks the flushes empty the cache at, making it start warming up again.
+    flushes: Vec<u64>,
+    /// Lookups and hits after the warm-up of the simulation, in total and during the current window.
+    lookups: u64,
+    hits: u64,
+    window_lookups: u64,
+    window_hits: u64,
+    /// Ticks over which the window hit ratio is measured, and the lowest one any window saw.
+    window: u64,
+    lowest_window_hit_ratio: Option<f64>,
+}
+
+/// What decides whether a lookup hits.
+#[derive(Serialize, Deserialize)]
+enum Model {
+    /// Hits drawn at random with the specified hit ratio, ramping up to it from zero over the specified
+    /// number of ticks after the cache was last emptied.
+    HitRatio {
+        hit_ratio: f64,
+        warmup_ticks: u64,
+        warm_from: u64,
+    },
+    /// The keys of the most recently answered requests, up to the specified number of them, evicting the
+    /// least recently used one. Warms up on its own, as the misses fill it.
+    Lru {
+        capacity: usize,
+        /// When every key in the cache was last used, and the keys by that time.
+        last_used: HashMap<u64, u64>,
+        by_last_use: BTreeMap<u64, u64>,
+        /// Counts the uses, for the order of the keys.
+        uses: u64,
+    },
+}
+
+impl Cache {
+    /// Cache with hits drawn at random, ramping up to the specified hit ratio over the warm-up ticks.
+    pub fn with_hit_ratio(
+        hit_ratio: f64,
+        warmup_ticks: u64,
+        flushes: &[u64],
+        window: u64,
+    ) -> Cache {
+        Cache::new(
+            Model::HitRatio {
+                hit_ratio,
+                warmup_ticks,
+                warm_from: 0,
+            },
+            flushes,
+            window,
+        )
+    }
+
+    /// Cache of the specified number of keys, evicting the least recently used one.
+    pub fn with_capacity(capacity: usize, flushes: &[u64], window: u64) -> Cache {
+        Cache::new(
+            Model::Lru {
+                capacity,
+                last_used: HashMap::new(),
+                by_last_use: BTreeMap::new(),
+                uses: 0,
+            },
+            flushes,
+            window,
+        )
+    }
+
+    fn new(model: Model, flushes: &[u64], window: u64) -> Cache {
+        Cache {
+            model,
+            flushes: flushes.to_vec(),
+            lookups: 0,
+            hits: 0,
+            window_lookups: 0,
+            window_hits: 0,
+            window,
+            lowest_window_hit_ratio: None,
+        }
+    }
+
+    /// Empties the cache if a flush is due at the specified tick, and closes the window of the hit ratio if it
+    /// just ended.
+    pub fn tick(&mut self, tick: u64, measured: bool) {
+        if self.flushes.contains(&tick) {
+            match &mut self.model {
+                Model::HitRatio { warm_from, .. } => *warm_from = tick,
+                Model::Lru {
+                    last_used,
+                    by_last_use,
+                    ..
+                } => {
+                    last_used.clear();
+                    by_last_use.clear();
+                }
+            }
+        }
+        if measured && tick.is_multiple_of(self.window) && self.window_lookups > 0 {
+            let hit_ratio = self.window_hits as f64 / self.window_lookups as f64;
+            if self
+                .lowest_window_hit_ratio
+                .is_none_or(|lowest| hit_ratio < lowest)
+            {
+                self.lowest_window_hit_ratio = Some(hit_ratio);
+            }
+            self.window_lookups = 0;
+            self.window_hits = 0;
+        }
+    }
+
+    /// Looks up the request with the specified key at the specified tick. Returns whether the cache has the
+    /// response.
+    pub fn lookup<R: Rng>(&mut self, key: u64, tick: u64, measured: bool, rng: &mut R) -> bool {
+        let hit = match &mut self.model {
+            Model::HitRatio {
+                hit_ratio,
+                warmup_ticks,
+                warm_from,
+            } => {
+                let warmth = if *warmup_ticks == 0 {
+                    1.0
+                } else {
+                    ((tick - *warm_from) as f64 / *warmup_ticks as f64).min(1.0)
+                };
+                rng.gen_bool(*hit_ratio * warmth)
+            }
+            Model::Lru {
+                last_used,
+                by_last_use,
+                uses,
+                ..
+            } => match last_used.get_mut(&key) {
+                Some(last_use) => {
+                    by_last_use.remove(last_use);
+                    *uses += 1;
+                    *last_use = *uses;
+                    by_last_use.insert(*uses, key);
+                    true
+                }
+                None => false,
+            },
+        };
+        if measured {
+            self.lookups += 1;
+            self.window_lookups += 1;
+            self.hits += hit as u64;
+            self.window_hits += hit as u64;
+        }
+        hit
+    }
+
+    /// Stores the response to a request with the specified key that missed, once a worker answered it.
+    pub fn fill(&mut self, key: u64) {
+        if let Model::Lru {
+            capacity,
+            last_used,
+            by_last_use,
+            uses,
+        } = &mut self.model
+        {
+            if last_used.contains_key(&key) {
+                return;
+            }
+            if last_used.len() == *capacity {
+                if let Some((_, evicted)) = by_last_use.pop_first() {
+                    last_used.remove(&evicted);
+                }
+            }
+            *uses += 1;
+            last_used.insert(key, *uses);
+            by_last_use.insert(*uses, key);
+        }
+    }
+
+    pub fn print_summary(&self) {
+        if self.lookups == 0 {
+            return;
+        }
+
+        print!(
+            "Cache: {} hits of {} lookups ({:.2}%)",
+            self.hits,
+            self.lookups,
+            self.hits as f64 / self.lookups as f64 * 100.0
+        );
+        match self.lowest_window_hit_ratio {
+            Some(lowest) => println!(
+                ", lowest over {} ticks: {:.2}%",
+                self.window,
+                lowest * 100.0
+            ),
+            None => println!(),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::simulation::{self, Stats};
+use crate::validation::{self, ConfigError};
+use crate::Opt;
+use rand::{thread_rng, Rng};
+use structopt::StructOpt;
+
+/// Relative precision the maximum sustainable arrival rate is searched to.
+const PRECISION: f64 = 0.01;
+
+/// Highest multiple of the nominal capacity of the workers the search tries.
+const MAX_MULTIPLIER: f64 = 64.0;
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct CapacityOpt {
+    /// Highest failure rate, in percent, the arrival rate is still considered sustainable at.
+    #[structopt(long = "max_failure_rate", default_value = "1")]
+    max_failure_rate: f64,
+
+    /// Length of the simulations the arrival rate is searched with. Defaults to the simulation time, up to
+    /// 200000 ticks.
+    #[structopt(long = "ticks")]
+    ticks: Option<u32>,
+}
+
+/// Finds the maximum sustainable arrival rate of the configured system: the knee past which the failure rate
+/// exceeds the threshold. Reruns the simulation with the same seed at every arrival rate tried, bisecting
+/// between no load and twice the nominal capacity of the workers, the number of workers over the mean service
+/// time.
+pub fn run(opt: &Opt, capacity: &CapacityOpt) {
+    if opt.arrival_trace.is_some()
+        || opt.generator.is_some()
+        || opt.clients.is_some()
+        || !opt.arrival_schedule().unwrap_or_default().is_empty()
+    {
+        ConfigError::Conflict {
+            problem: "Capacity search requires a constant arrival rate",
+            fix: "Leave out the arrival trace, generator, closed-loop clients and arrival schedule",
+        }
+        .exit();
+    }
+    if capacity.max_failure_rate <= 0.0 || capacity.max_failure_rate >= 100.0 {
+        ConfigError::OutOfRange {
+            parameter: "max_failure_rate",
+            expected: "a percentage greater than 0 and lower than 100",
+        }
+        .exit();
+    }
+    let seed = opt.seed.unwrap_or_else(|| thread_rng().gen());
+    let ticks = capacity.ticks.unwrap_or(opt.simulation_ticks.min(200_000));
+    let sustains = |rate: f64| {
+        let mut loaded = opt.clone();
+        loaded.request_arrival_rate = Some(rate);
+        loaded.seed = Some(seed);
+        loaded.simulation_ticks = ticks;
+        loaded.warmup_ticks = loaded.warmup_ticks.min(ticks as u64 / 2);
+        loaded.headroom_objectives.clear();
+        loaded.service_time_caps.clear();
+        loaded.batching_report = false;
+        loaded.explain = false;
+        loaded.queue_distribution = false;
+        loaded.littles_law = false;
+        loaded.telemetry = false;
+        loaded.tui = false;
+        loaded.metrics_address = None;
+        loaded.speed = None;
+        loaded.progress = false;
+        loaded.time_series = None;
+        loaded.snapshots = None;
+        loaded.event_log = None;
+        loaded.chrome_trace = None;
+        loaded.html_report = None;
+        loaded.plot = None;
+        loaded.alerts.clear();
+        validation::validate_or_exit(&loaded);
+        let stats = simulation::run(&loaded).stats;
+        let sustained = stats.failure_rate() <= capacity.max_failure_rate;
+        print_step(rate, &stats, sustained);
+        sustained
+    };
+
+    let workers: u16 = opt.pools().iter().map(|p| p.workers).sum();
+    let mean_service_time = opt
+        .service_time()
+        .unwrap_or_else(|e| ConfigError::Invalid(e).exit())
+        .mean();
+    let nominal = workers as f64 / mean_service_time;
+    let (mut low, mut high) = (0.0, 2.0 * nominal);
+    while sustains(high) {
+        low = high;
+        high *= 2.0;
+        if high > MAX_MULTIPLIER * nominal {
+            println!(
+                "Max sustainable arrival rate: over {:.4} requests/tick, {:.0}x the nominal capacity",
+                low,
+                low / nominal
+            );
+            return;
+        }
+    }
+    while (high - low) / high > PRECISION {
+        let middle = (low + high) / 2.0;
+        if sustains(middle) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    println!(
+        "Max sustainable arrival rate: {:.4} requests/tick ({:.2}% of the nominal capacity of {} workers) with a \
+         failure rate of at most {}%",
+        low,
+        low / nominal * 100.0,
+        workers,
+        capacity.max_failure_rate
+    );
+}
+
+fn print_step(rate: f64, stats: &Stats, sustained: bool) {
+    println!(
+        "Arrival rate {:.4}: failure rate {:.2}%, latency: mean={:.2} {} ({})",
+        rate,
+        stats.failure_rate(),
+        stats.latency.mean(),
+        stats.latency.format_percentiles(),
+        if sustained {
+            "sustained"
+        } else {
+            "not sustained"
+        }
+    );
+}
@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Fault injected into the system for a window of the run.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Fault {
+    /// Kill the specified number of workers of every pool. The requests they were processing fail.
+    Kill(u16),
+    /// Fail the specified fraction of the database queries: the ones in flight when the outage begins, and the
+    /// ones sent during it. The database returns an error right away, which the server passes on to its client.
+    DatabaseOutage(f64),
+    /// Make the database queries take the specified number of times longer.
+    DatabaseSlowdown(f64),
+}
+
+impl Fault {
+    /// Whether the fault is of the database, the dependency downstream of the pools.
+    pub fn is_database(&self) -> bool {
+        matches!(self, Fault::DatabaseOutage(_) | Fault::DatabaseSlowdown(_))
+    }
+}
+
+/// Fault of a chaos schedule, as `fault:start[:end]`, e.g. `kill:3:200000:260000` to kill 3 workers of every
+/// pool at tick 200000 and restore them at tick 260000, or `db_outage:1:200000:260000` to fail every database
+/// query in the meantime. Without an end, the fault lasts until the end of the run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChaosSpec {
+    fault: Fault,
+    start: u64,
+    end: Option<u64>,
+}
+
+impl FromStr for ChaosSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let (fault, window) = match parts.as_slice() {
+            ["kill", workers, window @ ..] if !window.is_empty() && window.len() <= 2 => {
+                let workers: u16 = workers.parse().ok().filter(|&w| w > 0).ok_or_else(|| {
+                    format!(
+                        "Invalid chaos '{}', expected a number of workers greater than 0",
+                        s
+                    )
+                })?;
+                (Fault::Kill(workers), window)
+            }
+            ["db_outage", fraction, window @ ..] if !window.is_empty() && window.len() <= 2 => {
+                let fraction: f64 = fraction
+                    .parse()
+                    .ok()
+                    .filter(|&f| f > 0.0 && f <= 1.0)
+                    .ok_or_else(|| {
+                        format!(
+                            "Invalid chaos '{}', expected a fraction of the queries greater than 0 and at most 1",
+                            s
+                        )
+                    })?;
+                (Fault::DatabaseOutage(fraction), window)
+            }
+            ["db_slowdown", multiplier, window @ ..] if !window.is_empty() && window.len() <= 2 => {
+                let multiplier: f64 =
+                    multiplier
+                        .parse()
+                        .ok()
+                        .filter(|&m| m >= 1.0)
+                        .ok_or_else(|| {
+                            format!(
+                                "Invalid chaos '{}', expected a slowdown multiplier of at least 1",
+                                s
+                            )
+                        })?;
+                (Fault::DatabaseSlowdown(multiplier), window)
+            }
+            _ => {
+                return Err(format!(
+                    "Expected chaos as kill:WORKERS:START[:END], db_outage:FRACTION:START[:END] or \
+                     db_slowdown:MULTIPLIER:START[:END], got '{}'",
+                    s
+                ))
+            }
+        };
+        let tick = |value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid tick '{}' in chaos '{}'", value, s))
+        };
+        let start = tick(window[0])?;
+        let end = window.get(1).map(|end| tick(end)).transpose()?;
+        if end.is_some_and(|end| end <= start) {
+            return Err(format!(
+                "Chaos '{}' has to end after it starts, at tick {}",
+                s, start
+            ));
+        }
+
+        Ok(ChaosSpec { fault, start, end })
+    }
+}
+
+impl ChaosSpec {
+    pub fn fault(&self) -> Fault {
+        self.fault
+    }
+}
+
+/// Change to the system a chaos schedule makes at the start of a tick.
+#[derive(Debug, Clone, Copy)]
+pub enum ChaosAction {
+    /// The fault with the specified index in the schedule begins.
+    Begin(usize, Fault),
+    /// The fault is over.
+    End(Fault),
+}
+
+/// Faults injected on a fixed schedule, for scripting capacity loss experiments that play out the same way
+/// every run, unlike the stalls.
+#[derive(Serialize, Deserialize)]
+pub struct Chaos {
+    specs: Vec<ChaosSpec>,
+    /// Whether each fault is in effect.
+    active: Vec<bool>,
+    /// Requests every fault failed, after the warm-up.
+    failed_requests: Vec<u64>,
+}
+
+impl Chaos {
+    pub fn new(specs: &[ChaosSpec]) -> Chaos {
+        Chaos {
+            specs: specs.to_vec(),
+            active: vec![false; specs.len()],
+            failed_requests: vec![0; specs.len()],
+        }
+    }
+
+    /// Faults beginning and ending at the specified tick, the ending ones first, so that a fault following
+    /// another one right away doesn't overlap with it.
+    pub fn due(&mut self, tick: u64) -> Vec<ChaosAction> {
+        let mut actions = Vec::new();
+        for (index, spec) in self.specs.iter().enumerate() {
+            if spec.end == Some(tick) {
+                self.active[index] = false;
+                actions.push(ChaosAction::End(spec.fault));
+            }
+        }
+        for (index, spec) in self.specs.iter().enumerate() {
+            if spec.start == tick {
+                self.active[index] = true;
+                actions.push(ChaosAction::Begin(index, spec.fault));
+            }
+        }
+        actions
+    }
+
+    /// The database outage in effect, if any, with its index in the schedule and the fraction of the queries
+    /// it fails. The first one wins if several overlap.
+    pub fn query_failure(&self) -> Option<(usize, f64)> {
+        self.specs
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| self.active[index])
+            .find_map(|(index, spec)| match spec.fault {
+                Fault::DatabaseOutage(fraction) => Some((index, fraction)),
+                _ => None,
+            })
+    }
+
+    /// How many times longer the database queries take, with the slowdowns in effect.
+    pub fn query_slowdown(&self) -> f64 {
+        self.specs
+            .iter()
+            .zip(self.active.iter())
+            .filter(|&(_, &active)| active)
+            .map(|(spec, _)| match spec.fault {
+                Fault::DatabaseSlowdown(multiplier) => multiplier,
+                _ => 1.0,
+            })
+            .product()
+    }
+
+    /// Counts the requests the fault with the specified index failed, if `measured`.
+    pub fn failed(&mut self, index: usize, requests: usize, measured: bool) {
+        if measured {
+            self.failed_requests[index] += requests as u64;
+        }
+    }
+
+    pub fn print_summary(&self) {
+        for (spec, failed_requests) in self.specs.iter().zip(self.failed_requests.iter()) {
+            let window = match spec.end {
+                Some(end) => format!("from tick {} to {}", spec.start, end),
+                None => format!("from tick {} on", spec.start),
+            };
+            match spec.fault {
+                Fault::Kill(workers) => println!(
+                    "Chaos: {} workers of every pool killed {}, failing {} requests in progress",
+                    workers, window, failed_requests
+                ),
+                Fault::DatabaseOutage(fraction) => println!(
+                    "Chaos: {:.0}% of the database queries failing {}, failing {} requests",
+                    fraction * 100.0,
+                    window,
+                    failed_requests
+                ),
+                Fault::DatabaseSlowdown(multiplier) => {
+                    println!("Chaos: database queries {}x slower {}", multiplier, window)
+                }
+            }
+        }
+    }
+}
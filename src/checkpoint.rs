@@ -0,0 +1,299 @@
+use crate::eventdriven::Engine;
+use crate::simulation::SimulationState;
+use crate::Opt;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+
+/// Full state of a simulation at the end of a tick, for resuming a long run where it left off. Resuming continues
+/// with the same state of the random number streams, so the resumed run ends up exactly where the uninterrupted one
+/// would have.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Configuration of the run, so that a checkpoint can't be resumed with different options by accident.
+    config: String,
+    /// Tick to resume at.
+    pub next_tick: u64,
+    pub state: SimulationState,
+}
+
+impl Checkpoint {
+    pub fn new(opt: &Opt, next_tick: u64, state: SimulationState) -> Checkpoint {
+        Checkpoint {
+            config: config(opt),
+            next_tick,
+            state,
+        }
+    }
+
+    /// Writes the checkpoint to a temporary file first, and then moves it over the previous one, so that a crash
+    /// while writing leaves the previous checkpoint intact.
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        let temporary = format!("{}.tmp", path);
+        let file = File::create(&temporary).map_err(|e| e.to_string())?;
+        serde_json::to_writer(BufWriter::new(file), self).map_err(|e| e.to_string())?;
+        fs::rename(&temporary, path).map_err(|e| e.to_string())
+    }
+
+    /// Reads a checkpoint written by a run with the same options, except for the simulation time, which a resumed
+    /// run can extend.
+    pub fn read(path: &str, opt: &Opt) -> Result<Checkpoint, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let checkpoint: Checkpoint =
+            serde_json::from_reader(BufReader::new(file)).map_err(|e| e.to_string())?;
+        if checkpoint.config != config(opt) {
+            return Err("the checkpoint was written by a run with different options".to_string());
+        }
+        Ok(checkpoint)
+    }
+}
+
+/// Options of the run, apart from the ones that can differ between the run that wrote the checkpoint and the
+/// one resuming it.
+fn config(opt: &Opt) -> String {
+    let mut opt = opt.clone();
+    opt.checkpoint_every = None;
+    opt.resume = false;
+    opt.simulation_ticks = 0;
+    opt.seed = None;
+    opt.arrival_seed = None;
+    opt.service_seed = None;
+    opt.retry_seed = None;
+    opt.failure_seed = None;
+    opt.progress = false;
+    format!("{:?}", opt)
+}
+
+/// State of an observer or a retry policy, for a checkpoint, when all of its fields are either state or settings
+/// the resumed run has the same of.
+pub fn save<T: Serialize>(state: &T) -> Result<Value, String> {
+    serde_json::to_value(state).map_err(|e| e.to_string())
+}
+
+/// Puts back the state an observer or a retry policy saved with `save`.
+pub fn restore<T: DeserializeOwned>(target: &mut T, state: Value) -> Result<(), String> {
+    *target = serde_json::from_value(state).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// First configured feature whose state the checkpoints don't cover, if any. The processes of the arrival
+/// generators and of the external policies keep their state to themselves.
+pub fn unsupported_feature(opt: &Opt) -> Option<&'static str> {
+    let features = [
+        (opt.engine == Engine::Event, "the event engine"),
+        (opt.generator.is_some(), "arrival generators"),
+        (opt.policy.is_some(), "external policies"),
+    ];
+    features
+        .iter()
+        .find(|(configured, _)| *configured)
+        .map(|(_, feature)| *feature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation;
+    use crate::validation;
+    use structopt::StructOpt;
+
+    /// Options of a scenario, which the checkpoints have to support.
+    fn opt(args: &str) -> Opt {
+        let opt =
+            Opt::from_iter(std::iter::once("queueingsimulator").chain(args.split_whitespace()));
+        validation::validate(&opt).unwrap_or_else(|e| panic!("{}: {}", args, e));
+        opt
+    }
+
+    fn checkpoint_file(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!(
+                "queueingsimulator-{}-{}.json",
+                name,
+                std::process::id()
+            ))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Runs the scenario for the full simulation time, and again in two parts, resuming the second from the last
+    /// checkpoint of the first, and checks that both end up with the same statistics.
+    fn assert_resumes_deterministically(name: &str, scenario: &str) {
+        let path = checkpoint_file(name);
+        let uninterrupted = simulation::run(&opt(&format!("{} --simulation_time 60000", scenario)));
+        simulation::run(&opt(&format!(
+            "{} --simulation_time 35000 --checkpoint_every 10000 --checkpoint_file {}",
+            scenario, path
+        )));
+        let resumed = simulation::run(&opt(&format!(
+            "{} --simulation_time 60000 --checkpoint_file {} --resume",
+            scenario, path
+        )));
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(resumed.ticks, uninterrupted.ticks);
+        assert_eq!(
+            serde_json::to_string(&resumed.stats).unwrap(),
+            serde_json::to_string(&uninterrupted.stats).unwrap(),
+            "{}",
+            scenario
+        );
+    }
+
+    #[test]
+    fn resumed_run_ends_where_the_uninterrupted_one_does() {
+        assert_resumes_deterministically("spike", "--seed 3 -r 0.12 --simulate_spike");
+    }
+
+    #[test]
+    fn resumed_run_with_requests_in_the_queue_ends_where_the_uninterrupted_one_does() {
+        assert_resumes_deterministically(
+            "lifo",
+            "--seed 5 -r 0.21 --discipline lifo --class api:0.7 --class batch:0.3 --queue_size 5000",
+        );
+    }
+
+    #[test]
+    fn resumed_run_with_bulkheads_and_retry_policies_ends_where_the_uninterrupted_one_does() {
+        assert_resumes_deterministically(
+            "bulkheads",
+            "--seed 3 -r 0.12 --simulate_spike --class a:1:300 --class b:1 --pool p1:2:20:a --pool p2:2:20:b \
+             --retry_policy a:budget:0.2 --retry_policy b:decorrelated:10:1000 --retry_after 50 \
+             --discipline_switch 20000:lifo --discipline_switch 40000:fifo --timeout_distribution exponential",
+        );
+    }
+
+    #[test]
+    fn resumed_run_with_routing_and_regions_ends_where_the_uninterrupted_one_does() {
+        assert_resumes_deterministically(
+            "queues",
+            "--seed 3 -r 0.12 --simulate_spike --queues 4 --routing choices:2",
+        );
+        assert_resumes_deterministically(
+            "regions",
+            "--seed 3 -r 0.12 --simulate_spike --region east:2:20:0.7 --region west:2:20:0.3 --failover rejected \
+             --cross_region_latency 20",
+        );
+    }
+
+    #[test]
+    fn resumed_run_with_admission_control_ends_where_the_uninterrupted_one_does() {
+        assert_resumes_deterministically(
+            "admission",
+            "--seed 3 -r 0.12 --simulate_spike --class a:1 --class b:1 --quota a:rate:0.03 --quota b:concurrency:3 \
+             --shed_threshold b:0.5 --late_shedding_interval 100 --brownout_queue_length 10 \
+             --balking_queue_length 30 --queue_timeout 500 --patience 300",
+        );
+    }
+
+    #[test]
+    fn resumed_run_with_backends_ends_where_the_uninterrupted_one_does() {
+        assert_resumes_deterministically(
+            "backends",
+            "--seed 3 -r 0.12 --report_every 100 --cache_size 100 --cache_flush 20000 --coalesce --fan_out 3 \
+             --fan_out_quorum 2 --db_contention 0.3 --resource_slots 3",
+        );
+    }
+
+    #[test]
+    fn resumed_run_with_incidents_ends_where_the_uninterrupted_one_does() {
+        assert_resumes_deterministically(
+            "incidents",
+            "--seed 3 -r 0.12 --workers 10 --autoscale_max_workers 12 --worker_startup_ticks 100 \
+             --mitigation queue_len>5:add_workers=2 --mitigation queue_len>8:cut_retries \
+             --chaos kill:2:20000:40000 --stall_interval 3000 --stall_duration 200 \
+             --straggler 1:10000:30000:10 --alert queue_len>5",
+        );
+    }
+
+    #[test]
+    fn resumed_run_with_clients_ends_where_the_uninterrupted_one_does() {
+        assert_resumes_deterministically(
+            "clients",
+            "--seed 3 --clients 30 --think_time 20 --client_connections 10 --client_queue_timeout 50 \
+             --network_latency 5 --request_size lognormal:1 --simulate_spike",
+        );
+    }
+
+    #[test]
+    fn resumed_run_with_observers_ends_where_the_uninterrupted_one_does() {
+        assert_resumes_deterministically(
+            "observers",
+            "--seed 3 -r 0.12 --simulate_spike --discipline siro --explain --littles_law --detect_collapse \
+             --detect_retry_storms --queue_distribution --telemetry",
+        );
+    }
+
+    #[test]
+    fn resumed_run_carries_on_writing_the_output_files() {
+        let path = checkpoint_file("outputs");
+        let outputs = |run: &str| {
+            ["ts.csv", "snapshots.ndjson", "events.ndjson", "trace.json"]
+                .iter()
+                .map(|file| checkpoint_file(&format!("outputs-{}-{}", run, file)))
+                .collect::<Vec<String>>()
+        };
+        let scenario = |outputs: &[String]| {
+            format!(
+                "--seed 3 -r 0.12 --simulate_spike --report_every 100 --timeseries {} --snapshots {} \
+                 --snapshot_every 1000 --event_log {} --chrome_trace {}",
+                outputs[0], outputs[1], outputs[2], outputs[3]
+            )
+        };
+        let (uninterrupted, resumed) = (outputs("uninterrupted"), outputs("resumed"));
+        simulation::run(&opt(&format!(
+            "{} --simulation_time 60000",
+            scenario(&uninterrupted)
+        )));
+        simulation::run(&opt(&format!(
+            "{} --simulation_time 35000 --checkpoint_every 10000 --checkpoint_file {}",
+            scenario(&resumed),
+            path
+        )));
+        simulation::run(&opt(&format!(
+            "{} --simulation_time 60000 --checkpoint_file {} --resume",
+            scenario(&resumed),
+            path
+        )));
+        fs::remove_file(&path).unwrap();
+
+        for (uninterrupted, resumed) in uninterrupted.iter().zip(resumed.iter()) {
+            let expected = fs::read(uninterrupted).unwrap();
+            let actual = fs::read(resumed).unwrap();
+            fs::remove_file(uninterrupted).unwrap();
+            fs::remove_file(resumed).unwrap();
+            assert!(
+                actual == expected,
+                "{} differs from {}",
+                resumed,
+                uninterrupted
+            );
+        }
+    }
+
+    #[test]
+    fn checkpoint_of_different_options_is_refused() {
+        let path = checkpoint_file("options");
+        let written = opt(&format!(
+            "--seed 3 -r 0.1 --simulation_time 1000 --checkpoint_every 500 --checkpoint_file {}",
+            path
+        ));
+        simulation::run(&written);
+        let extended = opt(&format!(
+            "--seed 3 -r 0.1 --simulation_time 2000 --checkpoint_file {} --resume",
+            path
+        ));
+        let changed = opt(&format!(
+            "--seed 3 -r 0.2 --simulation_time 1000 --checkpoint_file {} --resume",
+            path
+        ));
+        let extended = Checkpoint::read(&path, &extended).map(|c| c.next_tick);
+        let changed = Checkpoint::read(&path, &changed).map(|c| c.next_tick);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(extended, Ok(1000));
+        assert!(changed.is_err());
+    }
+}
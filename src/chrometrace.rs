@@ -0,0 +1,187 @@
+use crate::checkpoint;
+use crate::events::{Event, Observer, SystemState};
+use crate::output::Output;
+use crate::validation::ConfigError;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Microseconds of the trace timeline per tick, so that a tick shows as a millisecond.
+const TICK_MICROS: u64 = 1000;
+
+/// Writes the journey of every request through the pools as a Chrome trace (the JSON trace event format read by
+/// chrome://tracing and Perfetto): a span for the time it waited in the queue of a pool, and one for the time a
+/// worker spent on it, so that the unlucky requests can be picked out and inspected on a timeline. Every pool is
+/// a process of the trace, and rejections are instants in it.
+pub struct ChromeTrace {
+    writer: Output,
+    /// Whether an event was written already, and the next one has to be separated from it.
+    written: bool,
+    /// Pools the requests in the system are in, by their id.
+    request_pools: HashMap<u64, usize>,
+}
+
+/// Where a checkpoint left the trace off. The array of the events is closed only once the run is over, so a resumed
+/// run carries on with it.
+#[derive(Serialize, Deserialize)]
+struct TraceCheckpoint {
+    length: u64,
+    written: bool,
+    request_pools: HashMap<u64, usize>,
+}
+
+impl ChromeTrace {
+    /// Creates the trace in the specified output, naming the processes after the specified pools. A resumed trace
+    /// has them already.
+    pub fn new(writer: Output, pools: &[String]) -> io::Result<ChromeTrace> {
+        let mut trace = ChromeTrace {
+            writer,
+            written: false,
+            request_pools: HashMap::new(),
+        };
+        if trace.writer.is_resumed() {
+            return Ok(trace);
+        }
+        trace.writer.write_all(b"[\n")?;
+        for (pid, pool) in pools.iter().enumerate() {
+            trace.write(json!({
+                "ph": "M",
+                "name": "process_name",
+                "pid": pid,
+                "args": { "name": format!("Pool {}", pool) },
+            }))?;
+        }
+        Ok(trace)
+    }
+
+    fn write(&mut self, event: Value) -> io::Result<()> {
+        if self.written {
+            self.writer.write_all(b",\n")?;
+        }
+        self.written = true;
+        serde_json::to_writer(&mut self.writer, &event)?;
+        Ok(())
+    }
+
+    /// Begins or ends (`phase` "b" or "e") the span of the request in the specified lane of its pool.
+    fn span(&mut self, phase: &str, lane: &str, tick: u64, request: u64, pool: usize, args: Value) {
+        self.write(json!({
+            "ph": phase,
+            "cat": lane,
+            "name": format!("request {}", request),
+            "id": request,
+            "pid": pool,
+            "tid": 0,
+            "ts": tick * TICK_MICROS,
+            "args": args,
+        }))
+        .unwrap_or_else(write_failed);
+    }
+}
+
+impl Observer for ChromeTrace {
+    fn on_event(&mut self, tick: u64, event: &Event) {
+        match *event {
+            Event::Admitted {
+                request,
+                pool,
+                queued,
+            } => {
+                self.request_pools.insert(request, pool);
+                let lane = if queued { "queue" } else { "worker" };
+                self.span("b", lane, tick, request, pool, json!({}));
+            }
+            Event::Dequeued {
+                request,
+                pool,
+                timed_out,
+            } => {
+                self.span("e", "queue", tick, request, pool, json!({}));
+                let args = json!({ "timed_out_before_start": timed_out });
+                self.span("b", "worker", tick, request, pool, args);
+            }
+            Event::Completed {
+                request, timed_out, ..
+            } => {
+                if let Some(pool) = self.request_pools.remove(&request) {
+                    // The worker is busy until the end of the tick it finished the request in.
+                    let args = json!({ "timed_out": timed_out });
+                    self.span("e", "worker", tick + 1, request, pool, args);
+                }
+            }
+            Event::Killed { request, .. } => {
+                if let Some(pool) = self.request_pools.remove(&request) {
+                    let args = json!({ "killed": true });
+                    self.span("e", "worker", tick, request, pool, args);
+                }
+            }
+            Event::Shed { request, .. } | Event::Abandoned { request, .. } => {
+                if let Some(pool) = self.request_pools.remove(&request) {
+                    let outcome = match event {
+                        Event::Shed { .. } => "shed",
+                        _ => "abandoned",
+                    };
+                    self.span(
+                        "e",
+                        "queue",
+                        tick,
+                        request,
+                        pool,
+                        json!({ "outcome": outcome }),
+                    );
+                }
+            }
+            Event::Rejected { request, pool } => self
+                .write(json!({
+                    "ph": "i",
+                    "s": "p",
+                    "name": format!("request {} rejected", request),
+                    "pid": pool,
+                    "tid": 0,
+                    "ts": tick * TICK_MICROS,
+                }))
+                .unwrap_or_else(write_failed),
+            _ => {}
+        }
+    }
+
+    fn on_tick_end(&mut self, _tick: u64, _state: &SystemState) {}
+
+    fn checkpoint(&mut self) -> Result<Value, String> {
+        let length = self.writer.checkpoint().map_err(|e| e.to_string())?;
+        checkpoint::save(&TraceCheckpoint {
+            length,
+            written: self.written,
+            request_pools: self.request_pools.clone(),
+        })
+    }
+
+    fn resume(&mut self, state: Value) -> Result<(), String> {
+        let state: TraceCheckpoint = serde_json::from_value(state).map_err(|e| e.to_string())?;
+        self.writer
+            .resume(state.length)
+            .map_err(|e| e.to_string())?;
+        self.written = state.written;
+        self.request_pools = state.request_pools;
+        Ok(())
+    }
+}
+
+impl Drop for ChromeTrace {
+    /// Closes the array of the trace events once the run is over.
+    fn drop(&mut self) {
+        let _ = self
+            .writer
+            .write_all(b"\n]\n")
+            .and_then(|_| self.writer.flush());
+    }
+}
+
+fn write_failed(error: io::Error) {
+    ConfigError::Io {
+        action: "write the Chrome trace".to_string(),
+        error: error.to_string(),
+    }
+    .exit()
+}
@@ -0,0 +1,63 @@
+use std::str::FromStr;
+
+/// A class of requests (e.g. a tenant, or a particular API), together with its share of the incoming
+/// traffic, and optionally the timeout of its clients. Specified on the command line as `name:weight[:timeout]`.
+#[derive(Debug, Clone)]
+pub struct ClassSpec {
+    pub name: String,
+    pub weight: f64,
+    pub timeout: Option<u32>,
+}
+
+impl ClassSpec {
+    /// The only class used when no classes are configured.
+    pub fn default_class() -> ClassSpec {
+        ClassSpec {
+            name: "default".to_string(),
+            weight: 1.0,
+            timeout: None,
+        }
+    }
+}
+
+impl FromStr for ClassSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, weight, timeout) = match s.split(':').collect::<Vec<&str>>().as_slice() {
+            [name, weight] => (*name, *weight, None),
+            [name, weight, timeout] => (*name, *weight, Some(*timeout)),
+            _ => {
+                return Err(format!(
+                    "Expected class as name:weight[:timeout], got '{}'",
+                    s
+                ))
+            }
+        };
+        let weight: f64 = weight
+            .parse()
+            .map_err(|_| format!("Invalid weight '{}' for class '{}'", weight, name))?;
+        if name.is_empty() || weight <= 0.0 {
+            return Err(format!(
+                "Class '{}' needs a name and a weight greater than 0",
+                s
+            ));
+        }
+        let timeout = match timeout.map(str::parse::<u32>) {
+            Some(Ok(timeout)) if timeout > 0 => Some(timeout),
+            Some(_) => {
+                return Err(format!(
+                    "Invalid timeout of class '{}', expected a number of ticks greater than 0",
+                    name
+                ))
+            }
+            None => None,
+        };
+
+        Ok(ClassSpec {
+            name: name.to_string(),
+            weight,
+            timeout,
+        })
+    }
+}
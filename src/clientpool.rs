@@ -0,0 +1,115 @@
+use crate::histogram::Histogram;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// A request waiting in the client for a connection.
+#[derive(Serialize, Deserialize)]
+struct Waiting {
+    request: u64,
+    class: usize,
+    since_tick: u64,
+}
+
+/// Connection pool of the clients, limiting the requests they have in flight. The requests over the limit wait
+/// for a connection in a queue of the client, with a timeout of its own, before they're even sent. The two
+/// layers of queueing hide the overload of the server behind the client's queue, which is where many incidents
+/// actually play out.
+#[derive(Serialize, Deserialize)]
+pub struct ClientPool {
+    connections: u32,
+    in_use: u32,
+    waiting: VecDeque<Waiting>,
+    /// Ticks a request waits for a connection before the client gives up on it.
+    queue_timeout: u64,
+    /// Requests that arrived after the warm-up, how many of them had to wait for a connection and for how
+    /// long, and how many gave up waiting.
+    requests: u64,
+    waited: u64,
+    wait: Histogram,
+    timed_out: u64,
+}
+
+impl ClientPool {
+    pub fn new(connections: u32, queue_timeout: u64) -> ClientPool {
+        ClientPool {
+            connections,
+            in_use: 0,
+            waiting: VecDeque::new(),
+            queue_timeout,
+            requests: 0,
+            waited: 0,
+            wait: Histogram::new(),
+            timed_out: 0,
+        }
+    }
+
+    /// Takes a connection for the request with the specified id and class arriving during the specified tick, if
+    /// there is one free and no request waiting ahead of it. Otherwise, queues the request and returns false.
+    pub fn acquire(&mut self, request: u64, class: usize, tick: u64, measured: bool) -> bool {
+        if measured {
+            self.requests += 1;
+        }
+        if self.waiting.is_empty() && self.in_use < self.connections {
+            self.in_use += 1;
+            if measured {
+                self.wait.record(0);
+            }
+            return true;
+        }
+        if measured {
+            self.waited += 1;
+        }
+        self.waiting.push_back(Waiting {
+            request,
+            class,
+            since_tick: tick,
+        });
+        false
+    }
+
+    /// The client learned the outcome of a request, freeing up its connection.
+    pub fn release(&mut self) {
+        self.in_use -= 1;
+    }
+
+    /// Removes the requests that waited for a connection longer than the timeout by the specified tick from the
+    /// queue, and returns their ids and classes.
+    pub fn expire(&mut self, tick: u64, measured: bool) -> Vec<(u64, usize)> {
+        let mut expired = Vec::new();
+        while self
+            .waiting
+            .front()
+            .is_some_and(|w| tick - w.since_tick >= self.queue_timeout)
+        {
+            let waiting = self.waiting.pop_front().unwrap();
+            expired.push((waiting.request, waiting.class));
+        }
+        if measured {
+            self.timed_out += expired.len() as u64;
+        }
+        expired
+    }
+
+    /// Hands a free connection to the request that waited the longest, if any, and returns its id and class.
+    pub fn next(&mut self, tick: u64, measured: bool) -> Option<(u64, usize)> {
+        if self.in_use >= self.connections {
+            return None;
+        }
+        let waiting = self.waiting.pop_front()?;
+        self.in_use += 1;
+        if measured {
+            self.wait.record(tick - waiting.since_tick);
+        }
+        Some((waiting.request, waiting.class))
+    }
+
+    pub fn print_summary(&self) {
+        println!(
+            "Client connections: {}, {:.2}% of the requests waited for one ({} gave up waiting), wait: {}",
+            self.connections,
+            self.waited as f64 / self.requests.max(1) as f64 * 100.0,
+            self.timed_out,
+            self.wait.format_percentiles()
+        );
+    }
+}
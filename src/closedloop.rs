@@ -0,0 +1,89 @@
+use crate::trace::TracedArrival;
+use rand::distributions::Distribution;
+use rand::Rng;
+use rand_distr::Exp;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Closed-loop workload: a fixed population of clients, each of which sends a request, waits for its outcome
+/// (including the retries), thinks for a while, and only then sends the next one. Unlike the open-loop arrivals,
+/// which keep coming however slow the system gets, the load of a closed loop drops as the latency grows, since
+/// the clients spend their time waiting rather than sending. Overload then shows as latency, not as a queue
+/// growing without bound.
+#[derive(Serialize, Deserialize)]
+pub struct ClosedLoop {
+    clients: u32,
+    /// Ticks at which the thinking clients send their next request, earliest first.
+    wake_ticks: BinaryHeap<Reverse<u64>>,
+    /// Distribution of the think time, none if the clients send the next request right away.
+    think_time: Option<Exp<f64>>,
+    arrivals: Vec<TracedArrival>,
+    /// Sum over the measured ticks of the number of clients waiting for a response, and number of such ticks.
+    waiting_sum: u64,
+    ticks: u64,
+}
+
+impl ClosedLoop {
+    /// Starts the clients, each after a think time of its own, so that they don't all send at once.
+    pub fn new<R: Rng>(clients: u32, mean_think_time: f64, rng: &mut R) -> ClosedLoop {
+        let mut closed_loop = ClosedLoop {
+            clients,
+            wake_ticks: BinaryHeap::new(),
+            think_time: if mean_think_time > 0.0 {
+                Some(Exp::new(1.0 / mean_think_time).unwrap())
+            } else {
+                None
+            },
+            arrivals: Vec::new(),
+            waiting_sum: 0,
+            ticks: 0,
+        };
+        for _ in 0..clients {
+            let wake_tick = closed_loop.sample_think_time(rng);
+            closed_loop.wake_ticks.push(Reverse(wake_tick));
+        }
+        closed_loop
+    }
+
+    /// Requests the clients send during the specified tick.
+    pub fn arrivals_at(&mut self, tick: u64) -> &[TracedArrival] {
+        self.arrivals.clear();
+        while self.wake_ticks.peek().is_some_and(|w| w.0 <= tick) {
+            self.wake_ticks.pop();
+            self.arrivals.push(TracedArrival {
+                tick,
+                offset: None,
+                class: None,
+            });
+        }
+        &self.arrivals
+    }
+
+    /// A client got the final outcome of its request during the specified tick, and starts thinking.
+    pub fn finished<R: Rng>(&mut self, tick: u64, rng: &mut R) {
+        let wake_tick = tick + 1 + self.sample_think_time(rng);
+        self.wake_ticks.push(Reverse(wake_tick));
+    }
+
+    /// Records the number of clients waiting for a response at the end of a measured tick.
+    pub fn tick_end(&mut self) {
+        self.waiting_sum += self.clients as u64 - self.wake_ticks.len() as u64;
+        self.ticks += 1;
+    }
+
+    pub fn print_summary(&self) {
+        println!(
+            "Closed loop: {} clients, {:.2} waiting for a response on average",
+            self.clients,
+            self.waiting_sum as f64 / self.ticks.max(1) as f64
+        );
+    }
+
+    fn sample_think_time<R: Rng>(&self, rng: &mut R) -> u64 {
+        match self.think_time.as_ref() {
+            Some(think_time) => think_time.sample(rng).round() as u64,
+            None => 0,
+        }
+    }
+}
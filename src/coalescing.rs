@@ -0,0 +1,70 @@
+use crate::request::Request;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Single-flight coalescing of the requests with the same key: while a request for a key is in the system,
+/// the duplicates arriving for it wait on its result instead of taking workers of their own. A storm of
+/// requests for a hot key costs the workers a single request, however many clients are waiting for it.
+#[derive(Serialize, Deserialize)]
+pub struct Coalescer {
+    /// Requests waiting on the in-flight request of their key, by the key. Every key in flight has an entry,
+    /// even if nothing is waiting on it.
+    waiting: HashMap<u64, Vec<Request>>,
+    /// Requests that waited on another one after the warm-up, the worker ticks they saved, and the most that
+    /// waited on a single request.
+    coalesced: u64,
+    saved_ticks: u64,
+    max_waiting: usize,
+}
+
+impl Coalescer {
+    pub fn new() -> Coalescer {
+        Coalescer {
+            waiting: HashMap::new(),
+            coalesced: 0,
+            saved_ticks: 0,
+            max_waiting: 0,
+        }
+    }
+
+    /// Whether a request with the specified key is in flight, for the next ones with the key to wait on.
+    pub fn is_in_flight(&self, key: u64) -> bool {
+        self.waiting.contains_key(&key)
+    }
+
+    /// Makes the request wait on the in-flight request with the same key.
+    pub fn join(&mut self, request: Request, measured: bool) {
+        let waiting = self.waiting.get_mut(&request.key()).unwrap();
+        waiting.push(request);
+        if measured {
+            self.coalesced += 1;
+            self.max_waiting = self.max_waiting.max(waiting.len());
+        }
+    }
+
+    /// A request with the specified key was admitted, and the next ones with its key wait on it.
+    pub fn admitted(&mut self, key: u64) {
+        self.waiting.insert(key, Vec::new());
+    }
+
+    /// The in-flight request with the specified key finished, after the specified number of ticks of work.
+    /// Returns the requests that waited on it, which get its result.
+    pub fn finished(&mut self, key: u64, worked_ticks: u32, measured: bool) -> Vec<Request> {
+        let waiting = self.waiting.remove(&key).unwrap_or_default();
+        if measured {
+            self.saved_ticks += worked_ticks as u64 * waiting.len() as u64;
+        }
+        waiting
+    }
+
+    /// Prints how many of the specified number of requests waited on another one, and the work that saved.
+    pub fn print_summary(&self, total_requests: u64) {
+        println!(
+            "Coalescing: {} requests ({:.2}%) waited on an in-flight request with the same key, saving {} worker ticks, at most {} on a single request",
+            self.coalesced,
+            self.coalesced as f64 / total_requests.max(1) as f64 * 100.0,
+            self.saved_ticks,
+            self.max_waiting
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use crate::checkpoint;
+use crate::events::{Event, Observer, SystemState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Share of its capacity the queue has to stay filled to for it to count as pinned.
+const PINNED: f64 = 0.9;
+
+/// Detects congestion collapse: windows of ticks during which the queue stayed pinned near its capacity, while
+/// only a small share of the requests completed in time. Past that point, the workers are busy with requests
+/// whose clients already gave up, and the system may stay stuck there long after the load that tipped it over
+/// went away, which an aggregate failure rate doesn't tell apart from a brief overload.
+#[derive(Serialize, Deserialize)]
+pub struct CollapseDetector {
+    warmup_ticks: u64,
+    window: u64,
+    /// Queue capacity of the pools, together.
+    capacity: usize,
+    /// Share of the arriving requests that has to complete in time for the window not to count as collapsed.
+    min_goodput: f64,
+    /// First tick of the current window, the shortest the queue was during it, and the requests that arrived
+    /// and completed in time during it.
+    window_start: u64,
+    min_queue_len: usize,
+    arrived: u64,
+    completed: u64,
+    /// Tick the ongoing collapse began at, if the system is collapsed.
+    collapsed_since: Option<u64>,
+    /// Collapses that began, the tick the first one began at and the last one ended at, and ticks spent collapsed.
+    collapses: u64,
+    first_start: Option<u64>,
+    last_recovery: Option<u64>,
+    collapsed_ticks: u64,
+}
+
+impl CollapseDetector {
+    pub fn new(
+        warmup_ticks: u64,
+        window: u64,
+        capacity: usize,
+        min_goodput: f64,
+    ) -> CollapseDetector {
+        CollapseDetector {
+            warmup_ticks,
+            window,
+            capacity,
+            min_goodput,
+            window_start: warmup_ticks,
+            min_queue_len: usize::MAX,
+            arrived: 0,
+            completed: 0,
+            collapsed_since: None,
+            collapses: 0,
+            first_start: None,
+            last_recovery: None,
+            collapsed_ticks: 0,
+        }
+    }
+
+    /// Whether the window that just ended counts as collapsed.
+    fn is_collapsed(&self) -> bool {
+        self.min_queue_len as f64 >= self.capacity as f64 * PINNED
+            && self.arrived > 0
+            && (self.completed as f64) < self.arrived as f64 * self.min_goodput
+    }
+}
+
+impl Observer for CollapseDetector {
+    fn on_event(&mut self, tick: u64, event: &Event) {
+        if tick < self.warmup_ticks {
+            return;
+        }
+        match *event {
+            Event::Arrived { .. } => self.arrived += 1,
+            Event::Completed { timed_out, .. } if !timed_out => self.completed += 1,
+            _ => {}
+        }
+    }
+
+    fn on_tick_end(&mut self, tick: u64, state: &SystemState) {
+        if tick < self.warmup_ticks {
+            return;
+        }
+        self.min_queue_len = self.min_queue_len.min(state.queue_len);
+        if tick + 1 - self.window_start < self.window {
+            return;
+        }
+
+        match (self.is_collapsed(), self.collapsed_since) {
+            (true, None) => {
+                self.collapsed_since = Some(self.window_start);
+                self.first_start.get_or_insert(self.window_start);
+                self.collapses += 1;
+            }
+            (false, Some(_)) => {
+                self.collapsed_since = None;
+                self.last_recovery = Some(self.window_start);
+            }
+            _ => {}
+        }
+        if self.collapsed_since.is_some() {
+            self.collapsed_ticks += self.window;
+        }
+        self.window_start = tick + 1;
+        self.min_queue_len = usize::MAX;
+        self.arrived = 0;
+        self.completed = 0;
+    }
+
+    fn print_summary(&self) {
+        let first_start = match self.first_start {
+            Some(first_start) => first_start,
+            None => {
+                println!(
+                    "Collapse: none detected (no {} tick window with the queue over {:.0}% full and under {:.0}% of \
+                     the requests completing in time)",
+                    self.window,
+                    PINNED * 100.0,
+                    self.min_goodput * 100.0
+                );
+                return;
+            }
+        };
+        let outcome = match (self.collapsed_since, self.last_recovery) {
+            (Some(since), Some(_)) => {
+                format!("collapsed again at tick {} and never recovered", since)
+            }
+            (Some(_), None) => "never recovered".to_string(),
+            (None, Some(recovery)) => format!("last recovered at tick {}", recovery),
+            (None, None) => unreachable!(),
+        };
+        println!(
+            "Collapse: began at tick {}, {} ({} ticks collapsed in total, collapses: {})",
+            first_start, outcome, self.collapsed_ticks, self.collapses
+        );
+    }
+
+    fn checkpoint(&mut self) -> Result<Value, String> {
+        checkpoint::save(self)
+    }
+
+    fn resume(&mut self, state: Value) -> Result<(), String> {
+        checkpoint::restore(self, state)
+    }
+}
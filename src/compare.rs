@@ -0,0 +1,176 @@
+use crate::config;
+use crate::metadata;
+use crate::report::{self, Run};
+use crate::simulation::{self, Stats};
+use crate::validation::{self, ConfigError};
+use crate::Opt;
+use rand::{thread_rng, Rng};
+use std::env;
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct CompareOpt {
+    /// Options only configuration A runs with, e.g. "--retry_policy none".
+    #[structopt(long = "a", default_value = "", allow_hyphen_values = true)]
+    a: String,
+
+    /// Options only configuration B runs with, e.g. "--discipline lifo".
+    #[structopt(long = "b", default_value = "", allow_hyphen_values = true)]
+    b: String,
+}
+
+/// Runs two configurations, which share the options specified before the subcommand, and prints the differences
+/// in their results. Both runs use the same seed, so they see the identical sequence of arrivals (common random
+/// numbers), and the differences aren't swamped by sampling noise.
+pub fn run(opt: &Opt, compare: &CompareOpt) {
+    let seed = opt.seed.unwrap_or_else(|| thread_rng().gen());
+    let shared_args: Vec<String> = env::args().take_while(|arg| arg != "compare").collect();
+    let configure =
+        |args: &str| configure(&shared_args, args, seed).unwrap_or_else(|error| error.exit());
+    let (a, b) = (configure(&compare.a), configure(&compare.b));
+    let a_summary = simulation::run(&a);
+    let b_summary = simulation::run(&b);
+
+    metadata::print_summary(opt);
+    println!("Seed: {}", seed);
+    println!("A: {}", compare.a);
+    println!("B: {}", compare.b);
+    println!();
+    println!("{:<16}{:>14}{:>14}{:>14}", "", "A", "B", "Difference");
+    let (a_stats, b_stats) = (&a_summary.stats, &b_summary.stats);
+    for (name, metric, precision) in metrics() {
+        let (a_value, b_value) = (metric(a_stats), metric(b_stats));
+        println!(
+            "{:<16}{:>14.*}{:>14.*}{:>+14.*}",
+            name,
+            precision,
+            a_value,
+            precision,
+            b_value,
+            precision,
+            b_value - a_value
+        );
+    }
+
+    if let Some(path) = opt.html_report.as_ref() {
+        let runs = [
+            Run {
+                label: "A",
+                args: &compare.a,
+                summary: &a_summary,
+            },
+            Run {
+                label: "B",
+                args: &compare.b,
+                summary: &b_summary,
+            },
+        ];
+        report::write(
+            path,
+            &shared_args[1..].join(" "),
+            &metadata::entries(opt),
+            &runs,
+        )
+        .unwrap_or_else(|e| ConfigError::io("write HTML report", path, e).exit());
+    }
+}
+
+/// Options of one of the configurations: the shared arguments, followed by the ones specific to it, with the
+/// `--config` and `--preset` of either expanded, so that the specific ones override the scenario's too.
+fn configure(shared_args: &[String], args: &str, seed: u64) -> Result<Opt, ConfigError> {
+    let mut all_args = shared_args.to_vec();
+    all_args.extend(args.split_whitespace().map(str::to_string));
+    let mut opt = Opt::from_iter_safe(config::expand_args(all_args)?).map_err(|e| {
+        let message = e.message.lines().next().unwrap_or_default();
+        ConfigError::Invalid(message.trim_start_matches("error: ").to_string())
+    })?;
+    opt.seed = Some(seed);
+    validation::validate(&opt)?;
+    Ok(opt)
+}
+
+/// Metric compared between runs: its name, how to get it from the statistics, and the number of decimal places
+/// to print it with.
+pub type Metric = (&'static str, fn(&Stats) -> f64, usize);
+
+pub fn metrics() -> [Metric; 10] {
+    [
+        ("Failure rate %", Stats::failure_rate, 2),
+        ("Requests", |s| s.total_requests as f64, 0),
+        ("Rejected", |s| s.rejected_requests as f64, 0),
+        ("Timed out", |s| s.timed_out_requests as f64, 0),
+        ("Latency mean", |s| s.latency.mean(), 2),
+        ("Latency p50", |s| s.latency.percentile(50.0) as f64, 0),
+        ("Latency p90", |s| s.latency.percentile(90.0) as f64, 0),
+        ("Latency p99", |s| s.latency.percentile(99.0) as f64, 0),
+        ("Latency p99.9", |s| s.latency.percentile(99.9) as f64, 0),
+        ("Latency max", |s| s.latency.max() as f64, 0),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::Discipline;
+    use std::fs;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn configurations_expand_the_scenario_file_and_override_it() {
+        let path = env::temp_dir().join(format!(
+            "queueingsimulator-compare-{}.toml",
+            std::process::id()
+        ));
+        fs::write(
+            &path,
+            "arrival_rate = 0.15\ndiscipline = \"fifo\"\nworkers = 20\n",
+        )
+        .unwrap();
+        let shared_args = args(&format!(
+            "queueingsimulator --config {} --workers 12",
+            path.display()
+        ));
+        let a = configure(&shared_args, "", 7);
+        let b = configure(&shared_args, "--discipline lifo", 7);
+        fs::remove_file(&path).unwrap();
+
+        let (a, b) = (a.unwrap(), b.unwrap());
+        assert_eq!(a.request_arrival_rate, Some(0.15));
+        assert_eq!(a.num_workers, 12);
+        assert_eq!(a.discipline(), Discipline::Fifo);
+        assert_eq!(b.discipline(), Discipline::Lifo);
+        assert_eq!((a.seed, b.seed), (Some(7), Some(7)));
+    }
+
+    #[test]
+    fn configurations_expand_the_preset() {
+        let shared_args = args("queueingsimulator --preset overloaded");
+        assert!(configure(&shared_args, "", 7)
+            .unwrap()
+            .request_arrival_rate
+            .is_some());
+        assert!(configure(&args("queueingsimulator"), "", 7).is_err());
+    }
+
+    #[test]
+    fn invalid_options_of_a_configuration_are_reported() {
+        let shared_args = args("queueingsimulator -r 0.1");
+        assert!(configure(&shared_args, "--discipline lifo", 7).is_ok());
+        assert!(configure(&shared_args, "--bogus", 7).is_err());
+        assert!(configure(&shared_args, "--workers 0", 7).is_err());
+    }
+
+    #[test]
+    fn identical_configurations_have_identical_results() {
+        let shared_args =
+            args("queueingsimulator -r 0.15 --simulate_spike --simulation_time 50000");
+        let a = simulation::run(&configure(&shared_args, "", 7).unwrap());
+        let b = simulation::run(&configure(&shared_args, "", 7).unwrap());
+        for (name, metric, _) in metrics() {
+            assert_eq!(metric(&a.stats), metric(&b.stats), "{}", name);
+        }
+    }
+}
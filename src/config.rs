@@ -0,0 +1,279 @@
+use crate::validation::ConfigError;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+/// Short forms of the parameters, which a scenario file can't use but the command line can override it with.
+const SHORT_NAMES: [(char, &str); 4] = [
+    ('r', "arrival_rate"),
+    ('w', "workers"),
+    ('t', "timeout"),
+    ('q', "queue_size"),
+];
+
+/// Curated scenario showing one of the interesting regimes of the system, for new users to start from.
+#[derive(Debug, Clone, Copy)]
+pub enum Preset {
+    /// Half of the capacity used, nothing ever fails.
+    Stable,
+    /// A quarter more load than the workers can process, most requests fail.
+    Overloaded,
+    /// A latency spike at 75% utilization, after which eager retries keep the system collapsed for good.
+    RetryStorm,
+    /// A latency spike at 50% utilization, which the system recovers from once it's over.
+    SpikeRecovery,
+}
+
+impl Preset {
+    /// The parameters of the preset, in the format of a TOML scenario file.
+    fn parameters(self) -> &'static str {
+        match self {
+            Preset::Stable => "arrival_rate = 0.1",
+            Preset::Overloaded => "arrival_rate = 0.25",
+            Preset::RetryStorm => {
+                "arrival_rate = 0.15\nretry_policy = \"probability:0.9\"\nspike_start = 200000\nplot = \"ascii\""
+            }
+            Preset::SpikeRecovery => {
+                "arrival_rate = 0.1\nspike_start = 200000\nexplain = true\nplot = \"ascii\""
+            }
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stable" => Ok(Preset::Stable),
+            "overloaded" => Ok(Preset::Overloaded),
+            "retry-storm" => Ok(Preset::RetryStorm),
+            "spike-recovery" => Ok(Preset::SpikeRecovery),
+            _ => Err(format!(
+                "Unknown preset '{}', expected stable, overloaded, retry-storm or spike-recovery",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Preset::Stable => write!(f, "stable"),
+            Preset::Overloaded => write!(f, "overloaded"),
+            Preset::RetryStorm => write!(f, "retry-storm"),
+            Preset::SpikeRecovery => write!(f, "spike-recovery"),
+        }
+    }
+}
+
+/// Expands the `--config` and `--preset` parameters in the command line arguments into the parameters of the
+/// scenario file and preset they name. The file is a TOML or YAML (by its extension) mapping of parameter
+/// names, as on the command line but without the dashes, to their values, e.g. `arrival_rate = 0.15`.
+/// Repeatable parameters take a list, flags take true or false. A parameter on the command line replaces the
+/// file's value, repeatable ones included, and the file's replace the preset's.
+pub fn expand_args(args: Vec<String>) -> Result<Vec<String>, ConfigError> {
+    let mut overridden = command_line_names(&args);
+    let mut expanded_args = Vec::new();
+    if let Some(path) = value_of(&args, "config") {
+        let values = read(&path).map_err(|e| {
+            ConfigError::Invalid(format!("Failed to read config '{}': {}", path, e))
+        })?;
+        if values.contains_key("config") {
+            return Err(ConfigError::Invalid(format!(
+                "Config '{}' can't include another config",
+                path
+            )));
+        }
+        let names: Vec<String> = values.keys().cloned().collect();
+        expanded_args.extend(to_args(values, &overridden, &format!("config '{}'", path))?);
+        overridden.extend(names);
+    }
+    // An unknown preset is left for the parser to report.
+    if let Some(Ok(preset)) = value_of(&args, "preset").map(|p| p.parse::<Preset>()) {
+        let values = toml::from_str(preset.parameters()).unwrap();
+        expanded_args.extend(to_args(values, &overridden, &format!("preset {}", preset))?);
+    }
+    if expanded_args.is_empty() {
+        return Ok(args);
+    }
+    // The expanded parameters go before the command line's, so that they apply to the main simulation rather
+    // than to a subcommand.
+    let mut expanded = args;
+    expanded.splice(1..1, expanded_args);
+    Ok(expanded)
+}
+
+/// Command line arguments setting the parameters from the specified source, except for the overridden ones.
+pub fn to_args(
+    values: Map<String, Value>,
+    overridden: &[String],
+    source: &str,
+) -> Result<Vec<String>, ConfigError> {
+    let mut args = Vec::new();
+    for (name, value) in values {
+        if overridden.contains(&name) {
+            continue;
+        }
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                Value::Bool(true) => args.push(format!("--{}", name)),
+                Value::Bool(false) => {}
+                Value::String(s) => args.push(format!("--{}={}", name, s)),
+                Value::Number(n) => args.push(format!("--{}={}", name, n)),
+                _ => {
+                    return Err(ConfigError::Invalid(format!(
+                        "Invalid value of '{}' in {}, expected a string, number, boolean or a list of them",
+                        name, source
+                    )))
+                }
+            }
+        }
+    }
+    Ok(args)
+}
+
+/// Value of the specified parameter on the command line, if any, as either `--name value` or `--name=value`.
+fn value_of(args: &[String], name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let prefix = format!("--{}=", name);
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if *arg == flag {
+            args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix(&prefix).map(str::to_string)
+        }
+    })
+}
+
+/// Names of the parameters specified on the command line.
+fn command_line_names(args: &[String]) -> Vec<String> {
+    let mut names = Vec::new();
+    for arg in args.iter().skip(1) {
+        if let Some(long) = arg.strip_prefix("--") {
+            names.push(long.split('=').next().unwrap_or(long).to_string());
+        } else if let Some(short) = arg.strip_prefix('-').and_then(|s| s.chars().next()) {
+            if let Some((_, long)) = SHORT_NAMES.iter().find(|(c, _)| *c == short) {
+                names.push(long.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Reads the parameters of a scenario file.
+fn read(path: &str) -> Result<Map<String, Value>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    if path.ends_with(".yaml") || path.ends_with(".yml") {
+        serde_yaml::from_str(&contents).map_err(|e| e.to_string())
+    } else if path.ends_with(".toml") {
+        toml::from_str(&contents).map_err(|e| e.to_string())
+    } else {
+        Err("expected a .toml, .yaml or .yml file".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn args(args: &str) -> Vec<String> {
+        std::iter::once("queueingsimulator")
+            .chain(args.split_whitespace())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Writes the scenario file with the specified name to a directory of its own, returning its path.
+    fn scenario(name: &str, contents: &str) -> String {
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("queueingsimulator-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn the_command_line_overrides_the_file() {
+        let path = scenario(
+            "override.toml",
+            "arrival_rate = 0.15\nworkers = 4\nsimulate_spike = true\nlifo = false\nclass = [\"api:0.7\", \"batch:0.3\"]",
+        );
+        let expanded = expand_args(args(&format!("--config {} -w 8", path))).unwrap();
+        assert_eq!(
+            expanded,
+            args(&format!(
+                "--arrival_rate=0.15 --class=api:0.7 --class=batch:0.3 --simulate_spike --config {} -w 8",
+                path
+            ))
+        );
+
+        // Repeatable parameters are replaced as a whole.
+        let expanded = expand_args(args(&format!("--config={} --class web:1", path))).unwrap();
+        assert!(!expanded.contains(&"--class=api:0.7".to_string()));
+        assert!(expanded.ends_with(&args("web:1")[1..]));
+    }
+
+    #[test]
+    fn yaml_files_set_the_same_parameters() {
+        let toml = scenario(
+            "same.toml",
+            "arrival_rate = 0.15\nclass = [\"api:0.7\", \"batch:0.3\"]",
+        );
+        let yaml = scenario(
+            "same.yaml",
+            "arrival_rate: 0.15\nclass:\n  - api:0.7\n  - batch:0.3\n",
+        );
+        let from_toml = expand_args(args(&format!("--config {}", toml))).unwrap();
+        let from_yaml = expand_args(args(&format!("--config {}", yaml))).unwrap();
+        assert_eq!(from_toml[..4], from_yaml[..4]);
+    }
+
+    #[test]
+    fn invalid_files_are_refused() {
+        let error = |path: &str| {
+            expand_args(args(&format!("--config {}", path)))
+                .unwrap_err()
+                .to_string()
+        };
+        let nested = scenario("nested.toml", "config = \"other.toml\"");
+        assert_eq!(
+            error(&nested),
+            format!("Config '{}' can't include another config", nested)
+        );
+        let text = scenario("scenario.txt", "arrival_rate = 0.15");
+        assert_eq!(
+            error(&text),
+            format!(
+                "Failed to read config '{}': expected a .toml, .yaml or .yml file",
+                text
+            )
+        );
+        let table = scenario("table.toml", "[pool]\nname = \"api\"");
+        assert_eq!(
+            error(&table),
+            format!(
+                "Invalid value of 'pool' in config '{}', expected a string, number, boolean or a list of them",
+                table
+            )
+        );
+    }
+
+    #[test]
+    fn presets_go_under_the_file() {
+        let path = scenario("preset.toml", "arrival_rate = 0.2");
+        let expanded =
+            expand_args(args(&format!("--preset retry-storm --config {}", path))).unwrap();
+        assert!(expanded.contains(&"--arrival_rate=0.2".to_string()));
+        assert!(!expanded.contains(&"--arrival_rate=0.15".to_string()));
+        assert!(expanded.contains(&"--retry_policy=probability:0.9".to_string()));
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Shape of the curve describing how service time grows with the load (queue depth or concurrency).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Curve {
+    Linear,
+    Quadratic,
+    Exponential,
+}
+
+/// Makes service time a function of the queue depth at the moment a worker starts processing a request,
+/// modelling effects such as lock contention or swap pressure in an overloaded server. Since a longer queue
+/// makes the workers slower, which in turn makes the queue longer, this is a mechanism for metastable
+/// behavior that works even without any retries.
+#[derive(Debug, Clone, Copy)]
+pub struct QueueDepthPenalty {
+    curve: Curve,
+    /// Service time increase, in percent, per 100 queued requests.
+    percent_per_hundred: f64,
+}
+
+impl FromStr for Curve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Curve::Linear),
+            "quadratic" => Ok(Curve::Quadratic),
+            "exponential" => Ok(Curve::Exponential),
+            _ => Err(format!(
+                "Unknown curve '{}', expected linear, quadratic or exponential",
+                s
+            )),
+        }
+    }
+}
+
+impl Curve {
+    /// Slowdown factor for the specified load, given the slowdown rate per unit of load.
+    pub fn multiplier(&self, load: f64, rate: f64) -> f64 {
+        match self {
+            Curve::Linear => 1.0 + rate * load,
+            Curve::Quadratic => 1.0 + rate * load * load,
+            Curve::Exponential => (1.0 + rate).powf(load),
+        }
+    }
+}
+
+impl QueueDepthPenalty {
+    pub fn new(curve: Curve, percent_per_hundred: f64) -> QueueDepthPenalty {
+        QueueDepthPenalty {
+            curve,
+            percent_per_hundred,
+        }
+    }
+
+    /// Factor the service time of a request starting with the specified queue depth gets multiplied by.
+    pub fn multiplier(&self, queue_len: usize) -> f64 {
+        self.curve
+            .multiplier(queue_len as f64 / 100.0, self.percent_per_hundred / 100.0)
+    }
+}
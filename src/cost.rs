@@ -0,0 +1,36 @@
+use crate::simulation::Stats;
+
+/// Cost of a run: of the capacity, per tick each worker is available for, and of the failures, per failed
+/// request. Puts a price on both sides of the capacity planning trade-off, so that a larger fleet and the
+/// failures it would avoid can be compared directly.
+pub struct Cost {
+    worker_tick: f64,
+    failure: f64,
+}
+
+impl Cost {
+    pub fn new(worker_tick: f64, failure: f64) -> Cost {
+        Cost {
+            worker_tick,
+            failure,
+        }
+    }
+
+    /// Cost of the workers, and of the failures, after the warm-up.
+    pub fn split(&self, stats: &Stats) -> (f64, f64) {
+        (
+            stats.worker_ticks * self.worker_tick,
+            stats.failed_requests as f64 * self.failure,
+        )
+    }
+
+    pub fn print_summary(&self, stats: &Stats) {
+        let (workers, failures) = self.split(stats);
+        println!(
+            "Cost: {:.2} (workers: {:.2}, failures: {:.2})",
+            workers + failures,
+            workers,
+            failures
+        );
+    }
+}
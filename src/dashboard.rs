@@ -0,0 +1,278 @@
+use crate::simulation::Stats;
+use crate::validation::ConfigError;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Block, Borders, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// How often the dashboard is redrawn, at most.
+const FRAME: Duration = Duration::from_millis(50);
+
+/// Number of points kept for the sparklines, more than any terminal is wide.
+const HISTORY: usize = 1000;
+
+/// Live terminal dashboard with sparklines of the queue length, busy workers, failure rate and latency,
+/// updated as the simulation runs. The simulation is slowed down to the specified speed, so that the collapse
+/// of an overloaded system can be watched as it happens.
+pub struct Dashboard {
+    /// Terminal the dashboard draws to, until the user quits it.
+    terminal: Option<DefaultTerminal>,
+    ticks: u64,
+    workers: usize,
+    /// Simulated ticks per second of wall time.
+    speed: f64,
+    paused: bool,
+    next_frame: Instant,
+    frame: Point,
+    /// Cumulative counts at the start of the current frame.
+    last_total_requests: u64,
+    last_failed_requests: u64,
+    last_completed: u64,
+    last_latency_sum: f64,
+    points: VecDeque<Point>,
+}
+
+/// State of the system aggregated over a frame.
+#[derive(Default, Clone, Copy)]
+struct Point {
+    ticks: u64,
+    queue_len_sum: u64,
+    busy_workers_sum: u64,
+    /// Failure rate of the requests that arrived during the frame, in tenths of a percent.
+    failure_rate: u64,
+    /// Mean latency of the requests that completed during the frame.
+    latency: u64,
+}
+
+impl Point {
+    fn queue_len(&self) -> u64 {
+        self.queue_len_sum / self.ticks.max(1)
+    }
+
+    fn busy_workers(&self) -> u64 {
+        self.busy_workers_sum / self.ticks.max(1)
+    }
+}
+
+impl Dashboard {
+    /// Takes over the terminal, running `ticks` ticks at `speed` ticks per second.
+    pub fn new(ticks: u64, workers: usize, speed: f64) -> io::Result<Dashboard> {
+        let terminal = ratatui::try_init()?;
+        Ok(Dashboard {
+            terminal: Some(terminal),
+            ticks,
+            workers,
+            speed,
+            paused: false,
+            next_frame: Instant::now(),
+            frame: Point::default(),
+            last_total_requests: 0,
+            last_failed_requests: 0,
+            last_completed: 0,
+            last_latency_sum: 0.0,
+            points: VecDeque::with_capacity(HISTORY),
+        })
+    }
+
+    /// Measures the frames of a resumed run from the statistics it picks up with, rather than from none.
+    pub fn resume(&mut self, stats: &Stats) {
+        self.last_total_requests = stats.total_requests;
+        self.last_failed_requests = stats.failed_requests;
+        self.last_completed = stats.latency.total();
+        self.last_latency_sum = stats.latency.mean() * stats.latency.total() as f64;
+    }
+
+    /// Samples the state of the system at the end of the specified tick, and redraws the dashboard once the
+    /// ticks of a frame have passed, waiting to keep to the speed.
+    pub fn tick(&mut self, tick: u64, queue_len: usize, busy_workers: usize, stats: &Stats) {
+        if self.terminal.is_none() {
+            return;
+        }
+        self.frame.ticks += 1;
+        self.frame.queue_len_sum += queue_len as u64;
+        self.frame.busy_workers_sum += busy_workers as u64;
+        let frame_ticks = (self.speed * FRAME.as_secs_f64()).max(1.0) as u64;
+        if self.frame.ticks < frame_ticks && tick + 1 < self.ticks {
+            return;
+        }
+
+        let requests = stats.total_requests - self.last_total_requests;
+        let failures = stats.failed_requests - self.last_failed_requests;
+        self.frame.failure_rate = (failures * 1000).checked_div(requests).unwrap_or(0);
+        let latency_sum = stats.latency.mean() * stats.latency.total() as f64;
+        let completed = stats.latency.total() - self.last_completed;
+        if completed > 0 {
+            self.frame.latency = ((latency_sum - self.last_latency_sum) / completed as f64) as u64;
+        }
+        self.last_total_requests = stats.total_requests;
+        self.last_failed_requests = stats.failed_requests;
+        self.last_completed = stats.latency.total();
+        self.last_latency_sum = latency_sum;
+        if self.points.len() == HISTORY {
+            self.points.pop_front();
+        }
+        self.points.push_back(self.frame);
+        let frame_duration = Duration::from_secs_f64(self.frame.ticks as f64 / self.speed);
+        self.frame = Point::default();
+
+        self.draw(tick, false);
+        self.next_frame += frame_duration;
+        loop {
+            let now = Instant::now();
+            if !self.paused && now >= self.next_frame {
+                break;
+            }
+            let timeout = if self.paused {
+                FRAME
+            } else {
+                self.next_frame - now
+            };
+            if !self.handle_input(timeout) {
+                return;
+            }
+            if self.paused {
+                self.draw(tick, false);
+            }
+        }
+        // Don't try to catch up on the time spent paused, or drawing a slow terminal.
+        self.next_frame = self.next_frame.max(Instant::now());
+    }
+
+    /// Shows the final state of the dashboard until a key is pressed, and gives the terminal back.
+    pub fn finish(&mut self) {
+        if self.terminal.is_none() {
+            return;
+        }
+        self.draw(self.ticks - 1, true);
+        loop {
+            if let Ok(Event::Key(key)) = event::read() {
+                if key.kind == KeyEventKind::Press {
+                    break;
+                }
+            }
+        }
+        self.quit();
+    }
+
+    /// Handles the keys pressed within the specified time. Returns false if the user quit the dashboard.
+    fn handle_input(&mut self, timeout: Duration) -> bool {
+        if !event::poll(timeout).unwrap_or(false) {
+            return true;
+        }
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            _ => return true,
+        };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.quit();
+                return false;
+            }
+            KeyCode::Char(' ') => self.paused = !self.paused,
+            KeyCode::Char('+') => self.speed *= 2.0,
+            KeyCode::Char('-') => self.speed = (self.speed / 2.0).max(1.0),
+            _ => {}
+        }
+        true
+    }
+
+    /// Gives the terminal back. The rest of the simulation runs at full speed.
+    fn quit(&mut self) {
+        if self.terminal.take().is_some() {
+            ratatui::restore();
+        }
+    }
+
+    fn draw(&mut self, tick: u64, finished: bool) {
+        let status = if finished {
+            "finished, press any key to see the summary".to_string()
+        } else if self.paused {
+            "paused".to_string()
+        } else {
+            format!("{:.0} ticks/s", self.speed)
+        };
+        let title = format!(
+            " Tick {}/{} ({}) | q quit, space pause, +/- speed ",
+            tick + 1,
+            self.ticks,
+            status
+        );
+        let points = &self.points;
+        let workers = self.workers as u64;
+        if let Some(terminal) = self.terminal.as_mut() {
+            if let Err(error) = terminal.draw(|frame| render(frame, &title, points, workers)) {
+                self.quit();
+                ConfigError::Io {
+                    action: "draw the TUI".to_string(),
+                    error: error.to_string(),
+                }
+                .exit();
+            }
+        }
+    }
+}
+
+fn render(frame: &mut Frame, title: &str, points: &VecDeque<Point>, workers: u64) {
+    let [header, queue, busy, failures, latency] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Fill(1),
+        Constraint::Fill(1),
+        Constraint::Fill(1),
+        Constraint::Fill(1),
+    ])
+    .areas(frame.area());
+    frame.render_widget(Paragraph::new(title), header);
+
+    // Borders take a column on each side.
+    let visible = points.len().min(queue.width.saturating_sub(2) as usize);
+    let recent: Vec<Point> = points
+        .iter()
+        .skip(points.len() - visible)
+        .copied()
+        .collect();
+    let last = recent.last().copied().unwrap_or_default();
+    let charts = [
+        (
+            queue,
+            format!("Queue length: {}", last.queue_len()),
+            recent.iter().map(Point::queue_len).collect::<Vec<_>>(),
+            None,
+            Color::Yellow,
+        ),
+        (
+            busy,
+            format!("Busy workers: {} of {}", last.busy_workers(), workers),
+            recent.iter().map(Point::busy_workers).collect(),
+            Some(workers),
+            Color::Cyan,
+        ),
+        (
+            failures,
+            format!("Failure rate: {:.1}%", last.failure_rate as f64 / 10.0),
+            recent.iter().map(|p| p.failure_rate).collect(),
+            Some(1000),
+            Color::Red,
+        ),
+        (
+            latency,
+            format!("Latency: mean {} ticks", last.latency),
+            recent.iter().map(|p| p.latency).collect(),
+            None,
+            Color::Green,
+        ),
+    ];
+    for (area, title, data, max, color) in charts {
+        let mut sparkline = Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .style(Style::default().fg(color))
+            .data(data);
+        if let Some(max) = max {
+            sparkline = sparkline.max(max);
+        }
+        frame.render_widget(sparkline, area);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::contention::Curve;
+use rand::distributions::Distribution;
+use rand::Rng;
+use rand_distr::Normal;
+use serde::{Deserialize, Serialize};
+
+/// Downstream database every request queries after the worker is done with its own part of the work. The
+/// worker is held for the duration of the query, like it would be with a synchronous database client.
+///
+/// Unlike the workers, the database doesn't refuse work, it just slows down as more queries run concurrently
+/// (lock contention, buffer pool thrashing and so on). With a super-linear contention curve, the throughput of
+/// the database drops as concurrency grows, which holds the workers for longer, which lets the queue grow.
+#[derive(Serialize, Deserialize)]
+pub struct Database {
+    query_distribution: Normal<f64>,
+    curve: Curve,
+    /// Query slowdown, in percent, per concurrently running query.
+    percent_per_query: f64,
+    peak_concurrency: usize,
+    peak_slowdown: f64,
+    /// Sum of the slowdowns of the ticks during which at least one query was running, and number of such ticks.
+    total_slowdown: f64,
+    busy_ticks: u64,
+}
+
+impl Database {
+    pub fn new(mean_query_latency: f64, curve: Curve, percent_per_query: f64) -> Database {
+        Database {
+            query_distribution: Normal::new(mean_query_latency, mean_query_latency / 4.0).unwrap(),
+            curve,
+            percent_per_query,
+            peak_concurrency: 0,
+            peak_slowdown: 1.0,
+            total_slowdown: 0.0,
+            busy_ticks: 0,
+        }
+    }
+
+    /// Time the query of a new request would take on an otherwise idle database.
+    pub fn sample_query_time<R: Rng>(&self, rng: &mut R) -> f64 {
+        // Normal distribution can produce negative results.
+        0.0_f64.max(self.query_distribution.sample(rng))
+    }
+
+    /// Works out how much progress each of the specified number of concurrent queries makes during this tick.
+    pub fn tick(&mut self, concurrency: usize) -> f64 {
+        let slowdown = self
+            .curve
+            .multiplier(concurrency as f64, self.percent_per_query / 100.0);
+        if concurrency > 0 {
+            self.peak_concurrency = self.peak_concurrency.max(concurrency);
+            self.peak_slowdown = self.peak_slowdown.max(slowdown);
+            self.total_slowdown += slowdown;
+            self.busy_ticks += 1;
+        }
+        1.0 / slowdown
+    }
+
+    pub fn print_summary(&self) {
+        let mean_slowdown = if self.busy_ticks == 0 {
+            1.0
+        } else {
+            self.total_slowdown / self.busy_ticks as f64
+        };
+        println!(
+            "Database: peak concurrent queries: {}, peak slowdown: {:.2}x, mean slowdown: {:.2}x",
+            self.peak_concurrency, self.peak_slowdown, mean_slowdown
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use crate::checkpoint;
+use crate::events::{Event, Observer, SystemState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// Change of the queue discipline during the run, such as an operator flipping the queue to LIFO during an
+/// incident. Specified on the command line as `tick:lifo` or `tick:fifo`.
+#[derive(Debug, Clone, Copy)]
+pub struct DisciplineSwitch {
+    pub at: u64,
+    pub lifo: bool,
+}
+
+impl FromStr for DisciplineSwitch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Expected queue discipline switch as tick:lifo or tick:fifo, got '{}'",
+                s
+            )
+        };
+        let (at, discipline) = s.split_once(':').ok_or_else(invalid)?;
+        Ok(DisciplineSwitch {
+            at: at.parse().map_err(|_| invalid())?,
+            lifo: match discipline {
+                "lifo" => true,
+                "fifo" => false,
+                _ => return Err(invalid()),
+            },
+        })
+    }
+}
+
+/// Whether the queue is LIFO during the specified tick, given the initial discipline and the switches.
+pub fn lifo_at(lifo: bool, switches: &[DisciplineSwitch], tick: u64) -> bool {
+    switches
+        .iter()
+        .filter(|s| s.at <= tick)
+        .max_by_key(|s| s.at)
+        .map_or(lifo, |s| s.lifo)
+}
+
+/// How a queue discipline switch played out.
+#[derive(Serialize, Deserialize)]
+struct Transition {
+    tick: u64,
+    lifo: bool,
+    /// Requests waiting in the queue when the discipline switched.
+    queued: usize,
+    /// Requests picked up from the queue in the window before and after the switch, and how many of them
+    /// had already timed out.
+    dequeued: [u64; 2],
+    timed_out: [u64; 2],
+}
+
+/// Measures the transition behavior around each queue discipline switch: how many requests were caught in
+/// the queue, and the share of the requests picked up from the queue that had already timed out, within
+/// `window` ticks before and after the switch.
+#[derive(Serialize, Deserialize)]
+pub struct DisciplineSwitches {
+    window: u64,
+    transitions: Vec<Transition>,
+    last_queue_len: usize,
+}
+
+impl DisciplineSwitches {
+    pub fn new(switches: &[DisciplineSwitch], window: u64) -> DisciplineSwitches {
+        let mut transitions: Vec<Transition> = switches
+            .iter()
+            .map(|s| Transition {
+                tick: s.at,
+                lifo: s.lifo,
+                queued: 0,
+                dequeued: [0; 2],
+                timed_out: [0; 2],
+            })
+            .collect();
+        transitions.sort_by_key(|t| t.tick);
+        DisciplineSwitches {
+            window,
+            transitions,
+            last_queue_len: 0,
+        }
+    }
+}
+
+impl Observer for DisciplineSwitches {
+    fn on_event(&mut self, tick: u64, event: &Event) {
+        match *event {
+            Event::DisciplineSwitched { .. } => {
+                for transition in self.transitions.iter_mut().filter(|t| t.tick == tick) {
+                    transition.queued = self.last_queue_len;
+                }
+            }
+            Event::Dequeued { timed_out, .. } => {
+                for transition in self.transitions.iter_mut() {
+                    let (side, in_window) = if tick < transition.tick {
+                        (0, transition.tick - tick <= self.window)
+                    } else {
+                        (1, tick - transition.tick < self.window)
+                    };
+                    if in_window {
+                        transition.dequeued[side] += 1;
+                        transition.timed_out[side] += timed_out as u64;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_tick_end(&mut self, _tick: u64, state: &SystemState) {
+        self.last_queue_len = state.queue_len;
+    }
+
+    fn print_summary(&self) {
+        let share = |timed_out: u64, dequeued: u64| {
+            if dequeued == 0 {
+                0.0
+            } else {
+                timed_out as f64 / dequeued as f64 * 100.0
+            }
+        };
+        for t in self.transitions.iter() {
+            println!(
+                "Queue discipline switched to {} at tick {} with {} requests queued: {:.1}% of the requests \
+                 picked up from the queue in the {} ticks before had timed out, {:.1}% in the {} ticks after",
+                if t.lifo { "LIFO" } else { "FIFO" },
+                t.tick,
+                t.queued,
+                share(t.timed_out[0], t.dequeued[0]),
+                self.window,
+                share(t.timed_out[1], t.dequeued[1]),
+                self.window
+            );
+        }
+    }
+
+    fn checkpoint(&mut self) -> Result<Value, String> {
+        checkpoint::save(self)
+    }
+
+    fn resume(&mut self, state: Value) -> Result<(), String> {
+        checkpoint::restore(self, state)
+    }
+}
@@ -0,0 +1,169 @@
+use crate::checkpoint;
+use crate::events::{Event, Observer, SystemState};
+use crate::queuelength;
+use crate::Opt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Compares the simulated probability of waiting and mean queue length with the ones the Erlang C formula
+/// predicts for the equivalent M/M/c queue. The simulated queue is finite, so the two only agree as long as
+/// it rarely fills up.
+#[derive(Serialize, Deserialize)]
+pub struct ErlangC {
+    arrival_rate: f64,
+    service_rate: f64,
+    servers: usize,
+    warmup_ticks: u64,
+    /// Requests that were admitted, either to a free worker or to the queue.
+    admitted: u64,
+    /// Requests that had to wait in the queue for a worker.
+    waited: u64,
+    ticks: u64,
+    queue_len_sum: u64,
+}
+
+impl ErlangC {
+    /// Creates the comparison, if the configuration is equivalent to an M/M/c queue.
+    pub fn new(opt: &Opt) -> Option<ErlangC> {
+        let pool = queuelength::markovian_pool(opt)?;
+        Some(ErlangC {
+            arrival_rate: opt.request_arrival_rate(),
+            service_rate: 1.0 / opt.mean_request_latency,
+            servers: pool.workers as usize,
+            warmup_ticks: opt.warmup_ticks,
+            admitted: 0,
+            waited: 0,
+            ticks: 0,
+            queue_len_sum: 0,
+        })
+    }
+}
+
+impl Observer for ErlangC {
+    fn on_event(&mut self, tick: u64, event: &Event) {
+        if tick < self.warmup_ticks {
+            return;
+        }
+        match event {
+            Event::Arrived { .. } => self.admitted += 1,
+            Event::Rejected { .. } => self.admitted -= 1,
+            Event::Dequeued { .. } => self.waited += 1,
+            _ => {}
+        }
+    }
+
+    fn on_tick_end(&mut self, tick: u64, state: &SystemState) {
+        if tick < self.warmup_ticks {
+            return;
+        }
+        self.ticks += 1;
+        self.queue_len_sum += state.queue_len as u64;
+    }
+
+    fn print_summary(&self) {
+        let load = self.arrival_rate / self.service_rate;
+        let utilization = load / self.servers as f64;
+        if utilization >= 1.0 {
+            println!(
+                "Erlang C (M/M/{}): offered load of {:.2} workers exceeds the capacity, the queue grows without bound",
+                self.servers, load
+            );
+            return;
+        }
+
+        let waiting_probability = erlang_c(self.servers, load);
+        println!(
+            "Erlang C (M/M/{}): probability of waiting {:.2}% (simulated {:.2}%), mean queue length {:.2} (simulated {:.2})",
+            self.servers,
+            waiting_probability * 100.0,
+            self.waited as f64 / self.admitted.max(1) as f64 * 100.0,
+            mean_queue_length(self.servers, load),
+            self.queue_len_sum as f64 / self.ticks.max(1) as f64
+        );
+    }
+
+    fn checkpoint(&mut self) -> Result<Value, String> {
+        checkpoint::save(self)
+    }
+
+    fn resume(&mut self, state: Value) -> Result<(), String> {
+        checkpoint::restore(self, state)
+    }
+}
+
+/// Probability that a request finds all of `servers` servers busy when there's no queue to wait in, with the
+/// specified offered load (in Erlangs, the mean number of busy servers). Computed by the recursion over the
+/// number of servers, which stays accurate for any number of them, unlike the factorials of the formula.
+fn erlang_b(servers: usize, load: f64) -> f64 {
+    (1..=servers).fold(1.0, |b, k| load * b / (k as f64 + load * b))
+}
+
+/// Probability that a request has to wait for one of `servers` servers, with the specified offered load.
+fn erlang_c(servers: usize, load: f64) -> f64 {
+    let blocking = erlang_b(servers, load);
+    let utilization = load / servers as f64;
+    blocking / (1.0 - utilization * (1.0 - blocking))
+}
+
+/// Mean number of requests waiting for one of `servers` servers, with the specified offered load.
+fn mean_queue_length(servers: usize, load: f64) -> f64 {
+    let utilization = load / servers as f64;
+    erlang_c(servers, load) * utilization / (1.0 - utilization)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn erlang_b_matches_the_formula() {
+        assert_close(erlang_b(1, 1.0), 0.5);
+        // (1 / 2!) / (1 + 1 + 1 / 2!)
+        assert_close(erlang_b(2, 1.0), 0.2);
+        // (3^3 / 3!) / (1 + 3 + 3^2 / 2! + 3^3 / 3!)
+        assert_close(erlang_b(3, 3.0), 4.5 / 13.0);
+    }
+
+    #[test]
+    fn erlang_c_of_a_single_server_is_its_utilization() {
+        for load in [0.1, 0.5, 0.9] {
+            assert_close(erlang_c(1, load), load);
+        }
+    }
+
+    #[test]
+    fn erlang_c_matches_the_formula() {
+        // 0.2 / (1 - 0.5 * (1 - 0.2))
+        assert_close(erlang_c(2, 1.0), 1.0 / 3.0);
+        // The waiting probability is higher than the blocking probability of the same servers without a queue.
+        assert!(erlang_c(10, 8.0) > erlang_b(10, 8.0));
+    }
+
+    #[test]
+    fn erlang_c_stays_finite_for_many_servers() {
+        let waiting_probability = erlang_c(5000, 4950.0);
+        assert!(
+            waiting_probability > 0.0 && waiting_probability < 1.0,
+            "{}",
+            waiting_probability
+        );
+    }
+
+    #[test]
+    fn mean_queue_length_matches_the_formula() {
+        // M/M/1: utilization^2 / (1 - utilization)
+        assert_close(mean_queue_length(1, 0.5), 0.5);
+        assert_close(mean_queue_length(1, 0.9), 8.1);
+        // M/M/2: (1 / 3) * 0.5 / (1 - 0.5)
+        assert_close(mean_queue_length(2, 1.0), 1.0 / 3.0);
+    }
+}
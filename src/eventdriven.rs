@@ -0,0 +1,592 @@
+use crate::pool::Pool;
+use crate::progress::Progress;
+use crate::queue::{ArrivalOrder, Discipline};
+use crate::retrypolicy::{self, Attempt, RetryPolicy};
+use crate::schedule::{ArrivalDistribution, ArrivalSchedule};
+use crate::service::ServiceTime;
+use crate::simulation::{Failure, Stats, Summary};
+use crate::spike::Spike;
+use crate::streams::RngStreams;
+use crate::trace::ArrivalTrace;
+use crate::utilization::WorkerTime;
+use crate::Opt;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::{thread_rng, Rng};
+use rand_distr::{Normal, Poisson};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+use std::str::FromStr;
+
+/// Engine running the simulation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    /// Advances the clock one tick at a time, visiting every worker on each tick.
+    Tick,
+    /// Advances the clock straight to the next arrival or completion, doing no work in between.
+    Event,
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tick" => Ok(Engine::Tick),
+            "event" => Ok(Engine::Event),
+            _ => Err(format!("Unknown engine '{}', expected tick or event", s)),
+        }
+    }
+}
+
+/// Describes the first configured feature the event-driven engine doesn't support, if there is one.
+pub fn unsupported_feature(opt: &Opt) -> Option<&'static str> {
+    let features = [
+        (opt.pools().len() > 1, "bulkhead pools"),
+        (opt.queues.is_some(), "multiple queues"),
+        (!opt.quotas.is_empty(), "quotas"),
+        (opt.autoscale_max_workers > 0, "autoscaling"),
+        (opt.fan_out > 1, "fan-out"),
+        (opt.queue_latency_penalty > 0.0, "queue latency penalty"),
+        (opt.db_query_latency > 0.0, "database queries"),
+        (opt.resource_slots > 0, "second resources"),
+        (opt.late_shedding_interval > 0, "late shedding"),
+        (
+            !opt.discipline_switches.is_empty(),
+            "queue discipline switches",
+        ),
+        (!opt.mitigations.is_empty(), "mitigations"),
+        (!opt.chaos.is_empty(), "chaos schedules"),
+        (!opt.regions.is_empty(), "multiple regions"),
+        (opt.discipline() == Discipline::Edf, "EDF queues"),
+        (opt.cull_timed_out, "culling of the timed out requests"),
+        (opt.discipline() == Discipline::Siro, "random order queues"),
+        (opt.generator.is_some(), "arrival generators"),
+        (opt.clients.is_some(), "closed-loop clients"),
+        (opt.request_size.is_some(), "request sizes"),
+        (opt.stall_interval.is_some(), "stalls"),
+        (opt.straggler.is_some(), "stragglers"),
+        (opt.patience.is_some(), "client abandonment"),
+        (opt.balking_queue_length.is_some(), "balking"),
+        (opt.network_latency.is_some(), "network latency"),
+        (opt.client_connections.is_some(), "client connection pools"),
+        (opt.policy.is_some(), "external policies"),
+        (opt.explain, "narration"),
+        (opt.queue_distribution, "queue length distribution"),
+        (opt.littles_law, "Little's Law check"),
+        (opt.detect_collapse, "collapse detection"),
+        (opt.detect_retry_storms, "retry storm detection"),
+        (!opt.alerts.is_empty(), "alerts"),
+        (opt.telemetry, "telemetry"),
+        (opt.tui, "the TUI"),
+        (opt.metrics_address.is_some(), "the metrics endpoint"),
+        (opt.speed.is_some(), "speed limits"),
+        (opt.time_series.is_some(), "time series"),
+        (opt.snapshots.is_some(), "snapshots"),
+        (opt.event_log.is_some(), "event logs"),
+        (opt.chrome_trace.is_some(), "Chrome traces"),
+        (opt.run_forever, "running forever"),
+        (opt.brownout_queue_length.is_some(), "brownouts"),
+        (
+            opt.cache_hit_ratio.is_some() || opt.cache_size.is_some(),
+            "caches",
+        ),
+        (opt.coalesce, "coalescing"),
+        (!opt.shed_thresholds.is_empty(), "shed thresholds"),
+        (
+            opt.timeout_distribution.is_some() || opt.classes().iter().any(|c| c.timeout.is_some()),
+            "timeouts other than --timeout",
+        ),
+        (opt.queue_timeout.is_some(), "queue-wait budgets"),
+        (opt.html_report.is_some(), "HTML reports"),
+        (opt.plot.is_some(), "plots"),
+        (opt.batching_report, "batching report"),
+    ];
+    features
+        .iter()
+        .find(|(configured, _)| *configured)
+        .map(|(_, feature)| *feature)
+}
+
+/// A request in the system, either waiting in the queue or being worked on.
+struct Job {
+    arrival_time: f64,
+    service_time: f64,
+    class: usize,
+    attempt: Attempt,
+}
+
+enum EventKind {
+    /// Start of a tick, when the arrivals of the tick are generated.
+    Tick(u64),
+    Arrival {
+        class: usize,
+        attempt: Attempt,
+    },
+    Completion {
+        job: Job,
+        worker: usize,
+    },
+    /// Worker still idle a tick after finishing a request, which picks up the next one from the queue.
+    Dequeue {
+        worker: usize,
+    },
+}
+
+/// Event scheduled at a point in (continuous) time. Events scheduled at the same time happen in the order
+/// they were scheduled in.
+struct Scheduled {
+    time: f64,
+    sequence: u64,
+    kind: EventKind,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.time
+            .total_cmp(&other.time)
+            .then(self.sequence.cmp(&other.sequence))
+    }
+}
+
+/// Discrete-event version of the simulation. Instead of stepping through every tick, it jumps from one event
+/// to the next, so its cost is proportional to the number of requests rather than the number of ticks times the
+/// length of the queue. Requests arrive at random points within their arrival tick, and service times aren't
+/// rounded to whole ticks. It supports the core model only: a single pool, timeouts, retries and their policies,
+/// spikes, classes, arrival schedules and traces.
+///
+/// Workers hand over to the next request the same way they do in the tick engine, so that both agree on the
+/// outcome of a run: a worker that finished a request is free for the arrivals of the next tick, and only picks
+/// up a queued request once that tick is over, if none of them took it.
+pub struct EventDrivenSimulation<'a> {
+    opt: &'a Opt,
+    events: BinaryHeap<Reverse<Scheduled>>,
+    next_sequence: u64,
+    queue: VecDeque<Job>,
+    queue_size: usize,
+    /// Indices of the idle workers, lowest last, picked in the same order as the tick engine picks them.
+    free_workers: Vec<usize>,
+    /// Time each worker picks up a queued request at, a tick after it finished its last one.
+    dequeue_time: Vec<f64>,
+    /// Time each worker was busy, counted after the warm-up only.
+    busy_time: Vec<f64>,
+    class_distribution: Option<WeightedIndex<f64>>,
+    arrival_schedule: Option<ArrivalSchedule>,
+    arrival_trace: Option<ArrivalTrace>,
+    arrival_distribution: Normal<f64>,
+    service_time: ServiceTime,
+    spikes: Vec<Spike>,
+    spike_class: Option<usize>,
+    retry_policies: Vec<Box<dyn RetryPolicy>>,
+    incoming_requests: f64,
+    progress: Option<Progress>,
+    rng: RngStreams,
+    stats: Stats,
+}
+
+impl<'a> EventDrivenSimulation<'a> {
+    pub fn new(opt: &'a Opt) -> EventDrivenSimulation<'a> {
+        let classes = opt.classes();
+        let pool = opt.pools().remove(0);
+        let seed = opt.seed.unwrap_or_else(|| thread_rng().gen());
+        EventDrivenSimulation {
+            opt,
+            events: BinaryHeap::new(),
+            next_sequence: 0,
+            queue: VecDeque::new(),
+            queue_size: pool.queue_size,
+            free_workers: (0..pool.workers as usize).rev().collect(),
+            dequeue_time: vec![0.0; pool.workers as usize],
+            busy_time: vec![0.0; pool.workers as usize],
+            class_distribution: if classes.len() > 1 {
+                Some(WeightedIndex::new(classes.iter().map(|c| c.weight)).unwrap())
+            } else {
+                None
+            },
+            arrival_schedule: {
+                let segments = opt.arrival_schedule().unwrap();
+                if segments.is_empty() {
+                    None
+                } else {
+                    Some(ArrivalSchedule::new(segments))
+                }
+            },
+            arrival_trace: opt.arrival_trace(&classes).unwrap(),
+            arrival_distribution: Normal::new(1.0, 0.25).unwrap(),
+            service_time: opt.service_time().unwrap(),
+            spikes: opt.spikes(),
+            spike_class: opt
+                .spike_class
+                .as_ref()
+                .map(|name| classes.iter().position(|c| &c.name == name).unwrap()),
+            retry_policies: retrypolicy::policies(
+                &opt.retry_policies,
+                &classes,
+                opt.retry_probabilities(),
+            )
+            .unwrap(),
+            incoming_requests: 0.0,
+            progress: if opt.progress {
+                Some(Progress::new(opt.simulation_ticks as u64))
+            } else {
+                None
+            },
+            rng: RngStreams::new(opt, seed),
+            stats: Stats::new(&classes),
+        }
+    }
+
+    /// Runs the simulation until the configured number of ticks passed.
+    pub fn run(mut self) -> Summary {
+        let end = self.opt.simulation_ticks as f64;
+        self.schedule(0.0, EventKind::Tick(0));
+        while let Some(Reverse(event)) = self.events.pop() {
+            if event.time >= end {
+                break;
+            }
+            match event.kind {
+                EventKind::Tick(tick) => self.tick(tick),
+                EventKind::Arrival { class, attempt } => self.arrive(event.time, class, attempt),
+                EventKind::Completion { job, worker } => self.complete(event.time, job, worker),
+                EventKind::Dequeue { worker } => self.dequeue(event.time, worker),
+            }
+        }
+        if let Some(progress) = self.progress.as_ref() {
+            progress.finish();
+        }
+
+        let pool = idle_pool(self.opt);
+        let available = (end - self.opt.warmup_ticks as f64).max(0.0);
+        self.stats.worker_ticks = available * self.busy_time.len() as f64;
+        Summary {
+            ticks: self.opt.simulation_ticks as u64,
+            worker_times: self
+                .busy_time
+                .iter()
+                .map(|&busy| WorkerTime { busy, available })
+                .collect(),
+            stats: self.stats,
+            pools: vec![pool],
+            class_pools: vec![0; self.opt.classes().len()],
+            quotas: None,
+            shedding: None,
+            retry_policies: self.retry_policies,
+            fan_out: None,
+            database: None,
+            resource: None,
+            policy: None,
+            mitigations: None,
+            chaos: None,
+            regions: None,
+            closed_loop: None,
+            request_sizes: None,
+            timeouts: None,
+            balancer: None,
+            keys: None,
+            cache: None,
+            coalescer: None,
+            network: None,
+            client_pool: None,
+            stalls: None,
+            telemetry: None,
+            timeline: None,
+            observers: Vec::new(),
+        }
+    }
+
+    fn schedule(&mut self, time: f64, kind: EventKind) {
+        self.events.push(Reverse(Scheduled {
+            time,
+            sequence: self.next_sequence,
+            kind,
+        }));
+        self.next_sequence += 1;
+    }
+
+    /// Schedules the arrivals of the tick, and the start of the next one.
+    fn tick(&mut self, tick: u64) {
+        if let Some(progress) = self.progress.as_mut() {
+            progress.tick(
+                tick,
+                self.queue.len(),
+                self.stats.total_requests,
+                self.stats.failed_requests,
+            );
+        }
+        let mut arrivals = Vec::new();
+        match self.arrival_trace.as_mut() {
+            Some(trace) => {
+                for arrival in trace.arrivals_at(tick) {
+                    arrivals.push((arrival.offset, arrival.class));
+                }
+            }
+            None => {
+                let arrival_rate = match self.arrival_schedule.as_ref() {
+                    Some(schedule) => schedule.rate_at(self.opt.request_arrival_rate(), tick),
+                    None => self.opt.request_arrival_rate(),
+                };
+                self.incoming_requests += match self.opt.arrival_distribution {
+                    ArrivalDistribution::Normal => {
+                        arrival_rate * self.arrival_distribution.sample(&mut self.rng.arrivals)
+                    }
+                    ArrivalDistribution::Poisson if arrival_rate > 0.0 => {
+                        Poisson::new(arrival_rate)
+                            .unwrap()
+                            .sample(&mut self.rng.arrivals)
+                    }
+                    ArrivalDistribution::Poisson => 0.0,
+                };
+                while self.incoming_requests > 0.0 {
+                    self.incoming_requests -= 1.0;
+                    arrivals.push((None, None));
+                }
+            }
+        }
+
+        for (offset, class) in arrivals {
+            let class = match (class, self.class_distribution.as_ref()) {
+                (Some(class), _) => class,
+                (None, Some(distribution)) => distribution.sample(&mut self.rng.arrivals),
+                (None, None) => 0,
+            };
+            let offset = offset.unwrap_or_else(|| self.rng.arrivals.gen());
+            self.schedule(
+                tick as f64 + offset,
+                EventKind::Arrival {
+                    class,
+                    attempt: Attempt::first(),
+                },
+            );
+        }
+        self.schedule((tick + 1) as f64, EventKind::Tick(tick + 1));
+    }
+
+    /// A new request arrived from a client (either a fresh one, or the specified attempt of a failed one).
+    fn arrive(&mut self, time: f64, class: usize, attempt: Attempt) {
+        if self.is_warmed_up(time) {
+            self.stats.total_requests += 1;
+            self.stats.classes[class].total_requests += 1;
+            self.stats.record_attempt(attempt.number);
+        }
+        if attempt.number == 1 {
+            self.retry_policies[class].requested();
+        }
+
+        let job = Job {
+            arrival_time: time,
+            service_time: self.sample_service_time(time, class),
+            class,
+            attempt,
+        };
+        if let Some(worker) = self.free_workers.pop() {
+            self.start(time, job, worker);
+        } else if self.queue.len() < self.queue_size {
+            self.queue.push_back(job);
+        } else {
+            self.fail(time, class, attempt, Failure::Rejected);
+        }
+    }
+
+    fn start(&mut self, time: f64, job: Job, worker: usize) {
+        // Only the part of the work within the measured period counts towards the utilization.
+        let measured_start = time.max(self.opt.warmup_ticks as f64);
+        let measured_end = (time + job.service_time).min(self.opt.simulation_ticks as f64);
+        self.busy_time[worker] += (measured_end - measured_start).max(0.0);
+        self.schedule(
+            time + job.service_time,
+            EventKind::Completion { job, worker },
+        );
+    }
+
+    /// A worker finished processing the request. It's free for the arrivals of the next tick, and picks up the
+    /// next one from the queue after it.
+    fn complete(&mut self, time: f64, job: Job, worker: usize) {
+        let latency = time - job.arrival_time;
+        if self.is_warmed_up(time) {
+            self.stats.processed_requests += 1;
+            if latency >= self.opt.request_timeout as f64 {
+                self.stats.wasted_ticks += job.service_time;
+            } else {
+                let queue_wait = (latency - job.service_time).max(0.0);
+                self.stats.queue_wait.record(queue_wait.round() as u64);
+                self.stats
+                    .service_time
+                    .record(job.service_time.round() as u64);
+            }
+        }
+        if latency >= self.opt.request_timeout as f64 {
+            // The client went away while the server was still processing the request.
+            self.fail(time, job.class, job.attempt, Failure::TimedOut);
+        } else if self.is_warmed_up(time) {
+            let latency = latency.round() as u64;
+            self.stats.latency.record(latency);
+            self.stats.classes[job.class].latency.record(latency);
+        }
+
+        let position = self.free_workers.partition_point(|&w| w > worker);
+        self.free_workers.insert(position, worker);
+        self.dequeue_time[worker] = time + 1.0;
+        self.schedule(time + 1.0, EventKind::Dequeue { worker });
+    }
+
+    /// The worker picks up the next request from the queue, unless an arrival took it in the meantime.
+    fn dequeue(&mut self, time: f64, worker: usize) {
+        // A worker that took an arrival and finished it since scheduled a dequeue of its own.
+        if self.dequeue_time[worker] != time {
+            return;
+        }
+        let position = match self.free_workers.iter().position(|&w| w == worker) {
+            Some(position) => position,
+            None => return,
+        };
+        let next = if self.opt.discipline() == Discipline::Lifo {
+            self.queue.pop_back()
+        } else {
+            self.queue.pop_front()
+        };
+        if let Some(next) = next {
+            self.free_workers.remove(position);
+            self.start(time, next, worker);
+        }
+    }
+
+    /// Client observed a failed request. The retry policy of its class decides whether it's retried, and how
+    /// long after.
+    fn fail(&mut self, time: f64, class: usize, attempt: Attempt, failure: Failure) {
+        let mut backoff =
+            self.retry_policies[class].backoff(&attempt, failure, &mut self.rng.retries);
+        if let (Some(backoff), Some(retry_after)) = (backoff.as_mut(), self.opt.retry_after) {
+            // Rejections carry the retry-after hint, which the polite clients wait out before retrying.
+            if matches!(failure, Failure::Rejected)
+                && self.rng.retries.gen_bool(self.opt.retry_after_compliance)
+            {
+                if self.is_warmed_up(time) {
+                    self.stats.deferred_retries += 1;
+                }
+                *backoff = (*backoff).max(retry_after);
+            }
+        }
+        if let Some(backoff) = backoff {
+            self.schedule(
+                time + backoff as f64,
+                EventKind::Arrival {
+                    class,
+                    attempt: attempt.retry(backoff),
+                },
+            );
+        }
+        if self.is_warmed_up(time) {
+            self.stats
+                .record_failure(class, attempt.number, failure, backoff.is_some());
+        }
+    }
+
+    fn is_warmed_up(&self, time: f64) -> bool {
+        time >= self.opt.warmup_ticks as f64
+    }
+
+    fn sample_service_time(&mut self, time: f64, class: usize) -> f64 {
+        let mut service_time = self.service_time.sample(&mut self.rng.service);
+        if self.spike_class.is_none_or(|c| c == class) {
+            let tick = time as u64;
+            for spike in self.spikes.iter().filter(|s| s.is_active(tick)) {
+                service_time *= spike.multiplier;
+            }
+        }
+        if let Some(cap) = self.opt.service_time_cap {
+            service_time = service_time.min(cap);
+        }
+        service_time.max(0.0)
+    }
+}
+
+/// Idle pool standing in for the one the engine simulated, for the summary.
+fn idle_pool(opt: &Opt) -> Pool {
+    let pool = opt.pools().remove(0);
+    let queue = Box::new(ArrivalOrder::with_capacity(pool.queue_size));
+    Pool::new(&pool, queue, false, false, None, None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation;
+    use crate::validation;
+    use structopt::StructOpt;
+
+    fn opt(args: &str) -> Opt {
+        let opt =
+            Opt::from_iter(std::iter::once("queueingsimulator").chain(args.split_whitespace()));
+        validation::validate(&opt).unwrap_or_else(|e| panic!("{}: {}", args, e));
+        assert!(unsupported_feature(&opt).is_none(), "{}", args);
+        opt
+    }
+
+    /// Runs the scenario under both engines, returning their failure rates and throughputs.
+    fn both_engines(args: &str) -> [(f64, f64); 2] {
+        ["tick", "event"].map(|engine| {
+            let opt = opt(&format!("{} --engine {}", args, engine));
+            let summary = simulation::run(&opt);
+            let throughput = summary.stats.processed_requests as f64 / summary.ticks as f64;
+            (summary.stats.failure_rate(), throughput)
+        })
+    }
+
+    #[test]
+    fn engines_agree_on_the_core_model() {
+        let scenarios = [
+            "-r 0.17 --latency_distribution exponential",
+            "-r 0.5",
+            "-r 0.15 --simulate_spike --spike_start 50000",
+            "-r 0.15 --simulate_spike --spike_start 50000 --retry_policy exponential:10:1000",
+            "-r 0.3 --discipline lifo --retry_policy budget:0.1",
+            "-r 0.12 --class api:0.5 --class batch:0.5 --retry_after 100 --simulate_spike --spike_start 50000",
+        ];
+        for scenario in scenarios {
+            let args = format!("{} --seed 7 --simulation_time 200000", scenario);
+            let [(tick_failures, tick_throughput), (event_failures, event_throughput)] =
+                both_engines(&args);
+            assert!(
+                (tick_failures - event_failures).abs() < 2.0,
+                "{}: failure rates {:.2}% and {:.2}%",
+                scenario,
+                tick_failures,
+                event_failures
+            );
+            assert!(
+                (tick_throughput - event_throughput).abs() / tick_throughput < 0.03,
+                "{}: throughputs {:.4} and {:.4}",
+                scenario,
+                tick_throughput,
+                event_throughput
+            );
+        }
+    }
+
+    #[test]
+    fn retry_policies_decide_the_retries() {
+        let args = "-r 0.5 --seed 7 --simulation_time 100000 --engine event";
+        let retried =
+            |summary: &Summary| summary.stats.retried_rejections + summary.stats.retried_timeouts;
+        let immediate = EventDrivenSimulation::new(&opt(args)).run();
+        assert!(retried(&immediate) > 0);
+        let none = opt(&format!("{} --retry_policy none", args));
+        assert_eq!(retried(&EventDrivenSimulation::new(&none).run()), 0);
+        let hinted = opt(&format!("{} --retry_after 100", args));
+        let summary = EventDrivenSimulation::new(&hinted).run();
+        assert!(summary.stats.deferred_retries > 0);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::events::{Event, Observer, SystemState};
+use crate::output::Output;
+use crate::validation::ConfigError;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{self, Write};
+
+/// Writes the life of every request as NDJSON, one line per event: when it arrived, got queued, picked up and
+/// started, and how it ended, with the tick it happened at and the id of the request. Unlike the aggregated
+/// outputs, the log lets the story of a single request be followed, or the run be analyzed with other tools.
+///
+/// Every sub-request of a fanned out request has an id of its own. A retry is a new request, with a new id.
+pub struct EventLog {
+    writer: Output,
+    classes: Vec<String>,
+    pools: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    tick: u64,
+    event: &'static str,
+    request: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    class: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pool: Option<&'a str>,
+    /// Ticks the request spent in the system, for the events ending its stay.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_in_system: Option<u64>,
+}
+
+impl EventLog {
+    /// Creates the log writing to the specified output. The names of the classes and pools are indexed the way the
+    /// events refer to them.
+    pub fn new(writer: Output, classes: Vec<String>, pools: Vec<String>) -> EventLog {
+        EventLog {
+            writer,
+            classes,
+            pools,
+        }
+    }
+}
+
+impl Observer for EventLog {
+    fn on_event(&mut self, tick: u64, event: &Event) {
+        let (event, request, class, pool, time_in_system) = match *event {
+            Event::Arrived { request, class } => ("arrival", request, Some(class), None, None),
+            Event::Balked { request } => ("balk", request, None, None, None),
+            Event::Admitted {
+                request,
+                pool,
+                queued,
+            } => {
+                let event = if queued { "enqueue" } else { "start" };
+                (event, request, None, Some(pool), None)
+            }
+            Event::Dequeued { request, pool, .. } => {
+                // The worker that dequeued the request starts on it right away.
+                write(
+                    &mut self.writer,
+                    &Record {
+                        tick,
+                        event: "dequeue",
+                        request,
+                        class: None,
+                        pool: Some(&self.pools[pool]),
+                        time_in_system: None,
+                    },
+                );
+                ("start", request, None, Some(pool), None)
+            }
+            Event::Completed {
+                request,
+                time_in_system,
+                timed_out,
+            } => {
+                // A timed out request still occupied its worker until now, though its client gave up on it.
+                let event = if timed_out { "timeout" } else { "complete" };
+                (event, request, None, None, Some(time_in_system))
+            }
+            Event::Shed {
+                request,
+                pool,
+                time_in_system,
+            } => ("shed", request, None, Some(pool), Some(time_in_system)),
+            Event::Killed {
+                request,
+                pool,
+                time_in_system,
+            } => ("kill", request, None, Some(pool), Some(time_in_system)),
+            Event::Abandoned {
+                request,
+                time_in_system,
+            } => ("abandon", request, None, None, Some(time_in_system)),
+            Event::Rejected { request, pool } => ("reject", request, None, Some(pool), None),
+            Event::CacheHit { request } => ("cache_hit", request, None, None, None),
+            Event::Coalesced { request } => ("coalesce", request, None, None, None),
+            Event::Retried { request } => ("retry", request, None, None, None),
+            Event::SpikeStarted { .. } | Event::SpikeEnded | Event::DisciplineSwitched { .. } => {
+                return
+            }
+        };
+        write(
+            &mut self.writer,
+            &Record {
+                tick,
+                event,
+                request,
+                class: class.map(|c| self.classes[c].as_str()),
+                pool: pool.map(|p| self.pools[p].as_str()),
+                time_in_system,
+            },
+        );
+    }
+
+    fn on_tick_end(&mut self, _tick: u64, _state: &SystemState) {}
+
+    fn checkpoint(&mut self) -> Result<Value, String> {
+        let length = self.writer.checkpoint().map_err(|e| e.to_string())?;
+        Ok(Value::from(length))
+    }
+
+    fn resume(&mut self, state: Value) -> Result<(), String> {
+        let length = serde_json::from_value(state).map_err(|e| e.to_string())?;
+        self.writer.resume(length).map_err(|e| e.to_string())
+    }
+}
+
+fn write(writer: &mut dyn Write, record: &Record) {
+    serde_json::to_writer(&mut *writer, record)
+        .map_err(io::Error::from)
+        .and_then(|_| writeln!(writer))
+        .unwrap_or_else(|e| {
+            ConfigError::Io {
+                action: "write the event log".to_string(),
+                error: e.to_string(),
+            }
+            .exit()
+        });
+}
@@ -0,0 +1,105 @@
+use serde_json::Value;
+
+/// Notable things happening in the simulated system, in the order they happened. Events are collected
+/// during a tick, and handed to the observers at the end of it. The events about a request carry its id.
+pub enum Event {
+    /// A client sent a request of the specified class (either a fresh one, or a retry).
+    Arrived {
+        request: u64,
+        class: usize,
+    },
+    /// Client gave up on sending the request, put off by the length of the queue.
+    Balked {
+        request: u64,
+    },
+    /// Request (or sub-request) was admitted to the pool, either straight to a free worker or to its queue.
+    Admitted {
+        request: u64,
+        pool: usize,
+        queued: bool,
+    },
+    /// A worker finished a request (or sub-request), which spent the specified number of ticks in the system,
+    /// and whether its client had already given up on it.
+    Completed {
+        request: u64,
+        time_in_system: u64,
+        timed_out: bool,
+    },
+    /// Request was shed from the queue of the pool after waiting in it for the specified number of ticks.
+    Shed {
+        request: u64,
+        pool: usize,
+        time_in_system: u64,
+    },
+    /// The worker of the pool processing the request was killed, after the request spent the specified number of
+    /// ticks in the system.
+    Killed {
+        request: u64,
+        pool: usize,
+        time_in_system: u64,
+    },
+    /// Client abandoned the request waiting in the queue, after the specified number of ticks.
+    Abandoned {
+        request: u64,
+        time_in_system: u64,
+    },
+    /// Request was rejected by the pool, since all of its workers were busy and its queue was full.
+    Rejected {
+        request: u64,
+        pool: usize,
+    },
+    /// A worker of the pool picked up a request from the queue.
+    Dequeued {
+        request: u64,
+        pool: usize,
+        timed_out: bool,
+    },
+    /// Request (or sub-request) was answered by the cache, without taking a worker.
+    CacheHit {
+        request: u64,
+    },
+    /// Request (or sub-request) waits on the in-flight request with the same key, instead of taking a worker.
+    Coalesced {
+        request: u64,
+    },
+    /// Client decided to retry the failed request, which it sends again as a new one.
+    Retried {
+        request: u64,
+    },
+    /// Latency spike began, making new requests the specified number of times slower.
+    SpikeStarted {
+        multiplier: f64,
+    },
+    SpikeEnded,
+    /// Queue discipline switched, to LIFO or back to FIFO.
+    DisciplineSwitched {
+        lifo: bool,
+    },
+}
+
+/// State of the simulated system at the end of a tick.
+pub struct SystemState {
+    pub queue_len: usize,
+    pub busy_workers: usize,
+}
+
+/// Receives the stream of events from a running simulation.
+pub trait Observer {
+    fn on_event(&mut self, tick: u64, event: &Event);
+
+    /// Called at the end of every tick, after all events of that tick were delivered.
+    fn on_tick_end(&mut self, tick: u64, state: &SystemState);
+
+    /// Prints whatever the observer gathered during the simulation, at the end of the summary.
+    fn print_summary(&self) {}
+
+    /// What the observer gathered so far, for a checkpoint. Observers that gather nothing have nothing to save.
+    fn checkpoint(&mut self) -> Result<Value, String> {
+        Ok(Value::Null)
+    }
+
+    /// Picks up from what the observer had gathered at a checkpoint, for a resumed run.
+    fn resume(&mut self, _state: Value) -> Result<(), String> {
+        Ok(())
+    }
+}
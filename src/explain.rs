@@ -0,0 +1,223 @@
+use crate::checkpoint;
+use crate::events::{Event, Observer, SystemState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Maximum number of narrated events, so that a system oscillating in and out of trouble doesn't
+/// drown the summary.
+const MAX_NARRATED_EVENTS: usize = 100;
+
+/// Share of arrivals that have to be retries for the narrator to call out a retry storm.
+const RETRY_STORM_SHARE: f64 = 0.5;
+
+/// Turns the event stream into a narrated timeline of notable moments, such as the queue filling up or
+/// workers starting to process requests whose clients already gave up. Meant to explain the raw numbers
+/// to people new to queueing.
+#[derive(Serialize, Deserialize)]
+pub struct Narrator {
+    pool_names: Vec<String>,
+    /// Length of the window retries are counted in. Matches the request timeout, which is how long it takes
+    /// for the consequences of a condition to play out.
+    window_ticks: u64,
+    /// How long a condition has to be absent for the narrator to declare it over. Several windows, so that
+    /// a system oscillating at the edge of trouble doesn't get narrated on every swing.
+    quiet_ticks: u64,
+    last_rejection: Vec<Option<u64>>,
+    last_wasted_dequeue: Vec<Option<u64>>,
+    backlogged: bool,
+    peak_busy_workers: usize,
+    window_start: u64,
+    window_arrivals: u64,
+    window_retries: u64,
+    retry_storm: bool,
+    lines: Vec<String>,
+    omitted_lines: usize,
+}
+
+impl Narrator {
+    pub fn new(pool_names: Vec<String>, window_ticks: u64) -> Narrator {
+        Narrator {
+            last_rejection: vec![None; pool_names.len()],
+            last_wasted_dequeue: vec![None; pool_names.len()],
+            pool_names,
+            window_ticks: window_ticks.max(1),
+            quiet_ticks: window_ticks.max(1) * 10,
+            backlogged: false,
+            peak_busy_workers: 0,
+            window_start: 0,
+            window_arrivals: 0,
+            window_retries: 0,
+            retry_storm: false,
+            lines: Vec::new(),
+            omitted_lines: 0,
+        }
+    }
+
+    fn narrate(&mut self, tick: u64, text: String) {
+        if self.lines.len() < MAX_NARRATED_EVENTS {
+            self.lines
+                .push(format!("tick {}: {}", format_tick(tick), text));
+        } else {
+            self.omitted_lines += 1;
+        }
+    }
+
+    /// Describes the pool for narration, omitting it altogether when there is just one.
+    fn pool(&self, pool: usize) -> String {
+        if self.pool_names.len() > 1 {
+            format!(" of pool {}", self.pool_names[pool])
+        } else {
+            String::new()
+        }
+    }
+}
+
+impl Observer for Narrator {
+    fn on_event(&mut self, tick: u64, event: &Event) {
+        match *event {
+            Event::Arrived { .. } => self.window_arrivals += 1,
+            Event::Retried { .. } => self.window_retries += 1,
+            Event::Rejected { pool, .. } | Event::Shed { pool, .. } => {
+                if self.last_rejection[pool].is_none() {
+                    let text = format!(
+                        "queue{} reached capacity; rejections began",
+                        self.pool(pool)
+                    );
+                    self.narrate(tick, text);
+                }
+                self.last_rejection[pool] = Some(tick);
+            }
+            Event::Dequeued {
+                pool, timed_out, ..
+            } => {
+                if !timed_out {
+                    return;
+                }
+                if self.last_wasted_dequeue[pool].is_none() {
+                    let text = format!(
+                        "a worker{} picked up a request that already exceeded its timeout — dequeued work is now wasted",
+                        self.pool(pool)
+                    );
+                    self.narrate(tick, text);
+                }
+                self.last_wasted_dequeue[pool] = Some(tick);
+            }
+            Event::SpikeStarted { multiplier } => self.narrate(
+                tick,
+                format!("latency spike began; requests take {}x longer", multiplier),
+            ),
+            Event::SpikeEnded => self.narrate(tick, "latency spike ended".to_string()),
+            Event::DisciplineSwitched { lifo } => self.narrate(
+                tick,
+                format!(
+                    "queue discipline switched to {}",
+                    if lifo { "LIFO" } else { "FIFO" }
+                ),
+            ),
+            Event::Admitted { .. }
+            | Event::Balked { .. }
+            | Event::CacheHit { .. }
+            | Event::Coalesced { .. }
+            | Event::Completed { .. }
+            | Event::Killed { .. }
+            | Event::Abandoned { .. } => {}
+        }
+    }
+
+    fn on_tick_end(&mut self, tick: u64, state: &SystemState) {
+        for pool in 0..self.pool_names.len() {
+            if let Some(last_rejection) = self.last_rejection[pool] {
+                if tick - last_rejection >= self.quiet_ticks {
+                    self.last_rejection[pool] = None;
+                    let text = format!(
+                        "no rejections{} for {} ticks; the queue has room again",
+                        self.pool(pool),
+                        format_tick(self.quiet_ticks)
+                    );
+                    self.narrate(tick, text);
+                }
+            }
+            if let Some(last_wasted_dequeue) = self.last_wasted_dequeue[pool] {
+                if tick - last_wasted_dequeue >= self.quiet_ticks {
+                    self.last_wasted_dequeue[pool] = None;
+                    let text = format!(
+                        "no timed out requests picked up{} for {} ticks; workers are doing useful work again",
+                        self.pool(pool),
+                        format_tick(self.quiet_ticks)
+                    );
+                    self.narrate(tick, text);
+                }
+            }
+        }
+
+        // The queue is considered backlogged once it holds more requests than there are workers, i.e.
+        // every new request will have to wait for at least one full service time.
+        self.peak_busy_workers = self.peak_busy_workers.max(state.busy_workers);
+        if !self.backlogged && state.queue_len > self.peak_busy_workers.max(1) {
+            self.backlogged = true;
+            self.narrate(
+                tick,
+                "queue is building up faster than workers can drain it".to_string(),
+            );
+        } else if self.backlogged && state.queue_len == 0 {
+            self.backlogged = false;
+            self.narrate(tick, "queue drained; the system recovered".to_string());
+        }
+
+        if tick + 1 - self.window_start >= self.window_ticks {
+            let retry_share = if self.window_arrivals > 0 {
+                self.window_retries as f64 / self.window_arrivals as f64
+            } else {
+                0.0
+            };
+            if !self.retry_storm && retry_share >= RETRY_STORM_SHARE {
+                self.retry_storm = true;
+                let text = format!(
+                    "retries make up {:.0}% of incoming requests — clients are amplifying the load",
+                    retry_share * 100.0
+                );
+                self.narrate(tick, text);
+            } else if self.retry_storm && retry_share < RETRY_STORM_SHARE / 5.0 {
+                self.retry_storm = false;
+                self.narrate(tick, "retries subsided".to_string());
+            }
+            self.window_start = tick + 1;
+            self.window_arrivals = 0;
+            self.window_retries = 0;
+        }
+    }
+
+    fn print_summary(&self) {
+        println!("Timeline:");
+        if self.lines.is_empty() {
+            println!("  nothing notable happened");
+        }
+        for line in self.lines.iter() {
+            println!("  {}", line);
+        }
+        if self.omitted_lines > 0 {
+            println!("  ... and {} more", self.omitted_lines);
+        }
+    }
+
+    fn checkpoint(&mut self) -> Result<Value, String> {
+        checkpoint::save(self)
+    }
+
+    fn resume(&mut self, state: Value) -> Result<(), String> {
+        checkpoint::restore(self, state)
+    }
+}
+
+/// Formats a tick with thousands separated by underscores, e.g. 52_300.
+fn format_tick(tick: u64) -> String {
+    let digits = tick.to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push('_');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
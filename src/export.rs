@@ -0,0 +1,392 @@
+use crate::metadata;
+use crate::pool;
+use crate::queue::Discipline;
+use crate::validation::ConfigError;
+use crate::Opt;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Formats the configured topology can be exported to, for cross-validating results with other tools.
+#[derive(Debug, Clone, Copy)]
+pub enum ExportFormat {
+    /// Python script for the PDQ (Pretty Damn Quick) analytic solver.
+    Pdq,
+    /// Python script for the ciw discrete event simulation library.
+    Ciw,
+    /// Python scaffolding for a SimPy discrete event simulation.
+    SimPy,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pdq" => Ok(ExportFormat::Pdq),
+            "ciw" => Ok(ExportFormat::Ciw),
+            "simpy" => Ok(ExportFormat::SimPy),
+            _ => Err(format!(
+                "Unknown export format '{}', expected pdq, ciw or simpy",
+                s
+            )),
+        }
+    }
+}
+
+/// Queueing network equivalent of the configured simulation: one station per pool, one open workload per
+/// request class. The tick is used as the unit of time.
+struct Network {
+    stations: Vec<Station>,
+    workloads: Vec<Workload>,
+    mean_service_time: f64,
+    service_time_deviation: f64,
+    timeout: u32,
+    lifo: bool,
+}
+
+struct Station {
+    name: String,
+    servers: u16,
+    queue_size: usize,
+}
+
+struct Workload {
+    name: String,
+    arrival_rate: f64,
+    station: usize,
+}
+
+impl Network {
+    fn new(opt: &Opt) -> Result<Network, ConfigError> {
+        let classes = opt.classes();
+        let pools = opt.pools();
+        let routes = pool::route_classes(&pools, &classes).unwrap();
+        let total_weight: f64 = classes.iter().map(|c| c.weight).sum();
+        let service_time = opt.service_time().unwrap();
+        let arrival_rate = match opt.arrival_trace(&classes).unwrap() {
+            Some(trace) => trace.mean_rate(opt.simulation_ticks),
+            None => opt.request_arrival_rate(),
+        };
+        let service_time_deviation = service_time.std_dev();
+        if !service_time_deviation.is_finite() {
+            return Err(ConfigError::Conflict {
+                problem: "Service times with an infinite variance can't be exported",
+                fix: "Use a Pareto shape over 2, or another --latency_distribution",
+            });
+        }
+
+        Ok(Network {
+            stations: pools
+                .iter()
+                .map(|p| Station {
+                    name: p.name.clone(),
+                    servers: p.workers,
+                    queue_size: p.queue_size,
+                })
+                .collect(),
+            workloads: classes
+                .iter()
+                .zip(routes)
+                .map(|(c, station)| Workload {
+                    name: c.name.clone(),
+                    // Each fanned out request puts a sub-request into the station.
+                    arrival_rate: arrival_rate * c.weight / total_weight * opt.fan_out as f64,
+                    station,
+                })
+                .collect(),
+            mean_service_time: service_time.mean(),
+            service_time_deviation,
+            timeout: opt.request_timeout,
+            lifo: opt.discipline() == Discipline::Lifo,
+        })
+    }
+}
+
+/// Renders the topology configured by the options in the specified format.
+pub fn export(opt: &Opt, format: ExportFormat) -> Result<String, ConfigError> {
+    let network = Network::new(opt)?;
+    let mut out = String::new();
+    writeln!(
+        out,
+        "# Generated by queueingsimulator. Time unit is one simulator tick."
+    )
+    .unwrap();
+    out.push_str(&metadata::comment_lines(opt));
+    writeln!(
+        out,
+        "# Not represented: client retries (retry probability {} after rejections, {} after timeouts), latency spikes, stalls, stragglers, client abandonment, balking, multiple queues, network latency, client connection pools, request sizes, contention, database queries, second resources, queue discipline switches, mitigations, autoscaling and fan-out quorums.",
+        opt.retry_probabilities().rejected,
+        opt.retry_probabilities().timed_out
+    )
+    .unwrap();
+    match format {
+        ExportFormat::Pdq => export_pdq(&network, &mut out),
+        ExportFormat::Ciw => export_ciw(&network, &mut out),
+        ExportFormat::SimPy => export_simpy(&network, &mut out),
+    }
+    .unwrap();
+    Ok(out)
+}
+
+fn export_pdq(network: &Network, out: &mut String) -> std::fmt::Result {
+    writeln!(
+        out,
+        "# PDQ assumes unbounded queues and exponential service times."
+    )?;
+    writeln!(out, "import pdq")?;
+    writeln!(out)?;
+    writeln!(out, "pdq.Init(\"queueingsimulator\")")?;
+    writeln!(out, "pdq.SetWUnit(\"Requests\")")?;
+    writeln!(out, "pdq.SetTUnit(\"Ticks\")")?;
+    let discipline = if network.lifo { "LCFS" } else { "FCFS" };
+    for station in network.stations.iter() {
+        writeln!(
+            out,
+            "pdq.CreateMultiNode({}, \"{}\", pdq.CEN, pdq.{})",
+            station.servers, station.name, discipline
+        )?;
+    }
+    for workload in network.workloads.iter() {
+        writeln!(
+            out,
+            "pdq.CreateOpen(\"{}\", {})",
+            workload.name, workload.arrival_rate
+        )?;
+        for (index, station) in network.stations.iter().enumerate() {
+            let demand = if index == workload.station {
+                network.mean_service_time
+            } else {
+                0.0
+            };
+            writeln!(
+                out,
+                "pdq.SetDemand(\"{}\", \"{}\", {})",
+                station.name, workload.name, demand
+            )?;
+        }
+    }
+    writeln!(out, "pdq.Solve(pdq.CANON)")?;
+    writeln!(out, "pdq.Report()")
+}
+
+fn export_ciw(network: &Network, out: &mut String) -> std::fmt::Result {
+    writeln!(
+        out,
+        "# ciw reneging drops timed out requests from the queue, while the simulator still processes them."
+    )?;
+    writeln!(out, "import ciw")?;
+    writeln!(out)?;
+    let dists = |f: &dyn Fn(&Workload, usize) -> String| -> String {
+        network
+            .workloads
+            .iter()
+            .map(|w| {
+                let per_node: Vec<String> = (0..network.stations.len()).map(|n| f(w, n)).collect();
+                format!("        \"{}\": [{}],", w.name, per_node.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    writeln!(out, "network = ciw.create_network(")?;
+    writeln!(out, "    arrival_distributions={{")?;
+    writeln!(
+        out,
+        "{}",
+        dists(&|w, n| if n == w.station {
+            format!("ciw.dists.Exponential(rate={})", w.arrival_rate)
+        } else {
+            "None".to_string()
+        })
+    )?;
+    writeln!(out, "    }},")?;
+    writeln!(out, "    service_distributions={{")?;
+    writeln!(
+        out,
+        "{}",
+        dists(&|_, _| format!(
+            "ciw.dists.Normal(mean={}, sd={})",
+            network.mean_service_time, network.service_time_deviation
+        ))
+    )?;
+    writeln!(out, "    }},")?;
+    writeln!(out, "    reneging_time_distributions={{")?;
+    writeln!(
+        out,
+        "{}",
+        dists(&|_, _| format!("ciw.dists.Deterministic(value={})", network.timeout))
+    )?;
+    writeln!(out, "    }},")?;
+    let servers: Vec<String> = network
+        .stations
+        .iter()
+        .map(|s| s.servers.to_string())
+        .collect();
+    writeln!(out, "    number_of_servers=[{}],", servers.join(", "))?;
+    let capacities: Vec<String> = network
+        .stations
+        .iter()
+        .map(|s| s.queue_size.to_string())
+        .collect();
+    writeln!(out, "    queue_capacities=[{}],", capacities.join(", "))?;
+    let discipline = if network.lifo { "LIFO" } else { "FIFO" };
+    let disciplines = vec![format!("ciw.disciplines.{}", discipline); network.stations.len()];
+    writeln!(out, "    service_disciplines=[{}],", disciplines.join(", "))?;
+    writeln!(out, ")")?;
+    writeln!(out)?;
+    writeln!(out, "ciw.seed(0)")?;
+    writeln!(out, "simulation = ciw.Simulation(network)")?;
+    writeln!(out, "simulation.simulate_until_max_time(1000000)")?;
+    writeln!(out, "records = simulation.get_all_records()")?;
+    writeln!(
+        out,
+        "served = [r for r in records if r.record_type == \"service\"]"
+    )?;
+    writeln!(
+        out,
+        "rejected = sum(len(r) for node in simulation.rejection_dict.values() for r in node.values())"
+    )?;
+    writeln!(
+        out,
+        "print(\"Completed: {{}}, rejected: {{}}\".format(len(served), rejected))"
+    )
+}
+
+fn export_simpy(network: &Network, out: &mut String) -> std::fmt::Result {
+    writeln!(out, "import random")?;
+    writeln!(out, "import simpy")?;
+    writeln!(out)?;
+    writeln!(out, "TIMEOUT = {}", network.timeout)?;
+    writeln!(out, "MEAN_SERVICE_TIME = {}", network.mean_service_time)?;
+    writeln!(
+        out,
+        "SERVICE_TIME_DEVIATION = {}",
+        network.service_time_deviation
+    )?;
+    writeln!(out, "# name: (workers, queue size)")?;
+    writeln!(out, "STATIONS = {{")?;
+    for station in network.stations.iter() {
+        writeln!(
+            out,
+            "    \"{}\": ({}, {}),",
+            station.name, station.servers, station.queue_size
+        )?;
+    }
+    writeln!(out, "}}")?;
+    writeln!(out, "# name: (arrival rate, station)")?;
+    writeln!(out, "WORKLOADS = {{")?;
+    for workload in network.workloads.iter() {
+        writeln!(
+            out,
+            "    \"{}\": ({}, \"{}\"),",
+            workload.name, workload.arrival_rate, network.stations[workload.station].name
+        )?;
+    }
+    writeln!(out, "}}")?;
+    writeln!(out, "stats = {{\"total\": 0, \"failed\": 0}}")?;
+    writeln!(out)?;
+    writeln!(out)?;
+    writeln!(out, "def request(env, workers, queue_size):")?;
+    writeln!(out, "    stats[\"total\"] += 1")?;
+    writeln!(out, "    arrival = env.now")?;
+    writeln!(
+        out,
+        "    if workers.count == workers.capacity and len(workers.queue) >= queue_size:"
+    )?;
+    writeln!(out, "        stats[\"failed\"] += 1")?;
+    writeln!(out, "        return")?;
+    if network.lifo {
+        writeln!(
+            out,
+            "    # TODO: the simulator was configured with a LIFO queue, simpy.Resource is FIFO."
+        )?;
+    }
+    writeln!(out, "    with workers.request() as worker:")?;
+    writeln!(out, "        yield worker")?;
+    writeln!(
+        out,
+        "        yield env.timeout(max(0.0, random.normalvariate(MEAN_SERVICE_TIME, SERVICE_TIME_DEVIATION)))"
+    )?;
+    writeln!(out, "    if env.now - arrival >= TIMEOUT:")?;
+    writeln!(out, "        stats[\"failed\"] += 1")?;
+    writeln!(out)?;
+    writeln!(out)?;
+    writeln!(out, "def arrivals(env, rate, workers, queue_size):")?;
+    writeln!(out, "    while True:")?;
+    writeln!(out, "        yield env.timeout(random.expovariate(rate))")?;
+    writeln!(
+        out,
+        "        env.process(request(env, workers, queue_size))"
+    )?;
+    writeln!(out)?;
+    writeln!(out)?;
+    writeln!(out, "env = simpy.Environment()")?;
+    writeln!(
+        out,
+        "pools = {{name: (simpy.Resource(env, capacity=workers), queue_size) for name, (workers, queue_size) in STATIONS.items()}}"
+    )?;
+    writeln!(out, "for rate, station in WORKLOADS.values():")?;
+    writeln!(out, "    workers, queue_size = pools[station]")?;
+    writeln!(
+        out,
+        "    env.process(arrivals(env, rate, workers, queue_size))"
+    )?;
+    writeln!(out, "env.run(until=1000000)")?;
+    writeln!(
+        out,
+        "print(\"Failure rate: {{:.2f}}%\".format(stats[\"failed\"] / stats[\"total\"] * 100))"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    fn opt(args: &str) -> Opt {
+        Opt::from_iter(std::iter::once("queueingsimulator").chain(args.split_whitespace()))
+    }
+
+    #[test]
+    fn service_times_with_an_infinite_variance_are_refused() {
+        let pareto = opt("-r 0.1 --latency_distribution pareto:1.5");
+        assert!(export(&pareto, ExportFormat::Ciw).is_err());
+        let pareto = opt("-r 0.1 --latency_distribution pareto:3");
+        assert!(export(&pareto, ExportFormat::Ciw).is_ok());
+    }
+
+    #[test]
+    fn pdq_has_a_station_per_pool_and_a_workload_per_class() {
+        let opt = opt(
+            "-r 0.2 --mean_latency 5 --class api:0.5 --class batch:0.5 --pool fast:4:100:api --pool slow:2:50:batch",
+        );
+        let script = export(&opt, ExportFormat::Pdq).unwrap();
+        for line in [
+            "pdq.CreateMultiNode(4, \"fast\", pdq.CEN, pdq.FCFS)",
+            "pdq.CreateMultiNode(2, \"slow\", pdq.CEN, pdq.FCFS)",
+            "pdq.CreateOpen(\"api\", 0.1)",
+            "pdq.SetDemand(\"fast\", \"api\", 5)",
+            "pdq.SetDemand(\"slow\", \"api\", 0)",
+            "pdq.SetDemand(\"slow\", \"batch\", 5)",
+        ] {
+            assert!(script.lines().any(|l| l == line), "missing {}", line);
+        }
+    }
+
+    #[test]
+    fn ciw_keeps_the_discipline_capacities_and_fan_out() {
+        let script = export(
+            &opt("-r 0.1 -w 3 -q 20 --discipline lifo --fan_out 2 --timeout 30"),
+            ExportFormat::Ciw,
+        )
+        .unwrap();
+        for line in [
+            "        \"default\": [ciw.dists.Exponential(rate=0.2)],",
+            "        \"default\": [ciw.dists.Deterministic(value=30)],",
+            "    number_of_servers=[3],",
+            "    queue_capacities=[20],",
+            "    service_disciplines=[ciw.disciplines.LIFO],",
+        ] {
+            assert!(script.lines().any(|l| l == line), "missing {}", line);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::histogram::Histogram;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Tracks requests that were fanned out into several sub-requests. A fanned out request succeeds as
+/// soon as a quorum of its sub-requests succeeds, and fails as soon as too many of them failed for the
+/// quorum to still be reachable. Since the request has to wait for the slowest sub-request in its quorum,
+/// its latency is amplified compared to the latency of the individual sub-requests.
+#[derive(Serialize, Deserialize)]
+pub struct FanOut {
+    degree: u32,
+    quorum: u32,
+    pending: HashMap<u64, PendingRequest>,
+    sub_request_latency: Histogram,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PendingRequest {
+    succeeded: u32,
+    failed: u32,
+}
+
+pub enum Outcome {
+    /// Quorum was reached, after the specified number of ticks.
+    Succeeded(u64),
+    Failed,
+}
+
+impl FanOut {
+    pub fn new(degree: u32, quorum: u32) -> FanOut {
+        FanOut {
+            degree,
+            quorum,
+            pending: HashMap::new(),
+            sub_request_latency: Histogram::new(),
+        }
+    }
+
+    pub fn degree(&self) -> u32 {
+        self.degree
+    }
+
+    /// Starts tracking a new fanned out request with the specified id, which its sub-requests refer to.
+    pub fn start(&mut self, id: u64) {
+        self.pending.insert(
+            id,
+            PendingRequest {
+                succeeded: 0,
+                failed: 0,
+            },
+        );
+    }
+
+    /// Records completion of a single sub-request, with its latency if it succeeded or None if it
+    /// failed. Returns the outcome of the parent request, if it got decided by this sub-request.
+    pub fn sub_request_finished(&mut self, parent: u64, latency: Option<u64>) -> Option<Outcome> {
+        if let Some(latency) = latency {
+            self.sub_request_latency.record(latency);
+        }
+
+        // Parent may have been decided already, in which case this sub-request was wasted work.
+        let pending = self.pending.get_mut(&parent)?;
+        let outcome = match latency {
+            Some(latency) => {
+                pending.succeeded += 1;
+                if pending.succeeded < self.quorum {
+                    return None;
+                }
+                Outcome::Succeeded(latency)
+            }
+            None => {
+                pending.failed += 1;
+                if self.degree - pending.failed >= self.quorum {
+                    return None;
+                }
+                Outcome::Failed
+            }
+        };
+
+        self.pending.remove(&parent);
+        Some(outcome)
+    }
+
+    /// Prints fan-out specific statistics. Tail latency amplification is the ratio between the p99 latency
+    /// of the fanned out requests and the p99 latency of the individual sub-requests.
+    pub fn print_summary(&self, request_latency: &Histogram) {
+        println!(
+            "Fan-out: {} sub-requests per request, quorum of {}.",
+            self.degree, self.quorum
+        );
+        println!(
+            "Sub-request latency: {}",
+            self.sub_request_latency.format_percentiles()
+        );
+        println!("Request latency: {}", request_latency.format_percentiles());
+        let sub_request_p99 = self.sub_request_latency.percentile(99.0);
+        if sub_request_p99 > 0 {
+            println!(
+                "Tail latency amplification (p99): {:.2}x",
+                request_latency.percentile(99.0) as f64 / sub_request_p99 as f64
+            );
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use crate::class::ClassSpec;
+use crate::subprocess::Subprocess;
+use crate::trace::{ArrivalTrace, Record, TracedArrival};
+use crate::validation::ConfigError;
+use serde::{Deserialize, Serialize};
+
+/// Number of ticks worth of arrivals requested from the generator at once.
+const CHUNK_TICKS: u64 = 1000;
+
+/// Custom arrival generator, running as a subprocess. Lets proprietary traffic models drive the simulator
+/// without being part of it.
+///
+/// The simulator asks for the arrivals of the upcoming ticks by writing a single line of JSON to the standard
+/// input of the generator, e.g. `{"start": 0, "end": 1000}`. The generator answers with a single line of JSON
+/// on its standard output, with the arrivals between the start (inclusive) and the end (exclusive) tick, in
+/// the same format as the records of an NDJSON arrival trace:
+///
+/// `{"arrivals": [{"tick": 3, "count": 2}, {"time": 7.5, "class": "api"}]}`
+pub struct Generator {
+    command: String,
+    process: Subprocess,
+    classes: Vec<ClassSpec>,
+    /// Arrivals of the current chunk, and the first tick after it.
+    chunk: ArrivalTrace,
+    chunk_end: u64,
+}
+
+#[derive(Serialize)]
+struct ArrivalsRequest {
+    start: u64,
+    end: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ArrivalsResponse {
+    arrivals: Vec<Record>,
+}
+
+impl Generator {
+    /// Starts the generator, using the shell to run the specified command.
+    pub fn spawn(command: &str, classes: &[ClassSpec]) -> Result<Generator, String> {
+        Ok(Generator {
+            command: command.to_string(),
+            process: Subprocess::spawn(command)?,
+            classes: classes.to_vec(),
+            chunk: ArrivalTrace::new(Vec::new()),
+            chunk_end: 0,
+        })
+    }
+
+    /// Arrivals generated for the specified tick. Ticks have to be generated in order. Exits if the generator
+    /// fails to generate them.
+    pub fn arrivals_at(&mut self, tick: u64) -> &[TracedArrival] {
+        if tick >= self.chunk_end {
+            let end = tick + CHUNK_TICKS;
+            let arrivals = self
+                .request(tick, end)
+                .unwrap_or_else(|e| ConfigError::io("run generator", &self.command, e).exit());
+            self.chunk = ArrivalTrace::new(arrivals);
+            self.chunk_end = end;
+        }
+        self.chunk.arrivals_at(tick)
+    }
+
+    fn request(&mut self, start: u64, end: u64) -> Result<Vec<TracedArrival>, String> {
+        let request = serde_json::to_string(&ArrivalsRequest { start, end }).unwrap();
+        let line = self.process.request(&request)?;
+        let response: ArrivalsResponse = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid response to ticks {}..{}: {}", start, end, e))?;
+
+        let mut arrivals = Vec::new();
+        for record in response.arrivals {
+            record
+                .push_arrivals(&self.classes, &mut arrivals)
+                .map_err(|e| format!("{} in response to ticks {}..{}", e, start, end))?;
+        }
+        if let Some(arrival) = arrivals.iter().find(|a| a.tick < start || a.tick >= end) {
+            return Err(format!(
+                "Arrival at tick {} in response to ticks {}..{}",
+                arrival.tick, start, end
+            ));
+        }
+        Ok(arrivals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation;
+    use crate::Opt;
+    use structopt::StructOpt;
+
+    /// Generates two requests at the start of every chunk, and one of the api class 7.5 ticks into it.
+    const GENERATOR: &str = r#"while read line; do
+        start=$(echo "$line" | sed 's/.*"start":\([0-9]*\).*/\1/')
+        echo "{\"arrivals\": [{\"tick\": $start, \"count\": 2}, {\"time\": $((start + 7)).5, \"class\": \"api\"}]}"
+    done"#;
+
+    fn classes() -> Vec<ClassSpec> {
+        ["web", "api"]
+            .iter()
+            .map(|name| ClassSpec {
+                name: name.to_string(),
+                weight: 1.0,
+                timeout: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arrivals_are_requested_a_chunk_at_a_time() {
+        let mut generator = Generator::spawn(GENERATOR, &classes()).unwrap();
+        assert_eq!(generator.arrivals_at(0).len(), 2);
+        assert!(generator.arrivals_at(1).is_empty());
+        let arrival = &generator.arrivals_at(7)[0];
+        assert_eq!((arrival.offset, arrival.class), (Some(0.5), Some(1)));
+        assert_eq!(generator.arrivals_at(CHUNK_TICKS).len(), 2);
+        assert_eq!(generator.arrivals_at(CHUNK_TICKS + 7).len(), 1);
+    }
+
+    #[test]
+    fn invalid_responses_fail_the_request() {
+        let mut generator = Generator::spawn("while read line; do echo '{}'; done", &[]).unwrap();
+        assert!(generator.request(0, CHUNK_TICKS).is_err());
+
+        // Arrivals outside of the requested ticks.
+        let mut generator = Generator::spawn(
+            r#"while read line; do echo '{"arrivals": [{"tick": 5000, "count": 1}]}'; done"#,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            generator.request(0, CHUNK_TICKS).err(),
+            Some("Arrival at tick 5000 in response to ticks 0..1000".to_string())
+        );
+    }
+
+    #[test]
+    fn generated_arrivals_drive_the_simulation() {
+        let args = [
+            "queueingsimulator",
+            "--generator",
+            GENERATOR,
+            "--class",
+            "web:0.5",
+            "--class",
+            "api:0.5",
+            "--simulation_time",
+            "5000",
+            "--seed",
+            "1",
+        ];
+        let stats = simulation::run(&Opt::from_iter(args)).stats;
+        assert_eq!(stats.total_requests, 15);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::simulation::{self, Stats};
+use crate::Opt;
+use std::fmt;
+use std::str::FromStr;
+
+/// Relative precision the highest sustainable arrival rate is searched to.
+const PRECISION: f64 = 0.01;
+
+/// Highest multiple of the configured arrival rate the search tries.
+const MAX_MULTIPLIER: f64 = 64.0;
+
+/// Metric of a service level objective.
+#[derive(Debug, Clone, Copy)]
+enum Measure {
+    /// Failure rate, in percent.
+    FailureRate,
+    /// Mean latency of the successful requests, in ticks.
+    Mean,
+    /// Latency percentile of the successful requests, in ticks.
+    Percentile(f64),
+}
+
+/// Service level objective, as `metric<threshold`, where the metric is failure_rate (in percent), or mean or a
+/// latency percentile like p99 (in ticks), e.g. `p99<300`.
+#[derive(Debug, Clone)]
+pub struct Objective {
+    measure: Measure,
+    threshold: f64,
+}
+
+impl Objective {
+    pub fn is_met(&self, stats: &Stats) -> bool {
+        let value = match self.measure {
+            Measure::FailureRate => stats.failure_rate(),
+            Measure::Mean => stats.latency.mean(),
+            Measure::Percentile(percentile) => stats.latency.percentile(percentile) as f64,
+        };
+        value < self.threshold
+    }
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (metric, threshold) = s
+            .split_once('<')
+            .ok_or_else(|| format!("Expected objective as metric<threshold, got '{}'", s))?;
+        let measure = match metric.trim() {
+            "failure_rate" => Measure::FailureRate,
+            "mean" => Measure::Mean,
+            other => match other.strip_prefix('p').map(str::parse) {
+                Some(Ok(percentile)) if (0.0..=100.0).contains(&percentile) => {
+                    Measure::Percentile(percentile)
+                }
+                _ => {
+                    return Err(format!(
+                        "Unknown objective metric '{}', expected failure_rate, mean or a percentile like p99",
+                        other
+                    ))
+                }
+            },
+        };
+        Ok(Objective {
+            measure,
+            threshold: threshold
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid threshold in objective '{}'", s))?,
+        })
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.measure {
+            Measure::FailureRate => write!(f, "failure_rate<{}", self.threshold),
+            Measure::Mean => write!(f, "mean<{}", self.threshold),
+            Measure::Percentile(percentile) => write!(f, "p{}<{}", percentile, self.threshold),
+        }
+    }
+}
+
+/// Reports how much more sustained load the configured system could absorb while still meeting its
+/// objectives. Searches for the highest arrival rate that meets them with shorter reruns of the simulation,
+/// all with the same seed, first doubling the rate and then bisecting.
+pub fn print_report(opt: &Opt, ticks: u32) {
+    let rate = opt.request_arrival_rate();
+    let meets = |multiplier: f64| {
+        let mut scaled = opt.clone();
+        scaled.request_arrival_rate = Some(rate * multiplier);
+        scaled.simulation_ticks = ticks;
+        scaled.warmup_ticks = scaled.warmup_ticks.min(ticks as u64 / 2);
+        scaled.headroom_objectives.clear();
+        scaled.service_time_caps.clear();
+        scaled.batching_report = false;
+        scaled.explain = false;
+        scaled.queue_distribution = false;
+        scaled.littles_law = false;
+        scaled.telemetry = false;
+        scaled.tui = false;
+        scaled.metrics_address = None;
+        scaled.speed = None;
+        scaled.progress = false;
+        scaled.time_series = None;
+        scaled.snapshots = None;
+        scaled.event_log = None;
+        scaled.chrome_trace = None;
+        scaled.checkpoint_every = None;
+        scaled.resume = false;
+        scaled.html_report = None;
+        scaled.plot = None;
+        scaled.alerts.clear();
+        let stats = simulation::run(&scaled).stats;
+        opt.headroom_objectives.iter().all(|o| o.is_met(&stats))
+    };
+    let objectives: Vec<String> = opt
+        .headroom_objectives
+        .iter()
+        .map(Objective::to_string)
+        .collect();
+    let objectives = objectives.join(", ");
+
+    if !meets(1.0) {
+        println!(
+            "Headroom: none, the configured arrival rate of {} already misses {}",
+            rate, objectives
+        );
+        return;
+    }
+    let (mut low, mut high) = (1.0, 2.0);
+    while meets(high) {
+        low = high;
+        high *= 2.0;
+        if high > MAX_MULTIPLIER {
+            println!(
+                "Headroom: over {:.0}%, {} still met at an arrival rate of {}",
+                (low - 1.0) * 100.0,
+                objectives,
+                rate * low
+            );
+            return;
+        }
+    }
+    while (high - low) / low > PRECISION {
+        let middle = (low + high) / 2.0;
+        if meets(middle) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    println!(
+        "Headroom: {:.0}%, {} met up to an arrival rate of {:.4}, compared to the configured {}",
+        (low - 1.0) * 100.0,
+        objectives,
+        rate * low,
+        rate
+    );
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+
+/// Exact histogram of small non-negative integer values, such as latencies measured in ticks. Values are
+/// counted in a bucket per distinct value, which keeps percentiles exact at the cost of memory proportional
+/// to the largest recorded value.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl Histogram {
+    pub fn new() -> Histogram {
+        Histogram::default()
+    }
+
+    pub fn record(&mut self, value: u64) {
+        self.record_many(value, 1);
+    }
+
+    /// Records the same value the specified number of times.
+    pub fn record_many(&mut self, value: u64, count: u64) {
+        let index = value as usize;
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += count;
+        self.total += count;
+    }
+
+    /// Adds all the values recorded by the other histogram.
+    pub fn merge(&mut self, other: &Histogram) {
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+        self.total += other.total;
+    }
+
+    /// Values recorded since the histogram was the specified earlier copy of itself.
+    pub fn since(&self, earlier: &Histogram) -> Histogram {
+        let mut counts = self.counts.clone();
+        for (count, earlier_count) in counts.iter_mut().zip(earlier.counts.iter()) {
+            *count -= earlier_count;
+        }
+        Histogram {
+            counts,
+            total: self.total - earlier.total,
+        }
+    }
+
+    /// Number of recorded values.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Number of times the specified value was recorded.
+    pub fn count(&self, value: u64) -> u64 {
+        self.counts.get(value as usize).copied().unwrap_or(0)
+    }
+
+    /// Number of recorded values less than or equal to the specified one.
+    pub fn count_at_most(&self, value: u64) -> u64 {
+        let end = (value as usize).saturating_add(1).min(self.counts.len());
+        self.counts[..end].iter().sum()
+    }
+
+    /// Mean of the recorded values. Returns 0 for an empty histogram.
+    pub fn mean(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        let sum: u64 = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(value, count)| value as u64 * count)
+            .sum();
+        sum as f64 / self.total as f64
+    }
+
+    pub fn min(&self) -> u64 {
+        self.counts.iter().position(|&c| c > 0).unwrap_or(0) as u64
+    }
+
+    pub fn max(&self) -> u64 {
+        self.counts.iter().rposition(|&c| c > 0).unwrap_or(0) as u64
+    }
+
+    /// Smallest recorded value such that at least `percentile` percent of all values are less than or
+    /// equal to it. Returns 0 for an empty histogram.
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        let rank = ((percentile / 100.0) * self.total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (value, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return value as u64;
+            }
+        }
+        0
+    }
+
+    /// Formats the commonly reported percentiles, e.g. for printing in the summary.
+    pub fn format_percentiles(&self) -> String {
+        format!(
+            "p50={} p90={} p99={} p99.9={} max={}",
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.percentile(99.9),
+            self.max()
+        )
+    }
+}
@@ -0,0 +1,43 @@
+use rand::distributions::Distribution;
+use rand::Rng;
+use rand_distr::Zipf;
+
+/// Keys of the requests, following a Zipf distribution, for the features that care about what a request is
+/// for rather than just how long it takes: the consistent hashing of the load balancer and the cache.
+pub struct Keys {
+    /// Distribution of the keys, the lower ones the more popular.
+    distribution: Zipf<f64>,
+    count: u64,
+    skew: f64,
+}
+
+impl Keys {
+    /// Keys from 1 to the specified number, the higher the exponent the more popular the lower ones.
+    pub fn new(count: u64, skew: f64) -> Keys {
+        Keys {
+            distribution: Zipf::new(count, skew).unwrap(),
+            count,
+            skew,
+        }
+    }
+
+    /// Samples the key of a new request.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> u64 {
+        self.distribution.sample(rng) as u64
+    }
+
+    /// Share of the requests with the most popular key.
+    fn hottest_share(&self) -> f64 {
+        let total: f64 = (1..=self.count).map(|k| (k as f64).powf(-self.skew)).sum();
+        1.0 / total
+    }
+
+    pub fn print_summary(&self) {
+        println!(
+            "Keys: {} (Zipf exponent {}), the hottest one in {:.2}% of the requests",
+            self.count,
+            self.skew,
+            self.hottest_share() * 100.0
+        );
+    }
+}
@@ -0,0 +1,1237 @@
+mod alert;
+mod autoscaler;
+mod balancer;
+mod batching;
+mod cache;
+mod capacity;
+mod chaos;
+mod checkpoint;
+mod chrometrace;
+mod class;
+mod clientpool;
+mod closedloop;
+mod coalescing;
+mod collapse;
+mod compare;
+mod config;
+mod contention;
+mod cost;
+#[cfg(not(target_arch = "wasm32"))]
+mod dashboard;
+mod database;
+mod discipline;
+mod erlang;
+mod eventdriven;
+mod eventlog;
+mod events;
+mod explain;
+mod export;
+mod fanout;
+mod generator;
+mod headroom;
+mod histogram;
+mod keys;
+mod littleslaw;
+mod metadata;
+mod metrics;
+mod mitigation;
+mod network;
+mod output;
+mod pacing;
+mod plan;
+mod plot;
+mod policy;
+mod pool;
+mod progress;
+#[cfg(feature = "python")]
+mod python;
+mod queue;
+mod queuelength;
+mod quota;
+mod region;
+mod report;
+mod request;
+mod resource;
+mod retrypolicy;
+mod schedule;
+mod server;
+mod service;
+mod shedding;
+mod simulation;
+mod size;
+mod slo;
+mod snapshot;
+mod spike;
+mod stall;
+mod storm;
+mod straggler;
+mod streams;
+mod subprocess;
+mod sweep;
+mod telemetry;
+mod timeouts;
+mod timeseries;
+mod trace;
+mod truncation;
+mod tutorial;
+mod utilization;
+mod validation;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+mod windows;
+mod worker;
+
+use alert::AlertCondition;
+use balancer::Routing;
+use capacity::CapacityOpt;
+use chaos::ChaosSpec;
+use class::ClassSpec;
+use compare::CompareOpt;
+use config::Preset;
+use contention::Curve;
+use cost::Cost;
+use discipline::DisciplineSwitch;
+use eventdriven::Engine;
+use export::ExportFormat;
+use headroom::Objective;
+use metadata::Metadata;
+use mitigation::MitigationSpec;
+use plan::PlanOpt;
+use plot::Plot;
+use pool::PoolSpec;
+use queue::Discipline;
+use quota::QuotaSpec;
+use rand::{thread_rng, Rng};
+use region::{Failover, RegionSpec};
+use report::Run;
+use retrypolicy::{RetryPolicySpec, RetryProbabilities};
+use schedule::{ArrivalDistribution, ArrivalSchedule, Segment};
+use server::ServeOpt;
+use service::{LatencyDistribution, LatencyMode, ServiceTime};
+use shedding::ShedThresholdSpec;
+use simulation::{Stats, Summary};
+use size::SizeDistribution;
+use slo::Slo;
+use spike::Spike;
+use std::env;
+use std::process;
+use straggler::Straggler;
+use structopt::StructOpt;
+use sweep::{PhaseOpt, SweepOpt};
+use trace::ArrivalTrace;
+use validation::ConfigError;
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(name = "Queueing simulator", about = "Queueing simulator parameters.")]
+struct Opt {
+    /// Rate at which new requests arrive, must be >0. Required, unless running a subcommand.
+    #[structopt(short = "r", long = "arrival_rate")]
+    request_arrival_rate: Option<f64>,
+
+    /// Arrival rate schedule segment: step:T:R (rate R from tick T on), ramp:T1:T2:R1:R2 (rate changing
+    /// linearly from R1 to R2 between ticks T1 and T2) or sine:P:A (oscillation with period P and relative
+    /// amplitude A). Can be repeated. Before the first step or ramp, the rate is the --arrival_rate.
+    #[structopt(long = "arrival_schedule")]
+    arrival_schedule: Vec<Segment>,
+
+    /// File with arrival rate schedule segments, one per line.
+    #[structopt(long = "arrival_schedule_file")]
+    arrival_schedule_file: Option<String>,
+
+    /// Command running a custom arrival generator, which is asked for the arrivals of the upcoming ticks with
+    /// a JSON line on its standard input, and answers with a JSON line on its standard output. Replaces the
+    /// arrival rate and schedule.
+    #[structopt(long = "generator")]
+    generator: Option<String>,
+
+    /// Command running an external admission and scheduling policy, which gets the state of the pool as a JSON
+    /// line on its standard input, and answers with its decision on its standard output.
+    #[structopt(long = "policy")]
+    policy: Option<String>,
+
+    /// Distribution of the number of requests arriving during a tick: normal or poisson.
+    #[structopt(long = "arrival_distribution", default_value = "normal")]
+    arrival_distribution: ArrivalDistribution,
+
+    /// Number of clients of a closed-loop workload, each sending a request, waiting for its outcome, thinking
+    /// for --think_time, and only then sending the next one. Replaces the arrival rate and schedule.
+    #[structopt(long = "clients")]
+    clients: Option<u32>,
+
+    /// Mean time, in ticks, a closed-loop client thinks between getting the outcome of a request and sending the
+    /// next one. Think times are exponentially distributed.
+    #[structopt(long = "think_time", default_value = "0")]
+    think_time: f64,
+
+    /// CSV or NDJSON file with recorded arrivals to replay, either as per-tick counts or as timestamps.
+    /// Replaces the arrival rate and schedule.
+    #[structopt(long = "arrival_trace")]
+    arrival_trace: Option<String>,
+
+    /// Number of workers to simulate.
+    #[structopt(short = "w", long = "workers", default_value = "10")]
+    num_workers: u16,
+
+    /// How long before the request is considered timed out and failed. For a meaningful simulation,
+    /// this value needs to be larger than mean request process latency.
+    #[structopt(short = "t", long = "timeout", default_value = "1000")]
+    request_timeout: u32,
+
+    /// Distribution of the timeouts of the clients around the --timeout (or the timeout of their class), for
+    /// clients that don't all use the same one: normal, exponential, pareto:SHAPE or weibull:SHAPE.
+    #[structopt(long = "timeout_distribution")]
+    timeout_distribution: Option<LatencyDistribution>,
+
+    /// Mean request processing latency, has to be larger than 0.
+    #[structopt(long = "mean_latency", default_value = "50")]
+    mean_request_latency: f64,
+
+    /// Distribution of the request processing latency around the mean: normal, exponential, pareto:SHAPE (with
+    /// SHAPE greater than 1) or weibull:SHAPE. The lower the shape, the heavier the tail.
+    #[structopt(long = "latency_distribution", default_value = "normal")]
+    latency_distribution: LatencyDistribution,
+
+    /// Mode of a mixture of service times, as weight:mean, e.g. 0.95:20 and 0.05:500 for 95% of the requests
+    /// taking 20 ticks and 5% taking 500 on average. Each mode has the latency distribution around its own
+    /// mean, and replaces the mean latency. Can be repeated.
+    #[structopt(long = "latency_mode", number_of_values = 1)]
+    latency_modes: Vec<LatencyMode>,
+
+    /// Distribution of the payload size of the requests, relative to the mean size: lognormal:SIGMA or
+    /// pareto:SHAPE. The service time of a request is proportional to its size, with the latency distribution
+    /// adding noise around it, and the summary reports the requests of each range of sizes separately.
+    #[structopt(long = "request_size")]
+    request_size: Option<SizeDistribution>,
+
+    /// File with observed request latencies, one per line, in ticks. Service times are sampled from them
+    /// instead of from a normal distribution around the mean latency.
+    #[structopt(long = "latency_trace")]
+    latency_trace: Option<String>,
+
+    /// Size of the connection pool of the clients: requests over this many in flight wait in a queue of the
+    /// client for a connection before they're sent. Unlimited unless set.
+    #[structopt(long = "client_connections")]
+    client_connections: Option<u32>,
+
+    /// Ticks a request waits for a client connection before the client gives up on it, counting as timed out.
+    /// Defaults to the timeout.
+    #[structopt(long = "client_queue_timeout")]
+    client_queue_timeout: Option<u32>,
+
+    /// Mean one-way delay of the network between the clients and the server, in ticks, both before a request
+    /// reaches the queue and before its response reaches the client. Counts against the timeout, but takes none
+    /// of the server's capacity.
+    #[structopt(long = "network_latency")]
+    network_latency: Option<f64>,
+
+    /// Distribution of the network delay around its mean: normal, exponential, pareto:SHAPE or weibull:SHAPE.
+    #[structopt(long = "network_latency_distribution", default_value = "normal")]
+    network_latency_distribution: LatencyDistribution,
+
+    /// Seed of the random number generator. Runs with the same seed and options produce identical results.
+    /// Defaults to a random seed.
+    #[structopt(long = "seed")]
+    seed: Option<u64>,
+
+    /// Seed of the random number stream of the arrivals only, e.g. to try other arrivals while keeping the
+    /// service times and everything else the same. Defaults to the seed.
+    #[structopt(long = "arrival_seed")]
+    arrival_seed: Option<u64>,
+
+    /// Seed of the random number stream of the service times only. Defaults to the seed.
+    #[structopt(long = "service_seed")]
+    service_seed: Option<u64>,
+
+    /// Seed of the random number stream of the clients' retry decisions only. Defaults to the seed.
+    #[structopt(long = "retry_seed")]
+    retry_seed: Option<u64>,
+
+    /// Seed of the random number stream of the injected failures only. Defaults to the seed.
+    #[structopt(long = "failure_seed")]
+    failure_seed: Option<u64>,
+
+    /// Number of ticks at the start of the simulation during which the system reaches steady state. Requests
+    /// arriving, failing or completing during the warm-up aren't counted in the statistics.
+    #[structopt(long = "warmup_ticks", default_value = "0")]
+    warmup_ticks: u64,
+
+    /// Number of ticks to run this simulation.
+    #[structopt(long = "simulation_time", default_value = "1000000")]
+    simulation_ticks: u32,
+
+    /// Size of the request queue.
+    #[structopt(short = "q", long = "queue_size", default_value = "1000")]
+    queue_size: usize,
+
+    /// Order the requests in the queue are served in: fifo, lifo, edf (the earliest deadline first, from a queue
+    /// ordered by the ticks the clients give up at) or siro (in random order, regardless of how long they waited).
+    #[structopt(long = "discipline", default_value = "fifo")]
+    discipline: Discipline,
+
+    /// Deprecated, use --discipline lifo instead. Whether to use LIFO instead of FIFO queue.
+    #[structopt(long = "lifo")]
+    lifo: bool,
+
+    /// Whether the workers drop the requests they find in the queue after their clients gave up on them, instead
+    /// of serving them.
+    #[structopt(long = "cull_timed_out")]
+    cull_timed_out: bool,
+
+    /// Queue discipline switch during the run, as tick:lifo or tick:fifo, e.g. to simulate an operator flipping
+    /// the queue to LIFO during an incident. Requests already in the queue stay in it. Can be repeated.
+    #[structopt(long = "discipline_switch", number_of_values = 1)]
+    discipline_switches: Vec<DisciplineSwitch>,
+
+    /// Simulation engine: tick (advances the clock one tick at a time, and supports every feature) or event
+    /// (jumps from one arrival or completion to the next, which is much faster with many workers and long
+    /// runs, but only supports the core model).
+    #[structopt(long = "engine", default_value = "tick")]
+    engine: Engine,
+
+    /// Whether to spread the requests arriving during a tick randomly over the tick, instead of having them all
+    /// arrive at its start. Affects the order they are queued in and their latency.
+    #[structopt(long = "sub_tick_arrivals")]
+    sub_tick_arrivals: bool,
+
+    /// Whether to print the distribution of the queue length over time, compared with the analytical M/M/c/K
+    /// model when the configuration has an equivalent one.
+    #[structopt(long = "queue_distribution")]
+    queue_distribution: bool,
+
+    /// Whether to check that the mean number of requests in the system matches the arrival rate times the mean
+    /// time in the system (Little's Law), as a sanity check of the simulation.
+    #[structopt(long = "littles_law")]
+    littles_law: bool,
+
+    /// Number of ticks between checkpoints of the full state of the simulation, written to the checkpoint file
+    /// for resuming long runs.
+    #[structopt(long = "checkpoint_every")]
+    checkpoint_every: Option<u64>,
+
+    /// File to write the checkpoints to, and to resume from.
+    #[structopt(long = "checkpoint_file")]
+    checkpoint_file: Option<String>,
+
+    /// Whether to resume the run from the checkpoint file, rather than start it afresh. The options have to be
+    /// the same as the ones of the run that wrote the checkpoint, except for the simulation time, which can be
+    /// extended.
+    #[structopt(long = "resume")]
+    resume: bool,
+
+    /// Whether to detect congestion collapse: windows during which the queue stayed pinned near its capacity
+    /// while few requests completed in time. Reports the tick the collapse began at, and whether it recovered.
+    #[structopt(long = "detect_collapse")]
+    detect_collapse: bool,
+
+    /// Length of the windows the collapse detection looks at, in ticks.
+    #[structopt(long = "collapse_window", default_value = "1000")]
+    collapse_window: u64,
+
+    /// Share of the requests arriving during a window that have to complete in time for it not to count as
+    /// collapsed.
+    #[structopt(long = "collapse_goodput", default_value = "0.5")]
+    collapse_goodput: f64,
+
+    /// Whether to detect retry storms: windows during which the clients retried more than a share of the
+    /// arriving requests. Reports when each storm started, peaked and subsided.
+    #[structopt(long = "detect_retry_storms")]
+    detect_retry_storms: bool,
+
+    /// Length of the windows the retry storm detection looks at, in ticks.
+    #[structopt(long = "retry_storm_window", default_value = "1000")]
+    retry_storm_window: u64,
+
+    /// Share of the requests arriving during a window that have to be retried for it to count as part of a
+    /// retry storm.
+    #[structopt(long = "retry_storm_share", default_value = "0.2")]
+    retry_storm_share: f64,
+
+    /// Whether to print the distributions of the time the successful requests spent waiting in the queue and
+    /// being processed, the two components of their latency.
+    #[structopt(long = "latency_breakdown")]
+    latency_breakdown: bool,
+
+    /// Whether to print the latency the clients intended to experience, counting every failed request as taking
+    /// the full timeout, which corrects for the coordinated omission of measuring successful requests only.
+    #[structopt(long = "intended_latency")]
+    intended_latency: bool,
+
+    /// Latency the requests have to complete successfully within, in ticks, to count towards the service level
+    /// objective. The summary reports the share of the requests that did, failed ones counting against it.
+    #[structopt(long = "slo_latency")]
+    slo_latency: Option<u64>,
+
+    /// Percentage of the requests that have to complete within the SLO latency for the objective to be met.
+    #[structopt(long = "slo_target", default_value = "99.9")]
+    slo_target: f64,
+
+    /// Cost of a worker per tick, to report the total cost of the run with, next to the cost of the failures.
+    #[structopt(long = "worker_cost")]
+    worker_cost: Option<f64>,
+
+    /// Cost of a failed request, to report the total cost of the run with, next to the cost of the workers.
+    #[structopt(long = "failure_cost")]
+    failure_cost: Option<f64>,
+
+    /// Whether to exit with status 1 when the SLO target isn't met, e.g. to fail a CI job.
+    #[structopt(long = "slo_exit_code")]
+    slo_exit_code: bool,
+
+    /// Failure rate, in percent, above which to exit with status 1, e.g. to fail a CI job checking a config.
+    #[structopt(long = "fail_if_failure_rate_above")]
+    fail_if_failure_rate_above: Option<f64>,
+
+    /// p99 latency, in ticks, above which to exit with status 1, e.g. to fail a CI job checking a config.
+    #[structopt(long = "fail_if_p99_above")]
+    fail_if_p99_above: Option<u64>,
+
+    /// Whether to run the simulation a second time, with arrivals spread within the tick if they weren't (or
+    /// the other way around), and report how much of the latency is an artifact of the tick resolution.
+    #[structopt(long = "batching_report")]
+    batching_report: bool,
+
+    /// Cap on the service time, in ticks, to rerun the simulation with, reporting how much of the failure rate
+    /// the requests taking longer are responsible for. Can be repeated.
+    #[structopt(long = "truncate_service_time")]
+    service_time_caps: Vec<f64>,
+
+    /// Cap on the service time of this run, set when rerunning it for the truncation report.
+    #[structopt(skip)]
+    service_time_cap: Option<f64>,
+
+    /// Whether to simulate a temporary spike in the request processing latency (this tends to be the condition that
+    /// triggers the congestion collapse).
+    #[structopt(long = "simulate_spike")]
+    simulate_spike: bool,
+
+    /// Tick at which a latency spike starts. Can be repeated to simulate multiple spikes, and implies
+    /// --simulate_spike. Defaults to a single spike at the start of the simulation.
+    #[structopt(long = "spike_start")]
+    spike_starts: Vec<u64>,
+
+    /// Duration of the latency spike in ticks. When repeated, applies to the spike with the same position;
+    /// spikes without their own duration use the last one specified. Defaults to 10000 ticks.
+    #[structopt(long = "spike_duration")]
+    spike_durations: Vec<u64>,
+
+    /// How many times slower requests arriving during the latency spike are. Repeats the same way as
+    /// --spike_duration. (Default: 10)
+    #[structopt(long = "spike_multiplier")]
+    spike_multipliers: Vec<f64>,
+
+    /// Mean number of ticks between pauses freezing all the workers at once, like garbage collection pauses.
+    /// Pauses are disabled unless this is set.
+    #[structopt(long = "stall_interval")]
+    stall_interval: Option<f64>,
+
+    /// Mean duration of the pauses freezing all the workers, in ticks.
+    #[structopt(long = "stall_duration", default_value = "100")]
+    stall_duration: f64,
+
+    /// Workers of every pool turning slow for a window of the run, as workers:start:duration:multiplier, e.g.
+    /// 1:100000:50000:10 for the first worker of every pool processing the requests it starts between ticks 100000 and 150000 10 times slower.
+    #[structopt(long = "straggler")]
+    straggler: Option<Straggler>,
+
+    /// Deprecated, use --retry_policy probability:P instead. Probability a failed request will be retried, by the
+    /// classes without a --retry_policy. Must be between 0 and 1 inclusive.
+    #[structopt(long = "retry_probability", default_value = "0.5")]
+    retry_probability: f64,
+
+    /// Deprecated. Probability a request the server rejected right away (like a refused connection) will be
+    /// retried, by the classes without a --retry_policy. Defaults to the retry probability.
+    #[structopt(long = "connection_retry_probability")]
+    connection_retry_probability: Option<f64>,
+
+    /// Deprecated. Probability a request that timed out will be retried, by the classes without a --retry_policy.
+    /// Defaults to the retry probability.
+    #[structopt(long = "timeout_retry_probability")]
+    timeout_retry_probability: Option<f64>,
+
+    /// Backoff hint, in ticks, the server sends with its rejections, asking the clients to wait that long before
+    /// retrying. Without it, the clients retry right away.
+    #[structopt(long = "retry_after")]
+    retry_after: Option<u64>,
+
+    /// Share of the clients that honor the retry-after hint. The others retry right away anyway.
+    #[structopt(long = "retry_after_compliance", default_value = "1")]
+    retry_after_compliance: f64,
+
+    /// Whether and how the clients retry their failed requests, as [class:]policy: none, immediate,
+    /// probability:P (immediate retries of that share of the failures), fixed:DELAY, exponential:BASE:CAP,
+    /// decorrelated:BASE:CAP (jittered) or budget:RATIO (immediate retries of at most that ratio of the
+    /// requests). Without a class, the policy applies to every class. Can be repeated, the later ones taking
+    /// precedence. Defaults to immediate retries with the retry probabilities.
+    #[structopt(long = "retry_policy")]
+    retry_policies: Vec<RetryPolicySpec>,
+
+    /// Mean time, in ticks, the clients wait for their request to leave the queue before abandoning it, which
+    /// withdraws it from the queue. Clients never abandon their requests unless this is set.
+    #[structopt(long = "patience")]
+    patience: Option<f64>,
+
+    /// Distribution of the patience of the clients around its mean: normal, exponential, pareto:SHAPE or
+    /// weibull:SHAPE.
+    #[structopt(long = "patience_distribution", default_value = "exponential")]
+    patience_distribution: LatencyDistribution,
+
+    /// Deprecated. Probability a request the client abandoned will be retried, by the classes without a
+    /// --retry_policy.
+    #[structopt(long = "abandonment_retry_probability", default_value = "0")]
+    abandonment_retry_probability: f64,
+
+    /// Queue length at which arriving clients always balk, not sending their request at all. Below it, they
+    /// balk with a probability proportional to the queue length. Clients never balk unless this is set.
+    #[structopt(long = "balking_queue_length")]
+    balking_queue_length: Option<u32>,
+
+    /// Maximum number of workers the autoscaler is allowed to grow to. Autoscaling is disabled when this is 0.
+    #[structopt(long = "autoscale_max_workers", default_value = "0")]
+    autoscale_max_workers: u16,
+
+    /// Queue length at which the autoscaler decides to add another worker.
+    #[structopt(long = "autoscale_queue_threshold", default_value = "100")]
+    autoscale_queue_threshold: usize,
+
+    /// Minimum number of ticks between two consecutive scale up attempts.
+    #[structopt(long = "autoscale_cooldown", default_value = "1000")]
+    autoscale_cooldown: u32,
+
+    /// Number of ticks it takes a newly added worker to start taking requests.
+    #[structopt(long = "worker_startup_ticks", default_value = "500")]
+    worker_startup_ticks: u32,
+
+    /// Probability that a worker added by the autoscaler never starts. Must be between 0 and 1 inclusive.
+    #[structopt(long = "worker_start_failure_probability", default_value = "0")]
+    worker_start_failure_probability: f64,
+
+    /// Probability that a worker added by the autoscaler starts slowly. Must be between 0 and 1 inclusive.
+    #[structopt(long = "worker_slow_start_probability", default_value = "0")]
+    worker_slow_start_probability: f64,
+
+    /// How many times longer than usual it takes a slow starting worker to start taking requests.
+    #[structopt(long = "worker_slow_start_multiplier", default_value = "10")]
+    worker_slow_start_multiplier: u32,
+
+    /// Number of sub-requests each incoming request fans out to. Values above 1 enable the fan-out model.
+    #[structopt(long = "fan_out", default_value = "1")]
+    fan_out: u32,
+
+    /// Number of sub-requests that need to succeed for a fanned out request to succeed. Defaults to all of them.
+    #[structopt(long = "fan_out_quorum")]
+    fan_out_quorum: Option<u32>,
+
+    /// Whether to report telemetry about the simulation engine itself (speed, memory usage, queue allocations).
+    #[structopt(long = "telemetry")]
+    telemetry: bool,
+
+    /// Request classes (e.g. tenants) as name:weight[:timeout], where weight is the relative share of the
+    /// incoming traffic, and timeout the timeout of its clients instead of --timeout. Failure rate and latency
+    /// are reported per class. Without any classes, all requests belong to a single default class.
+    #[structopt(long = "class")]
+    classes: Vec<ClassSpec>,
+
+    /// Bulkhead pools as name:workers:queue_size:class1+class2. Each pool has its own workers and queue, and
+    /// serves only the listed classes. Without any pools, all classes share the same workers and queue.
+    #[structopt(long = "pool")]
+    pools: Vec<PoolSpec>,
+
+    /// Splits the workers and the queue into the specified number of independent queues, each with its own
+    /// share of the workers, and a load balancer routing every request to one of them. The summary compares
+    /// the outcome with that of a single shared queue.
+    #[structopt(long = "queues")]
+    queues: Option<u16>,
+
+    /// How the load balancer in front of the queues picks the queue of a request: jsq (the shortest queue),
+    /// choices:D (the shortest of D queues picked at random), round-robin, random or least-connections (the
+    /// fewest requests waiting or being processed) or hash (consistent hashing of the key of the request).
+    #[structopt(long = "routing", default_value = "jsq")]
+    routing: Routing,
+
+    /// Regions as name:workers:queue_size:traffic, each a copy of the server with its own workers and queue.
+    /// The clients are spread over the regions by their traffic, and send their requests to their own region.
+    #[structopt(long = "region")]
+    regions: Vec<RegionSpec>,
+
+    /// Which failed requests the clients retry in the next region instead of their own: none, rejected or
+    /// failed (rejected, timed out or abandoned).
+    #[structopt(long = "failover", default_value = "none")]
+    failover: Failover,
+
+    /// Ticks a request takes to cross from the region of its client to another one, and its response to cross
+    /// back.
+    #[structopt(long = "cross_region_latency", default_value = "0")]
+    cross_region_latency: u64,
+
+    /// Number of distinct keys of the requests, for the hash routing.
+    #[structopt(long = "keys", default_value = "1000")]
+    keys: u64,
+
+    /// Exponent of the Zipf distribution of the keys of the requests: 0 for all of them equally popular, the
+    /// higher the more the requests concentrate on a few hot keys.
+    #[structopt(long = "key_skew", default_value = "1")]
+    key_skew: f64,
+
+    /// Hit ratio of a cache in front of the workers, which answers the hits in --cache_latency ticks without
+    /// taking a worker. The hits are drawn at random, ramping up from none over --cache_warmup_ticks.
+    #[structopt(long = "cache_hit_ratio")]
+    cache_hit_ratio: Option<f64>,
+
+    /// Number of keys a cache in front of the workers holds, evicting the least recently used one. Hits depend
+    /// on the --keys and --key_skew of the requests, and the cache warms up as the misses fill it.
+    #[structopt(long = "cache_size")]
+    cache_size: Option<usize>,
+
+    /// Ticks the cache takes to answer a hit.
+    #[structopt(long = "cache_latency", default_value = "1")]
+    cache_latency: u32,
+
+    /// Ticks the hit ratio of the --cache_hit_ratio cache takes to ramp up, from the start or a flush.
+    #[structopt(long = "cache_warmup_ticks", default_value = "0")]
+    cache_warmup_ticks: u64,
+
+    /// Tick to empty the cache at, sending all of the load to the workers while it warms up again. Can be
+    /// repeated.
+    #[structopt(long = "cache_flush", number_of_values = 1)]
+    cache_flushes: Vec<u64>,
+
+    /// Whether requests arriving for the key of a request already in the system wait on its result instead of
+    /// taking a worker (single-flight coalescing). The keys are drawn with --keys and --key_skew.
+    #[structopt(long = "coalesce")]
+    coalesce: bool,
+
+    /// Per-class admission quota, as class:concurrency:N (at most N requests of the class in the system at
+    /// once) or class:rate:R[:burst] (token bucket refilled with R tokens per tick, holding up to burst
+    /// tokens, 1000 ticks worth by default). Requests over quota are rejected even if the queue has room.
+    #[structopt(long = "quota")]
+    quotas: Vec<QuotaSpec>,
+
+    /// Per-class shed threshold, as class:fraction: requests of the class that would have to wait are shed
+    /// once the queue is filled to that fraction of its size, keeping the rest of it for the more critical
+    /// classes. Classes without one are only rejected by a full queue.
+    #[structopt(long = "shed_threshold")]
+    shed_thresholds: Vec<ShedThresholdSpec>,
+
+    /// Percentage by which service time grows per 100 queued requests, modelling lock contention or swap
+    /// pressure in an overloaded server. Measured when a worker picks up the request. 0 disables the effect.
+    #[structopt(long = "queue_latency_penalty", default_value = "0")]
+    queue_latency_penalty: f64,
+
+    /// Shape of the queue latency penalty curve: linear, quadratic or exponential.
+    #[structopt(long = "queue_latency_curve", default_value = "linear")]
+    queue_latency_curve: Curve,
+
+    /// Mean time, in ticks, of the database query each request makes after the worker is done executing it.
+    /// The worker waits for the query to finish. 0 disables the database stage.
+    #[structopt(long = "db_query_latency", default_value = "0")]
+    db_query_latency: f64,
+
+    /// Percentage by which database queries slow down per concurrently running query.
+    #[structopt(long = "db_contention", default_value = "1")]
+    db_contention: f64,
+
+    /// Shape of the database contention curve: linear, quadratic or exponential.
+    #[structopt(long = "db_contention_curve", default_value = "quadratic")]
+    db_contention_curve: Curve,
+
+    /// Most ticks a request may wait in the queue. A worker getting to a request that waited longer fails it
+    /// right away, without working on it, and moves on to the next one. Unlike --timeout, which the server
+    /// doesn't know about, it keeps the workers from spending their time on requests likely to time out.
+    #[structopt(long = "queue_timeout")]
+    queue_timeout: Option<u64>,
+
+    /// Instead of rejecting requests arriving to a full queue, admit them optimistically, and every this many
+    /// ticks shed the requests over the queue size from the back of the queue. 0 rejects up front.
+    #[structopt(long = "late_shedding_interval", default_value = "0")]
+    late_shedding_interval: u64,
+
+    /// Queue length at which a pool browns out: instead of queueing every arriving request for the full
+    /// treatment, it serves --brownout_fraction of them on a degraded fast path, taking
+    /// --brownout_service_fraction of the service time (e.g. cached or partial responses). Disabled by default.
+    #[structopt(long = "brownout_queue_length")]
+    brownout_queue_length: Option<usize>,
+
+    /// Fraction of the requests arriving to a browned out pool that get the degraded response.
+    #[structopt(long = "brownout_fraction", default_value = "1")]
+    brownout_fraction: f64,
+
+    /// Service time of a degraded response, as a fraction of the full service time of the request.
+    #[structopt(long = "brownout_service_fraction", default_value = "0.1")]
+    brownout_service_fraction: f64,
+
+    /// Number of slots of a second resource (e.g. memory or GPU slots), which requests of the classes in
+    /// --resource_classes hold from admission until they complete, in addition to a worker. 0 disables it.
+    #[structopt(long = "resource_slots", default_value = "0")]
+    resource_slots: u32,
+
+    /// Class of requests that need a slot of the second resource. Can be repeated. Defaults to all classes.
+    #[structopt(long = "resource_class")]
+    resource_classes: Vec<String>,
+
+    /// Number of requests that can wait for a slot of the second resource, before further ones are rejected.
+    #[structopt(long = "resource_queue_size", default_value = "1000")]
+    resource_queue_size: usize,
+
+    /// Limit the simulated latency spike to requests of a single class.
+    #[structopt(long = "spike_class")]
+    spike_class: Option<String>,
+
+    /// File to write time series of queue length, busy workers and failures to, as CSV. Use "-" for stdout.
+    #[structopt(long = "timeseries")]
+    time_series: Option<String>,
+
+    /// File to write snapshots of the state of the system to, as NDJSON: the distribution of the queue length
+    /// and of the age of the requests in the system, and per class counts. Use "-" for stdout.
+    #[structopt(long = "snapshots")]
+    snapshots: Option<String>,
+
+    /// Number of ticks between snapshots.
+    #[structopt(long = "snapshot_every", default_value = "1000")]
+    snapshot_every: u64,
+
+    /// File to write the events of every request to, as NDJSON: arrival, enqueue, dequeue, start, completion,
+    /// timeout, shedding, rejection, abandonment and retry, with the tick and id of the request. Use "-" for stdout.
+    #[structopt(long = "event_log")]
+    event_log: Option<String>,
+
+    /// File to write a Chrome trace of the requests to, for chrome://tracing or Perfetto: a span for the time
+    /// every request waited in a queue, and one for the time a worker spent on it.
+    #[structopt(long = "chrome_trace")]
+    chrome_trace: Option<String>,
+
+    /// Number of ticks aggregated into each time series point (as min/mean/max). Defaults to the number of
+    /// ticks that produces 1000 points.
+    #[structopt(long = "report_every")]
+    report_every: Option<u64>,
+
+    /// File to write a standalone HTML report of the run to, with the configuration, results and charts. With
+    /// the compare subcommand, the report compares the two runs.
+    #[structopt(long = "html_report")]
+    html_report: Option<String>,
+
+    /// Service level objective to report the capacity headroom against, as metric<threshold, where the metric
+    /// is failure_rate (in percent), mean or a latency percentile like p99 (in ticks). Can be repeated, in which
+    /// case all of them have to be met.
+    #[structopt(long = "headroom", number_of_values = 1)]
+    headroom_objectives: Vec<Objective>,
+
+    /// Length of the auxiliary simulations the headroom is searched with. Defaults to the simulation time, up to
+    /// 200000 ticks.
+    #[structopt(long = "headroom_ticks")]
+    headroom_ticks: Option<u32>,
+
+    /// Plot of the peak queue length and failure rate over time to show at the end of the run: ascii prints
+    /// braille charts to stdout, and a file name ending in .svg writes an image with the latency percentiles
+    /// as well.
+    #[structopt(long = "plot")]
+    plot: Option<Plot>,
+
+    /// Instead of running the simulation, print the configured topology in the specified format (pdq, ciw or
+    /// simpy), for cross-validating the results with other tools.
+    #[structopt(long = "export")]
+    export: Option<ExportFormat>,
+
+    /// Whether to report the progress of the simulation on stderr, with the current queue length and failure
+    /// rate, once a second. Ignored by the sweep and phase subcommands, which run many simulations at once.
+    #[structopt(long = "progress")]
+    progress: bool,
+
+    /// Whether to keep simulating until interrupted (Ctrl-C) instead of for --simulation_time, printing the
+    /// metrics of every window of --report_every ticks as it goes. The summary is printed once interrupted.
+    #[structopt(long = "run_forever")]
+    run_forever: bool,
+
+    /// Address to serve the live counters and gauges of the simulation on, at /metrics in the Prometheus text
+    /// format, e.g. 127.0.0.1:9898. Best combined with --speed, so that there is something to scrape.
+    #[structopt(long = "metrics_address")]
+    metrics_address: Option<String>,
+
+    /// Number of ticks to simulate per second of wall time, slowing the simulation down so that it can be
+    /// watched as it runs. Unlimited by default.
+    #[structopt(long = "speed")]
+    speed: Option<f64>,
+
+    /// Whether to show a live dashboard with sparklines of the queue length, busy workers, failure rate and
+    /// latency while the simulation runs, slowed down to --tui_speed.
+    #[structopt(long = "tui")]
+    tui: bool,
+
+    /// Number of ticks the TUI simulates per second. Defaults to the speed that takes 30 seconds for the whole
+    /// simulation.
+    #[structopt(long = "tui_speed")]
+    tui_speed: Option<f64>,
+
+    /// Whether to print a narrated timeline of the notable moments of the simulation, such as the queue
+    /// filling up or workers starting to waste their time on timed out requests.
+    #[structopt(long = "explain")]
+    explain: bool,
+
+    /// Condition to alert on, as metric>threshold or metric<threshold, where the metric is queue_len,
+    /// busy_workers (both at the end of a tick) or rejections (during a tick). Can be repeated.
+    #[structopt(long = "alert")]
+    alerts: Vec<AlertCondition>,
+
+    /// Command to run whenever an alert fires, e.g. to call a webhook with curl. Gets the condition, tick and
+    /// value of the alert in the ALERT_CONDITION, ALERT_TICK and ALERT_VALUE environment variables.
+    #[structopt(long = "alert_command")]
+    alert_command: Option<String>,
+
+    /// Number of ticks an alert's condition has to be absent for the alert to fire again.
+    #[structopt(long = "alert_cooldown", default_value = "1000")]
+    alert_cooldown: u64,
+
+    /// Free-form note describing the experiment, e.g. "testing new retry budget", carried into every output, so
+    /// that the results remain understandable later on. Can be repeated.
+    #[structopt(long = "note", number_of_values = 1)]
+    notes: Vec<String>,
+
+    /// Experiment metadata as key=value, e.g. git_sha=1a2b3c or ticket=OPS-123, carried into every output
+    /// along with the notes. Can be repeated.
+    #[structopt(long = "meta", number_of_values = 1)]
+    metadata: Vec<Metadata>,
+
+    /// Mitigation to take once its condition first holds, as condition:action, where the condition is like
+    /// an alert's and the action is add_workers=N, shed=N (shrink the queues to N), cut_retries or lifo, e.g.
+    /// queue_len>500:cut_retries. Can be repeated.
+    #[structopt(long = "mitigation", number_of_values = 1)]
+    mitigations: Vec<MitigationSpec>,
+
+    /// Number of ticks it takes to detect the condition of a mitigation and decide to act on it, before the
+    /// action takes effect.
+    #[structopt(long = "mitigation_delay", default_value = "0")]
+    mitigation_delay: u64,
+
+    /// Fault to inject on a schedule, as fault:start[:end], e.g. kill:3:200000:260000 to kill 3 workers of
+    /// every pool at tick 200000 and restore them at tick 260000. The database faults, db_outage:FRACTION and
+    /// db_slowdown:MULTIPLIER, fail that fraction of the queries or make them that many times slower, and
+    /// require --db_query_latency. Without an end, the fault lasts until the end of the run. Can be repeated;
+    /// in a config file, takes a list.
+    #[structopt(long = "chaos", number_of_values = 1)]
+    chaos: Vec<ChaosSpec>,
+
+    /// TOML or YAML scenario file with the values of any of the other parameters, by their names without the
+    /// dashes, e.g. `arrival_rate = 0.15` or `arrival_schedule = ["step:1000:0.2", "step:2000:0.1"]`.
+    /// Parameters on the command line override the file's.
+    #[structopt(long = "config")]
+    config: Option<String>,
+
+    /// Curated scenario to start from: stable, overloaded, retry-storm or spike-recovery. Both the command line
+    /// and the scenario file override its parameters.
+    #[structopt(long = "preset")]
+    preset: Option<Preset>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Clone, StructOpt)]
+enum Command {
+    /// Walk through a series of simulations (baseline, latency spike, retries, load shedding), explaining
+    /// what to look for in each of them.
+    #[structopt(name = "tutorial")]
+    Tutorial,
+    /// Run the simulation for a range of values of one parameter, writing the results as CSV. The rest of the
+    /// options apply to every run, e.g. `queueingsimulator --workers 20 sweep --parameter arrival_rate --from
+    /// 0.1 --to 0.5 --step 0.05`.
+    #[structopt(name = "sweep")]
+    Sweep(SweepOpt),
+    /// Run the simulation for every combination of client timeout and server queue size, writing the goodput
+    /// (successful requests per tick) of each as a CSV grid with a row per queue size and a column per timeout.
+    #[structopt(name = "phase")]
+    Phase(PhaseOpt),
+    /// Run two configurations against the identical sequence of arrivals, and print the differences in their
+    /// results. The options before the subcommand apply to both, e.g. `queueingsimulator -r 0.1
+    /// --simulate_spike compare --a "--retry_policy none" --b "--discipline lifo"`.
+    #[structopt(name = "compare")]
+    Compare(CompareOpt),
+    /// Find the smallest number of workers that meets the objectives for the configured workload, rerunning the
+    /// simulation for every fleet size tried, e.g. `queueingsimulator -r 0.15 --simulate_spike plan --objective
+    /// failure_rate<0.1 --objective p99<300`.
+    #[structopt(name = "plan")]
+    Plan(PlanOpt),
+    /// Find the maximum sustainable arrival rate of the configured system, past which the failure rate exceeds
+    /// a threshold, rerunning the simulation for every arrival rate tried, e.g. `queueingsimulator --workers 20
+    /// capacity --max_failure_rate 0.1`.
+    #[structopt(name = "capacity")]
+    Capacity(CapacityOpt),
+    /// Serve simulations over HTTP: POST a scenario as JSON to /simulate, and get its results back as JSON, or
+    /// with ?stream as server-sent events with the state of the system as it runs, e.g. `queueingsimulator serve
+    /// --address 127.0.0.1:8080`.
+    #[structopt(name = "serve")]
+    Serve(ServeOpt),
+}
+
+impl Opt {
+    /// Arrival schedule segments, both from the command line and the schedule file.
+    fn arrival_schedule(&self) -> Result<Vec<Segment>, String> {
+        let mut segments = self.arrival_schedule.clone();
+        if let Some(path) = self.arrival_schedule_file.as_ref() {
+            segments.extend(ArrivalSchedule::read_segments(path)?);
+        }
+        Ok(segments)
+    }
+
+    fn arrival_trace(&self, classes: &[ClassSpec]) -> Result<Option<ArrivalTrace>, String> {
+        self.arrival_trace
+            .as_ref()
+            .map(|path| ArrivalTrace::read(path, classes))
+            .transpose()
+    }
+
+    fn service_time(&self) -> Result<ServiceTime, String> {
+        match self.latency_trace.as_ref() {
+            Some(_) if !self.latency_modes.is_empty() => {
+                Err("Latency modes can't be combined with a latency trace".to_string())
+            }
+            Some(path) => ServiceTime::read_trace(path),
+            None if !self.latency_modes.is_empty() => Ok(ServiceTime::mixture(
+                self.latency_distribution,
+                &self.latency_modes,
+            )),
+            None => Ok(ServiceTime::new(
+                self.latency_distribution,
+                self.mean_request_latency,
+            )),
+        }
+    }
+
+    fn request_arrival_rate(&self) -> f64 {
+        self.request_arrival_rate
+            .unwrap_or_else(|| ConfigError::MissingArrivals.exit())
+    }
+
+    fn report_every(&self) -> u64 {
+        self.report_every
+            .unwrap_or((self.simulation_ticks as u64 / 1000).max(1))
+    }
+
+    /// Latency spikes to simulate.
+    fn spikes(&self) -> Vec<Spike> {
+        let mut starts = self.spike_starts.clone();
+        if starts.is_empty() && self.simulate_spike {
+            starts.push(0);
+        }
+
+        starts
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| Spike {
+                start,
+                duration: nth_or_last(&self.spike_durations, i).unwrap_or(spike::DEFAULT_DURATION),
+                multiplier: nth_or_last(&self.spike_multipliers, i).unwrap_or(10.0),
+            })
+            .collect()
+    }
+
+    fn classes(&self) -> Vec<ClassSpec> {
+        if self.classes.is_empty() {
+            vec![ClassSpec::default_class()]
+        } else {
+            self.classes.clone()
+        }
+    }
+
+    fn slo(&self) -> Option<Slo> {
+        self.slo_latency
+            .map(|latency| Slo::new(latency, self.slo_target))
+    }
+
+    /// Cost model of the run, if either cost is specified.
+    fn cost(&self) -> Option<Cost> {
+        if self.worker_cost.is_none() && self.failure_cost.is_none() {
+            return None;
+        }
+        Some(Cost::new(
+            self.worker_cost.unwrap_or(0.0),
+            self.failure_cost.unwrap_or(0.0),
+        ))
+    }
+
+    fn pools(&self) -> Vec<PoolSpec> {
+        if self.pools.is_empty() {
+            vec![PoolSpec {
+                name: "shared".to_string(),
+                workers: self.num_workers,
+                queue_size: self.queue_size,
+                classes: self.classes().into_iter().map(|c| c.name).collect(),
+            }]
+        } else {
+            self.pools.clone()
+        }
+    }
+
+    /// Whether the requests have keys, for the consistent hashing, the cache or the coalescing.
+    fn keyed(&self) -> bool {
+        (self.queues.is_some() && matches!(self.routing, Routing::ConsistentHash))
+            || self.cache_size.is_some()
+            || self.coalesce
+    }
+
+    fn fan_out_quorum(&self) -> u32 {
+        self.fan_out_quorum.unwrap_or(self.fan_out)
+    }
+
+    /// Probabilities of the default retry policy, for the classes without a --retry_policy.
+    fn retry_probabilities(&self) -> RetryProbabilities {
+        RetryProbabilities {
+            rejected: self
+                .connection_retry_probability
+                .unwrap_or(self.retry_probability),
+            timed_out: self
+                .timeout_retry_probability
+                .unwrap_or(self.retry_probability),
+            abandoned: self.abandonment_retry_probability,
+        }
+    }
+
+    /// Order the requests in the queue are served in, LIFO with the deprecated --lifo.
+    fn discipline(&self) -> Discipline {
+        if self.lifo {
+            Discipline::Lifo
+        } else {
+            self.discipline
+        }
+    }
+
+    /// Whether any of the deprecated retry probability options is set to something else than its default.
+    fn sets_retry_probabilities(&self) -> bool {
+        self.retry_probability != 0.5
+            || self.connection_retry_probability.is_some()
+            || self.timeout_retry_probability.is_some()
+            || self.abandonment_retry_probability != 0.0
+    }
+}
+
+/// Runs the simulator, or one of its subcommands, with the command line arguments of the process.
+pub fn run() {
+    let mut opt = Opt::from_iter(
+        config::expand_args(env::args().collect()).unwrap_or_else(|error| error.exit()),
+    );
+    match opt.command.as_ref() {
+        Some(Command::Tutorial) => {
+            tutorial::run();
+            return;
+        }
+        Some(Command::Sweep(sweep)) => {
+            sweep::run(&opt, sweep);
+            return;
+        }
+        Some(Command::Phase(phase)) => {
+            sweep::run_phase(&opt, phase);
+            return;
+        }
+        Some(Command::Compare(compare)) => {
+            compare::run(&opt, compare);
+            return;
+        }
+        Some(Command::Plan(plan)) => {
+            plan::run(&opt, plan);
+            return;
+        }
+        Some(Command::Capacity(capacity)) => {
+            capacity::run(&opt, capacity);
+            return;
+        }
+        Some(Command::Serve(serve)) => {
+            server::run(serve).unwrap_or_else(|error| error.exit());
+            return;
+        }
+        None => {}
+    }
+
+    validation::validate_or_exit(&opt);
+    validation::print_warnings(&opt);
+    if let Some(format) = opt.export {
+        let exported = export::export(&opt, format).unwrap_or_else(|error| error.exit());
+        print!("{}", exported);
+        return;
+    }
+
+    if (!opt.service_time_caps.is_empty()
+        || !opt.headroom_objectives.is_empty()
+        || opt.queues.is_some())
+        && opt.seed.is_none()
+    {
+        // The truncation, headroom and queue comparison reports rerun the simulation, and have to see the same
+        // arrivals.
+        opt.seed = Some(thread_rng().gen());
+    }
+    let summary = simulation::run(&opt);
+    print_summary(&opt, &summary);
+    match (opt.plot.as_ref(), summary.timeline.as_ref()) {
+        (Some(Plot::Ascii), Some(timeline)) => plot::print(timeline),
+        (Some(Plot::Svg(path)), Some(timeline)) => plot::write_svg(path, timeline)
+            .unwrap_or_else(|e| ConfigError::io("write plot", path, e).exit()),
+        _ => {}
+    }
+    if opt.batching_report {
+        batching::print_report(&opt, &summary);
+    }
+    if !opt.service_time_caps.is_empty() {
+        truncation::print_report(&opt, &summary);
+    }
+    if opt.queues.is_some() {
+        balancer::print_comparison(&opt, &summary);
+    }
+    if !opt.headroom_objectives.is_empty() {
+        let ticks = opt
+            .headroom_ticks
+            .unwrap_or(opt.simulation_ticks.min(200_000));
+        headroom::print_report(&opt, ticks);
+    }
+    if let Some(path) = opt.html_report.as_ref() {
+        let args: Vec<String> = env::args().skip(1).collect();
+        let run = Run {
+            label: "Run",
+            args: "",
+            summary: &summary,
+        };
+        report::write(path, &args.join(" "), &metadata::entries(&opt), &[run])
+            .unwrap_or_else(|e| ConfigError::io("write HTML report", path, e).exit());
+    }
+    let violations = threshold_violations(&opt, &summary.stats);
+    for violation in violations.iter() {
+        eprintln!("{}", violation);
+    }
+    if !violations.is_empty()
+        || opt.slo_exit_code && opt.slo().is_some_and(|slo| !slo.is_met(&summary.stats))
+    {
+        process::exit(1);
+    }
+}
+
+/// Descriptions of the --fail_if thresholds the run went over.
+fn threshold_violations(opt: &Opt, stats: &Stats) -> Vec<String> {
+    let mut violations = Vec::new();
+    if let Some(threshold) = opt.fail_if_failure_rate_above {
+        if stats.failure_rate() > threshold {
+            violations.push(format!(
+                "Failure rate {:.2}% is above the threshold of {}%",
+                stats.failure_rate(),
+                threshold
+            ));
+        }
+    }
+    if let Some(threshold) = opt.fail_if_p99_above {
+        let p99 = stats.latency.percentile(99.0);
+        if p99 > threshold {
+            violations.push(format!(
+                "p99 latency {} is above the threshold of {}",
+                p99, threshold
+            ));
+        }
+    }
+    violations
+}
+
+/// Value of a repeated parameter at the specified position, falling back to the last value specified.
+fn nth_or_last<T: Copy>(values: &[T], index: usize) -> Option<T> {
+    values.get(index).or(values.last()).copied()
+}
+
+fn print_summary(opt: &Opt, summary: &Summary) {
+    metadata::print_summary(opt);
+    println!("Failure rate: {:.2}%", summary.stats.failure_rate());
+    summary.stats.print_failure_summary();
+    summary.stats.print_retry_amplification_summary();
+    summary.stats.print_shedding_summary();
+    if let Some(retry_after) = opt.retry_after {
+        summary.stats.print_retry_after_summary(retry_after);
+    }
+    if let Some(queue_timeout) = opt.queue_timeout {
+        summary.stats.print_queue_wait_budget_summary(queue_timeout);
+    }
+    if opt.cull_timed_out {
+        summary.stats.print_culling_summary();
+    }
+    summary.stats.print_brownout_summary();
+    summary
+        .stats
+        .print_throughput_summary(summary.ticks.saturating_sub(opt.warmup_ticks));
+    utilization::print_summary(&summary.worker_times);
+    summary.print_wasted_work_summary();
+    if opt.latency_breakdown {
+        summary.stats.print_latency_breakdown();
+    }
+    if opt.intended_latency {
+        summary.stats.print_intended_latency(opt.request_timeout);
+    }
+    if let Some(slo) = opt.slo() {
+        slo.print_summary(&summary.stats);
+    }
+    if let Some(cost) = opt.cost() {
+        cost.print_summary(&summary.stats);
+    }
+    summary.print_class_summary();
+    if !opt.retry_policies.is_empty() {
+        summary.print_retry_policy_summary();
+    }
+    if let Some(request_sizes) = summary.request_sizes.as_ref() {
+        request_sizes.print_summary();
+    }
+    if let Some(timeouts) = summary.timeouts.as_ref() {
+        timeouts.print_summary();
+    }
+    if let Some(keys) = summary.keys.as_ref() {
+        keys.print_summary();
+    }
+    summary.print_pool_summary();
+    if let Some(quotas) = summary.quotas.as_ref() {
+        quotas.print_summary(&summary.stats.classes);
+    }
+    if let Some(shedding) = summary.shedding.as_ref() {
+        shedding.print_summary(&summary.stats.classes);
+    }
+    for pool in summary.pools.iter() {
+        // Only named with more than one pool.
+        let name = Some(pool.name()).filter(|_| summary.pools.len() > 1);
+        if let Some(autoscaler) = pool.autoscaler() {
+            autoscaler.print_summary(name);
+        }
+        if let Some(straggler) = pool.straggler() {
+            straggler.print_summary(name, pool.workers());
+        }
+    }
+    if let Some(fan_out) = summary.fan_out.as_ref() {
+        fan_out.print_summary(&summary.stats.latency);
+    }
+    if let Some(cache) = summary.cache.as_ref() {
+        cache.print_summary();
+    }
+    if let Some(coalescer) = summary.coalescer.as_ref() {
+        coalescer.print_summary(summary.stats.total_requests);
+    }
+    if let Some(database) = summary.database.as_ref() {
+        database.print_summary();
+    }
+    if let Some(resource) = summary.resource.as_ref() {
+        resource.print_summary();
+    }
+    if let Some(policy) = summary.policy.as_ref() {
+        policy.print_summary();
+    }
+    if let Some(mitigations) = summary.mitigations.as_ref() {
+        mitigations.print_summary();
+    }
+    if let Some(chaos) = summary.chaos.as_ref() {
+        chaos.print_summary();
+    }
+    if let Some(closed_loop) = summary.closed_loop.as_ref() {
+        closed_loop.print_summary();
+    }
+    if let Some(client_pool) = summary.client_pool.as_ref() {
+        client_pool.print_summary();
+    }
+    if let Some(network) = summary.network.as_ref() {
+        network.print_summary(summary.stats.latency.mean());
+    }
+    if let Some(stalls) = summary.stalls.as_ref() {
+        stalls.print_summary(summary.ticks.saturating_sub(opt.warmup_ticks));
+    }
+    if let Some(telemetry) = summary.telemetry.as_ref() {
+        telemetry.print_summary(opt.simulation_ticks);
+    }
+    for observer in summary.observers.iter() {
+        observer.print_summary();
+    }
+}
@@ -0,0 +1,96 @@
+use crate::checkpoint;
+use crate::events::{Event, Observer, SystemState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Checks that the simulation obeys Little's Law: the mean number of requests in the system (L) equals the
+/// rate they enter it at (λ) times the mean time they spend in it (W). The law holds for any stable system, so
+/// a large difference means the run was too short to reach a steady state, or a bug in the bookkeeping.
+///
+/// Sub-requests of fanned out requests count as separate requests, and timed out requests count until their
+/// worker finishes them, since they occupy the system all the same.
+#[derive(Serialize, Deserialize)]
+pub struct LittlesLaw {
+    warmup_ticks: u64,
+    ticks: u64,
+    admitted: u64,
+    completed: u64,
+    /// Sum of the number of requests in the system during each tick.
+    in_system_sum: u64,
+    time_in_system_sum: u64,
+    /// Requests completed during the current tick. They are no longer in the system at its end, but spent
+    /// (part of) the tick in it.
+    completed_this_tick: u64,
+}
+
+impl LittlesLaw {
+    pub fn new(warmup_ticks: u64) -> LittlesLaw {
+        LittlesLaw {
+            warmup_ticks,
+            ticks: 0,
+            admitted: 0,
+            completed: 0,
+            in_system_sum: 0,
+            time_in_system_sum: 0,
+            completed_this_tick: 0,
+        }
+    }
+}
+
+impl Observer for LittlesLaw {
+    fn on_event(&mut self, tick: u64, event: &Event) {
+        if tick < self.warmup_ticks {
+            return;
+        }
+        match *event {
+            Event::Admitted { .. } => self.admitted += 1,
+            Event::Completed { time_in_system, .. }
+            | Event::Shed { time_in_system, .. }
+            | Event::Killed { time_in_system, .. }
+            | Event::Abandoned { time_in_system, .. } => {
+                self.completed += 1;
+                self.completed_this_tick += 1;
+                self.time_in_system_sum += time_in_system;
+            }
+            _ => {}
+        }
+    }
+
+    fn on_tick_end(&mut self, tick: u64, state: &SystemState) {
+        if tick < self.warmup_ticks {
+            return;
+        }
+        self.ticks += 1;
+        self.in_system_sum +=
+            (state.queue_len + state.busy_workers) as u64 + self.completed_this_tick;
+        self.completed_this_tick = 0;
+    }
+
+    fn print_summary(&self) {
+        if self.completed == 0 {
+            println!("Little's Law: no requests completed, nothing to check");
+            return;
+        }
+
+        let in_system = self.in_system_sum as f64 / self.ticks as f64;
+        let arrival_rate = self.admitted as f64 / self.ticks as f64;
+        let time_in_system = self.time_in_system_sum as f64 / self.completed as f64;
+        let predicted = arrival_rate * time_in_system;
+        println!(
+            "Little's Law: L={:.2} requests in system, λ={:.4} per tick, W={:.2} ticks, λW={:.2} ({:+.2}% off)",
+            in_system,
+            arrival_rate,
+            time_in_system,
+            predicted,
+            (predicted - in_system) / in_system * 100.0
+        );
+    }
+
+    fn checkpoint(&mut self) -> Result<Value, String> {
+        checkpoint::save(self)
+    }
+
+    fn resume(&mut self, state: Value) -> Result<(), String> {
+        checkpoint::restore(self, state)
+    }
+}
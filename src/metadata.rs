@@ -0,0 +1,65 @@
+use crate::Opt;
+use std::str::FromStr;
+
+/// A piece of experiment metadata, such as the git SHA of the system being modeled or the ticket the experiment
+/// is for. Specified on the command line as `key=value`.
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for Metadata {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Ok(Metadata {
+                key: key.trim().to_string(),
+                value: value.trim().to_string(),
+            }),
+            _ => Err(format!("Expected metadata as key=value, got '{}'", s)),
+        }
+    }
+}
+
+/// The notes and metadata of the run, as key and value pairs in the order they were specified, notes first,
+/// followed by the scenario file and preset, if any. Line breaks are replaced with spaces, so that every pair fits on a
+/// line of any of the outputs.
+pub fn entries(opt: &Opt) -> Vec<(String, String)> {
+    let one_line = |s: &str| s.replace(['\r', '\n'], " ");
+    opt.notes
+        .iter()
+        .map(|note| ("note".to_string(), one_line(note)))
+        .chain(
+            opt.config
+                .iter()
+                .map(|path| ("config".to_string(), one_line(path))),
+        )
+        .chain(
+            opt.preset
+                .iter()
+                .map(|preset| ("preset".to_string(), preset.to_string())),
+        )
+        .chain(
+            opt.metadata
+                .iter()
+                .map(|m| (one_line(&m.key), one_line(&m.value))),
+        )
+        .collect()
+}
+
+/// The notes and metadata of the run as comment lines starting with `#`, for the CSV and script outputs.
+pub fn comment_lines(opt: &Opt) -> String {
+    entries(opt)
+        .iter()
+        .map(|(key, value)| format!("# {}: {}\n", key, value))
+        .collect()
+}
+
+/// Prints the notes and metadata at the top of the summary, so that a saved output says what it was for.
+pub fn print_summary(opt: &Opt) {
+    for (key, value) in entries(opt) {
+        println!("{}: {}", key, value);
+    }
+}
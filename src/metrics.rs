@@ -0,0 +1,184 @@
+use crate::simulation::Stats;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the served values are updated.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Live values of the simulation, as last published.
+#[derive(Default, Clone)]
+struct Snapshot {
+    tick: u64,
+    queue_len: usize,
+    busy_workers: usize,
+    workers: usize,
+    requests: u64,
+    rejected: u64,
+    timed_out: u64,
+    abandoned: u64,
+    balked: u64,
+    retried: u64,
+    latency_sum: f64,
+    latency_count: u64,
+}
+
+/// Serves the live counters and gauges of a running simulation on `/metrics`, in the Prometheus text format,
+/// so that dashboards can watch a slowed down simulation (see --speed) as if it was a real system.
+pub struct MetricsServer {
+    snapshot: Arc<Mutex<Snapshot>>,
+    last_update: Instant,
+}
+
+impl MetricsServer {
+    /// Starts serving on the specified address, e.g. 127.0.0.1:9898.
+    pub fn start(address: &str) -> io::Result<MetricsServer> {
+        let listener = TcpListener::bind(address)?;
+        let snapshot = Arc::new(Mutex::new(Snapshot::default()));
+        let served = Arc::clone(&snapshot);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A misbehaving client only loses its own response.
+                let _ = respond(stream, &served);
+            }
+        });
+        Ok(MetricsServer {
+            snapshot,
+            last_update: Instant::now(),
+        })
+    }
+
+    /// Publishes the state of the system at the end of the specified tick, if it's time for an update.
+    pub fn tick(
+        &mut self,
+        tick: u64,
+        queue_len: usize,
+        busy_workers: usize,
+        workers: usize,
+        stats: &Stats,
+    ) {
+        if self.last_update.elapsed() < UPDATE_INTERVAL {
+            return;
+        }
+        self.last_update = Instant::now();
+        let snapshot = Snapshot {
+            tick,
+            queue_len,
+            busy_workers,
+            workers,
+            requests: stats.total_requests,
+            rejected: stats.rejected_requests,
+            timed_out: stats.timed_out_requests,
+            abandoned: stats.abandoned_requests,
+            balked: stats.balked_requests,
+            retried: stats.retried_rejections + stats.retried_timeouts + stats.retried_abandonments,
+            latency_sum: stats.latency.mean() * stats.latency.total() as f64,
+            latency_count: stats.latency.total(),
+        };
+        *self.snapshot.lock().unwrap() = snapshot;
+    }
+}
+
+/// Answers a single HTTP request.
+fn respond(stream: TcpStream, snapshot: &Mutex<Snapshot>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The rest of the request (the headers) doesn't matter.
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut stream = reader.into_inner();
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+    if path != "/metrics" {
+        return stream.write_all(
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        );
+    }
+    let body = format_metrics(&snapshot.lock().unwrap().clone());
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+/// Formats the values in the Prometheus text exposition format.
+fn format_metrics(s: &Snapshot) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, f64)]| {
+        writeln!(out, "# HELP queueingsimulator_{} {}", name, help).unwrap();
+        writeln!(out, "# TYPE queueingsimulator_{} {}", name, kind).unwrap();
+        for (labels, value) in samples {
+            writeln!(out, "queueingsimulator_{}{} {}", name, labels, value).unwrap();
+        }
+    };
+    metric(
+        "tick",
+        "gauge",
+        "Current simulated tick.",
+        &[("", s.tick as f64)],
+    );
+    metric(
+        "queue_length",
+        "gauge",
+        "Requests waiting in the queues.",
+        &[("", s.queue_len as f64)],
+    );
+    metric(
+        "busy_workers",
+        "gauge",
+        "Workers processing a request.",
+        &[("", s.busy_workers as f64)],
+    );
+    metric(
+        "workers",
+        "gauge",
+        "Workers in the pools.",
+        &[("", s.workers as f64)],
+    );
+    metric(
+        "utilization",
+        "gauge",
+        "Share of the workers processing a request.",
+        &[("", s.busy_workers as f64 / s.workers.max(1) as f64)],
+    );
+    metric(
+        "requests_total",
+        "counter",
+        "Requests sent by the clients, including retries.",
+        &[("", s.requests as f64)],
+    );
+    metric(
+        "failures_total",
+        "counter",
+        "Requests that failed, by how they failed.",
+        &[
+            ("{kind=\"rejected\"}", s.rejected as f64),
+            ("{kind=\"timed_out\"}", s.timed_out as f64),
+            ("{kind=\"abandoned\"}", s.abandoned as f64),
+            ("{kind=\"balked\"}", s.balked as f64),
+        ],
+    );
+    metric(
+        "retries_total",
+        "counter",
+        "Failed requests the clients retried.",
+        &[("", s.retried as f64)],
+    );
+    metric(
+        "latency_ticks",
+        "summary",
+        "Latency of the successful requests, in ticks.",
+        &[("_sum", s.latency_sum), ("_count", s.latency_count as f64)],
+    );
+    out
+}
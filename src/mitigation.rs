@@ -0,0 +1,157 @@
+use crate::alert::AlertCondition;
+use crate::events::SystemState;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Action taken to mitigate an incident.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Action {
+    /// Add the specified number of workers to every pool. They take --worker_startup_ticks to start.
+    AddWorkers(u16),
+    /// Shrink the queue of every pool to the specified size, shedding the requests over it.
+    Shed(usize),
+    /// Make the clients stop retrying failed requests.
+    CutRetries,
+    /// Switch the queues to LIFO.
+    Lifo,
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, argument) = match s.split_once('=') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (s, None),
+        };
+        let invalid = || format!("Invalid mitigation action '{}'", s);
+        match (name, argument) {
+            ("add_workers", Some(workers)) => {
+                Ok(Action::AddWorkers(workers.parse().map_err(|_| invalid())?))
+            }
+            ("shed", Some(queue_size)) => Ok(Action::Shed(queue_size.parse().map_err(|_| invalid())?)),
+            ("cut_retries", None) => Ok(Action::CutRetries),
+            ("lifo", None) => Ok(Action::Lifo),
+            _ => Err(format!(
+                "Unknown mitigation action '{}', expected add_workers=N, shed=N, cut_retries or lifo",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::AddWorkers(workers) => write!(f, "add_workers={}", workers),
+            Action::Shed(queue_size) => write!(f, "shed={}", queue_size),
+            Action::CutRetries => write!(f, "cut_retries"),
+            Action::Lifo => write!(f, "lifo"),
+        }
+    }
+}
+
+/// Mitigation taken once its condition first holds, as `condition:action`, e.g. `queue_len>500:cut_retries`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MitigationSpec {
+    condition: AlertCondition,
+    action: Action,
+}
+
+impl FromStr for MitigationSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (condition, action) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected mitigation as condition:action, got '{}'", s))?;
+        Ok(MitigationSpec {
+            condition: condition.parse()?,
+            action: action.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for MitigationSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.condition, self.action)
+    }
+}
+
+/// State of a single mitigation.
+#[derive(Serialize, Deserialize)]
+struct Mitigation {
+    spec: MitigationSpec,
+    /// Tick the condition first held at.
+    triggered: Option<u64>,
+    applied: bool,
+}
+
+/// Mid-run mitigations, each taken once, a delay after its condition first holds. The delay stands for the
+/// time it takes to detect the problem and decide what to do about it, whether by a human or by automation.
+#[derive(Serialize, Deserialize)]
+pub struct Mitigations {
+    mitigations: Vec<Mitigation>,
+    delay_ticks: u64,
+}
+
+impl Mitigations {
+    pub fn new(specs: &[MitigationSpec], delay_ticks: u64) -> Mitigations {
+        Mitigations {
+            mitigations: specs
+                .iter()
+                .map(|spec| Mitigation {
+                    spec: spec.clone(),
+                    triggered: None,
+                    applied: false,
+                })
+                .collect(),
+            delay_ticks,
+        }
+    }
+
+    /// Checks the conditions at the end of the specified tick, during which `rejections` requests were
+    /// rejected.
+    pub fn check(&mut self, tick: u64, state: &SystemState, rejections: u64) {
+        for mitigation in self.mitigations.iter_mut() {
+            let condition = &mitigation.spec.condition;
+            if mitigation.triggered.is_none() && condition.holds(condition.value(state, rejections))
+            {
+                mitigation.triggered = Some(tick);
+            }
+        }
+    }
+
+    /// Actions taking effect at the start of the specified tick.
+    pub fn due(&mut self, tick: u64) -> Vec<Action> {
+        let mut actions = Vec::new();
+        for mitigation in self.mitigations.iter_mut() {
+            if let Some(triggered) = mitigation.triggered {
+                if !mitigation.applied && tick > triggered + self.delay_ticks {
+                    mitigation.applied = true;
+                    actions.push(mitigation.spec.action);
+                }
+            }
+        }
+        actions
+    }
+
+    pub fn print_summary(&self) {
+        for mitigation in self.mitigations.iter() {
+            match (mitigation.triggered, mitigation.applied) {
+                (Some(tick), true) => println!(
+                    "Mitigation {}: triggered at tick {}, took effect at tick {}",
+                    mitigation.spec,
+                    tick,
+                    tick + self.delay_ticks + 1
+                ),
+                (Some(tick), false) => println!(
+                    "Mitigation {}: triggered at tick {}, the run ended before it took effect",
+                    mitigation.spec, tick
+                ),
+                (None, _) => println!("Mitigation {}: never triggered", mitigation.spec),
+            }
+        }
+    }
+}
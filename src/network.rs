@@ -0,0 +1,79 @@
+use crate::request::Request;
+use crate::service::{LatencyDistribution, ServiceTime};
+use rand::distributions::Distribution;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Network between the clients and the server, delaying both the requests on their way to the queue and the
+/// responses on their way back. The delays count against the timeout of the client, but take none of the
+/// server's capacity, leaving the server less of the timeout to respond in.
+#[derive(Serialize, Deserialize)]
+pub struct Network {
+    /// Distribution of the one-way delay, in ticks.
+    delay: ServiceTime,
+    /// Requests on their way to the server, by the tick they reach it at.
+    in_flight: BTreeMap<u64, Vec<Request>>,
+    /// Sum of the round trip delays of the requests sent after the warm-up, and their number.
+    round_trip_sum: u64,
+    requests: u64,
+}
+
+impl Network {
+    pub fn new(distribution: LatencyDistribution, mean_delay: f64) -> Network {
+        Network {
+            delay: ServiceTime::new(distribution, mean_delay),
+            in_flight: BTreeMap::new(),
+            round_trip_sum: 0,
+            requests: 0,
+        }
+    }
+
+    /// Samples the delays of a request sent during the specified tick, on its way to the server and back, and
+    /// holds on to it until it reaches the server. Returns the request if it reaches the server right away.
+    pub fn send<R: Rng>(
+        &mut self,
+        mut request: Request,
+        tick: u64,
+        measured: bool,
+        rng: &mut R,
+    ) -> Option<Request> {
+        let request_delay = self.delay.sample(rng).round() as u64;
+        let response_delay = self.delay.sample(rng).round() as u64;
+        request.set_network_delays(request_delay, response_delay);
+        if measured {
+            self.round_trip_sum += request_delay + response_delay;
+            self.requests += 1;
+        }
+        if request_delay == 0 {
+            return Some(request);
+        }
+        self.in_flight
+            .entry(tick + request_delay)
+            .or_default()
+            .push(request);
+        None
+    }
+
+    /// Requests reaching the server during the specified tick.
+    pub fn arrivals_at(&mut self, tick: u64) -> Vec<Request> {
+        self.in_flight.remove(&tick).unwrap_or_default()
+    }
+
+    /// Prints the mean round trip, and the share of the specified mean latency of the requests it accounts for.
+    pub fn print_summary(&self, mean_latency: f64) {
+        let round_trip = self.round_trip_sum as f64 / self.requests.max(1) as f64;
+        let share = if mean_latency > 0.0 {
+            format!(
+                ", {:.2}% of the mean latency",
+                round_trip / mean_latency * 100.0
+            )
+        } else {
+            String::new()
+        };
+        println!(
+            "Network: mean round trip of {:.2} ticks{}",
+            round_trip, share
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+/// File a run writes as it goes, or stdout if the path is "-". A checkpoint records how much of the file was
+/// written, and the run resuming from it cuts the file back to that and carries on after it, so that the file ends
+/// up the way the uninterrupted run would have written it.
+pub struct Output {
+    /// The file, none for stdout.
+    file: Option<BufWriter<File>>,
+    /// Whether the file was written by the run being resumed, and already has its beginning.
+    resumed: bool,
+}
+
+impl Output {
+    /// Creates the output at the specified path. A resumed run opens the file the run it resumes wrote instead,
+    /// and cuts it back once it knows where the checkpoint left off.
+    pub fn create(path: &str, resumed: bool) -> io::Result<Output> {
+        if path == "-" {
+            return Ok(Output {
+                file: None,
+                resumed: false,
+            });
+        }
+        let file = if resumed {
+            OpenOptions::new().write(true).open(path)?
+        } else {
+            File::create(path)?
+        };
+        Ok(Output {
+            file: Some(BufWriter::new(file)),
+            resumed,
+        })
+    }
+
+    /// Whether the output carries on from the run being resumed, so that whatever starts the output, like a
+    /// header, was written already.
+    pub fn is_resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// Flushes the output, and returns the length of the file written so far, for a checkpoint.
+    pub fn checkpoint(&mut self) -> io::Result<u64> {
+        self.flush()?;
+        match self.file.as_mut() {
+            Some(file) => file.get_mut().stream_position(),
+            None => Ok(0),
+        }
+    }
+
+    /// Cuts the file back to the length it had at the checkpoint, to carry on writing after it.
+    pub fn resume(&mut self, length: u64) -> io::Result<()> {
+        if let Some(file) = self.file.as_mut() {
+            file.flush()?;
+            let file = file.get_mut();
+            file.set_len(length)?;
+            file.seek(SeekFrom::Start(length))?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.file.as_mut() {
+            Some(file) => file.write(buf),
+            None => io::stdout().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => io::stdout().flush(),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Slows the simulation down to a fixed number of ticks per second of wall time, so that it can be watched
+/// by external tools as it runs.
+pub struct Pacer {
+    ticks_per_second: f64,
+    started: Instant,
+}
+
+impl Pacer {
+    pub fn new(ticks_per_second: f64) -> Pacer {
+        Pacer {
+            ticks_per_second,
+            started: Instant::now(),
+        }
+    }
+
+    /// Waits until it's time for the tick after the specified one.
+    pub fn tick(&self, tick: u64) {
+        let due = self.started + Duration::from_secs_f64((tick + 1) as f64 / self.ticks_per_second);
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+    }
+}
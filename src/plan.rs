@@ -0,0 +1,116 @@
+use crate::headroom::Objective;
+use crate::simulation::{self, Stats};
+use crate::validation::{self, ConfigError};
+use crate::Opt;
+use rand::{thread_rng, Rng};
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct PlanOpt {
+    /// Objective the fleet has to meet, as metric<threshold, where the metric is failure_rate (in percent), mean
+    /// or a latency percentile like p99 (in ticks). Can be repeated, in which case all of them have to be met.
+    #[structopt(long = "objective", number_of_values = 1)]
+    objectives: Vec<Objective>,
+
+    /// Largest fleet to try.
+    #[structopt(long = "max_workers", default_value = "10000")]
+    max_workers: u16,
+}
+
+/// Finds the smallest number of workers that meets the objectives, and the SLO if there is one, for the
+/// configured workload. Reruns the simulation with the same seed for every fleet size, first doubling the
+/// workers and then bisecting, so that the failure rate only changes with the workers and not with the arrivals.
+pub fn run(opt: &Opt, plan: &PlanOpt) {
+    let slo = opt.slo();
+    if plan.objectives.is_empty() && slo.is_none() {
+        ConfigError::Invalid(
+            "Plan requires at least one --objective or an --slo_latency".to_string(),
+        )
+        .exit();
+    }
+    if !opt.pools.is_empty() || opt.autoscale_max_workers > 0 {
+        ConfigError::Conflict {
+            problem: "Plan can't be combined with worker pools or autoscaling",
+            fix: "Leave out --pool and --autoscale_max_workers",
+        }
+        .exit();
+    }
+    let seed = opt.seed.unwrap_or_else(|| thread_rng().gen());
+    let meets = |workers: u16| {
+        let mut fleet = opt.clone();
+        fleet.num_workers = workers;
+        fleet.seed = Some(seed);
+        fleet.headroom_objectives.clear();
+        fleet.service_time_caps.clear();
+        fleet.batching_report = false;
+        fleet.explain = false;
+        fleet.queue_distribution = false;
+        fleet.littles_law = false;
+        fleet.telemetry = false;
+        fleet.tui = false;
+        fleet.metrics_address = None;
+        fleet.speed = None;
+        fleet.progress = false;
+        fleet.time_series = None;
+        fleet.snapshots = None;
+        fleet.event_log = None;
+        fleet.chrome_trace = None;
+        fleet.html_report = None;
+        fleet.plot = None;
+        fleet.alerts.clear();
+        validation::validate_or_exit(&fleet);
+        let stats = simulation::run(&fleet).stats;
+        let met = plan.objectives.iter().all(|o| o.is_met(&stats))
+            && slo.as_ref().is_none_or(|slo| slo.is_met(&stats));
+        print_step(workers, &stats, met);
+        met
+    };
+    let objectives: Vec<String> = plan
+        .objectives
+        .iter()
+        .map(Objective::to_string)
+        .chain(opt.slo_latency.map(|latency| {
+            format!(
+                "{}% of the requests within {} ticks",
+                opt.slo_target, latency
+            )
+        }))
+        .collect();
+    let objectives = objectives.join(", ");
+
+    // With independent queues, every queue needs a worker of its own.
+    let fewest = opt.queues.unwrap_or(1);
+    let (mut low, mut high) = (fewest - 1, fewest);
+    while !meets(high) {
+        low = high;
+        if high >= plan.max_workers {
+            println!(
+                "Plan: {} not met with up to {} workers",
+                objectives, plan.max_workers
+            );
+            return;
+        }
+        high = high.saturating_mul(2).min(plan.max_workers);
+    }
+    // The objectives are met with `high` workers, and missed with `low` (or it's too few to run).
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        if meets(middle) {
+            high = middle;
+        } else {
+            low = middle;
+        }
+    }
+    println!("Plan: {} workers meet {}", high, objectives);
+}
+
+fn print_step(workers: u16, stats: &Stats, met: bool) {
+    println!(
+        "Workers {}: failure rate {:.2}%, latency: mean={:.2} {} ({})",
+        workers,
+        stats.failure_rate(),
+        stats.latency.mean(),
+        stats.latency.format_percentiles(),
+        if met { "met" } else { "missed" }
+    );
+}
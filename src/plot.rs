@@ -0,0 +1,227 @@
+use crate::report::Timeline;
+use plotters::prelude::*;
+use std::str::FromStr;
+
+/// Width of the plots, in characters, not counting the axis labels.
+const WIDTH: usize = 72;
+/// Height of the plots, in characters.
+const HEIGHT: usize = 8;
+/// Bits of the braille dots, by column and by row from the top.
+const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+/// Size of the SVG plots, in pixels.
+const SVG_SIZE: (u32, u32) = (800, 900);
+
+/// Where to plot the queue length and failure rate over time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Plot {
+    /// Braille charts printed to stdout at the end of the run, for terminals without a GUI.
+    Ascii,
+    /// SVG image with the latency percentiles as well, written to the specified file.
+    Svg(String),
+}
+
+impl FromStr for Plot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(Plot::Ascii),
+            _ if s.ends_with(".svg") => Ok(Plot::Svg(s.to_string())),
+            _ => Err(format!(
+                "Unknown plot '{}', expected ascii or a file name ending in .svg",
+                s
+            )),
+        }
+    }
+}
+
+/// Prints charts of the peak queue length and the failure rate over time.
+pub fn print(timeline: &Timeline) {
+    let samples = timeline.peak_queue_lens().len();
+    let columns = samples.min(WIDTH * 2);
+    // Range of samples shown by each column of dots.
+    let column = |index: usize| index * samples / columns..(index + 1) * samples / columns;
+
+    let queue_lens: Vec<f64> = (0..columns)
+        .map(|i| {
+            timeline.peak_queue_lens()[column(i)]
+                .iter()
+                .max()
+                .copied()
+                .unwrap_or(0) as f64
+        })
+        .collect();
+    let failure_rates: Vec<f64> = (0..columns)
+        .map(|i| {
+            let requests: u64 = timeline.requests()[column(i)].iter().sum();
+            let failures: u64 = timeline.failures()[column(i)].iter().sum();
+            if requests == 0 {
+                0.0
+            } else {
+                failures as f64 / requests as f64 * 100.0
+            }
+        })
+        .collect();
+
+    let ticks = samples as u64 * timeline.every();
+    println!("Peak queue length:");
+    chart(
+        &queue_lens,
+        queue_lens.iter().copied().fold(1.0, f64::max),
+        ticks,
+    );
+    println!("Failure rate (%):");
+    chart(
+        &failure_rates,
+        failure_rates.iter().copied().fold(1.0, f64::max),
+        ticks,
+    );
+}
+
+/// Writes an SVG image with charts of the peak queue length, the latency percentiles and the failure rate over
+/// time, one above the other.
+pub fn write_svg(path: &str, timeline: &Timeline) -> Result<(), String> {
+    let every = timeline.every() as f64;
+    let ticks = (timeline.peak_queue_lens().len() as f64 * every).max(1.0);
+    let points = |values: Vec<f64>| -> Vec<(f64, f64)> {
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| (index as f64 * every, value))
+            .collect()
+    };
+    let queue_lens = points(
+        timeline
+            .peak_queue_lens()
+            .iter()
+            .map(|&l| l as f64)
+            .collect(),
+    );
+    let failure_rates = points(
+        timeline
+            .requests()
+            .iter()
+            .zip(timeline.failures())
+            .map(|(&requests, &failures)| {
+                if requests == 0 {
+                    0.0
+                } else {
+                    failures as f64 / requests as f64 * 100.0
+                }
+            })
+            .collect(),
+    );
+    let percentile = |index: usize| {
+        points(
+            timeline
+                .latency_percentiles()
+                .iter()
+                .map(|p| p[index] as f64)
+                .collect(),
+        )
+    };
+    let latencies = [
+        ("p50", percentile(0), BLUE),
+        ("p90", percentile(1), GREEN),
+        ("p99", percentile(2), RED),
+    ];
+
+    let root = SVGBackend::new(path, SVG_SIZE).into_drawing_area();
+    root.fill(&WHITE).map_err(|e| e.to_string())?;
+    let areas = root.split_evenly((3, 1));
+    let max = |values: &[(f64, f64)]| values.iter().map(|p| p.1).fold(1.0, f64::max);
+    let charts = [
+        (
+            "Peak queue length",
+            vec![("peak", queue_lens.clone(), BLACK)],
+            max(&queue_lens),
+        ),
+        (
+            "Latency (ticks)",
+            latencies.to_vec(),
+            latencies.iter().map(|l| max(&l.1)).fold(1.0, f64::max),
+        ),
+        (
+            "Failure rate (%)",
+            vec![("failure rate", failure_rates.clone(), RED)],
+            max(&failure_rates),
+        ),
+    ];
+    for (area, (title, lines, y_max)) in areas.iter().zip(charts) {
+        let mut chart = ChartBuilder::on(area)
+            .caption(title, ("sans-serif", 18))
+            .margin(10)
+            .x_label_area_size(30)
+            .y_label_area_size(50)
+            .build_cartesian_2d(0.0..ticks, 0.0..y_max * 1.05)
+            .map_err(|e| e.to_string())?;
+        chart
+            .configure_mesh()
+            .x_desc("tick")
+            .draw()
+            .map_err(|e| e.to_string())?;
+        let legend = lines.len() > 1;
+        for (label, line, color) in lines {
+            let series = chart
+                .draw_series(LineSeries::new(line, &color))
+                .map_err(|e| e.to_string())?;
+            if legend {
+                series
+                    .label(label)
+                    .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], color));
+            }
+        }
+        if legend {
+            chart
+                .configure_series_labels()
+                .background_style(WHITE.mix(0.8))
+                .border_style(BLACK)
+                .draw()
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    root.present().map_err(|e| e.to_string())
+}
+
+/// Prints an area chart of the values in braille characters, each of which is two dots wide and four high.
+fn chart(values: &[f64], max: f64, ticks: u64) {
+    let dot_rows = HEIGHT * 4;
+    let heights: Vec<usize> = values
+        .iter()
+        .map(|v| ((v / max) * dot_rows as f64).round() as usize)
+        .collect();
+    let label_width = format!("{:.0}", max).len();
+
+    for row in 0..HEIGHT {
+        let (label, axis) = match row {
+            0 => (format!("{:.0}", max), '┤'),
+            _ if row == HEIGHT - 1 => ("0".to_string(), '┤'),
+            _ => (String::new(), '│'),
+        };
+        let mut line = format!("{:>w$} {}", label, axis, w = label_width);
+        for pair in heights.chunks(2) {
+            let mut bits = 0;
+            for (side, &height) in pair.iter().enumerate() {
+                for (dot, bit) in DOTS[side].iter().enumerate() {
+                    // Distance of the dot from the bottom of the chart, counted in dots.
+                    let level = (HEIGHT - 1 - row) * 4 + (3 - dot);
+                    if level < height {
+                        bits |= bit;
+                    }
+                }
+            }
+            line.push(char::from_u32(0x2800 + bits).unwrap());
+        }
+        println!("{}", line);
+    }
+    let axis_width = values.len().div_ceil(2);
+    println!("{:>w$} └{}", "", "─".repeat(axis_width), w = label_width);
+    println!(
+        "{:>w$}  0{:>x$}",
+        "",
+        format!("{} ticks", ticks),
+        w = label_width,
+        x = axis_width.saturating_sub(1)
+    );
+}
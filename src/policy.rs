@@ -0,0 +1,193 @@
+use crate::subprocess::Subprocess;
+use crate::validation::ConfigError;
+use serde::Serialize;
+
+/// Admission and scheduling policy implemented by an external process, e.g. to prototype a policy in Python
+/// before porting it to Rust. Every decision is a round trip to the process, so this is slow.
+///
+/// The simulator writes the state relevant to the decision as a single line of JSON to the standard input of
+/// the policy, and expects the decision as a single line on its standard output:
+///
+/// * `{"decision": "admit", "tick": 5, "class": "api", "pool": "shared", ...}` for every request about to be
+///   admitted to a pool, answered with `admit` or `reject`.
+/// * `{"decision": "dequeue", "tick": 5, "pool": "shared", ...}` once a tick for every pool with both queued
+///   requests and free workers, answered with `front` (oldest request first) or `back` (newest first).
+pub struct Policy {
+    command: String,
+    process: Subprocess,
+    admitted: u64,
+    rejected: u64,
+    dequeued_from_front: u64,
+    dequeued_from_back: u64,
+}
+
+/// State of a pool, as seen by the policy.
+#[derive(Serialize)]
+pub struct PoolState<'a> {
+    pub tick: u64,
+    pub pool: &'a str,
+    pub queue_len: usize,
+    pub queue_size: usize,
+    pub busy_workers: usize,
+    pub workers: usize,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "decision", rename_all = "lowercase")]
+enum Decision<'a> {
+    Admit {
+        class: &'a str,
+        #[serde(flatten)]
+        state: &'a PoolState<'a>,
+    },
+    Dequeue {
+        #[serde(flatten)]
+        state: &'a PoolState<'a>,
+    },
+}
+
+impl Policy {
+    /// Starts the policy, using the shell to run the specified command.
+    pub fn spawn(command: &str) -> Result<Policy, String> {
+        Ok(Policy {
+            command: command.to_string(),
+            process: Subprocess::spawn(command)?,
+            admitted: 0,
+            rejected: 0,
+            dequeued_from_front: 0,
+            dequeued_from_back: 0,
+        })
+    }
+
+    /// Whether a request of the specified class should be admitted to the pool. Exits if the policy fails to
+    /// decide.
+    pub fn admit(&mut self, class: &str, state: &PoolState) -> bool {
+        let admit = self
+            .decide(&Decision::Admit { class, state }, ["admit", "reject"])
+            .unwrap_or_else(|e| self.fail(e));
+        if admit {
+            self.admitted += 1;
+        } else {
+            self.rejected += 1;
+        }
+        admit
+    }
+
+    /// Whether the free workers of the pool should pick up the newest queued requests, instead of the oldest.
+    /// Exits if the policy fails to decide.
+    pub fn dequeue_from_back(&mut self, state: &PoolState) -> bool {
+        let back = !self
+            .decide(&Decision::Dequeue { state }, ["front", "back"])
+            .unwrap_or_else(|e| self.fail(e));
+        if back {
+            self.dequeued_from_back += 1;
+        } else {
+            self.dequeued_from_front += 1;
+        }
+        back
+    }
+
+    /// Whether the policy answered the decision with the first of the two possible answers, rather than the
+    /// second.
+    fn decide(&mut self, decision: &Decision, answers: [&str; 2]) -> Result<bool, String> {
+        let request = serde_json::to_string(decision).unwrap();
+        let response = self.process.request(&request)?;
+        match response.trim() {
+            answer if answer == answers[0] => Ok(true),
+            answer if answer == answers[1] => Ok(false),
+            other => Err(format!(
+                "Expected {} or {}, got '{}'",
+                answers[0], answers[1], other
+            )),
+        }
+    }
+
+    fn fail(&self, error: String) -> ! {
+        ConfigError::io("run policy", &self.command, error).exit()
+    }
+
+    pub fn print_summary(&self) {
+        println!(
+            "Policy: {} requests admitted, {} rejected; dequeued from the front {} times, from the back {} times",
+            self.admitted, self.rejected, self.dequeued_from_front, self.dequeued_from_back
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation;
+    use crate::Opt;
+    use structopt::StructOpt;
+
+    fn state(queue_len: usize) -> PoolState<'static> {
+        PoolState {
+            tick: 5,
+            pool: "shared",
+            queue_len,
+            queue_size: 10,
+            busy_workers: 1,
+            workers: 2,
+        }
+    }
+
+    #[test]
+    fn decisions_come_from_the_process() {
+        // Rejects the batch class and serves the newest request first once the queue is half full.
+        let mut policy = Policy::spawn(
+            r#"while read line; do case "$line" in
+                 *'"class":"batch"'*) echo reject;;
+                 *'"decision":"admit"'*) echo admit;;
+                 *'"queue_len":5'*) echo back;;
+                 *) echo front;;
+               esac; done"#,
+        )
+        .unwrap();
+
+        assert!(policy.admit("api", &state(0)));
+        assert!(!policy.admit("batch", &state(0)));
+        assert!(!policy.dequeue_from_back(&state(1)));
+        assert!(policy.dequeue_from_back(&state(5)));
+        assert_eq!(
+            (
+                policy.admitted,
+                policy.rejected,
+                policy.dequeued_from_front,
+                policy.dequeued_from_back
+            ),
+            (1, 1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn unexpected_answers_fail_the_decision() {
+        let mut policy = Policy::spawn("while read line; do echo maybe; done").unwrap();
+        let decision = Decision::Admit {
+            class: "api",
+            state: &state(0),
+        };
+        assert_eq!(
+            policy.decide(&decision, ["admit", "reject"]),
+            Err("Expected admit or reject, got 'maybe'".to_string())
+        );
+    }
+
+    #[test]
+    fn rejecting_policy_rejects_every_request() {
+        let args = [
+            "queueingsimulator",
+            "-r",
+            "0.05",
+            "--policy",
+            "while read line; do echo reject; done",
+            "--simulation_time",
+            "2000",
+            "--seed",
+            "1",
+        ];
+        let stats = simulation::run(&Opt::from_iter(args)).stats;
+        assert!(stats.total_requests > 50);
+        assert_eq!(stats.rejected_requests, stats.total_requests);
+    }
+}
@@ -0,0 +1,442 @@
+use crate::autoscaler::Autoscaler;
+use crate::class::ClassSpec;
+use crate::contention::QueueDepthPenalty;
+use crate::events::Event;
+use crate::policy::PoolState;
+use crate::queue::{
+    self, ArrivalOrder, Discipline, QueueCheckpoint, QueueDiscipline, ReverseArrivalOrder,
+};
+use crate::request::Request;
+use crate::straggler::Straggler;
+use crate::utilization::WorkerTime;
+use crate::worker::Worker;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// A bulkhead: a named pool of workers with its own queue, serving a subset of the request classes.
+/// Specified on the command line as `name:workers:queue_size:class1+class2`.
+#[derive(Debug, Clone)]
+pub struct PoolSpec {
+    pub name: String,
+    pub workers: u16,
+    pub queue_size: usize,
+    pub classes: Vec<String>,
+}
+
+/// State of a pool that changes as the simulation runs, for checkpoints.
+#[derive(Serialize, Deserialize)]
+pub struct PoolCheckpoint {
+    queue: QueueCheckpoint,
+    /// Size of the queue, which a mitigation may have shrunk.
+    queue_size: usize,
+    workers: Vec<Worker>,
+    free_workers: Vec<usize>,
+    autoscaler: Option<Autoscaler>,
+    straggler: Option<Straggler>,
+    next_abandonment_tick: u64,
+}
+
+/// Pool of workers sharing a single request queue.
+pub struct Pool {
+    name: String,
+    queue: Box<dyn QueueDiscipline>,
+    workers: Vec<Worker>,
+    /// Indices of the idle workers, lowest last, so that an arriving request goes to the same worker a scan
+    /// would find without scanning. Rebuilt at the end of every working tick, which visits every worker anyway.
+    free_workers: Vec<usize>,
+    queue_size: usize,
+    /// Whether the queue admits requests over its size, leaving it to the late shedding to get rid of them.
+    late_shedding: bool,
+    /// Whether the workers drop the requests whose clients already gave up on them, instead of serving them.
+    cull_timed_out: bool,
+    autoscaler: Option<Autoscaler>,
+    queue_depth_penalty: Option<QueueDepthPenalty>,
+    straggler: Option<Straggler>,
+    /// Earliest tick a request in the queue gets abandoned at, so that the queue only needs to be scanned
+    /// when some request does.
+    next_abandonment_tick: u64,
+}
+
+impl FromStr for PoolSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() != 4 || parts[0].is_empty() {
+            return Err(format!(
+                "Expected pool as name:workers:queue_size:class1+class2, got '{}'",
+                s
+            ));
+        }
+
+        Ok(PoolSpec {
+            name: parts[0].to_string(),
+            workers: parts[1]
+                .parse()
+                .map_err(|_| format!("Invalid number of workers in pool '{}'", s))?,
+            queue_size: parts[2]
+                .parse()
+                .map_err(|_| format!("Invalid queue size in pool '{}'", s))?,
+            classes: parts[3].split('+').map(str::to_string).collect(),
+        })
+    }
+}
+
+/// Maps each class to the index of the pool that serves it. Every class has to be served by exactly one pool.
+pub fn route_classes(pools: &[PoolSpec], classes: &[ClassSpec]) -> Result<Vec<usize>, String> {
+    let mut routes = vec![None; classes.len()];
+    for (pool_index, pool) in pools.iter().enumerate() {
+        if pools.iter().filter(|p| p.name == pool.name).count() > 1 {
+            return Err(format!("Pool '{}' is defined more than once", pool.name));
+        }
+        for class_name in pool.classes.iter() {
+            let class_index = classes
+                .iter()
+                .position(|c| &c.name == class_name)
+                .ok_or_else(|| {
+                    format!("Pool '{}' serves unknown class '{}'", pool.name, class_name)
+                })?;
+            if routes[class_index].is_some() {
+                return Err(format!(
+                    "Class '{}' is served by more than one pool",
+                    class_name
+                ));
+            }
+            routes[class_index] = Some(pool_index);
+        }
+    }
+
+    routes
+        .iter()
+        .zip(classes.iter())
+        .map(|(route, class)| {
+            route.ok_or_else(|| format!("Class '{}' isn't served by any pool", class.name))
+        })
+        .collect()
+}
+
+impl PoolSpec {
+    /// Splits the pool into the specified number of independent queues serving the same classes, sharing out
+    /// its workers and queue size as evenly as possible.
+    pub fn split(&self, queues: u16) -> Vec<PoolSpec> {
+        let queues = queues as usize;
+        (0..queues)
+            .map(|i| PoolSpec {
+                name: format!("queue{}", i + 1),
+                workers: self.workers / queues as u16
+                    + ((i as u16) < self.workers % queues as u16) as u16,
+                queue_size: self.queue_size / queues + (i < self.queue_size % queues) as usize,
+                classes: self.classes.clone(),
+            })
+            .collect()
+    }
+}
+
+impl Pool {
+    pub fn new(
+        spec: &PoolSpec,
+        queue: Box<dyn QueueDiscipline>,
+        late_shedding: bool,
+        cull_timed_out: bool,
+        autoscaler: Option<Autoscaler>,
+        queue_depth_penalty: Option<QueueDepthPenalty>,
+        straggler: Option<Straggler>,
+    ) -> Pool {
+        Pool {
+            name: spec.name.clone(),
+            queue,
+            workers: (0..spec.workers).map(|_| Worker::new()).collect(),
+            free_workers: (0..spec.workers as usize).rev().collect(),
+            queue_size: spec.queue_size,
+            late_shedding,
+            cull_timed_out,
+            autoscaler,
+            queue_depth_penalty,
+            straggler,
+            next_abandonment_tick: u64::MAX,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn queue_size(&self) -> usize {
+        self.queue_size
+    }
+
+    pub fn queue_capacity(&self) -> usize {
+        self.queue.capacity()
+    }
+
+    /// Whether the workers drop the requests whose clients already gave up, instead of serving them.
+    pub fn culls_timed_out(&self) -> bool {
+        self.cull_timed_out
+    }
+
+    /// Number of workers, including the ones still starting.
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn worker_times(&self) -> Vec<WorkerTime> {
+        self.workers.iter().map(Worker::time).collect()
+    }
+
+    /// Requests in the pool, the ones waiting in the queue first, then the ones being processed.
+    pub fn requests(&self) -> impl Iterator<Item = &Request> {
+        self.queue
+            .iter()
+            .chain(self.workers.iter().filter_map(Worker::current_request))
+    }
+
+    /// Takes the requests, workers and autoscaler out of the pool, for a checkpoint. The pool is left empty.
+    pub fn checkpoint(&mut self) -> PoolCheckpoint {
+        PoolCheckpoint {
+            queue: self.queue.checkpoint(),
+            queue_size: self.queue_size,
+            workers: std::mem::take(&mut self.workers),
+            free_workers: std::mem::take(&mut self.free_workers),
+            autoscaler: self.autoscaler.take(),
+            straggler: self.straggler,
+            next_abandonment_tick: self.next_abandonment_tick,
+        }
+    }
+
+    /// Puts the requests, workers and autoscaler of a checkpoint back into the pool. The queue is served in the
+    /// order it was at the checkpoint, even if a discipline switch or a mitigation changed it since the start.
+    pub fn resume(&mut self, checkpoint: PoolCheckpoint) {
+        self.queue = queue::resume(checkpoint.queue, checkpoint.queue_size);
+        self.queue_size = checkpoint.queue_size;
+        self.workers = checkpoint.workers;
+        self.free_workers = checkpoint.free_workers;
+        self.autoscaler = checkpoint.autoscaler;
+        self.straggler = checkpoint.straggler;
+        self.next_abandonment_tick = checkpoint.next_abandonment_tick;
+    }
+
+    /// Starts measuring the utilization of the workers afresh, e.g. at the end of the warm-up.
+    pub fn reset_worker_times(&mut self) {
+        for worker in self.workers.iter_mut() {
+            worker.reset_time();
+        }
+    }
+
+    pub fn busy_workers(&self) -> usize {
+        self.workers.iter().filter(|w| w.is_busy()).count()
+    }
+
+    pub fn has_free_worker(&self) -> bool {
+        !self.free_workers.is_empty()
+    }
+
+    /// State of the pool at the specified tick, for an external policy to base its decisions on.
+    pub fn state(&self, tick: u64) -> PoolState<'_> {
+        PoolState {
+            tick,
+            pool: &self.name,
+            queue_len: self.queue.len(),
+            queue_size: self.queue_size,
+            busy_workers: self.busy_workers(),
+            workers: self.workers.len(),
+        }
+    }
+
+    pub fn queries_in_flight(&self) -> usize {
+        self.workers.iter().filter(|w| w.is_querying()).count()
+    }
+
+    /// Puts the database queries in flight through an outage of the database, the ones that haven't gone through
+    /// it yet failing if `fails` says so. Returns how many failed.
+    pub fn fail_queries(&mut self, mut fails: impl FnMut() -> bool) -> usize {
+        let mut failed = 0;
+        for request in self
+            .workers
+            .iter_mut()
+            .filter_map(Worker::current_request_mut)
+        {
+            if request.is_exposed_to_outage() {
+                let query_failed = fails();
+                request.outage(query_failed);
+                failed += query_failed as usize;
+            }
+        }
+        failed
+    }
+
+    pub fn autoscaler(&self) -> Option<&Autoscaler> {
+        self.autoscaler.as_ref()
+    }
+
+    pub fn straggler(&self) -> Option<&Straggler> {
+        self.straggler.as_ref()
+    }
+
+    /// Hands the request to an idle worker, or enqueues it if there isn't one. Returns false if the request
+    /// had to be rejected.
+    pub fn admit(&mut self, mut request: Request) -> bool {
+        if let Some(index) = self.free_workers.pop() {
+            if let Some(penalty) = self.queue_depth_penalty {
+                request.stretch(penalty.multiplier(self.queue.len()));
+            }
+            if let Some(straggler) = self.straggler.as_mut() {
+                request.stretch(straggler.started(index, request.arrival_tick()));
+            }
+            request.started_on_arrival();
+            self.workers[index].take(request);
+        } else if self.late_shedding || self.queue.len() < self.queue_size {
+            self.next_abandonment_tick = self
+                .next_abandonment_tick
+                .min(request.patience_deadline_tick());
+            self.queue.push(request);
+        } else {
+            // Queue is full and all workers busy. This request is failed.
+            return false;
+        }
+
+        true
+    }
+
+    /// Adds workers to the pool, which take the specified number of ticks to start.
+    pub fn add_workers(&mut self, workers: u16, startup_ticks: u32) {
+        self.workers
+            .extend((0..workers).map(|_| Worker::starting(startup_ticks)));
+    }
+
+    /// Kills the specified number of workers, the first ones still alive. Returns the requests they were
+    /// processing.
+    pub fn kill_workers(&mut self, workers: u16) -> Vec<Request> {
+        let killed: Vec<Request> = self
+            .workers
+            .iter_mut()
+            .filter(|w| !w.is_killed())
+            .take(workers as usize)
+            .filter_map(|w| w.kill())
+            .collect();
+        let workers = &self.workers;
+        self.free_workers.retain(|&i| workers[i].is_free());
+        killed
+    }
+
+    /// Restores the specified number of killed workers, which take the specified number of ticks to start.
+    pub fn restore_workers(&mut self, workers: u16, startup_ticks: u32) {
+        for worker in self
+            .workers
+            .iter_mut()
+            .filter(|w| w.is_killed())
+            .take(workers as usize)
+        {
+            worker.restore(startup_ticks);
+        }
+        self.free_workers.clear();
+        let workers = &self.workers;
+        self.free_workers
+            .extend((0..workers.len()).rev().filter(|&i| workers[i].is_free()));
+    }
+
+    /// Changes the size of the queue. Requests over the new size stay in the queue until shed.
+    pub fn set_queue_size(&mut self, queue_size: usize) {
+        self.queue_size = queue_size;
+    }
+
+    /// Removes the requests whose clients ran out of patience by the end of the specified tick from the queue.
+    pub fn abandon(&mut self, tick: u64) -> Vec<Request> {
+        if tick < self.next_abandonment_tick {
+            return Vec::new();
+        }
+        let abandoned = self.queue.extract(&mut |r| r.has_abandoned(tick));
+        self.next_abandonment_tick = self
+            .queue
+            .iter()
+            .map(Request::patience_deadline_tick)
+            .min()
+            .unwrap_or(u64::MAX);
+        abandoned
+    }
+
+    /// Removes the requests over the queue size from the back of the queue, i.e. the most recent arrivals.
+    pub fn shed_excess(&mut self) -> Vec<Request> {
+        self.queue.truncate(self.queue_size)
+    }
+
+    /// Spends a tick with all the workers frozen, holding on to their requests without making any progress.
+    pub fn stalled_tick(&mut self) {
+        for worker in self.workers.iter_mut() {
+            worker.stalled_tick();
+        }
+    }
+
+    /// Switches the queue to LIFO, or back to FIFO, keeping the requests waiting in it. The queues serving the
+    /// requests by their deadlines or in random order stay as they are.
+    pub fn switch_lifo(&mut self, lifo: bool) {
+        let discipline = self.queue.discipline();
+        if !discipline.is_arrival_order() || (discipline == Discipline::Lifo) == lifo {
+            return;
+        }
+        let mut queue: Box<dyn QueueDiscipline> = if lifo {
+            Box::new(ReverseArrivalOrder::with_capacity(self.queue_size))
+        } else {
+            Box::new(ArrivalOrder::with_capacity(self.queue_size))
+        };
+        for request in self.queue.drain() {
+            queue.push(request);
+        }
+        self.queue = queue;
+    }
+
+    /// Lets every worker spend the specified tick, collecting the requests they finished into `finished`.
+    /// `index` identifies this pool in the emitted events, `query_speed` is the progress database queries make.
+    /// Returns the requests the workers found over their queue-wait budget.
+    pub fn working_tick(
+        &mut self,
+        tick: u64,
+        index: usize,
+        query_speed: f64,
+        finished: &mut Vec<Request>,
+        events: &mut Vec<Event>,
+    ) -> Vec<Request> {
+        let mut expired = Vec::new();
+        for (worker_index, worker) in self.workers.iter_mut().enumerate() {
+            let was_free = worker.is_free();
+            if let Some(request) = worker.tick(
+                tick,
+                self.queue.as_mut(),
+                self.cull_timed_out,
+                query_speed,
+                &mut expired,
+            ) {
+                finished.push(request);
+            }
+            if !was_free {
+                continue;
+            }
+            if let Some(request) = worker.current_request_mut() {
+                if let Some(penalty) = self.queue_depth_penalty {
+                    request.stretch(penalty.multiplier(self.queue.len()));
+                }
+                if let Some(straggler) = self.straggler.as_mut() {
+                    request.stretch(straggler.started(worker_index, tick));
+                }
+                events.push(Event::Dequeued {
+                    request: request.id(),
+                    pool: index,
+                    timed_out: request.is_timed_out(tick),
+                });
+            }
+        }
+
+        if let Some(autoscaler) = self.autoscaler.as_mut() {
+            autoscaler.tick(self.queue.len(), &mut self.workers);
+        }
+
+        self.free_workers.clear();
+        let workers = &self.workers;
+        self.free_workers
+            .extend((0..workers.len()).rev().filter(|&i| workers[i].is_free()));
+        expired
+    }
+}
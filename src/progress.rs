@@ -0,0 +1,73 @@
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// How often the status line is updated.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of ticks between checks of the clock, so that fast runs don't spend their time reading it.
+const CHECK_EVERY: u64 = 1024;
+
+/// Periodic one-line status of a long simulation, with the current queue length and failure rate, written to
+/// stderr so that it doesn't end up in the results. On a terminal the line is updated in place.
+pub struct Progress {
+    ticks: u64,
+    started: Instant,
+    last_update: Instant,
+    in_place: bool,
+    /// Whether a status line was written yet.
+    written: bool,
+}
+
+impl Progress {
+    pub fn new(ticks: u64) -> Progress {
+        let now = Instant::now();
+        Progress {
+            ticks,
+            started: now,
+            last_update: now,
+            in_place: io::stderr().is_terminal(),
+            written: false,
+        }
+    }
+
+    /// Reports the state of the system at the end of the specified tick, if it's time for an update. Total and
+    /// failed requests are cumulative counts since the beginning of the simulation.
+    pub fn tick(&mut self, tick: u64, queue_len: usize, total_requests: u64, failed_requests: u64) {
+        if !tick.is_multiple_of(CHECK_EVERY) || self.last_update.elapsed() < INTERVAL {
+            return;
+        }
+        self.last_update = Instant::now();
+        self.written = true;
+
+        let done = (tick + 1) as f64 / self.ticks as f64;
+        let remaining = self.started.elapsed().as_secs_f64() * (1.0 - done) / done;
+        let failure_rate = if total_requests == 0 {
+            0.0
+        } else {
+            failed_requests as f64 / total_requests as f64 * 100.0
+        };
+        let status = format!(
+            "Tick {}/{} ({:.1}%), queue length {}, failure rate {:.2}%, about {:.0}s left",
+            tick + 1,
+            self.ticks,
+            done * 100.0,
+            queue_len,
+            failure_rate,
+            remaining
+        );
+        if self.in_place {
+            // Clears the rest of the line, in case the previous status was longer.
+            eprint!("\r{}\x1b[K", status);
+            io::stderr().flush().ok();
+        } else {
+            eprintln!("{}", status);
+        }
+    }
+
+    /// Ends the status line, so that whatever is printed next starts on a line of its own.
+    pub fn finish(&self) {
+        if self.in_place && self.written {
+            eprintln!();
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::server;
+use crate::simulation;
+use crate::Opt;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use rayon::prelude::*;
+use serde_json::{Map, Value};
+
+/// Runs the simulator from Python, e.g. to sweep a parameter and plot the results in a notebook without parsing
+/// the summary the command line prints. Scenarios are keyword arguments named after the command line flags, and
+/// the results are the same as the serve subcommand answers with, as dicts.
+#[pymodule]
+fn queueingsimulator(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(simulate, module)?)?;
+    module.add_function(wrap_pyfunction!(sweep, module)?)?;
+    Ok(())
+}
+
+/// Runs the scenario of the keyword arguments, e.g. `simulate(arrival_rate=0.15, simulate_spike=True)`, and
+/// returns its results.
+#[pyfunction]
+#[pyo3(signature = (**parameters))]
+fn simulate<'py>(
+    py: Python<'py>,
+    parameters: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let opt = scenario(py, parameters)?;
+    let results = py.detach(|| server::results(&opt, &simulation::run(&opt)));
+    to_python(py, &results)
+}
+
+/// Runs the scenarios, dicts of the same parameters as the keyword arguments of `simulate`, in parallel, and
+/// returns their results in the same order. Every scenario is validated before any of them runs.
+#[pyfunction]
+fn sweep<'py>(py: Python<'py>, scenarios: Vec<Bound<'py, PyDict>>) -> PyResult<Bound<'py, PyList>> {
+    let opts = scenarios
+        .iter()
+        .map(|parameters| scenario(py, Some(parameters)))
+        .collect::<PyResult<Vec<Opt>>>()?;
+    let results: Vec<Value> = py.detach(|| {
+        opts.par_iter()
+            .map(|opt| server::results(opt, &simulation::run(opt)))
+            .collect()
+    });
+    let results = results
+        .iter()
+        .map(|results| to_python(py, results))
+        .collect::<PyResult<Vec<_>>>()?;
+    PyList::new(py, results)
+}
+
+/// Options of a scenario, refusing the same parameters as the server does, since the simulation exits the
+/// process if it can't write its files, and the terminal is the notebook's.
+fn scenario(py: Python<'_>, parameters: Option<&Bound<'_, PyDict>>) -> PyResult<Opt> {
+    let values: Map<String, Value> = match parameters {
+        Some(parameters) => {
+            let json: String = py
+                .import("json")?
+                .call_method1("dumps", (parameters,))?
+                .extract()?;
+            serde_json::from_str(&json)
+                .map_err(|e| PyValueError::new_err(format!("Invalid scenario: {}", e)))?
+        }
+        None => Map::new(),
+    };
+    if let Some(name) = server::refused_parameter(&values) {
+        return Err(PyValueError::new_err(format!(
+            "Parameter '{}' can't be set from Python",
+            name
+        )));
+    }
+    server::scenario(values).map_err(PyValueError::new_err)
+}
+
+fn to_python<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    py.import("json")?
+        .call_method1("loads", (value.to_string(),))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenarios_run_from_python() {
+        pyo3::append_to_inittab!(queueingsimulator);
+        Python::initialize();
+        Python::attach(|py| {
+            py.run(
+                cr#"
+import queueingsimulator
+
+results = queueingsimulator.simulate(seed=3, arrival_rate=0.12, simulation_time=20000)
+assert results["requests"] > 2000 and results["failure_rate"] == 0.0, results
+assert queueingsimulator.simulate(seed=3, arrival_rate=0.12, simulation_time=20000) == results
+
+swept = queueingsimulator.sweep(
+    [{"seed": 3, "arrival_rate": rate, "simulation_time": 20000} for rate in (0.12, 0.3)]
+)
+assert swept[0] == results, swept
+assert swept[1]["failure_rate"] > 50.0, swept
+
+for scenario, error in [
+    ({"timeseries": "ts.csv"}, "Parameter 'timeseries' can't be set from Python"),
+    ({"arrival_rate": 0.1, "workers": 0}, "workers"),
+]:
+    try:
+        queueingsimulator.simulate(**scenario)
+    except ValueError as e:
+        assert error in str(e), str(e)
+    else:
+        raise AssertionError(scenario)
+"#,
+                None,
+                None,
+            )
+            .unwrap_or_else(|e| panic!("{}", e));
+        });
+    }
+}
@@ -0,0 +1,630 @@
+use crate::request::Request;
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+/// Order the requests waiting in a queue are served in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Discipline {
+    /// The oldest request first.
+    Fifo,
+    /// The most recent request first.
+    Lifo,
+    /// The request with the earliest deadline first.
+    Edf,
+    /// Any request, at random.
+    Siro,
+}
+
+impl Discipline {
+    /// Whether the discipline serves the requests by the order they arrived in, so that it can switch between
+    /// FIFO and LIFO during the run.
+    pub fn is_arrival_order(self) -> bool {
+        matches!(self, Discipline::Fifo | Discipline::Lifo)
+    }
+}
+
+impl FromStr for Discipline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fifo" => Ok(Discipline::Fifo),
+            "lifo" => Ok(Discipline::Lifo),
+            "edf" => Ok(Discipline::Edf),
+            "siro" => Ok(Discipline::Siro),
+            _ => Err(format!(
+                "Unknown queue discipline '{}', expected fifo, lifo, edf or siro",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Discipline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Discipline::Fifo => write!(f, "fifo"),
+            Discipline::Lifo => write!(f, "lifo"),
+            Discipline::Edf => write!(f, "edf"),
+            Discipline::Siro => write!(f, "siro"),
+        }
+    }
+}
+
+/// Order the requests waiting in the queue of a pool are served in, together with the storage keeping them in
+/// that order, so that every discipline gets the data structure suiting it instead of working around a deque.
+pub trait QueueDiscipline {
+    fn discipline(&self) -> Discipline;
+
+    fn len(&self) -> usize;
+
+    /// Number of requests the storage holds without growing, for the telemetry.
+    fn capacity(&self) -> usize;
+
+    fn push(&mut self, request: Request);
+
+    /// Takes the next request to serve.
+    fn pop(&mut self) -> Option<Request>;
+
+    /// The requests in the queue, in no particular order.
+    fn iter(&self) -> Box<dyn Iterator<Item = &Request> + '_>;
+
+    /// Removes the requests matching the predicate, and returns them.
+    fn extract(&mut self, predicate: &mut dyn FnMut(&Request) -> bool) -> Vec<Request>;
+
+    /// Removes the most recent arrivals over the specified length, and returns them.
+    fn truncate(&mut self, len: usize) -> Vec<Request>;
+
+    /// Removes all the requests, and returns them in the order they arrived in.
+    fn drain(&mut self) -> Vec<Request>;
+
+    /// Takes the requests out of the queue for a checkpoint, in the order the queue keeps them in, together with
+    /// whatever else decides the order they are served in.
+    fn checkpoint(&mut self) -> QueueCheckpoint;
+}
+
+/// Requests waiting in a queue, in the order the queue keeps them in, and the state of the order they are served
+/// in, for checkpoints.
+#[derive(Serialize, Deserialize)]
+pub enum QueueCheckpoint {
+    Fifo(Vec<Request>),
+    Lifo(Vec<Request>),
+    /// The requests with their arrival numbers, and the number of the next arrival.
+    Edf(Vec<(u64, Request)>, u64),
+    /// The same, and the generator the queue draws from.
+    Siro(Vec<(u64, Request)>, u64, Box<ChaCha12Rng>),
+}
+
+/// Queue of the specified size, serving the requests by the specified discipline. A random order queue draws
+/// from the specified generator, the others ignore it.
+pub fn new(
+    discipline: Discipline,
+    queue_size: usize,
+    rng: ChaCha12Rng,
+) -> Box<dyn QueueDiscipline> {
+    match discipline {
+        Discipline::Fifo => Box::new(ArrivalOrder::with_capacity(queue_size)),
+        Discipline::Lifo => Box::new(ReverseArrivalOrder::with_capacity(queue_size)),
+        Discipline::Edf => Box::new(DeadlineOrder::with_capacity(queue_size)),
+        Discipline::Siro => Box::new(RandomOrder::with_capacity(queue_size, rng)),
+    }
+}
+
+/// Queue of the specified size, picking up where the queue of a checkpoint left off.
+pub fn resume(checkpoint: QueueCheckpoint, queue_size: usize) -> Box<dyn QueueDiscipline> {
+    match checkpoint {
+        QueueCheckpoint::Fifo(requests) => {
+            let mut queue = ArrivalOrder::with_capacity(queue_size);
+            queue.requests.extend(requests);
+            Box::new(queue)
+        }
+        QueueCheckpoint::Lifo(requests) => {
+            let mut queue = ReverseArrivalOrder::with_capacity(queue_size);
+            queue.requests.extend(requests);
+            Box::new(queue)
+        }
+        QueueCheckpoint::Edf(requests, next_arrival) => {
+            let mut entries = Vec::with_capacity(queue_size);
+            entries.extend(requests.into_iter().map(|(arrival, request)| {
+                Reverse(ByDeadline {
+                    deadline: request.deadline_tick(),
+                    arrival,
+                    request,
+                })
+            }));
+            Box::new(DeadlineOrder {
+                heap: BinaryHeap::from(entries),
+                next_arrival,
+            })
+        }
+        QueueCheckpoint::Siro(requests, next_arrival, rng) => {
+            let mut queue = RandomOrder::with_capacity(queue_size, *rng);
+            queue.requests.extend(requests);
+            queue.next_arrival = next_arrival;
+            Box::new(queue)
+        }
+    }
+}
+
+/// FIFO: the requests in the order they arrived in, served from the front.
+pub struct ArrivalOrder {
+    requests: VecDeque<Request>,
+}
+
+impl ArrivalOrder {
+    pub fn with_capacity(capacity: usize) -> ArrivalOrder {
+        ArrivalOrder {
+            requests: VecDeque::with_capacity(capacity),
+        }
+    }
+}
+
+impl QueueDiscipline for ArrivalOrder {
+    fn discipline(&self) -> Discipline {
+        Discipline::Fifo
+    }
+
+    fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.requests.capacity()
+    }
+
+    fn push(&mut self, request: Request) {
+        self.requests.push_back(request);
+    }
+
+    fn pop(&mut self) -> Option<Request> {
+        self.requests.pop_front()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Request> + '_> {
+        Box::new(self.requests.iter())
+    }
+
+    fn extract(&mut self, predicate: &mut dyn FnMut(&Request) -> bool) -> Vec<Request> {
+        let (extracted, kept): (VecDeque<Request>, VecDeque<Request>) =
+            self.requests.drain(..).partition(|r| predicate(r));
+        self.requests = kept;
+        extracted.into()
+    }
+
+    fn truncate(&mut self, len: usize) -> Vec<Request> {
+        let len = len.min(self.requests.len());
+        self.requests.split_off(len).into()
+    }
+
+    fn drain(&mut self) -> Vec<Request> {
+        self.requests.drain(..).collect()
+    }
+
+    fn checkpoint(&mut self) -> QueueCheckpoint {
+        QueueCheckpoint::Fifo(self.drain())
+    }
+}
+
+/// LIFO: the requests in the order they arrived in, the most recent on top of the stack, and served first.
+pub struct ReverseArrivalOrder {
+    requests: Vec<Request>,
+}
+
+impl ReverseArrivalOrder {
+    pub fn with_capacity(capacity: usize) -> ReverseArrivalOrder {
+        ReverseArrivalOrder {
+            requests: Vec::with_capacity(capacity),
+        }
+    }
+}
+
+impl QueueDiscipline for ReverseArrivalOrder {
+    fn discipline(&self) -> Discipline {
+        Discipline::Lifo
+    }
+
+    fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.requests.capacity()
+    }
+
+    fn push(&mut self, request: Request) {
+        self.requests.push(request);
+    }
+
+    fn pop(&mut self) -> Option<Request> {
+        self.requests.pop()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Request> + '_> {
+        Box::new(self.requests.iter())
+    }
+
+    fn extract(&mut self, predicate: &mut dyn FnMut(&Request) -> bool) -> Vec<Request> {
+        let (extracted, kept) = self.requests.drain(..).partition(|r| predicate(r));
+        self.requests = kept;
+        extracted
+    }
+
+    fn truncate(&mut self, len: usize) -> Vec<Request> {
+        let len = len.min(self.requests.len());
+        self.requests.split_off(len)
+    }
+
+    fn drain(&mut self) -> Vec<Request> {
+        std::mem::take(&mut self.requests)
+    }
+
+    fn checkpoint(&mut self) -> QueueCheckpoint {
+        QueueCheckpoint::Lifo(self.drain())
+    }
+}
+
+/// A request in the deadline-ordered queue, ordered by the tick its client gives up on it at, and by its
+/// arrival among the ones with the same deadline.
+struct ByDeadline {
+    deadline: u64,
+    arrival: u64,
+    request: Request,
+}
+
+impl Ord for ByDeadline {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.deadline, self.arrival).cmp(&(other.deadline, other.arrival))
+    }
+}
+
+impl PartialOrd for ByDeadline {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ByDeadline {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ByDeadline {}
+
+/// EDF: the request with the earliest deadline first, from a binary heap keyed by the deadline. The requests
+/// whose clients already gave up come out first.
+pub struct DeadlineOrder {
+    heap: BinaryHeap<Reverse<ByDeadline>>,
+    /// Arrival number of the next request pushed, for the ties and the most recent arrivals.
+    next_arrival: u64,
+}
+
+impl DeadlineOrder {
+    pub fn with_capacity(capacity: usize) -> DeadlineOrder {
+        DeadlineOrder {
+            heap: BinaryHeap::with_capacity(capacity),
+            next_arrival: 0,
+        }
+    }
+
+    /// Takes the requests out of the heap, in the order they arrived in.
+    fn take_in_arrival_order(&mut self) -> Vec<ByDeadline> {
+        let mut entries: Vec<ByDeadline> = std::mem::take(&mut self.heap)
+            .into_vec()
+            .into_iter()
+            .map(|Reverse(entry)| entry)
+            .collect();
+        entries.sort_unstable_by_key(|entry| entry.arrival);
+        entries
+    }
+}
+
+impl QueueDiscipline for DeadlineOrder {
+    fn discipline(&self) -> Discipline {
+        Discipline::Edf
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.heap.capacity()
+    }
+
+    fn push(&mut self, request: Request) {
+        self.heap.push(Reverse(ByDeadline {
+            deadline: request.deadline_tick(),
+            arrival: self.next_arrival,
+            request,
+        }));
+        self.next_arrival += 1;
+    }
+
+    fn pop(&mut self) -> Option<Request> {
+        self.heap.pop().map(|Reverse(entry)| entry.request)
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Request> + '_> {
+        Box::new(self.heap.iter().map(|Reverse(entry)| &entry.request))
+    }
+
+    fn extract(&mut self, predicate: &mut dyn FnMut(&Request) -> bool) -> Vec<Request> {
+        let (extracted, kept): (Vec<Reverse<ByDeadline>>, Vec<Reverse<ByDeadline>>) =
+            std::mem::take(&mut self.heap)
+                .into_vec()
+                .into_iter()
+                .partition(|Reverse(entry)| predicate(&entry.request));
+        self.heap = BinaryHeap::from(kept);
+        extracted
+            .into_iter()
+            .map(|Reverse(entry)| entry.request)
+            .collect()
+    }
+
+    fn truncate(&mut self, len: usize) -> Vec<Request> {
+        if len >= self.heap.len() {
+            return Vec::new();
+        }
+        let mut entries = self.take_in_arrival_order();
+        let truncated = entries.split_off(len);
+        self.heap = entries.into_iter().map(Reverse).collect();
+        truncated.into_iter().map(|entry| entry.request).collect()
+    }
+
+    fn drain(&mut self) -> Vec<Request> {
+        self.take_in_arrival_order()
+            .into_iter()
+            .map(|entry| entry.request)
+            .collect()
+    }
+
+    fn checkpoint(&mut self) -> QueueCheckpoint {
+        let requests = std::mem::take(&mut self.heap)
+            .into_vec()
+            .into_iter()
+            .map(|Reverse(entry)| (entry.arrival, entry.request))
+            .collect();
+        QueueCheckpoint::Edf(requests, self.next_arrival)
+    }
+}
+
+/// SIRO: service in random order, any request in the queue as likely to be next as any other, regardless of how
+/// long it waited. Keeps the mean wait of FIFO, but not its tail.
+pub struct RandomOrder {
+    /// Requests in the queue, in no particular order, with their arrival numbers.
+    requests: Vec<(u64, Request)>,
+    next_arrival: u64,
+    rng: ChaCha12Rng,
+}
+
+impl RandomOrder {
+    pub fn with_capacity(capacity: usize, rng: ChaCha12Rng) -> RandomOrder {
+        RandomOrder {
+            requests: Vec::with_capacity(capacity),
+            next_arrival: 0,
+            rng,
+        }
+    }
+
+    /// Takes the requests out of the queue, in the order they arrived in.
+    fn take_in_arrival_order(&mut self) -> Vec<(u64, Request)> {
+        let mut requests = std::mem::take(&mut self.requests);
+        requests.sort_unstable_by_key(|&(arrival, _)| arrival);
+        requests
+    }
+}
+
+impl QueueDiscipline for RandomOrder {
+    fn discipline(&self) -> Discipline {
+        Discipline::Siro
+    }
+
+    fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.requests.capacity()
+    }
+
+    fn push(&mut self, request: Request) {
+        self.requests.push((self.next_arrival, request));
+        self.next_arrival += 1;
+    }
+
+    fn pop(&mut self) -> Option<Request> {
+        if self.requests.is_empty() {
+            return None;
+        }
+        let index = self.rng.gen_range(0..self.requests.len());
+        Some(self.requests.swap_remove(index).1)
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Request> + '_> {
+        Box::new(self.requests.iter().map(|(_, request)| request))
+    }
+
+    fn extract(&mut self, predicate: &mut dyn FnMut(&Request) -> bool) -> Vec<Request> {
+        let mut extracted = Vec::new();
+        let mut index = 0;
+        while index < self.requests.len() {
+            if predicate(&self.requests[index].1) {
+                extracted.push(self.requests.swap_remove(index).1);
+            } else {
+                index += 1;
+            }
+        }
+        extracted
+    }
+
+    fn truncate(&mut self, len: usize) -> Vec<Request> {
+        if len >= self.requests.len() {
+            return Vec::new();
+        }
+        let mut requests = self.take_in_arrival_order();
+        let truncated = requests.split_off(len);
+        self.requests = requests;
+        truncated.into_iter().map(|(_, request)| request).collect()
+    }
+
+    fn drain(&mut self) -> Vec<Request> {
+        self.take_in_arrival_order()
+            .into_iter()
+            .map(|(_, request)| request)
+            .collect()
+    }
+
+    fn checkpoint(&mut self) -> QueueCheckpoint {
+        QueueCheckpoint::Siro(
+            std::mem::take(&mut self.requests),
+            self.next_arrival,
+            Box::new(self.rng.clone()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// Every discipline, with a queue of its own.
+    fn queues() -> Vec<Box<dyn QueueDiscipline>> {
+        [
+            Discipline::Fifo,
+            Discipline::Lifo,
+            Discipline::Edf,
+            Discipline::Siro,
+        ]
+        .into_iter()
+        .map(|discipline| new(discipline, 10, ChaCha12Rng::seed_from_u64(1)))
+        .collect()
+    }
+
+    /// Pushes a request arriving at every tick, with the specified timeouts.
+    fn push_arrivals(queue: &mut dyn QueueDiscipline, timeouts: &[u32]) {
+        for (tick, &timeout) in timeouts.iter().enumerate() {
+            queue.push(Request::new(10, timeout, tick as u64, 0));
+        }
+    }
+
+    fn arrival_ticks(requests: Vec<Request>) -> Vec<u64> {
+        requests.iter().map(Request::arrival_tick).collect()
+    }
+
+    fn pop_all(queue: &mut dyn QueueDiscipline) -> Vec<u64> {
+        arrival_ticks(std::iter::from_fn(|| queue.pop()).collect())
+    }
+
+    #[test]
+    fn fifo_serves_the_oldest_request_first() {
+        let mut queue = ArrivalOrder::with_capacity(10);
+        push_arrivals(&mut queue, &[100; 5]);
+        assert_eq!(pop_all(&mut queue), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn lifo_serves_the_most_recent_request_first() {
+        let mut queue = ReverseArrivalOrder::with_capacity(10);
+        push_arrivals(&mut queue, &[100; 5]);
+        assert_eq!(pop_all(&mut queue), [4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn edf_serves_the_earliest_deadline_first_and_ties_in_arrival_order() {
+        let mut queue = DeadlineOrder::with_capacity(10);
+        // Deadlines of 50, 21, 12, 50 and 12.
+        push_arrivals(&mut queue, &[50, 20, 10, 47, 8]);
+        assert_eq!(pop_all(&mut queue), [2, 4, 1, 0, 3]);
+    }
+
+    #[test]
+    fn siro_serves_every_request_once_in_an_order_set_by_the_generator() {
+        let order = |seed| {
+            let mut queue = RandomOrder::with_capacity(10, ChaCha12Rng::seed_from_u64(seed));
+            push_arrivals(&mut queue, &[100; 20]);
+            pop_all(&mut queue)
+        };
+        let mut served = order(1);
+        assert_eq!(served, order(1));
+        assert_ne!(served, (0..20).collect::<Vec<u64>>());
+        served.sort_unstable();
+        assert_eq!(served, (0..20).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn truncate_removes_the_most_recent_arrivals() {
+        for mut queue in queues() {
+            push_arrivals(queue.as_mut(), &[50, 20, 10, 47, 8]);
+            assert!(queue.truncate(5).is_empty(), "{}", queue.discipline());
+            assert_eq!(
+                arrival_ticks(queue.truncate(2)),
+                [2, 3, 4],
+                "{}",
+                queue.discipline()
+            );
+            assert_eq!(queue.len(), 2, "{}", queue.discipline());
+            let mut kept = pop_all(queue.as_mut());
+            kept.sort_unstable();
+            assert_eq!(kept, [0, 1], "{}", queue.discipline());
+        }
+    }
+
+    #[test]
+    fn drain_returns_the_requests_in_arrival_order() {
+        for mut queue in queues() {
+            push_arrivals(queue.as_mut(), &[50, 20, 10, 47, 8]);
+            queue.pop();
+            let served = queue.len();
+            let drained = arrival_ticks(queue.drain());
+            assert_eq!(drained.len(), served, "{}", queue.discipline());
+            assert!(
+                drained.windows(2).all(|pair| pair[0] < pair[1]),
+                "{}: {:?}",
+                queue.discipline(),
+                drained
+            );
+            assert_eq!(queue.len(), 0, "{}", queue.discipline());
+        }
+    }
+
+    #[test]
+    fn extract_removes_only_the_matching_requests() {
+        for mut queue in queues() {
+            push_arrivals(queue.as_mut(), &[50, 20, 10, 47, 8]);
+            let mut extracted =
+                arrival_ticks(queue.extract(&mut |request| request.arrival_tick() % 2 == 0));
+            extracted.sort_unstable();
+            assert_eq!(extracted, [0, 2, 4], "{}", queue.discipline());
+            assert_eq!(queue.drain().len(), 2, "{}", queue.discipline());
+        }
+    }
+
+    #[test]
+    fn resumed_queue_serves_the_requests_in_the_same_order() {
+        for (mut queue, mut checkpointed) in queues().into_iter().zip(queues()) {
+            for queue in [&mut queue, &mut checkpointed] {
+                push_arrivals(queue.as_mut(), &[50, 20, 10, 47, 8]);
+                queue.pop();
+            }
+            let checkpoint = serde_json::to_string(&checkpointed.checkpoint()).unwrap();
+            let mut resumed = resume(serde_json::from_str(&checkpoint).unwrap(), 10);
+            for queue in [&mut queue, &mut resumed] {
+                queue.push(Request::new(10, 5, 5, 0));
+            }
+            assert_eq!(resumed.discipline(), queue.discipline());
+            assert_eq!(
+                pop_all(resumed.as_mut()),
+                pop_all(queue.as_mut()),
+                "{}",
+                queue.discipline()
+            );
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use crate::checkpoint;
+use crate::events::{Event, Observer, SystemState};
+use crate::histogram::Histogram;
+use crate::pool::PoolSpec;
+use crate::retrypolicy;
+use crate::schedule::ArrivalDistribution;
+use crate::service::LatencyDistribution;
+use crate::Opt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Number of bars of the queue length histogram.
+const BARS: u64 = 10;
+/// Width of the longest bar of the queue length histogram, in characters.
+const BAR_WIDTH: f64 = 40.0;
+
+/// Records the queue length at the end of every tick, and compares its distribution with the stationary
+/// distribution of the equivalent M/M/c/K queue, if there is one. Since the analytical model is exact, a large
+/// difference points at an artifact of the simulation engine (or a bug in it).
+#[derive(Serialize, Deserialize)]
+pub struct QueueLengths {
+    queue_lengths: Histogram,
+    /// Probability of each queue length, according to the analytical model.
+    markov_model: Option<Vec<f64>>,
+}
+
+impl QueueLengths {
+    pub fn new(opt: &Opt) -> QueueLengths {
+        QueueLengths {
+            queue_lengths: Histogram::new(),
+            markov_model: markov_model(opt),
+        }
+    }
+}
+
+impl Observer for QueueLengths {
+    fn on_event(&mut self, _tick: u64, _event: &Event) {}
+
+    fn on_tick_end(&mut self, _tick: u64, state: &SystemState) {
+        self.queue_lengths.record(state.queue_len as u64);
+    }
+
+    fn print_summary(&self) {
+        println!(
+            "Queue length: min={} mean={:.2} {}",
+            self.queue_lengths.min(),
+            self.queue_lengths.mean(),
+            self.queue_lengths.format_percentiles()
+        );
+        self.print_histogram();
+        let model = match self.markov_model.as_ref() {
+            Some(model) => model,
+            None => {
+                println!("Queue length: configuration has no M/M/c/K equivalent to compare with");
+                return;
+            }
+        };
+
+        let percentile = |p: f64| {
+            let mut cumulative = 0.0;
+            model
+                .iter()
+                .position(|probability| {
+                    cumulative += probability;
+                    cumulative >= p / 100.0
+                })
+                .unwrap_or(model.len() - 1)
+        };
+        let mean: f64 = model.iter().enumerate().map(|(q, p)| q as f64 * p).sum();
+        println!(
+            "Queue length (M/M/c/K): mean={:.2} p50={} p90={} p99={} p99.9={} max={}",
+            mean,
+            percentile(50.0),
+            percentile(90.0),
+            percentile(99.0),
+            percentile(99.9),
+            model.len() - 1
+        );
+
+        // Half the sum of the absolute differences between the simulated and the analytical probabilities.
+        let ticks = self.queue_lengths.total() as f64;
+        let distance: f64 = (0..model.len().max(self.queue_lengths.max() as usize + 1))
+            .map(|q| {
+                let simulated = self.queue_lengths.count(q as u64) as f64 / ticks;
+                (simulated - model.get(q).copied().unwrap_or(0.0)).abs()
+            })
+            .sum::<f64>()
+            / 2.0;
+        println!(
+            "Queue length: total variation distance from M/M/c/K: {:.4}",
+            distance
+        );
+    }
+
+    fn checkpoint(&mut self) -> Result<Value, String> {
+        checkpoint::save(self)
+    }
+
+    fn resume(&mut self, state: Value) -> Result<(), String> {
+        checkpoint::restore(self, state)
+    }
+}
+
+impl QueueLengths {
+    /// Prints the share of time the queue length spent in each of equally wide ranges, as a bar chart.
+    fn print_histogram(&self) {
+        let ticks = self.queue_lengths.total().max(1) as f64;
+        let max = self.queue_lengths.max();
+        // Round bar widths, so that the ranges are easy to read.
+        let mut width = 1;
+        while width * BARS < max {
+            width = match width.to_string().as_bytes()[0] {
+                b'2' => width / 2 * 5,
+                _ => width * 2,
+            };
+        }
+        let shares: Vec<(u64, f64)> = (0..=max)
+            .step_by(width as usize)
+            .map(|from| {
+                let count: u64 = (from..from + width)
+                    .map(|q| self.queue_lengths.count(q))
+                    .sum();
+                (from, count as f64 / ticks)
+            })
+            .collect();
+        let largest = shares.iter().map(|s| s.1).fold(0.0, f64::max);
+        let label_width = max.to_string().len();
+        for (from, share) in shares {
+            let to = (from + width - 1).min(max);
+            let range = if to == from {
+                format!("{:>w$}", from, w = label_width * 2 + 1)
+            } else {
+                format!("{:>w$}-{:<w$}", from, to, w = label_width)
+            };
+            println!(
+                "  {} {:>6.2}% {}",
+                range,
+                share * 100.0,
+                "█".repeat((share / largest * BAR_WIDTH).round() as usize)
+            );
+        }
+    }
+}
+
+/// Stationary distribution of the queue length of the M/M/c/K queue equivalent to the configuration, if the
+/// configuration has one.
+fn markov_model(opt: &Opt) -> Option<Vec<f64>> {
+    let pool = markovian_pool(opt)?;
+    Some(mmck_queue_lengths(
+        opt.request_arrival_rate(),
+        1.0 / opt.mean_request_latency,
+        pool.workers as usize,
+        pool.queue_size,
+    ))
+}
+
+/// The only pool of a configuration that behaves like a Markovian queue: Poisson arrivals, exponential
+/// service times, and a single pool with nothing that would change the arrival or service rates while the
+/// simulation runs.
+pub fn markovian_pool(opt: &Opt) -> Option<PoolSpec> {
+    let mut pools = opt.pools();
+    let compatible = opt.arrival_distribution == ArrivalDistribution::Poisson
+        && opt.latency_distribution == LatencyDistribution::Exponential
+        && opt.latency_trace.is_none()
+        && opt.latency_modes.is_empty()
+        && opt.request_size.is_none()
+        && opt.stall_interval.is_none()
+        && opt.straggler.is_none()
+        && opt.patience.is_none()
+        && opt.balking_queue_length.is_none()
+        && opt.network_latency.is_none()
+        && opt.client_connections.is_none()
+        && opt.arrival_trace.is_none()
+        && opt.generator.is_none()
+        && opt.clients.is_none()
+        && opt.policy.is_none()
+        && opt.arrival_schedule().is_ok_and(|s| s.is_empty())
+        && pools.len() == 1
+        && opt.regions.is_empty()
+        && !opt.cull_timed_out
+        && retrypolicy::never_retries(&opt.retry_policies, opt.retry_probabilities())
+        && opt.spikes().is_empty()
+        && opt.fan_out == 1
+        && opt.quotas.is_empty()
+        && opt.autoscale_max_workers == 0
+        && opt.queue_latency_penalty == 0.0
+        && opt.db_query_latency == 0.0
+        && opt.resource_slots == 0
+        && opt.late_shedding_interval == 0
+        && opt.mitigations.is_empty();
+    if compatible {
+        pools.pop()
+    } else {
+        None
+    }
+}
+
+/// Stationary probability of each queue length (not counting the requests being served) of an M/M/c/K queue
+/// with `servers` servers and room for `queue_size` waiting requests.
+fn mmck_queue_lengths(
+    arrival_rate: f64,
+    service_rate: f64,
+    servers: usize,
+    queue_size: usize,
+) -> Vec<f64> {
+    // Unnormalized probabilities of n requests in the system, in log space, since the factorials overflow
+    // for any realistic number of workers.
+    let load = (arrival_rate / service_rate).ln();
+    let mut log_factorial = 0.0;
+    let mut log_probabilities = Vec::with_capacity(servers + queue_size + 1);
+    for n in 0..=servers + queue_size {
+        if n > 0 && n <= servers {
+            log_factorial += (n as f64).ln();
+        }
+        let log_probability = if n <= servers {
+            n as f64 * load - log_factorial
+        } else {
+            n as f64 * load - log_factorial - (n - servers) as f64 * (servers as f64).ln()
+        };
+        log_probabilities.push(log_probability);
+    }
+    let max = log_probabilities
+        .iter()
+        .copied()
+        .fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = log_probabilities.iter().map(|p| (p - max).exp()).collect();
+    let total: f64 = weights.iter().sum();
+
+    // All states with free servers have an empty queue.
+    let mut queue_lengths = vec![weights[..=servers].iter().sum::<f64>() / total];
+    queue_lengths.extend(weights[servers + 1..].iter().map(|w| w / total));
+    queue_lengths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn mm1k_matches_the_geometric_distribution() {
+        // With one server, the probability of n requests in the system is proportional to the utilization to
+        // the power of n: 1, 1/2, 1/4, 1/8 and 1/16 for up to 4 of them.
+        let total = 1.9375;
+        let queue_lengths = mmck_queue_lengths(0.1, 0.2, 1, 3);
+        assert_eq!(queue_lengths.len(), 4);
+        for (actual, expected) in queue_lengths.iter().zip([1.5, 0.25, 0.125, 0.0625]) {
+            assert_close(*actual, expected / total);
+        }
+    }
+
+    #[test]
+    fn mmck_without_a_queue_never_queues() {
+        assert_eq!(mmck_queue_lengths(0.5, 0.1, 3, 0), [1.0]);
+    }
+
+    #[test]
+    fn mmck_with_a_long_queue_approaches_erlang_c() {
+        // M/M/2 with an offered load of 1: the mean queue length of Erlang C is 1/3.
+        let queue_lengths = mmck_queue_lengths(0.1, 0.1, 2, 1000);
+        let mean: f64 = queue_lengths
+            .iter()
+            .enumerate()
+            .map(|(q, p)| q as f64 * p)
+            .sum();
+        assert_close(mean, 1.0 / 3.0);
+    }
+
+    #[test]
+    fn mmck_stays_finite_for_many_workers() {
+        let queue_lengths = mmck_queue_lengths(1.5, 0.001, 1000, 500);
+        assert!(queue_lengths.iter().all(|p| p.is_finite() && *p >= 0.0));
+        assert_close(queue_lengths.iter().sum(), 1.0);
+        // Overloaded by half, so every queue length is half again as likely as the one before it, and the full
+        // queue as likely as 1 - 1 / 1.5 of the time.
+        assert_close(queue_lengths[500] / queue_lengths[499], 1.5);
+        assert_close(queue_lengths[500], 1.0 / 3.0);
+    }
+
+    #[test]
+    fn only_markovian_configurations_have_a_model() {
+        let opt = |args: &str| {
+            Opt::from_iter(
+                "queueingsimulator --arrival_distribution poisson --latency_distribution exponential"
+                    .split(' ')
+                    .chain(args.split_whitespace()),
+            )
+        };
+        let model = markov_model(&opt(
+            "-r 0.1 --workers 4 --queue_size 20 --retry_policy none",
+        ))
+        .unwrap();
+        assert_eq!(model.len(), 21);
+        assert_close(model.iter().sum(), 1.0);
+        assert!(markov_model(&opt("-r 0.1 --retry_probability 0")).is_some());
+        assert!(markov_model(&opt("-r 0.1")).is_none());
+        assert!(markov_model(&opt("-r 0.1 --retry_policy none --simulate_spike")).is_none());
+        assert!(markov_model(&opt("-r 0.1 --retry_policy none --fan_out 2")).is_none());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::class::ClassSpec;
+use crate::simulation::ClassStats;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Default size of a rate quota's token bucket, in ticks worth of tokens.
+const DEFAULT_BURST_TICKS: f64 = 1000.0;
+
+/// Admission quota of a single class, specified on the command line as `class:concurrency:N` (at most N
+/// requests of the class in the system at once) or `class:rate:R[:burst]` (token bucket refilled with R
+/// tokens per tick, holding at most `burst` tokens).
+#[derive(Debug, Clone)]
+pub struct QuotaSpec {
+    class: String,
+    limit: Limit,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+enum Limit {
+    Concurrency(u32),
+    Rate { per_tick: f64, burst: f64 },
+}
+
+/// Per-class admission quotas. Requests over their class quota are shed at admission, even if the queue
+/// still has room for them.
+#[derive(Serialize, Deserialize)]
+pub struct Quotas {
+    /// Quota limits of each class.
+    limits: Vec<Vec<Limit>>,
+    in_flight: Vec<u32>,
+    /// Tokens in the bucket of each class, none for the classes without a rate quota.
+    tokens: Vec<Option<f64>>,
+    shed: Vec<u64>,
+}
+
+impl FromStr for QuotaSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let invalid = || {
+            format!(
+                "Expected quota as class:concurrency:N or class:rate:R[:burst], got '{}'",
+                s
+            )
+        };
+        let limit = match parts.as_slice() {
+            [_, "concurrency", n] => Limit::Concurrency(n.parse().map_err(|_| invalid())?),
+            [_, "rate", rate] => {
+                let per_tick: f64 = rate.parse().map_err(|_| invalid())?;
+                Limit::Rate {
+                    per_tick,
+                    burst: (per_tick * DEFAULT_BURST_TICKS).max(1.0),
+                }
+            }
+            [_, "rate", rate, burst] => Limit::Rate {
+                per_tick: rate.parse().map_err(|_| invalid())?,
+                burst: burst.parse().map_err(|_| invalid())?,
+            },
+            _ => return Err(invalid()),
+        };
+        if let Limit::Rate { per_tick, burst } = limit {
+            if per_tick <= 0.0 || burst < 1.0 {
+                return Err(format!(
+                    "Quota '{}' needs a rate greater than 0 and a burst of at least 1",
+                    s
+                ));
+            }
+        }
+
+        Ok(QuotaSpec {
+            class: parts[0].to_string(),
+            limit,
+        })
+    }
+}
+
+impl Quotas {
+    pub fn new(specs: &[QuotaSpec], classes: &[ClassSpec]) -> Result<Quotas, String> {
+        let mut limits = vec![Vec::new(); classes.len()];
+        for spec in specs.iter() {
+            let class = classes
+                .iter()
+                .position(|c| c.name == spec.class)
+                .ok_or_else(|| format!("Quota for unknown class '{}'", spec.class))?;
+            limits[class].push(spec.limit);
+        }
+
+        // Token buckets start full, as if the system had been idle before the simulation.
+        let tokens = limits
+            .iter()
+            .map(|class_limits| {
+                class_limits
+                    .iter()
+                    .filter_map(|limit| match limit {
+                        Limit::Rate { burst, .. } => Some(*burst),
+                        Limit::Concurrency(_) => None,
+                    })
+                    .reduce(f64::min)
+            })
+            .collect();
+        Ok(Quotas {
+            in_flight: vec![0; classes.len()],
+            shed: vec![0; classes.len()],
+            limits,
+            tokens,
+        })
+    }
+
+    /// Refills the token buckets of the rate quotas.
+    pub fn tick(&mut self) {
+        for (class, class_limits) in self.limits.iter().enumerate() {
+            for limit in class_limits.iter() {
+                if let Limit::Rate { per_tick, burst } = *limit {
+                    self.tokens[class] =
+                        self.tokens[class].map(|tokens| (tokens + per_tick).min(burst));
+                }
+            }
+        }
+    }
+
+    /// Checks whether a request of the class fits into its quotas. Requests that don't are counted as shed.
+    pub fn allows(&mut self, class: usize) -> bool {
+        let allowed = self.limits[class].iter().all(|limit| match *limit {
+            Limit::Concurrency(max) => self.in_flight[class] < max,
+            Limit::Rate { .. } => self.tokens[class].is_some_and(|tokens| tokens >= 1.0),
+        });
+        if !allowed {
+            self.shed[class] += 1;
+        }
+        allowed
+    }
+
+    /// Request of the class was admitted into the system, so it counts against the class quotas.
+    pub fn admitted(&mut self, class: usize) {
+        self.in_flight[class] += 1;
+        if let Some(tokens) = self.tokens[class].as_mut() {
+            *tokens -= 1.0;
+        }
+    }
+
+    /// Request of the class left the system.
+    pub fn released(&mut self, class: usize) {
+        self.in_flight[class] -= 1;
+    }
+
+    pub fn print_summary(&self, classes: &[ClassStats]) {
+        for (class, stats) in classes.iter().enumerate() {
+            if self.limits[class].is_empty() {
+                continue;
+            }
+            println!(
+                "Class {}: {} requests shed by quota ({:.2}%)",
+                stats.name,
+                self.shed[class],
+                self.shed[class] as f64 / stats.total_requests as f64 * 100.0
+            );
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use crate::class::ClassSpec;
+use crate::pool::{Pool, PoolSpec};
+use crate::request::Request;
+use crate::simulation::Failure;
+use crate::utilization;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A region: a copy of the server with its own workers and queue, and the clients sending it their share of the
+/// traffic. Specified on the command line as `name:workers:queue_size:traffic`, the traffic being relative to
+/// that of the other regions.
+#[derive(Debug, Clone)]
+pub struct RegionSpec {
+    pub name: String,
+    pub workers: u16,
+    pub queue_size: usize,
+    pub traffic: f64,
+}
+
+impl FromStr for RegionSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() != 4 || parts[0].is_empty() {
+            return Err(format!(
+                "Expected region as name:workers:queue_size:traffic, got '{}'",
+                s
+            ));
+        }
+
+        Ok(RegionSpec {
+            name: parts[0].to_string(),
+            workers: parts[1]
+                .parse()
+                .map_err(|_| format!("Invalid number of workers in region '{}'", s))?,
+            queue_size: parts[2]
+                .parse()
+                .map_err(|_| format!("Invalid queue size in region '{}'", s))?,
+            traffic: parts[3]
+                .parse()
+                .ok()
+                .filter(|&traffic: &f64| traffic > 0.0)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid traffic in region '{}', expected a number greater than 0",
+                        s
+                    )
+                })?,
+        })
+    }
+}
+
+impl RegionSpec {
+    /// The pool of the region, serving every class.
+    pub fn pool(&self, classes: &[ClassSpec]) -> PoolSpec {
+        PoolSpec {
+            name: self.name.clone(),
+            workers: self.workers,
+            queue_size: self.queue_size,
+            classes: classes.iter().map(|c| c.name.clone()).collect(),
+        }
+    }
+}
+
+/// Which failed requests the clients retry in another region.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Failover {
+    /// Every retry goes to the region the failed attempt went to.
+    None,
+    /// Rejected requests are retried in the next region, timed out ones in the same one.
+    Rejected,
+    /// Rejected, timed out and abandoned requests are all retried in the next region.
+    Failed,
+}
+
+impl FromStr for Failover {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Failover::None),
+            "rejected" => Ok(Failover::Rejected),
+            "failed" => Ok(Failover::Failed),
+            _ => Err(format!(
+                "Unknown failover '{}', expected none, rejected or failed",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Failover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failover::None => write!(f, "none"),
+            Failover::Rejected => write!(f, "rejected"),
+            Failover::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// Region the client of an attempt is in, and the region the attempt goes to.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+pub struct Route {
+    pub home: usize,
+    pub region: usize,
+}
+
+impl Route {
+    /// Whether the attempt crosses from the region of its client to another one.
+    pub fn is_remote(&self) -> bool {
+        self.home != self.region
+    }
+}
+
+/// Outcomes of the attempts sent to a region after the warm-up.
+#[derive(Default, Serialize, Deserialize)]
+struct RegionStats {
+    attempts: u64,
+    /// Attempts from the clients of the other regions, and the ones that failed.
+    remote_attempts: u64,
+    failed: u64,
+}
+
+/// Regions the clients are spread over, each sending its requests to its own region, and retrying the failed
+/// ones in the next region if the failover policy says so. A request crossing to another region pays the
+/// latency between the regions both ways.
+#[derive(Serialize, Deserialize)]
+pub struct Regions {
+    names: Vec<String>,
+    traffic: WeightedIndex<f64>,
+    failover: Failover,
+    /// One-way latency between any two regions, in ticks.
+    latency: u64,
+    /// Requests on their way to a remote region, by the tick they reach it at.
+    in_flight: BTreeMap<u64, Vec<Request>>,
+    stats: Vec<RegionStats>,
+    /// Retries sent to another region than the failed attempt, after the warm-up.
+    failovers: u64,
+}
+
+impl Regions {
+    pub fn new(specs: &[RegionSpec], failover: Failover, latency: u64) -> Regions {
+        Regions {
+            names: specs.iter().map(|r| r.name.clone()).collect(),
+            traffic: WeightedIndex::new(specs.iter().map(|r| r.traffic)).unwrap(),
+            failover,
+            latency,
+            in_flight: BTreeMap::new(),
+            stats: specs.iter().map(|_| RegionStats::default()).collect(),
+            failovers: 0,
+        }
+    }
+
+    /// Route of the first attempt of a new request, to the region of its client, picked by the traffic of the
+    /// regions.
+    pub fn first_route<R: Rng>(&self, rng: &mut R) -> Route {
+        let home = self.traffic.sample(rng);
+        Route { home, region: home }
+    }
+
+    /// Counts an attempt sent along the route.
+    pub fn sent(&mut self, route: Route, measured: bool) {
+        if measured {
+            let stats = &mut self.stats[route.region];
+            stats.attempts += 1;
+            stats.remote_attempts += route.is_remote() as u64;
+        }
+    }
+
+    /// Counts an attempt that failed along the route.
+    pub fn failed(&mut self, route: Route, measured: bool) {
+        if measured {
+            self.stats[route.region].failed += 1;
+        }
+    }
+
+    /// Route of the retry of an attempt that failed along the specified route: the next region, if the failover
+    /// policy fails over from the failure, or the same one.
+    pub fn retry_route(&mut self, failed: Route, failure: Failure, measured: bool) -> Route {
+        let fails_over = match self.failover {
+            Failover::None => false,
+            Failover::Rejected => matches!(failure, Failure::Rejected),
+            Failover::Failed => true,
+        };
+        if !fails_over {
+            return failed;
+        }
+        if measured {
+            self.failovers += 1;
+        }
+        Route {
+            home: failed.home,
+            region: (failed.region + 1) % self.names.len(),
+        }
+    }
+
+    /// Sends a request along the route, returning it if it reaches its region right away. A request to a remote
+    /// region is held until it crosses over, and its response takes as long to cross back.
+    pub fn send(&mut self, mut request: Request, route: Route, tick: u64) -> Option<Request> {
+        if !route.is_remote() || self.latency == 0 {
+            return Some(request);
+        }
+        request.add_network_delays(self.latency, self.latency);
+        self.in_flight
+            .entry(tick + self.latency)
+            .or_default()
+            .push(request);
+        None
+    }
+
+    /// Requests reaching a remote region during the specified tick.
+    pub fn arrivals_at(&mut self, tick: u64) -> Vec<Request> {
+        self.in_flight.remove(&tick).unwrap_or_default()
+    }
+
+    /// Prints the outcomes of the attempts sent to every region, and the utilization of its pool.
+    pub fn print_summary(&self, pools: &[Pool]) {
+        for ((name, stats), pool) in self.names.iter().zip(self.stats.iter()).zip(pools.iter()) {
+            println!(
+                "Region {}: {} attempts, {} ({:.2}%) from the clients of other regions, failure rate: {:.2}%, utilization: {:.2}%",
+                name,
+                stats.attempts,
+                stats.remote_attempts,
+                stats.remote_attempts as f64 / stats.attempts.max(1) as f64 * 100.0,
+                stats.failed as f64 / stats.attempts.max(1) as f64 * 100.0,
+                utilization::aggregate(&pool.worker_times()) * 100.0
+            );
+        }
+        println!(
+            "Failover ({}): {} retries sent to another region, {} ticks away",
+            self.failover, self.failovers, self.latency
+        );
+    }
+}
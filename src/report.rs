@@ -0,0 +1,323 @@
+use crate::compare;
+use crate::histogram::Histogram;
+use crate::simulation::{Stats, Summary};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+
+/// Colors of the runs in the charts.
+const COLORS: [&str; 2] = ["#1f77b4", "#d62728"];
+
+const CHART_WIDTH: f64 = 640.0;
+const CHART_HEIGHT: f64 = 240.0;
+const CHART_MARGIN: f64 = 40.0;
+
+/// Peak queue length, failures and latency over time, sampled every `every` ticks for the charts of the HTML
+/// report and the plots.
+#[derive(Serialize, Deserialize)]
+pub struct Timeline {
+    every: u64,
+    peak_queue_lens: Vec<usize>,
+    /// Requests that arrived, and failed, during each sample.
+    requests: Vec<u64>,
+    failures: Vec<u64>,
+    /// Latency percentiles (p50, p90 and p99) of the requests that completed during each sample.
+    latency_percentiles: Vec<[u64; 3]>,
+    last_total_requests: u64,
+    last_failed_requests: u64,
+    /// Latencies recorded before the current sample.
+    last_latency: Histogram,
+}
+
+impl Timeline {
+    pub fn new(every: u64) -> Timeline {
+        Timeline {
+            every,
+            peak_queue_lens: Vec::new(),
+            requests: Vec::new(),
+            failures: Vec::new(),
+            latency_percentiles: Vec::new(),
+            last_total_requests: 0,
+            last_failed_requests: 0,
+            last_latency: Histogram::new(),
+        }
+    }
+
+    /// Samples the state of the system at the end of the specified tick.
+    pub fn tick(&mut self, tick: u64, queue_len: usize, stats: &Stats) {
+        let index = (tick / self.every) as usize;
+        if index >= self.peak_queue_lens.len() {
+            self.peak_queue_lens.push(queue_len);
+            self.requests.push(0);
+            self.failures.push(0);
+        } else {
+            self.peak_queue_lens[index] = self.peak_queue_lens[index].max(queue_len);
+        }
+        self.requests[index] += stats.total_requests - self.last_total_requests;
+        self.failures[index] += stats.failed_requests - self.last_failed_requests;
+        self.last_total_requests = stats.total_requests;
+        self.last_failed_requests = stats.failed_requests;
+        if (tick + 1).is_multiple_of(self.every) {
+            self.end_sample(&stats.latency);
+        }
+    }
+
+    /// Ends the last sample, if the simulation ended part way through it.
+    pub fn finish(&mut self, stats: &Stats) {
+        if self.latency_percentiles.len() < self.peak_queue_lens.len() {
+            self.end_sample(&stats.latency);
+        }
+    }
+
+    fn end_sample(&mut self, latency: &Histogram) {
+        let sample = latency.since(&self.last_latency);
+        self.latency_percentiles.push([
+            sample.percentile(50.0),
+            sample.percentile(90.0),
+            sample.percentile(99.0),
+        ]);
+        self.last_latency = latency.clone();
+    }
+
+    /// Number of ticks in each sample.
+    pub fn every(&self) -> u64 {
+        self.every
+    }
+
+    pub fn peak_queue_lens(&self) -> &[usize] {
+        &self.peak_queue_lens
+    }
+
+    pub fn requests(&self) -> &[u64] {
+        &self.requests
+    }
+
+    pub fn failures(&self) -> &[u64] {
+        &self.failures
+    }
+
+    pub fn latency_percentiles(&self) -> &[[u64; 3]] {
+        &self.latency_percentiles
+    }
+}
+
+/// A simulation run shown in the report.
+pub struct Run<'a> {
+    pub label: &'a str,
+    /// Options specific to the run.
+    pub args: &'a str,
+    pub summary: &'a Summary,
+}
+
+/// Writes a standalone HTML report of one run, or a comparison of two, with the configuration, a table of the
+/// results and charts of the latency distribution and queue length. Everything is embedded in the file, so it
+/// can be attached to a design review or postmortem as is.
+pub fn write(
+    path: &str,
+    shared_args: &str,
+    metadata: &[(String, String)],
+    runs: &[Run],
+) -> io::Result<()> {
+    let mut html = String::new();
+    html.push_str(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Queueing simulation</title>\n<style>\n\
+         body { font-family: sans-serif; max-width: 720px; margin: 2em auto; color: #222; }\n\
+         table { border-collapse: collapse; }\n\
+         th, td { padding: 4px 12px; border-bottom: 1px solid #ddd; text-align: right; }\n\
+         th:first-child, td:first-child { text-align: left; }\n\
+         code { background: #f4f4f4; padding: 2px 4px; }\n\
+         </style>\n</head>\n<body>\n<h1>Queueing simulation</h1>\n",
+    );
+
+    html.push_str("<h2>Configuration</h2>\n<table>\n");
+    for (key, value) in metadata {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape(key),
+            escape(value)
+        )
+        .unwrap();
+    }
+    writeln!(
+        html,
+        "<tr><td>Shared</td><td><code>{}</code></td></tr>",
+        escape(shared_args)
+    )
+    .unwrap();
+    if runs.len() > 1 {
+        for (run, color) in runs.iter().zip(COLORS.iter()) {
+            writeln!(
+                html,
+                "<tr><td style=\"color: {}\">{}</td><td><code>{}</code></td></tr>",
+                color,
+                escape(run.label),
+                escape(run.args)
+            )
+            .unwrap();
+        }
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Results</h2>\n<table>\n<tr><th></th>");
+    for run in runs {
+        write!(html, "<th>{}</th>", escape(run.label)).unwrap();
+    }
+    if runs.len() == 2 {
+        html.push_str("<th>Difference</th>");
+    }
+    html.push_str("</tr>\n");
+    for (name, metric, precision) in compare::metrics() {
+        write!(html, "<tr><td>{}</td>", name).unwrap();
+        let values: Vec<f64> = runs.iter().map(|r| metric(&r.summary.stats)).collect();
+        for value in values.iter() {
+            write!(html, "<td>{:.*}</td>", precision, value).unwrap();
+        }
+        if let [a, b] = values[..] {
+            write!(html, "<td>{:+.*}</td>", precision, b - a).unwrap();
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Latency distribution</h2>\n");
+    let latencies: Vec<Vec<(f64, f64)>> = runs
+        .iter()
+        .map(|r| cumulative_distribution(&r.summary.stats.latency))
+        .collect();
+    chart(&mut html, &latencies, "latency (ticks)", "requests (%)");
+
+    html.push_str("<h2>Queue length</h2>\n");
+    let queue_lens: Vec<Vec<(f64, f64)>> = runs
+        .iter()
+        .map(|r| match r.summary.timeline.as_ref() {
+            Some(timeline) => timeline
+                .peak_queue_lens
+                .iter()
+                .enumerate()
+                .map(|(index, &len)| ((index as u64 * timeline.every) as f64, len as f64))
+                .collect(),
+            None => Vec::new(),
+        })
+        .collect();
+    chart(&mut html, &queue_lens, "tick", "peak queue length");
+
+    html.push_str("</body>\n</html>\n");
+    fs::write(path, html)
+}
+
+/// Points of the cumulative distribution of the recorded values, as percentages.
+fn cumulative_distribution(histogram: &Histogram) -> Vec<(f64, f64)> {
+    let total = histogram.total().max(1) as f64;
+    let mut seen = 0;
+    let mut points = Vec::new();
+    for value in 0..=histogram.max() {
+        let count = histogram.count(value);
+        if count > 0 {
+            seen += count;
+            points.push((value as f64, seen as f64 / total * 100.0));
+        }
+    }
+    points
+}
+
+/// Appends an SVG line chart with a line per run, scaled to fit all of them.
+fn chart(html: &mut String, lines: &[Vec<(f64, f64)>], x_label: &str, y_label: &str) {
+    let points = lines.iter().flatten();
+    let x_max = points.clone().map(|p| p.0).fold(0.0, f64::max).max(1.0);
+    let y_max = points.map(|p| p.1).fold(0.0, f64::max).max(1.0);
+    let (width, height) = (
+        CHART_WIDTH - 2.0 * CHART_MARGIN,
+        CHART_HEIGHT - 2.0 * CHART_MARGIN,
+    );
+
+    writeln!(
+        html,
+        "<svg width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\" font-size=\"11\">",
+        CHART_WIDTH, CHART_HEIGHT
+    )
+    .unwrap();
+    writeln!(
+        html,
+        "<path d=\"M{m} {m} V{b} H{r}\" fill=\"none\" stroke=\"#888\"/>",
+        m = CHART_MARGIN,
+        b = CHART_MARGIN + height,
+        r = CHART_MARGIN + width
+    )
+    .unwrap();
+    writeln!(
+        html,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+        CHART_MARGIN - 4.0,
+        CHART_MARGIN + 4.0,
+        format_axis(y_max)
+    )
+    .unwrap();
+    writeln!(
+        html,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">0</text>",
+        CHART_MARGIN - 4.0,
+        CHART_MARGIN + height
+    )
+    .unwrap();
+    writeln!(
+        html,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+        CHART_MARGIN + width,
+        CHART_MARGIN + height + 14.0,
+        format_axis(x_max)
+    )
+    .unwrap();
+    writeln!(
+        html,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+        CHART_MARGIN + width / 2.0,
+        CHART_MARGIN + height + 28.0,
+        x_label
+    )
+    .unwrap();
+    writeln!(
+        html,
+        "<text x=\"{}\" y=\"{}\">{}</text>",
+        CHART_MARGIN,
+        CHART_MARGIN - 12.0,
+        y_label
+    )
+    .unwrap();
+    for (line, color) in lines.iter().zip(COLORS.iter()) {
+        let points: Vec<String> = line
+            .iter()
+            .map(|(x, y)| {
+                format!(
+                    "{:.1},{:.1}",
+                    CHART_MARGIN + x / x_max * width,
+                    CHART_MARGIN + height - y / y_max * height
+                )
+            })
+            .collect();
+        writeln!(
+            html,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"/>",
+            points.join(" "),
+            color
+        )
+        .unwrap();
+    }
+    html.push_str("</svg>\n");
+}
+
+fn format_axis(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value)
+    } else {
+        format!("{:.1}", value)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
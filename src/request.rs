@@ -0,0 +1,270 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct Request {
+    /// Identifies the request in the event log. Every sub-request of a fanned out request has an id of its own.
+    id: u64,
+    remaining_ticks: u32,
+    /// Remaining time of the database query, in ticks of an otherwise idle database. The query runs once
+    /// the execution time runs out.
+    query_ticks: f64,
+    /// Whether the database query went through an outage of the database, and whether it failed in it.
+    query_exposed: bool,
+    query_failed: bool,
+    /// Ticks the client waits for the response before giving up on the request, and the tick it does at.
+    timeout: u32,
+    deadline_tick: u64,
+    /// Tick at the end of which the client abandons the request if it's still waiting in the queue.
+    patience_deadline_tick: u64,
+    /// Most ticks the request may wait in the queue of the server before a worker gets to it.
+    queue_wait_budget: u64,
+    arrival_tick: u64,
+    /// Point within the arrival tick the request arrived at, between 0 (inclusive) and 1 (exclusive).
+    arrival_offset: f64,
+    class: usize,
+    fan_out_parent: Option<u64>,
+    /// Number of ticks a worker spent on the request so far.
+    worked_ticks: u32,
+    /// Payload size, relative to the mean size.
+    size: f64,
+    /// Ticks the request spends crossing the network to the server, and the response crossing it back.
+    request_delay: u64,
+    response_delay: u64,
+    /// Whether the request is served on the degraded fast path of a browned out pool.
+    degraded: bool,
+    /// What the request is for, with the cache or the consistent hashing. 0 if the requests have no keys.
+    key: u64,
+}
+
+impl Request {
+    /// New request, with specified execution time and timeout. It is totally possible (but unlikely)
+    /// to end up with a request that takes longer to complete than its timeout, even if the request
+    /// was not waiting in the queue. The normal distribution used to generate request cost should make
+    /// that probability extremely unlikely, however. That is unless request ends up waiting in the
+    /// queue for a long time.
+    pub fn new(execution_time: u32, timeout: u32, arrival_tick: u64, class: usize) -> Request {
+        Request {
+            id: 0,
+            remaining_ticks: execution_time,
+            query_ticks: 0.0,
+            query_exposed: false,
+            query_failed: false,
+            timeout,
+            deadline_tick: arrival_tick + timeout as u64,
+            patience_deadline_tick: u64::MAX,
+            queue_wait_budget: u64::MAX,
+            arrival_tick,
+            arrival_offset: 0.0,
+            class,
+            fan_out_parent: None,
+            worked_ticks: 0,
+            size: 1.0,
+            request_delay: 0,
+            response_delay: 0,
+            degraded: false,
+            key: 0,
+        }
+    }
+
+    /// New sub-request, spawned by a fanned out parent request.
+    pub fn sub_request(
+        execution_time: u32,
+        timeout: u32,
+        arrival_tick: u64,
+        class: usize,
+        parent: u64,
+    ) -> Request {
+        Request {
+            fan_out_parent: Some(parent),
+            ..Request::new(execution_time, timeout, arrival_tick, class)
+        }
+    }
+
+    /// The request was handed straight to a worker, which works on it during its arrival tick. Unlike the
+    /// arrival tick of a queued request, that tick counts towards the timeout.
+    pub fn started_on_arrival(&mut self) {
+        self.deadline_tick = self.deadline_tick.saturating_sub(1);
+    }
+
+    /// Places the arrival of the request at the specified point within its arrival tick.
+    pub fn set_arrival_offset(&mut self, arrival_offset: f64) {
+        self.arrival_offset = arrival_offset;
+    }
+
+    /// Makes the client abandon the request if it's still waiting in the queue after the specified time.
+    pub fn set_patience(&mut self, patience_ticks: u64) {
+        self.patience_deadline_tick = self.arrival_tick + patience_ticks;
+    }
+
+    /// Makes the server fail the request instead of working on it if it waited in the queue for longer than
+    /// the specified number of ticks.
+    pub fn set_queue_wait_budget(&mut self, queue_wait_budget: u64) {
+        self.queue_wait_budget = queue_wait_budget;
+    }
+
+    /// Whether the request waited in the queue for longer than its budget by the start of the specified tick.
+    pub fn is_over_queue_wait_budget(&self, current_tick: u64) -> bool {
+        current_tick.saturating_sub(self.arrival_tick + self.request_delay) > self.queue_wait_budget
+    }
+
+    /// Makes the request reach the server the specified number of ticks after the client sent it, and the
+    /// response reach the client the specified number of ticks after the server finished it.
+    pub fn set_network_delays(&mut self, request_delay: u64, response_delay: u64) {
+        self.request_delay = request_delay;
+        self.response_delay = response_delay;
+    }
+
+    /// Adds to the time the request takes to reach the server, and its response to reach the client.
+    pub fn add_network_delays(&mut self, request_delay: u64, response_delay: u64) {
+        self.request_delay += request_delay;
+        self.response_delay += response_delay;
+    }
+
+    /// Serves the request on the degraded fast path, which takes the specified fraction of its service time,
+    /// database query included, but at least a tick.
+    pub fn degrade(&mut self, service_fraction: f64) {
+        self.remaining_ticks =
+            ((self.remaining_ticks as f64 * service_fraction).round() as u32).max(1);
+        self.query_ticks *= service_fraction;
+        self.degraded = true;
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    pub fn set_key(&mut self, key: u64) {
+        self.key = key;
+    }
+
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    pub fn set_id(&mut self, id: u64) {
+        self.id = id;
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn set_size(&mut self, size: f64) {
+        self.size = size;
+    }
+
+    /// Makes the request query the database for the specified time, once it's done executing.
+    pub fn set_query_time(&mut self, query_ticks: f64) {
+        self.query_ticks = query_ticks;
+    }
+
+    /// One tick passed while request is being worked on, bringing it closer to completion. `query_speed` is
+    /// the fraction of a tick worth of progress the database query makes.
+    pub fn working_tick(&mut self, query_speed: f64) {
+        self.worked_ticks += 1;
+        if self.remaining_ticks != 0 {
+            self.remaining_ticks -= 1;
+        } else if self.query_ticks > 0.0 {
+            self.query_ticks -= query_speed;
+        }
+    }
+
+    /// Multiplies the remaining execution time of the request by the specified factor.
+    pub fn stretch(&mut self, factor: f64) {
+        self.remaining_ticks = (self.remaining_ticks as f64 * factor) as u32;
+    }
+
+    /// Whether the client gave up on the request by the end of the specified tick. Deadlines are absolute, so
+    /// that requests waiting in the queue don't need to be visited on every tick.
+    pub fn is_timed_out(&self, current_tick: u64) -> bool {
+        current_tick >= self.deadline_tick
+    }
+
+    /// Whether the client ran out of patience waiting in the queue by the end of the specified tick.
+    pub fn has_abandoned(&self, current_tick: u64) -> bool {
+        current_tick >= self.patience_deadline_tick
+    }
+
+    /// Tick the client gives up on the request at.
+    pub fn deadline_tick(&self) -> u64 {
+        self.deadline_tick
+    }
+
+    pub fn patience_deadline_tick(&self) -> u64 {
+        self.patience_deadline_tick
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.remaining_ticks == 0 && self.query_ticks <= 0.0
+    }
+
+    pub fn is_querying(&self) -> bool {
+        self.remaining_ticks == 0 && self.query_ticks > 0.0
+    }
+
+    /// Whether the database query is in flight, and hasn't gone through an outage of the database yet.
+    pub fn is_exposed_to_outage(&self) -> bool {
+        self.is_querying() && !self.query_exposed
+    }
+
+    /// The database query went through an outage. If it `failed`, the database returned an error right away,
+    /// and the request is done.
+    pub fn outage(&mut self, failed: bool) {
+        self.query_exposed = true;
+        if failed {
+            self.query_ticks = 0.0;
+            self.query_failed = true;
+        }
+    }
+
+    /// Whether the database query failed, so the response to the client is an error.
+    pub fn has_failed_query(&self) -> bool {
+        self.query_failed
+    }
+
+    /// Number of ticks the request spent in the system so far, including the current one. Rounded to the
+    /// nearest tick if the request arrived part way through its arrival tick.
+    pub fn latency(&self, current_tick: u64) -> u64 {
+        let latency = current_tick + 1 - self.arrival_tick;
+        (latency as f64 - self.arrival_offset).round() as u64
+    }
+
+    /// Number of ticks the request spent in the server so far, after crossing the network to it.
+    pub fn time_in_server(&self, current_tick: u64) -> u64 {
+        self.latency(current_tick)
+            .saturating_sub(self.request_delay)
+    }
+
+    /// Total time the request and its response spend crossing the network.
+    pub fn network_delay(&self) -> u64 {
+        self.request_delay + self.response_delay
+    }
+
+    pub fn response_delay(&self) -> u64 {
+        self.response_delay
+    }
+
+    pub fn timeout(&self) -> u32 {
+        self.timeout
+    }
+
+    pub fn arrival_tick(&self) -> u64 {
+        self.arrival_tick
+    }
+
+    pub fn size(&self) -> f64 {
+        self.size
+    }
+
+    pub fn worked_ticks(&self) -> u32 {
+        self.worked_ticks
+    }
+
+    pub fn class(&self) -> usize {
+        self.class
+    }
+
+    pub fn fan_out_parent(&self) -> Option<u64> {
+        self.fan_out_parent
+    }
+}
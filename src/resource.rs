@@ -0,0 +1,102 @@
+use crate::class::ClassSpec;
+use crate::request::Request;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// A second resource, such as memory or GPU slots, that requests of some classes need in addition to a
+/// worker. Such requests take a slot when they are admitted and hold it until they complete, waiting in the
+/// resource's own queue while all slots are taken. Workers can sit idle while requests wait for slots, which is
+/// how a bottleneck on a resource other than CPU shows up.
+#[derive(Serialize, Deserialize)]
+pub struct Resource {
+    /// Whether requests of each class need a slot.
+    needed_by: Vec<bool>,
+    slots: u32,
+    in_use: u32,
+    queue: VecDeque<Request>,
+    queue_size: usize,
+    peak_in_use: u32,
+    peak_queue_len: usize,
+    waited: u64,
+    rejected: u64,
+}
+
+impl Resource {
+    /// Creates the resource, needed by the named classes (or by all of them, if there are no names).
+    pub fn new(
+        slots: u32,
+        queue_size: usize,
+        class_names: &[String],
+        classes: &[ClassSpec],
+    ) -> Result<Resource, String> {
+        let mut needed_by = vec![class_names.is_empty(); classes.len()];
+        for name in class_names {
+            let class = classes
+                .iter()
+                .position(|c| &c.name == name)
+                .ok_or_else(|| format!("Resource class '{}' is not a known class", name))?;
+            needed_by[class] = true;
+        }
+        Ok(Resource {
+            needed_by,
+            slots,
+            in_use: 0,
+            queue: VecDeque::new(),
+            queue_size,
+            peak_in_use: 0,
+            peak_queue_len: 0,
+            waited: 0,
+            rejected: 0,
+        })
+    }
+
+    pub fn is_needed_by(&self, class: usize) -> bool {
+        self.needed_by[class]
+    }
+
+    pub fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Takes a free slot, if there is one.
+    pub fn try_acquire(&mut self) -> bool {
+        if self.in_use == self.slots {
+            return false;
+        }
+        self.in_use += 1;
+        self.peak_in_use = self.peak_in_use.max(self.in_use);
+        true
+    }
+
+    pub fn release(&mut self) {
+        self.in_use -= 1;
+    }
+
+    /// Queues the request until a slot frees up. Returns false if the queue is full, and the request has to be
+    /// rejected.
+    pub fn enqueue(&mut self, request: Request) -> bool {
+        if self.queue.len() >= self.queue_size {
+            self.rejected += 1;
+            return false;
+        }
+        self.queue.push_back(request);
+        self.peak_queue_len = self.peak_queue_len.max(self.queue.len());
+        self.waited += 1;
+        true
+    }
+
+    /// Hands out a slot to the request that waited for one the longest, if there is a free slot.
+    pub fn next_waiting(&mut self) -> Option<Request> {
+        if self.queue.is_empty() || !self.try_acquire() {
+            return None;
+        }
+        self.queue.pop_front()
+    }
+
+    pub fn print_summary(&self) {
+        println!(
+            "Resource: peak {} of {} slots in use, {} requests waited for a slot (peak queue {}), {} rejected.",
+            self.peak_in_use, self.slots, self.waited, self.peak_queue_len, self.rejected
+        );
+    }
+}
@@ -0,0 +1,674 @@
+use crate::checkpoint;
+use crate::class::ClassSpec;
+use crate::region::Route;
+use crate::simulation::Failure;
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// Most retries a retry budget saves up beyond its ratio, e.g. to ride through a short burst of failures.
+const BUDGET_RESERVE: f64 = 10.0;
+
+/// An attempt of a request: the original request is the first one, and every retry the next.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Attempt {
+    pub number: u32,
+    /// Ticks the client waited after the previous attempt failed, before sending this one.
+    pub backoff: u64,
+    /// Region the attempt goes to, with multiple regions.
+    pub route: Route,
+}
+
+impl Attempt {
+    pub fn first() -> Attempt {
+        Attempt {
+            number: 1,
+            backoff: 0,
+            route: Route::default(),
+        }
+    }
+
+    /// The retry of this attempt, sent after the specified backoff.
+    pub fn retry(&self, backoff: u64) -> Attempt {
+        Attempt {
+            number: self.number + 1,
+            backoff,
+            route: self.route,
+        }
+    }
+}
+
+/// Probabilities the clients retry a request with, by how it failed. The default retry policy, kept from before
+/// the retry policies, and set by the deprecated retry probability options.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryProbabilities {
+    pub rejected: f64,
+    pub timed_out: f64,
+    pub abandoned: f64,
+}
+
+impl RetryProbabilities {
+    pub fn of(&self, failure: Failure) -> f64 {
+        match failure {
+            Failure::Rejected => self.rejected,
+            Failure::TimedOut => self.timed_out,
+            Failure::Abandoned => self.abandoned,
+            Failure::Balked => 0.0,
+        }
+    }
+}
+
+/// How the clients of a class retry their failed requests: whether they do, and how long they wait before
+/// sending the retry.
+pub trait RetryPolicy {
+    /// Ticks the client waits before retrying the attempt that failed the specified way, or `None` if the client
+    /// gives up on it.
+    fn backoff(&mut self, failed: &Attempt, failure: Failure, rng: &mut ChaCha12Rng)
+        -> Option<u64>;
+
+    /// Counts an original request of the class, which a retry budget grows with.
+    fn requested(&mut self) {}
+
+    /// Describes the policy, and what it did during the run, for the summary.
+    fn describe(&self) -> String;
+
+    /// What the policy carries over from one request to the next, for a checkpoint. Most policies decide every
+    /// retry on its own, and have nothing to save.
+    fn checkpoint(&self) -> Result<Value, String> {
+        Ok(Value::Null)
+    }
+
+    /// Picks up from what the policy had at a checkpoint, for a resumed run.
+    fn resume(&mut self, _state: Value) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Retry policy of a class, specified on the command line as `[class:]policy`, the policy being `none`,
+/// `immediate`, `probability:P`, `fixed:DELAY`, `exponential:BASE:CAP`, `decorrelated:BASE:CAP` or
+/// `budget:RATIO`. Without a class, it applies to every class.
+#[derive(Debug, Clone)]
+pub struct RetryPolicySpec {
+    class: Option<String>,
+    policy: PolicySpec,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PolicySpec {
+    None,
+    Immediate,
+    Probability(f64),
+    Default(RetryProbabilities),
+    Fixed(u64),
+    Exponential(u64, u64),
+    Decorrelated(u64, u64),
+    Budget(f64),
+}
+
+impl FromStr for RetryPolicySpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let (class, parts) = match parts.as_slice() {
+            [name, ..] if is_policy(name) => (None, parts.as_slice()),
+            [class, rest @ ..] if !rest.is_empty() => (Some(class.to_string()), rest),
+            _ => return Err(format!("Unknown retry policy '{}'", s)),
+        };
+        let ticks = |value: &str| {
+            value.parse::<u64>().map_err(|_| {
+                format!(
+                    "Invalid retry policy '{}', expected a number of ticks, got '{}'",
+                    s, value
+                )
+            })
+        };
+        let policy = match parts {
+            ["none"] => PolicySpec::None,
+            ["immediate"] => PolicySpec::Immediate,
+            ["probability", probability] => match probability.parse::<f64>() {
+                Ok(probability) if (0.0..=1.0).contains(&probability) => {
+                    PolicySpec::Probability(probability)
+                }
+                _ => {
+                    return Err(format!(
+                        "Retry policy '{}' needs a probability between 0 and 1",
+                        s
+                    ))
+                }
+            },
+            ["fixed", delay] => PolicySpec::Fixed(ticks(delay)?),
+            ["exponential", base, cap] | ["decorrelated", base, cap] => {
+                let (base, cap) = (ticks(base)?, ticks(cap)?);
+                if base == 0 || cap < base {
+                    return Err(format!(
+                        "Retry policy '{}' needs a base greater than 0, and a cap at least the base",
+                        s
+                    ));
+                }
+                if parts[0] == "exponential" {
+                    PolicySpec::Exponential(base, cap)
+                } else {
+                    PolicySpec::Decorrelated(base, cap)
+                }
+            }
+            ["budget", ratio] => match ratio.parse::<f64>() {
+                Ok(ratio) if ratio > 0.0 => PolicySpec::Budget(ratio),
+                _ => {
+                    return Err(format!(
+                        "Retry budget '{}' needs a ratio of retries to requests greater than 0",
+                        s
+                    ))
+                }
+            },
+            _ => {
+                return Err(format!(
+                    "Expected retry policy as [class:]policy, with the policy one of none, immediate, probability:P, \
+                     fixed:DELAY, exponential:BASE:CAP, decorrelated:BASE:CAP or budget:RATIO, got '{}'",
+                    s
+                ))
+            }
+        };
+
+        Ok(RetryPolicySpec { class, policy })
+    }
+}
+
+fn is_policy(name: &str) -> bool {
+    [
+        "none",
+        "immediate",
+        "probability",
+        "fixed",
+        "exponential",
+        "decorrelated",
+        "budget",
+    ]
+    .contains(&name)
+}
+
+/// Retry policies of the specified classes, the ones without a policy retrying right away with the specified
+/// probabilities. The specs apply in order, so a class-specific policy overrides a preceding one for every class.
+pub fn policies(
+    specs: &[RetryPolicySpec],
+    classes: &[ClassSpec],
+    defaults: RetryProbabilities,
+) -> Result<Vec<Box<dyn RetryPolicy>>, String> {
+    let mut class_policies = vec![PolicySpec::Default(defaults); classes.len()];
+    for spec in specs.iter() {
+        match spec.class.as_ref() {
+            Some(name) => {
+                let class = classes
+                    .iter()
+                    .position(|c| &c.name == name)
+                    .ok_or_else(|| format!("Retry policy for unknown class '{}'", name))?;
+                class_policies[class] = spec.policy;
+            }
+            None => class_policies.fill(spec.policy),
+        }
+    }
+
+    Ok(class_policies.into_iter().map(PolicySpec::build).collect())
+}
+
+/// Whether the clients of every class give up on their failed requests, under the specified policies and default
+/// probabilities.
+pub fn never_retries(specs: &[RetryPolicySpec], defaults: RetryProbabilities) -> bool {
+    specs
+        .iter()
+        .all(|spec| matches!(spec.policy, PolicySpec::None))
+        && (specs.iter().any(|spec| spec.class.is_none())
+            || (defaults.rejected == 0.0 && defaults.timed_out == 0.0))
+}
+
+impl PolicySpec {
+    fn build(self) -> Box<dyn RetryPolicy> {
+        match self {
+            PolicySpec::None => Box::new(NoRetry),
+            PolicySpec::Immediate => Box::new(Immediate),
+            PolicySpec::Probability(probability) => Box::new(Probabilistic {
+                probabilities: RetryProbabilities {
+                    rejected: probability,
+                    timed_out: probability,
+                    abandoned: probability,
+                },
+                uniform: true,
+            }),
+            PolicySpec::Default(probabilities) => Box::new(Probabilistic {
+                probabilities,
+                uniform: false,
+            }),
+            PolicySpec::Fixed(delay) => Box::new(FixedDelay { delay }),
+            PolicySpec::Exponential(base, cap) => Box::new(Exponential { base, cap }),
+            PolicySpec::Decorrelated(base, cap) => Box::new(DecorrelatedJitter { base, cap }),
+            PolicySpec::Budget(ratio) => Box::new(RetryBudget {
+                ratio,
+                balance: 0.0,
+                retries: 0,
+                denied: 0,
+            }),
+        }
+    }
+}
+
+/// Never retries.
+struct NoRetry;
+
+impl RetryPolicy for NoRetry {
+    fn backoff(
+        &mut self,
+        _failed: &Attempt,
+        _failure: Failure,
+        _rng: &mut ChaCha12Rng,
+    ) -> Option<u64> {
+        None
+    }
+
+    fn describe(&self) -> String {
+        "no retries".to_string()
+    }
+}
+
+/// Retries every failed request right away.
+struct Immediate;
+
+impl RetryPolicy for Immediate {
+    fn backoff(
+        &mut self,
+        _failed: &Attempt,
+        _failure: Failure,
+        _rng: &mut ChaCha12Rng,
+    ) -> Option<u64> {
+        Some(0)
+    }
+
+    fn describe(&self) -> String {
+        "immediate retries".to_string()
+    }
+}
+
+/// Retries right away some of the failed requests, drawing whether to retry each with the probability for how it
+/// failed.
+struct Probabilistic {
+    probabilities: RetryProbabilities,
+    /// Whether the probability is the same for every failure, as given by `probability:P`.
+    uniform: bool,
+}
+
+impl RetryPolicy for Probabilistic {
+    fn backoff(
+        &mut self,
+        _failed: &Attempt,
+        failure: Failure,
+        rng: &mut ChaCha12Rng,
+    ) -> Option<u64> {
+        rng.gen_bool(self.probabilities.of(failure)).then_some(0)
+    }
+
+    fn describe(&self) -> String {
+        let RetryProbabilities {
+            rejected,
+            timed_out,
+            abandoned,
+        } = self.probabilities;
+        if self.uniform {
+            return format!(
+                "immediate retries of {:.0}% of the failures",
+                rejected * 100.0
+            );
+        }
+        format!(
+            "immediate retries of {:.0}% of the rejected, {:.0}% of the timed out and {:.0}% of the abandoned requests",
+            rejected * 100.0,
+            timed_out * 100.0,
+            abandoned * 100.0
+        )
+    }
+}
+
+/// Waits the same number of ticks before every retry.
+struct FixedDelay {
+    delay: u64,
+}
+
+impl RetryPolicy for FixedDelay {
+    fn backoff(
+        &mut self,
+        _failed: &Attempt,
+        _failure: Failure,
+        _rng: &mut ChaCha12Rng,
+    ) -> Option<u64> {
+        Some(self.delay)
+    }
+
+    fn describe(&self) -> String {
+        format!("retries after {} ticks", self.delay)
+    }
+}
+
+/// Doubles the wait with every retry, from the base up to the cap.
+struct Exponential {
+    base: u64,
+    cap: u64,
+}
+
+impl RetryPolicy for Exponential {
+    fn backoff(
+        &mut self,
+        failed: &Attempt,
+        _failure: Failure,
+        _rng: &mut ChaCha12Rng,
+    ) -> Option<u64> {
+        let doublings = (failed.number - 1).min(63);
+        Some(self.base.saturating_mul(1 << doublings).min(self.cap))
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "exponential backoff from {} to {} ticks",
+            self.base, self.cap
+        )
+    }
+}
+
+/// Waits a random time between the base and three times the previous wait, up to the cap. The randomness keeps
+/// the clients that failed together from retrying together.
+struct DecorrelatedJitter {
+    base: u64,
+    cap: u64,
+}
+
+impl RetryPolicy for DecorrelatedJitter {
+    fn backoff(
+        &mut self,
+        failed: &Attempt,
+        _failure: Failure,
+        rng: &mut ChaCha12Rng,
+    ) -> Option<u64> {
+        let previous = failed.backoff.max(self.base);
+        Some(
+            rng.gen_range(self.base..=previous.saturating_mul(3))
+                .min(self.cap),
+        )
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "decorrelated jitter backoff from {} to {} ticks",
+            self.base, self.cap
+        )
+    }
+}
+
+/// Retries right away, as long as the retries stay within a ratio of the requests: every request adds the ratio
+/// to the budget, up to a reserve of a few retries, and every retry takes one from it. Once the system fails
+/// most requests, the clients stop adding their retries to the load.
+#[derive(Serialize, Deserialize)]
+struct RetryBudget {
+    ratio: f64,
+    balance: f64,
+    retries: u64,
+    denied: u64,
+}
+
+impl RetryPolicy for RetryBudget {
+    fn backoff(
+        &mut self,
+        _failed: &Attempt,
+        _failure: Failure,
+        _rng: &mut ChaCha12Rng,
+    ) -> Option<u64> {
+        if self.balance < 1.0 {
+            self.denied += 1;
+            return None;
+        }
+        self.balance -= 1.0;
+        self.retries += 1;
+        Some(0)
+    }
+
+    fn requested(&mut self) {
+        self.balance = (self.balance + self.ratio).min(BUDGET_RESERVE.max(self.ratio));
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "retry budget of {:.0}% of the requests, {} retries, {} denied ({:.2}%)",
+            self.ratio * 100.0,
+            self.retries,
+            self.denied,
+            self.denied as f64 / (self.retries + self.denied).max(1) as f64 * 100.0
+        )
+    }
+
+    fn checkpoint(&self) -> Result<Value, String> {
+        checkpoint::save(self)
+    }
+
+    fn resume(&mut self, state: Value) -> Result<(), String> {
+        checkpoint::restore(self, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    const FAILURES: [Failure; 3] = [Failure::Rejected, Failure::TimedOut, Failure::Abandoned];
+
+    const NO_RETRIES: RetryProbabilities = RetryProbabilities {
+        rejected: 0.0,
+        timed_out: 0.0,
+        abandoned: 0.0,
+    };
+
+    fn policy(spec: &str) -> Box<dyn RetryPolicy> {
+        spec.parse::<RetryPolicySpec>().unwrap().policy.build()
+    }
+
+    /// Backoffs of the specified number of attempts of a request, each failing the specified way, up to the first
+    /// one the policy gives up on.
+    fn backoffs(policy: &mut dyn RetryPolicy, failure: Failure, attempts: u32) -> Vec<u64> {
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let mut attempt = Attempt::first();
+        let mut backoffs = Vec::new();
+        while attempt.number <= attempts {
+            match policy.backoff(&attempt, failure, &mut rng) {
+                Some(backoff) => {
+                    backoffs.push(backoff);
+                    attempt = attempt.retry(backoff);
+                }
+                None => break,
+            }
+        }
+        backoffs
+    }
+
+    fn classes(names: &[&str]) -> Vec<ClassSpec> {
+        names
+            .iter()
+            .map(|name| format!("{}:1", name).parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn invalid_policies_are_rejected() {
+        for spec in [
+            "",
+            "never",
+            "probability:1.5",
+            "fixed:-1",
+            "exponential:0:10",
+            "exponential:10:5",
+            "decorrelated:10",
+            "budget:0",
+            "api:fixed",
+        ] {
+            assert!(spec.parse::<RetryPolicySpec>().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn none_never_retries_and_immediate_always_does() {
+        for failure in FAILURES {
+            assert!(backoffs(policy("none").as_mut(), failure, 5).is_empty());
+            assert_eq!(
+                backoffs(policy("immediate").as_mut(), failure, 5),
+                [0, 0, 0, 0, 0]
+            );
+        }
+    }
+
+    #[test]
+    fn probability_retries_that_share_of_every_failure() {
+        assert_eq!(
+            backoffs(policy("probability:1").as_mut(), Failure::Abandoned, 3),
+            [0, 0, 0]
+        );
+        assert!(backoffs(policy("probability:0").as_mut(), Failure::Rejected, 3).is_empty());
+        let mut retry = policy("probability:0.3");
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let retried = (0..10000)
+            .filter(|_| {
+                retry
+                    .backoff(&Attempt::first(), Failure::TimedOut, &mut rng)
+                    .is_some()
+            })
+            .count();
+        assert!((2800..3200).contains(&retried), "{}", retried);
+    }
+
+    #[test]
+    fn default_probabilities_depend_on_the_failure() {
+        let mut retry = PolicySpec::Default(RetryProbabilities {
+            rejected: 1.0,
+            timed_out: 0.0,
+            abandoned: 1.0,
+        })
+        .build();
+        assert_eq!(backoffs(retry.as_mut(), Failure::Rejected, 2), [0, 0]);
+        assert!(backoffs(retry.as_mut(), Failure::TimedOut, 2).is_empty());
+        assert_eq!(backoffs(retry.as_mut(), Failure::Abandoned, 2), [0, 0]);
+        assert!(backoffs(retry.as_mut(), Failure::Balked, 2).is_empty());
+    }
+
+    #[test]
+    fn fixed_waits_the_same_every_time() {
+        assert_eq!(
+            backoffs(policy("fixed:25").as_mut(), Failure::TimedOut, 4),
+            [25, 25, 25, 25]
+        );
+    }
+
+    #[test]
+    fn exponential_doubles_up_to_the_cap() {
+        assert_eq!(
+            backoffs(policy("exponential:10:100").as_mut(), Failure::Rejected, 6),
+            [10, 20, 40, 80, 100, 100]
+        );
+        // Without overflowing, however many attempts there were.
+        let mut retry = policy("exponential:10:100");
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let mut attempt = Attempt::first();
+        attempt.number = 200;
+        assert_eq!(
+            retry.backoff(&attempt, Failure::Rejected, &mut rng),
+            Some(100)
+        );
+    }
+
+    #[test]
+    fn decorrelated_jitter_stays_between_the_base_and_three_times_the_previous_wait() {
+        let waits = backoffs(
+            policy("decorrelated:10:1000").as_mut(),
+            Failure::Rejected,
+            50,
+        );
+        assert_eq!(waits.len(), 50);
+        let mut previous = 10;
+        for &wait in waits.iter() {
+            assert!(
+                wait >= 10 && wait <= (previous * 3).min(1000),
+                "{:?}",
+                waits
+            );
+            previous = wait;
+        }
+        assert!(waits.iter().any(|&wait| wait != waits[0]));
+    }
+
+    #[test]
+    fn budget_retries_up_to_its_ratio_of_the_requests() {
+        let mut retry = policy("budget:0.25");
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let mut retry_all = |retry: &mut Box<dyn RetryPolicy>, failures: usize| {
+            (0..failures)
+                .filter(|_| {
+                    retry
+                        .backoff(&Attempt::first(), Failure::Rejected, &mut rng)
+                        .is_some()
+                })
+                .count()
+        };
+        // Nothing saved up before the first requests.
+        assert_eq!(retry_all(&mut retry, 5), 0);
+        for _ in 0..20 {
+            retry.requested();
+        }
+        assert_eq!(retry_all(&mut retry, 50), 5);
+        // Saving up no more than the reserve.
+        for _ in 0..1000 {
+            retry.requested();
+        }
+        assert_eq!(retry_all(&mut retry, 50), 10);
+        assert!(retry.describe().contains("15 retries, 90 denied"));
+    }
+
+    #[test]
+    fn class_policies_override_the_preceding_ones() {
+        let specs: Vec<RetryPolicySpec> = ["immediate", "batch:none", "fixed:7", "api:none"]
+            .iter()
+            .map(|spec| spec.parse().unwrap())
+            .collect();
+        let class_policies = policies(&specs, &classes(&["api", "batch"]), NO_RETRIES).unwrap();
+        let descriptions: Vec<String> = class_policies.iter().map(|p| p.describe()).collect();
+        assert_eq!(descriptions, ["no retries", "retries after 7 ticks"]);
+    }
+
+    #[test]
+    fn classes_without_a_policy_retry_with_the_default_probabilities() {
+        let specs = ["api:fixed:5".parse().unwrap()];
+        let defaults = RetryProbabilities {
+            rejected: 0.5,
+            timed_out: 0.5,
+            abandoned: 0.0,
+        };
+        let class_policies = policies(&specs, &classes(&["api", "batch"]), defaults).unwrap();
+        assert_eq!(class_policies[0].describe(), "retries after 5 ticks");
+        assert!(class_policies[1]
+            .describe()
+            .starts_with("immediate retries of 50%"));
+        assert!(policies(&specs, &classes(&["batch"]), defaults).is_err());
+    }
+
+    #[test]
+    fn never_retries_only_without_any_retrying_class() {
+        let specs = |specs: &[&str]| -> Vec<RetryPolicySpec> {
+            specs.iter().map(|spec| spec.parse().unwrap()).collect()
+        };
+        let defaults = RetryProbabilities {
+            rejected: 0.5,
+            timed_out: 0.5,
+            abandoned: 0.0,
+        };
+        assert!(never_retries(&specs(&[]), NO_RETRIES));
+        assert!(!never_retries(&specs(&[]), defaults));
+        assert!(never_retries(&specs(&["none"]), defaults));
+        assert!(never_retries(&specs(&["api:none", "none"]), defaults));
+        assert!(!never_retries(&specs(&["api:none"]), defaults));
+        assert!(!never_retries(&specs(&["none", "api:fixed:5"]), NO_RETRIES));
+    }
+}
@@ -0,0 +1,159 @@
+use std::f64::consts::PI;
+use std::fs;
+use std::str::FromStr;
+
+/// Distribution of the number of requests arriving during a tick, around the mean arrival rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArrivalDistribution {
+    /// Normal distribution with a standard deviation of a quarter of the rate. Fractions of requests carry over
+    /// to the next tick.
+    Normal,
+    /// Poisson distribution, i.e. independent arrivals, as assumed by most of the queueing theory.
+    Poisson,
+}
+
+/// A segment of the arrival rate schedule. Specified as one of:
+///
+/// * `step:T:R` - from tick T on, requests arrive at rate R.
+/// * `ramp:T1:T2:R1:R2` - between ticks T1 and T2, the rate changes linearly from R1 to R2, and stays at R2
+///   afterwards.
+/// * `sine:P:A` - the rate oscillates with period of P ticks and relative amplitude A (between 0 and 1),
+///   e.g. to model a diurnal traffic pattern.
+#[derive(Debug, Clone, Copy)]
+pub enum Segment {
+    Step {
+        at: u64,
+        rate: f64,
+    },
+    Ramp {
+        from: u64,
+        to: u64,
+        start_rate: f64,
+        end_rate: f64,
+    },
+    Sine {
+        period: u64,
+        amplitude: f64,
+    },
+}
+
+/// Time-varying arrival rate. Steps and ramps change the rate relative to the one specified on the command
+/// line, with the most recently started one in effect. Sine segments then modulate the resulting rate.
+pub struct ArrivalSchedule {
+    segments: Vec<Segment>,
+}
+
+impl FromStr for ArrivalDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(ArrivalDistribution::Normal),
+            "poisson" => Ok(ArrivalDistribution::Poisson),
+            _ => Err(format!(
+                "Unknown arrival distribution '{}', expected normal or poisson",
+                s
+            )),
+        }
+    }
+}
+
+impl FromStr for Segment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.trim().split(':').collect();
+        let invalid = || format!("Invalid arrival schedule segment '{}'", s);
+        let tick = |i: usize| parts[i].parse::<u64>().map_err(|_| invalid());
+        let number = |i: usize| parts[i].parse::<f64>().map_err(|_| invalid());
+        let segment = match (parts[0], parts.len()) {
+            ("step", 3) => Segment::Step {
+                at: tick(1)?,
+                rate: number(2)?,
+            },
+            ("ramp", 5) => Segment::Ramp {
+                from: tick(1)?,
+                to: tick(2)?,
+                start_rate: number(3)?,
+                end_rate: number(4)?,
+            },
+            ("sine", 3) => Segment::Sine {
+                period: tick(1)?,
+                amplitude: number(2)?,
+            },
+            _ => {
+                return Err(format!(
+                    "Expected arrival schedule segment as step:T:R, ramp:T1:T2:R1:R2 or sine:P:A, got '{}'",
+                    s
+                ))
+            }
+        };
+
+        match segment {
+            Segment::Step { rate, .. } if rate < 0.0 => Err(invalid()),
+            Segment::Ramp {
+                from,
+                to,
+                start_rate,
+                end_rate,
+            } if to <= from || start_rate < 0.0 || end_rate < 0.0 => Err(invalid()),
+            Segment::Sine { period, amplitude }
+                if period == 0 || !(0.0..=1.0).contains(&amplitude) =>
+            {
+                Err(invalid())
+            }
+            _ => Ok(segment),
+        }
+    }
+}
+
+impl ArrivalSchedule {
+    pub fn new(segments: Vec<Segment>) -> ArrivalSchedule {
+        ArrivalSchedule { segments }
+    }
+
+    /// Reads segments from a file, one per line. Empty lines and lines starting with # are ignored.
+    pub fn read_segments(path: &str) -> Result<Vec<Segment>, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read arrival schedule '{}': {}", path, e))?;
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::parse)
+            .collect()
+    }
+
+    /// Mean arrival rate at the specified tick.
+    pub fn rate_at(&self, base_rate: f64, tick: u64) -> f64 {
+        let mut rate = base_rate;
+        let mut latest_start = None;
+        for segment in self.segments.iter() {
+            let (start, segment_rate) = match *segment {
+                Segment::Step { at, rate } => (at, rate),
+                Segment::Ramp {
+                    from,
+                    to,
+                    start_rate,
+                    end_rate,
+                } => {
+                    let progress = (tick.saturating_sub(from) as f64 / (to - from) as f64).min(1.0);
+                    (from, start_rate + (end_rate - start_rate) * progress)
+                }
+                Segment::Sine { .. } => continue,
+            };
+            if start <= tick && latest_start.is_none_or(|latest| start >= latest) {
+                latest_start = Some(start);
+                rate = segment_rate;
+            }
+        }
+
+        for segment in self.segments.iter() {
+            if let Segment::Sine { period, amplitude } = *segment {
+                let phase = 2.0 * PI * (tick % period) as f64 / period as f64;
+                rate *= 1.0 + amplitude * phase.sin();
+            }
+        }
+        rate
+    }
+}
@@ -0,0 +1,573 @@
+use crate::config;
+use crate::eventdriven::Engine;
+use crate::events::{Event, Observer, SystemState};
+use crate::simulation::{self, Simulation, Summary};
+use crate::validation::{self, ConfigError};
+use crate::{utilization, Opt};
+use serde_json::{json, Map, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use structopt::StructOpt;
+
+/// Parameters a scenario sent to the server can't set: the ones running commands, reading or writing local
+/// files, or taking over the terminal or a port of the machine the server runs on.
+const REFUSED: &[&str] = &[
+    "generator",
+    "policy",
+    "alert_command",
+    "arrival_trace",
+    "arrival_schedule_file",
+    "latency_trace",
+    "timeseries",
+    "snapshots",
+    "event_log",
+    "chrome_trace",
+    "run_forever",
+    "html_report",
+    "plot",
+    "checkpoint_every",
+    "checkpoint_file",
+    "resume",
+    "metrics_address",
+    "tui",
+    "progress",
+    "speed",
+    "export",
+    "config",
+];
+
+/// Largest request body the server reads, in bytes. A scenario is a small JSON object, so anything over this is
+/// refused before it takes up any memory.
+const MAX_BODY_SIZE: usize = 1 << 20;
+
+/// Longest simulation a scenario can ask for, in ticks, so that a single request can't tie up a core for hours.
+const MAX_SIMULATION_TICKS: u64 = 10_000_000;
+
+/// Largest queue a scenario can ask for, in requests, summed over its pools and regions.
+const MAX_QUEUE_SIZE: u64 = 1_000_000;
+
+/// Most workers a scenario can ask for, summed over its pools and regions and counting the autoscaler's.
+const MAX_WORKERS: u64 = 10_000;
+
+/// Most keys a scenario can draw its requests from or cache, since their popularity is tabulated up front.
+const MAX_KEYS: u64 = 10_000_000;
+
+/// Most closed-loop clients a scenario can ask for.
+const MAX_CLIENTS: u64 = 100_000;
+
+/// Most connections the server handles at once, each of which may be running a simulation. Any more are
+/// answered with a 503 right away.
+const MAX_CONNECTIONS: usize = 64;
+
+/// Largest request line and headers the server reads, in bytes, all together.
+const MAX_HEADER_SIZE: u64 = 16 << 10;
+
+/// How long the server waits on a client sending its request, so that a client that stops sending doesn't
+/// hold on to its connection forever.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct ServeOpt {
+    /// Address to listen on.
+    #[structopt(long = "address", default_value = "127.0.0.1:8080")]
+    address: String,
+}
+
+/// Serves simulations over HTTP, for capacity planning dashboards to run scenarios without shelling out. A POST
+/// to `/simulate` with a scenario as a JSON object, with the same parameters as a scenario file, runs it and
+/// answers with its results as JSON. With `?stream`, the answer is a stream of server-sent events instead: the
+/// state of the system every `every` ticks (the reporting interval by default), then the results.
+pub fn run(serve: &ServeOpt) -> Result<(), ConfigError> {
+    let listener = TcpListener::bind(&serve.address)
+        .map_err(|e| ConfigError::io("listen on", &serve.address, e))?;
+    eprintln!("Serving simulations on http://{}/simulate", serve.address);
+    serve_connections(listener);
+    Ok(())
+}
+
+/// Answers the connections to the listener, until it fails.
+fn serve_connections(listener: TcpListener) {
+    let connections = Arc::new(AtomicUsize::new(0));
+    for mut stream in listener.incoming().flatten() {
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            let body = json!({ "error": "too many simulations running, try again later" });
+            let _ = write_response(
+                &mut stream,
+                "503 Service Unavailable",
+                "application/json",
+                &body.to_string(),
+            );
+            continue;
+        }
+        // Every simulation runs on a thread of its own, and a misbehaving client only loses its own response.
+        let connections = connections.clone();
+        thread::spawn(move || {
+            let _ = respond(stream);
+            connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+/// Answers a single HTTP request.
+fn respond(stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut head = (&mut reader).take(MAX_HEADER_SIZE);
+    let mut request_line = String::new();
+    head.read_line(&mut request_line)?;
+    let mut content_length = 0;
+    let mut complete = request_line.ends_with('\n');
+    while complete {
+        let mut header = String::new();
+        head.read_line(&mut header)?;
+        complete = header.ends_with('\n');
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    if !complete && head.limit() == 0 {
+        let body = json!({
+            "error": format!("the request line and headers can be at most {} bytes", MAX_HEADER_SIZE)
+        });
+        return write_response(
+            reader.get_mut(),
+            "431 Request Header Fields Too Large",
+            "application/json",
+            &body.to_string(),
+        );
+    }
+    if content_length > MAX_BODY_SIZE {
+        let body =
+            json!({ "error": format!("the scenario can be at most {} bytes", MAX_BODY_SIZE) });
+        return write_response(
+            reader.get_mut(),
+            "413 Payload Too Large",
+            "application/json",
+            &body.to_string(),
+        );
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let mut stream = reader.into_inner();
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path != "/simulate" {
+        return write_response(&mut stream, "404 Not Found", "text/plain", "");
+    }
+    if method != "POST" {
+        return write_response(&mut stream, "405 Method Not Allowed", "text/plain", "");
+    }
+    let opt = match parse_scenario(&body) {
+        Ok(opt) => opt,
+        Err(error) => {
+            let body = json!({ "error": error }).to_string();
+            return write_response(&mut stream, "400 Bad Request", "application/json", &body);
+        }
+    };
+
+    let mut stream_every = None;
+    for parameter in query.split('&') {
+        match parameter.split_once('=') {
+            None if parameter == "stream" => stream_every = Some(opt.report_every()),
+            Some(("every", every)) => match every.parse() {
+                Ok(every) if every > 0 => stream_every = Some(every),
+                _ => {
+                    let body = json!({ "error": "every has to be a positive number of ticks" });
+                    return write_response(
+                        &mut stream,
+                        "400 Bad Request",
+                        "application/json",
+                        &body.to_string(),
+                    );
+                }
+            },
+            _ => {}
+        }
+    }
+    if stream_every.is_some() && opt.engine == Engine::Event {
+        let body = json!({ "error": "the event engine can't stream the state of the system" });
+        return write_response(
+            &mut stream,
+            "400 Bad Request",
+            "application/json",
+            &body.to_string(),
+        );
+    }
+
+    match stream_every {
+        Some(every) => {
+            let mut simulation = match Simulation::new(&opt) {
+                Ok(simulation) => simulation,
+                Err(error) => {
+                    let body = json!({ "error": error.to_string() }).to_string();
+                    return write_response(
+                        &mut stream,
+                        "500 Internal Server Error",
+                        "application/json",
+                        &body,
+                    );
+                }
+            };
+            stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+            )?;
+            simulation.add_observer(Box::new(EventStream::new(stream.try_clone()?, every)));
+            let summary = simulation.run();
+            write!(
+                stream,
+                "event: results\ndata: {}\n\n",
+                results(&opt, &summary)
+            )
+        }
+        None => {
+            let summary = simulation::run(&opt);
+            write_response(
+                &mut stream,
+                "200 OK",
+                "application/json",
+                &results(&opt, &summary).to_string(),
+            )
+        }
+    }
+}
+
+/// Options of a scenario, as a JSON object of parameters, validated the same way as the command line.
+pub fn parse_scenario(body: &[u8]) -> Result<Opt, String> {
+    let values: Map<String, Value> =
+        serde_json::from_slice(body).map_err(|e| format!("Invalid scenario: {}", e))?;
+    if let Some(name) = refused_parameter(&values) {
+        return Err(format!("Parameter '{}' can't be set over HTTP", name));
+    }
+    let opt = scenario(values)?;
+    check_limits(&opt)?;
+    Ok(opt)
+}
+
+/// First parameter of a scenario that reads or writes files, runs processes, or takes over the terminal, if any.
+pub fn refused_parameter(values: &Map<String, Value>) -> Option<&str> {
+    values
+        .keys()
+        .map(String::as_str)
+        .find(|name| REFUSED.contains(name))
+}
+
+/// Options of a scenario whose parameters are named after the command line flags, validated the same way as
+/// the command line, without the limits of the server.
+pub fn scenario(values: Map<String, Value>) -> Result<Opt, String> {
+    let mut args = vec!["queueingsimulator".to_string()];
+    args.extend(config::to_args(values, &[], "the scenario").map_err(|e| e.to_string())?);
+    let opt = Opt::from_iter_safe(args)
+        .map_err(|e| e.message.lines().next().unwrap_or_default().to_string())?;
+    validation::validate(&opt).map_err(|e| e.to_string())?;
+    Ok(opt)
+}
+
+/// Refuses a scenario that would take more time or memory than a single request should be able to.
+fn check_limits(opt: &Opt) -> Result<(), String> {
+    let pools = opt.pools();
+    let workers = pools
+        .iter()
+        .map(|pool| pool.workers as u64)
+        .chain(opt.regions.iter().map(|region| region.workers as u64))
+        .sum::<u64>();
+    let queue_size = pools
+        .iter()
+        .map(|pool| pool.queue_size)
+        .chain(opt.regions.iter().map(|region| region.queue_size))
+        .fold(0, usize::saturating_add);
+    let limits = [
+        (
+            "simulation_time",
+            opt.simulation_ticks as u64,
+            MAX_SIMULATION_TICKS,
+        ),
+        (
+            "workers",
+            workers.max(opt.autoscale_max_workers as u64),
+            MAX_WORKERS,
+        ),
+        ("queue_size", queue_size as u64, MAX_QUEUE_SIZE),
+        (
+            "resource_queue_size",
+            opt.resource_queue_size as u64,
+            MAX_QUEUE_SIZE,
+        ),
+        ("keys", opt.keys, MAX_KEYS),
+        ("cache_size", opt.cache_size.unwrap_or(0) as u64, MAX_KEYS),
+        ("clients", opt.clients.unwrap_or(0) as u64, MAX_CLIENTS),
+    ];
+    match limits.iter().find(|(_, value, max)| value > max) {
+        Some((parameter, _, max)) => Err(format!("{} can be at most {} over HTTP", parameter, max)),
+        None => Ok(()),
+    }
+}
+
+/// Results of a run, as JSON.
+pub fn results(opt: &Opt, summary: &Summary) -> Value {
+    let stats = &summary.stats;
+    let ticks = summary.ticks.saturating_sub(opt.warmup_ticks).max(1);
+    let mut results = json!({
+        "requests": stats.total_requests,
+        "failure_rate": stats.failure_rate(),
+        "rejected": stats.rejected_requests,
+        "timed_out": stats.timed_out_requests,
+        "abandoned": stats.abandoned_requests,
+        "balked": stats.balked_requests,
+        "retried": stats.retried_rejections + stats.retried_timeouts + stats.retried_abandonments,
+        "throughput": stats.processed_requests as f64 / ticks as f64,
+        "goodput": stats.goodput() as f64 / ticks as f64,
+        "utilization": utilization::aggregate(&summary.worker_times),
+        "latency": {
+            "mean": stats.latency.mean(),
+            "p50": stats.latency.percentile(50.0),
+            "p90": stats.latency.percentile(90.0),
+            "p99": stats.latency.percentile(99.0),
+            "p999": stats.latency.percentile(99.9),
+            "max": stats.latency.max(),
+        },
+    });
+    if let Some(slo) = opt.slo() {
+        results["slo_attainment"] = json!(slo.attainment(stats));
+        results["slo_met"] = json!(slo.is_met(stats));
+    }
+    if let Some(cost) = opt.cost() {
+        let (workers, failures) = cost.split(stats);
+        results["cost"] = json!(workers + failures);
+    }
+    results
+}
+
+fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Streams the state of the system to the client as server-sent events, one every `every` ticks, with the
+/// requests that arrived, were rejected and completed (in time or not) since the previous one.
+struct EventStream {
+    writer: TcpStream,
+    every: u64,
+    arrived: u64,
+    rejected: u64,
+    completed: u64,
+    timed_out: u64,
+    /// Whether the client went away, after which the simulation carries on without it.
+    closed: bool,
+}
+
+impl EventStream {
+    fn new(writer: TcpStream, every: u64) -> EventStream {
+        EventStream {
+            writer,
+            every,
+            arrived: 0,
+            rejected: 0,
+            completed: 0,
+            timed_out: 0,
+            closed: false,
+        }
+    }
+}
+
+impl Observer for EventStream {
+    fn on_event(&mut self, _tick: u64, event: &Event) {
+        match *event {
+            Event::Arrived { .. } => self.arrived += 1,
+            Event::Rejected { .. } => self.rejected += 1,
+            Event::Completed { timed_out, .. } => {
+                self.completed += 1;
+                self.timed_out += timed_out as u64;
+            }
+            _ => {}
+        }
+    }
+
+    fn on_tick_end(&mut self, tick: u64, state: &SystemState) {
+        if !(tick + 1).is_multiple_of(self.every) || self.closed {
+            return;
+        }
+        let data = json!({
+            "tick": tick,
+            "queue_len": state.queue_len,
+            "busy_workers": state.busy_workers,
+            "arrived": self.arrived,
+            "rejected": self.rejected,
+            "completed": self.completed,
+            "timed_out": self.timed_out,
+        });
+        self.closed = write!(self.writer, "data: {}\n\n", data).is_err();
+        self.arrived = 0;
+        self.rejected = 0;
+        self.completed = 0;
+        self.timed_out = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Starts a server on a free port, returning its address.
+    fn start() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || serve_connections(listener));
+        address
+    }
+
+    /// Sends the raw request to the server, returning its status line and body.
+    fn send(address: &str, request: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    fn post(address: &str, target: &str, scenario: &str) -> (String, String) {
+        send(
+            address,
+            &format!(
+                "POST {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                target,
+                scenario.len(),
+                scenario
+            ),
+        )
+    }
+
+    #[test]
+    fn simulates_the_scenario() {
+        let address = start();
+        let scenario = r#"{"arrival_rate": 0.1, "simulation_time": 20000, "seed": 3}"#;
+        let (status, body) = post(&address, "/simulate", scenario);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let results: Value = serde_json::from_str(&body).unwrap();
+
+        // The same results as running the scenario directly.
+        let opt = parse_scenario(scenario.as_bytes()).unwrap();
+        assert_eq!(results, results_of(&opt));
+        assert!(results["requests"].as_u64().unwrap() > 1000);
+    }
+
+    fn results_of(opt: &Opt) -> Value {
+        // Round trip through the text, as the numbers of the response went through it too.
+        serde_json::from_str(&results(opt, &simulation::run(opt)).to_string()).unwrap()
+    }
+
+    #[test]
+    fn streams_the_state_then_the_results() {
+        let address = start();
+        let scenario = r#"{"arrival_rate": 0.1, "simulation_time": 1000, "seed": 3}"#;
+        let (status, body) = post(&address, "/simulate?stream&every=100", scenario);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let events: Vec<&str> = body.split("\n\n").filter(|e| !e.is_empty()).collect();
+        assert_eq!(events.len(), 11);
+        assert!(events[0].starts_with("data: {"));
+        assert!(events[10].starts_with("event: results\ndata: {"));
+    }
+
+    #[test]
+    fn refuses_bad_requests() {
+        let address = start();
+        let (status, _) = post(&address, "/other", "{}");
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        let (status, _) = send(&address, "GET /simulate HTTP/1.1\r\n\r\n");
+        assert_eq!(status, "HTTP/1.1 405 Method Not Allowed");
+        let (status, body) = post(
+            &address,
+            "/simulate",
+            r#"{"arrival_rate": 0.1, "policy": "sh"}"#,
+        );
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        assert!(body.contains("Parameter 'policy' can't be set over HTTP"));
+
+        // Exactly as many bytes as the server reads, without the end of the headers in sight.
+        let request = format!(
+            "POST /simulate HTTP/1.1\r\nX-Padding: {}",
+            "a".repeat(MAX_HEADER_SIZE as usize)
+        );
+        let (status, _) = send(&address, &request[..MAX_HEADER_SIZE as usize]);
+        assert_eq!(status, "HTTP/1.1 431 Request Header Fields Too Large");
+        let (status, _) = send(
+            &address,
+            &format!(
+                "POST /simulate HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                MAX_BODY_SIZE + 1
+            ),
+        );
+        assert_eq!(status, "HTTP/1.1 413 Payload Too Large");
+    }
+
+    #[test]
+    fn limits_the_connections() {
+        let address = start();
+        let idle: Vec<TcpStream> = (0..MAX_CONNECTIONS)
+            .map(|_| TcpStream::connect(&address).unwrap())
+            .collect();
+        // Answered before the server reads anything.
+        let (status, _) = send(&address, "");
+        assert_eq!(status, "HTTP/1.1 503 Service Unavailable");
+
+        // The server takes new connections again once the others go away.
+        drop(idle);
+        let scenario = r#"{"arrival_rate": 0.1, "simulation_time": 100}"#;
+        let status = (0..100)
+            .map(|_| {
+                thread::sleep(Duration::from_millis(10));
+                post(&address, "/simulate", scenario).0
+            })
+            .find(|status| status != "HTTP/1.1 503 Service Unavailable");
+        assert_eq!(status.as_deref(), Some("HTTP/1.1 200 OK"));
+    }
+
+    #[test]
+    fn limits_the_size_of_the_scenario() {
+        let error = |scenario: &str| parse_scenario(scenario.as_bytes()).unwrap_err();
+        assert_eq!(
+            error(r#"{"arrival_rate": 0.1, "queue_size": 100000000}"#),
+            "queue_size can be at most 1000000 over HTTP"
+        );
+        assert_eq!(
+            error(r#"{"arrival_rate": 0.1, "region": ["a:6000:10:0.5", "b:6000:10:0.5"]}"#),
+            "workers can be at most 10000 over HTTP"
+        );
+        assert_eq!(
+            error(r#"{"arrival_rate": 0.1, "keys": 100000000000}"#),
+            "keys can be at most 10000000 over HTTP"
+        );
+        assert_eq!(
+            error(r#"{"clients": 1000000}"#),
+            "clients can be at most 100000 over HTTP"
+        );
+        assert_eq!(
+            error(r#"{"arrival_rate": 0.1, "simulation_time": 20000000}"#),
+            "simulation_time can be at most 10000000 over HTTP"
+        );
+        assert!(parse_scenario(br#"{"arrival_rate": 0.1, "workers": 10000}"#).is_ok());
+    }
+}
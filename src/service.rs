@@ -0,0 +1,259 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use rand_distr::{Exp, Normal, Pareto, Weibull};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::str::FromStr;
+
+/// Shape of the service time distribution, when it isn't sampled from a latency trace.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LatencyDistribution {
+    Normal,
+    Exponential,
+    /// Pareto with the specified shape, which has to be greater than 1 for the mean to exist. The variance is
+    /// infinite up to a shape of 2.
+    Pareto(f64),
+    /// Weibull with the specified shape: heavy-tailed below 1, exponential at 1, and increasingly concentrated
+    /// around the mean above it.
+    Weibull(f64),
+}
+
+/// One mode of a mixture of service times: the share of the requests, relative to the other modes, and their
+/// mean service time, e.g. 0.95:20 for 95% of the requests taking 20 ticks on average.
+#[derive(Debug, Clone, Copy)]
+pub struct LatencyMode {
+    pub weight: f64,
+    pub mean: f64,
+}
+
+impl FromStr for LatencyMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let (weight, mean) = match parts.as_slice() {
+            [weight, mean] => (weight.parse::<f64>(), mean.parse::<f64>()),
+            _ => return Err(format!("Expected latency mode as weight:mean, got '{}'", s)),
+        };
+        match (weight, mean) {
+            (Ok(weight), Ok(mean)) if weight > 0.0 && mean > 0.0 => {
+                Ok(LatencyMode { weight, mean })
+            }
+            _ => Err(format!(
+                "Invalid latency mode '{}', weight and mean have to be greater than 0",
+                s
+            )),
+        }
+    }
+}
+
+/// Distribution of the time it takes a worker to process a request, in ticks.
+#[derive(Serialize, Deserialize)]
+pub enum ServiceTime {
+    /// Normal distribution with the configured mean and a standard deviation of a quarter of it. Latency
+    /// distribution isn't really normal (for example, it can't have negative values), so it is only a rough
+    /// approximation.
+    Normal(Normal<f64>),
+    /// Exponential distribution with the configured mean, the service time of the textbook M/M/c queue.
+    Exponential { distribution: Exp<f64>, mean: f64 },
+    /// Pareto distribution with the configured mean: most requests fast, and a heavy tail of very slow ones,
+    /// which make the choice of queue discipline matter far more than the normal distribution does.
+    Pareto {
+        distribution: Pareto<f64>,
+        scale: f64,
+        shape: f64,
+    },
+    /// Weibull distribution with the configured mean.
+    Weibull {
+        distribution: Weibull<f64>,
+        scale: f64,
+        shape: f64,
+    },
+    /// Empirical distribution of observed latencies, each of them equally likely. Preserves the heavy tail of
+    /// real-world latencies, which the normal distribution can't represent.
+    Empirical(Vec<f64>),
+    /// Mixture of distributions, each request taking the service time of a mode picked according to the
+    /// weights. Real services are rarely unimodal (cache hits and misses, fast and slow paths), and it's the
+    /// slow mode that clogs the queue.
+    Mixture {
+        weights: WeightedIndex<f64>,
+        modes: Vec<(f64, ServiceTime)>,
+    },
+}
+
+impl FromStr for LatencyDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, shape) = match s.split_once(':') {
+            Some((name, shape)) => match shape.parse::<f64>() {
+                Ok(shape) => (name, Some(shape)),
+                Err(_) => return Err(format!("Invalid shape of latency distribution '{}'", s)),
+            },
+            None => (s, None),
+        };
+        match (name, shape) {
+            ("normal", None) => Ok(LatencyDistribution::Normal),
+            ("exponential", None) => Ok(LatencyDistribution::Exponential),
+            ("pareto", Some(shape)) if shape > 1.0 => Ok(LatencyDistribution::Pareto(shape)),
+            ("weibull", Some(shape)) if shape > 0.0 => Ok(LatencyDistribution::Weibull(shape)),
+            ("pareto", _) => Err("Expected pareto:SHAPE, with SHAPE greater than 1".to_string()),
+            ("weibull", _) => Err("Expected weibull:SHAPE, with SHAPE greater than 0".to_string()),
+            _ => Err(format!(
+                "Unknown latency distribution '{}', expected normal, exponential, pareto:SHAPE or weibull:SHAPE",
+                s
+            )),
+        }
+    }
+}
+
+impl ServiceTime {
+    pub fn new(distribution: LatencyDistribution, mean: f64) -> ServiceTime {
+        match distribution {
+            LatencyDistribution::Normal => {
+                ServiceTime::Normal(Normal::new(mean, mean / 4.0).unwrap())
+            }
+            LatencyDistribution::Exponential => ServiceTime::Exponential {
+                distribution: Exp::new(1.0 / mean).unwrap(),
+                mean,
+            },
+            LatencyDistribution::Pareto(shape) => {
+                let scale = mean * (shape - 1.0) / shape;
+                ServiceTime::Pareto {
+                    distribution: Pareto::new(scale, shape).unwrap(),
+                    scale,
+                    shape,
+                }
+            }
+            LatencyDistribution::Weibull(shape) => {
+                let scale = mean / gamma(1.0 + 1.0 / shape);
+                ServiceTime::Weibull {
+                    distribution: Weibull::new(scale, shape).unwrap(),
+                    scale,
+                    shape,
+                }
+            }
+        }
+    }
+
+    /// Mixture of the specified modes, each with the specified distribution around its own mean.
+    pub fn mixture(distribution: LatencyDistribution, modes: &[LatencyMode]) -> ServiceTime {
+        let total: f64 = modes.iter().map(|m| m.weight).sum();
+        ServiceTime::Mixture {
+            weights: WeightedIndex::new(modes.iter().map(|m| m.weight)).unwrap(),
+            modes: modes
+                .iter()
+                .map(|m| (m.weight / total, ServiceTime::new(distribution, m.mean)))
+                .collect(),
+        }
+    }
+
+    /// Reads observed latencies from a file, one per line, in ticks. Empty lines and lines starting with # are
+    /// ignored.
+    pub fn read_trace(path: &str) -> Result<ServiceTime, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read latency trace '{}': {}", path, e))?;
+        let latencies = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| match line.parse::<f64>() {
+                Ok(latency) if latency >= 0.0 => Ok(latency),
+                _ => Err(format!("Invalid latency '{}' in '{}'", line, path)),
+            })
+            .collect::<Result<Vec<f64>, String>>()?;
+        if latencies.is_empty() {
+            return Err(format!("Latency trace '{}' is empty", path));
+        }
+
+        Ok(ServiceTime::Empirical(latencies))
+    }
+
+    pub fn mean(&self) -> f64 {
+        match self {
+            ServiceTime::Normal(normal) => normal.mean(),
+            ServiceTime::Exponential { mean, .. } => *mean,
+            ServiceTime::Pareto { scale, shape, .. } => scale * shape / (shape - 1.0),
+            ServiceTime::Weibull { scale, shape, .. } => scale * gamma(1.0 + 1.0 / shape),
+            ServiceTime::Empirical(latencies) => {
+                latencies.iter().sum::<f64>() / latencies.len() as f64
+            }
+            ServiceTime::Mixture { modes, .. } => modes.iter().map(|(w, m)| w * m.mean()).sum(),
+        }
+    }
+
+    /// Standard deviation, infinite for the Pareto distribution with a shape of 2 or less.
+    pub fn std_dev(&self) -> f64 {
+        match self {
+            ServiceTime::Normal(normal) => normal.std_dev(),
+            ServiceTime::Exponential { mean, .. } => *mean,
+            ServiceTime::Pareto { scale, shape, .. } if *shape > 2.0 => {
+                scale / (shape - 1.0) * (shape / (shape - 2.0)).sqrt()
+            }
+            ServiceTime::Pareto { .. } => f64::INFINITY,
+            ServiceTime::Weibull { scale, shape, .. } => {
+                let g1 = gamma(1.0 + 1.0 / shape);
+                scale * (gamma(1.0 + 2.0 / shape) - g1 * g1).max(0.0).sqrt()
+            }
+            ServiceTime::Empirical(latencies) => {
+                let mean = self.mean();
+                let variance = latencies.iter().map(|l| (l - mean).powi(2)).sum::<f64>()
+                    / latencies.len() as f64;
+                variance.sqrt()
+            }
+            ServiceTime::Mixture { modes, .. } => {
+                // The law of total variance: the variance within the modes plus that of their means.
+                let mean = self.mean();
+                let second_moment: f64 = modes
+                    .iter()
+                    .map(|(w, m)| w * (m.std_dev().powi(2) + m.mean().powi(2)))
+                    .sum();
+                (second_moment - mean * mean).max(0.0).sqrt()
+            }
+        }
+    }
+}
+
+impl Distribution<f64> for ServiceTime {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        match self {
+            // Normal distribution can produce negative results.
+            ServiceTime::Normal(normal) => 0.0_f64.max(normal.sample(rng)),
+            ServiceTime::Exponential { distribution, .. } => distribution.sample(rng),
+            ServiceTime::Pareto { distribution, .. } => distribution.sample(rng),
+            ServiceTime::Weibull { distribution, .. } => distribution.sample(rng),
+            ServiceTime::Empirical(latencies) => latencies[rng.gen_range(0..latencies.len())],
+            ServiceTime::Mixture { weights, modes } => modes[weights.sample(rng)].1.sample(rng),
+        }
+    }
+}
+
+/// Gamma function, using the Lanczos approximation, accurate to about 15 significant digits for positive
+/// arguments.
+fn gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula.
+        return std::f64::consts::PI / ((std::f64::consts::PI * x).sin() * gamma(1.0 - x));
+    }
+    let x = x - 1.0;
+    let t = x + G + 0.5;
+    let sum = COEFFICIENTS[0]
+        + COEFFICIENTS[1..]
+            .iter()
+            .enumerate()
+            .map(|(i, c)| c / (x + i as f64 + 1.0))
+            .sum::<f64>();
+    (2.0 * std::f64::consts::PI).sqrt() * t.powf(x + 0.5) * (-t).exp() * sum
+}
@@ -0,0 +1,97 @@
+use crate::class::ClassSpec;
+use crate::simulation::ClassStats;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Queue fill at which the requests of a class start getting shed, specified on the command line as
+/// `class:fraction`, e.g. `batch:0.5` to shed the batch requests once the queue is half full.
+#[derive(Debug, Clone)]
+pub struct ShedThresholdSpec {
+    class: String,
+    threshold: f64,
+}
+
+/// Priority-aware load shedding: the requests of the less critical classes get shed while the queue still has
+/// room, keeping it for the more critical ones. The classes without a threshold only get rejected once the
+/// queue is full.
+#[derive(Serialize, Deserialize)]
+pub struct Shedding {
+    /// Fraction of the queue size each class gets shed at.
+    thresholds: Vec<f64>,
+    shed: Vec<u64>,
+}
+
+impl FromStr for ShedThresholdSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (class, threshold) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected shed threshold as class:fraction, got '{}'", s))?;
+        let threshold: f64 = threshold.parse().map_err(|_| {
+            format!(
+                "Invalid shed threshold '{}' for class '{}'",
+                threshold, class
+            )
+        })?;
+        if threshold <= 0.0 || threshold > 1.0 {
+            return Err(format!(
+                "Shed threshold '{}' needs a fraction of the queue greater than 0 and at most 1",
+                s
+            ));
+        }
+
+        Ok(ShedThresholdSpec {
+            class: class.to_string(),
+            threshold,
+        })
+    }
+}
+
+impl Shedding {
+    pub fn new(specs: &[ShedThresholdSpec], classes: &[ClassSpec]) -> Result<Shedding, String> {
+        let mut thresholds = vec![1.0; classes.len()];
+        for spec in specs.iter() {
+            let class = classes
+                .iter()
+                .position(|c| c.name == spec.class)
+                .ok_or_else(|| format!("Shed threshold for unknown class '{}'", spec.class))?;
+            thresholds[class] = spec.threshold;
+        }
+
+        Ok(Shedding {
+            shed: vec![0; classes.len()],
+            thresholds,
+        })
+    }
+
+    /// Checks whether a request of the class has to wait in a queue of the specified length and size that is
+    /// filled over the threshold of the class. Such requests are counted as shed if `measured`.
+    pub fn sheds(
+        &mut self,
+        class: usize,
+        queue_len: usize,
+        queue_size: usize,
+        measured: bool,
+    ) -> bool {
+        let threshold = self.thresholds[class];
+        let shed = threshold < 1.0 && queue_len as f64 >= threshold * queue_size as f64;
+        if shed && measured {
+            self.shed[class] += 1;
+        }
+        shed
+    }
+
+    pub fn print_summary(&self, classes: &[ClassStats]) {
+        for (class, stats) in classes.iter().enumerate() {
+            println!(
+                "Class {}: shed at {:.0}% of the queue, {} requests shed ({:.2}%), failure rate: {:.2}%",
+                stats.name,
+                self.thresholds[class] * 100.0,
+                self.shed[class],
+                self.shed[class] as f64 / stats.total_requests.max(1) as f64 * 100.0,
+                stats.failed_requests as f64 / stats.total_requests.max(1) as f64 * 100.0
+            );
+        }
+    }
+}
@@ -0,0 +1,2506 @@
+use crate::alert::Alerts;
+use crate::autoscaler::Autoscaler;
+use crate::balancer::Balancer;
+use crate::cache::Cache;
+use crate::chaos::{Chaos, ChaosAction, Fault};
+use crate::checkpoint::Checkpoint;
+use crate::chrometrace::ChromeTrace;
+use crate::class::ClassSpec;
+use crate::clientpool::ClientPool;
+use crate::closedloop::ClosedLoop;
+use crate::coalescing::Coalescer;
+use crate::collapse::CollapseDetector;
+use crate::contention::QueueDepthPenalty;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::dashboard::Dashboard;
+use crate::database::Database;
+use crate::discipline::{self, DisciplineSwitches};
+use crate::erlang::ErlangC;
+use crate::eventdriven::{Engine, EventDrivenSimulation};
+use crate::eventlog::EventLog;
+use crate::events::{Event, Observer, SystemState};
+use crate::explain::Narrator;
+use crate::fanout::{FanOut, Outcome};
+use crate::generator::Generator;
+use crate::histogram::Histogram;
+use crate::keys::Keys;
+use crate::littleslaw::LittlesLaw;
+use crate::metadata;
+use crate::metrics::MetricsServer;
+use crate::mitigation::{Action, Mitigations};
+use crate::network::Network;
+use crate::output::Output;
+use crate::pacing::Pacer;
+use crate::policy::Policy;
+use crate::pool::{self, Pool, PoolCheckpoint};
+use crate::progress::Progress;
+use crate::queue::{self, Discipline};
+use crate::queuelength::QueueLengths;
+use crate::quota::Quotas;
+use crate::region::{Regions, Route};
+use crate::report::Timeline;
+use crate::request::Request;
+use crate::resource::Resource;
+use crate::retrypolicy::{self, Attempt, RetryPolicy};
+use crate::schedule::{ArrivalDistribution, ArrivalSchedule};
+use crate::service::ServiceTime;
+use crate::shedding::Shedding;
+use crate::size::RequestSizes;
+use crate::snapshot::{Snapshots, SnapshotsCheckpoint};
+use crate::spike::Spike;
+use crate::stall::Stalls;
+use crate::storm::RetryStormDetector;
+use crate::streams::RngStreams;
+use crate::telemetry::Telemetry;
+use crate::timeouts::Timeouts;
+use crate::timeseries::{TimeSeries, TimeSeriesCheckpoint};
+use crate::trace::ArrivalTrace;
+use crate::utilization::{self, WorkerTime};
+use crate::validation::ConfigError;
+use crate::windows::{self, WindowedMetrics};
+use crate::Opt;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::{thread_rng, Rng};
+use rand_distr::{Normal, Poisson};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Runs the simulation with the configured engine. Exits if it can't create its output files or start the
+/// processes or servers it calls for.
+pub fn run(opt: &Opt) -> Summary {
+    match opt.engine {
+        Engine::Tick => Simulation::new(opt)
+            .unwrap_or_else(|error| error.exit())
+            .run(),
+        Engine::Event => EventDrivenSimulation::new(opt).run(),
+    }
+}
+
+/// State of a single simulation run. The simulation uses a virtual clock, advancing it one tick at
+/// a time.
+pub struct Simulation<'a> {
+    opt: &'a Opt,
+    pools: Vec<Pool>,
+    class_distribution: Option<WeightedIndex<f64>>,
+    /// Index of the pool serving each class.
+    class_pools: Vec<usize>,
+    /// Load balancer picking the pool of every request instead, when the workers are split into queues.
+    balancer: Option<Balancer>,
+    /// Regions the clients send their requests to instead, each with a pool of its own.
+    regions: Option<Regions>,
+    keys: Option<Keys>,
+    cache: Option<Cache>,
+    coalescer: Option<Coalescer>,
+    quotas: Option<Quotas>,
+    shedding: Option<Shedding>,
+    /// How the clients of every class retry.
+    retry_policies: Vec<Box<dyn RetryPolicy>>,
+    fan_out: Option<FanOut>,
+    database: Option<Database>,
+    resource: Option<Resource>,
+    policy: Option<Policy>,
+    mitigations: Option<Mitigations>,
+    chaos: Option<Chaos>,
+    /// Whether a mitigation made the clients stop retrying.
+    retries_cut: bool,
+    /// Whether a mitigation switched the queues to LIFO.
+    mitigated_lifo: bool,
+    telemetry: Option<Telemetry>,
+    timeline: Option<Timeline>,
+    time_series: Option<TimeSeries>,
+    snapshots: Option<Snapshots>,
+    progress: Option<Progress>,
+    windows: Option<WindowedMetrics>,
+    /// Raised by an interrupt of a run that never ends on its own, to stop it.
+    interrupted: Option<Arc<AtomicBool>>,
+    /// The terminal dashboard, which WebAssembly has no terminal for.
+    #[cfg(not(target_arch = "wasm32"))]
+    dashboard: Option<Dashboard>,
+    metrics_server: Option<MetricsServer>,
+    pacer: Option<Pacer>,
+    observers: Vec<Box<dyn Observer>>,
+    /// Events that happened during the current tick, not yet handed to the observers.
+    events: Vec<Event>,
+    arrival_schedule: Option<ArrivalSchedule>,
+    arrival_trace: Option<ArrivalTrace>,
+    generator: Option<Generator>,
+    closed_loop: Option<ClosedLoop>,
+    request_sizes: Option<RequestSizes>,
+    timeouts: Option<Timeouts>,
+    stalls: Option<Stalls>,
+    network: Option<Network>,
+    client_pool: Option<ClientPool>,
+    /// Distribution of the arrivals per tick, relative to the mean arrival rate.
+    arrival_distribution: Normal<f64>,
+    service_time: ServiceTime,
+    /// Distribution of the time the clients wait in the queue before abandoning their requests.
+    patience: Option<ServiceTime>,
+    spikes: Vec<Spike>,
+    spike_class: Option<usize>,
+    /// Latency multiplier of the spikes active during the current tick.
+    spike_multiplier: f64,
+    incoming_requests: f64,
+    retries: Retries,
+    current_tick: u64,
+    /// Sources of all randomness of the run, so that runs with the same seed are identical.
+    rng: RngStreams,
+    stats: Stats,
+    /// Checkpoint a resumed run picks up from, until it starts.
+    resumed: Option<Checkpoint>,
+}
+
+/// State of a simulation that changes as it runs, for checkpoints: everything but the settings, which a resumed
+/// run has the same of, and the outputs that only show how the run is going, like the progress and the TUI.
+#[derive(Serialize, Deserialize)]
+pub struct SimulationState {
+    rng: RngStreams,
+    incoming_requests: f64,
+    retries: Retries,
+    pools: Vec<PoolCheckpoint>,
+    stats: Stats,
+    balancer: Option<Balancer>,
+    regions: Option<Regions>,
+    cache: Option<Cache>,
+    coalescer: Option<Coalescer>,
+    quotas: Option<Quotas>,
+    shedding: Option<Shedding>,
+    /// What the retry policy of every class carries over from one request to the next.
+    retry_policies: Vec<Value>,
+    fan_out: Option<FanOut>,
+    database: Option<Database>,
+    resource: Option<Resource>,
+    mitigations: Option<Mitigations>,
+    chaos: Option<Chaos>,
+    retries_cut: bool,
+    mitigated_lifo: bool,
+    telemetry: Option<Telemetry>,
+    timeline: Option<Timeline>,
+    time_series: Option<TimeSeriesCheckpoint>,
+    snapshots: Option<SnapshotsCheckpoint>,
+    windows: Option<WindowedMetrics>,
+    /// What every observer gathered so far.
+    observers: Vec<Value>,
+    /// Index of the next arrival of the trace to replay.
+    arrival_trace: Option<usize>,
+    closed_loop: Option<ClosedLoop>,
+    request_sizes: Option<RequestSizes>,
+    timeouts: Option<Timeouts>,
+    stalls: Option<Stalls>,
+    network: Option<Network>,
+    client_pool: Option<ClientPool>,
+}
+
+/// State of the system at the end of the current tick. Requests waiting for a slot of the second resource count
+/// as queued.
+fn system_state(pools: &[Pool], resource: Option<&Resource>) -> SystemState {
+    SystemState {
+        queue_len: pools.iter().map(Pool::queue_len).sum::<usize>()
+            + resource.map_or(0, Resource::queue_len),
+        busy_workers: pools.iter().map(Pool::busy_workers).sum(),
+    }
+}
+
+/// How a request failed, as seen by the client.
+#[derive(Debug, Clone, Copy)]
+pub enum Failure {
+    /// The server refused the request right away. Cheap for the client, like a refused connection.
+    Rejected,
+    /// The client gave up waiting for the response. Expensive, since the client waited the full timeout.
+    TimedOut,
+    /// The client ran out of patience while the request was waiting in the queue, and withdrew it. Unlike a
+    /// timed out request, the server never spends any work on it.
+    Abandoned,
+    /// The client saw a long queue and didn't send the request at all. Never retried, since the client went
+    /// elsewhere.
+    Balked,
+}
+
+/// Statistics collected during a simulation run.
+#[derive(Serialize, Deserialize)]
+pub struct Stats {
+    pub total_requests: u64,
+    pub failed_requests: u64,
+    /// Failures split by kind, and how many of each were retried.
+    pub rejected_requests: u64,
+    pub timed_out_requests: u64,
+    pub retried_rejections: u64,
+    pub retried_timeouts: u64,
+    pub abandoned_requests: u64,
+    pub retried_abandonments: u64,
+    pub balked_requests: u64,
+    /// Requests the workers finished processing, including the ones whose client had already given up.
+    pub processed_requests: u64,
+    /// Ticks the workers spent processing requests that timed out before they completed.
+    pub wasted_ticks: f64,
+    /// Ticks the workers were available for, summed over the workers.
+    pub worker_ticks: f64,
+    /// Latency of the requests that completed successfully.
+    pub latency: Histogram,
+    /// Time the requests (or sub-requests) that were processed in time spent waiting in the queue, and being
+    /// processed. Together, they make up the latency.
+    pub queue_wait: Histogram,
+    pub service_time: Histogram,
+    /// Time the requests shed by the late shedding spent waiting before they were shed.
+    pub shed_wait: Histogram,
+    /// Requests failed by a worker without working on them, since they waited over the queue-wait budget.
+    pub queue_wait_expired: u64,
+    /// Requests dropped by a worker without working on them, since their clients already gave up on them.
+    pub culled_requests: u64,
+    /// Requests that completed successfully with a degraded response, served by a browned out pool.
+    pub degraded_responses: u64,
+    /// Requests answered successfully without a worker of their own: by the cache, or with the response to the
+    /// in-flight request they were coalesced with.
+    pub free_responses: u64,
+    /// Retries of rejected requests that waited out the retry-after hint, instead of being sent right away.
+    pub deferred_retries: u64,
+    /// Attempts of the requests by their number, the original request first and then its retries.
+    pub attempts: Vec<AttemptStats>,
+    pub classes: Vec<ClassStats>,
+}
+
+/// Retries the clients decided on, the attempts of the retried requests still in flight, and the id of the next
+/// request, which the attempts are keyed by.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Retries {
+    /// Classes of the failed requests clients decided to retry right away, with the attempt the retry makes.
+    pending: Vec<(usize, Attempt)>,
+    /// Retries the clients back off from, or hold back to honor the retry-after hint, by the tick they are sent at.
+    delayed: BTreeMap<u64, Vec<(usize, Attempt)>>,
+    /// Attempts of the requests in flight that are retries, by their id.
+    attempts: HashMap<u64, Attempt>,
+    /// Id of the next request sent, for the event log and the attempts. A resumed run carries on from it, so that
+    /// its requests don't take over the attempts of the ones in flight.
+    next_request_id: u64,
+}
+
+/// Statistics of the attempts with the same number, e.g. of the first retries of the requests.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct AttemptStats {
+    pub total: u64,
+    pub failed: u64,
+}
+
+/// Statistics of a single request class.
+#[derive(Serialize, Deserialize)]
+pub struct ClassStats {
+    pub name: String,
+    pub total_requests: u64,
+    pub failed_requests: u64,
+    /// Latency of the requests of this class that completed successfully.
+    pub latency: Histogram,
+}
+
+/// Results of a completed simulation run.
+pub struct Summary {
+    /// Number of ticks simulated, fewer than configured if the run was interrupted.
+    pub ticks: u64,
+    pub stats: Stats,
+    pub pools: Vec<Pool>,
+    pub class_pools: Vec<usize>,
+    pub balancer: Option<Balancer>,
+    pub regions: Option<Regions>,
+    pub keys: Option<Keys>,
+    pub cache: Option<Cache>,
+    pub coalescer: Option<Coalescer>,
+    pub quotas: Option<Quotas>,
+    pub shedding: Option<Shedding>,
+    pub retry_policies: Vec<Box<dyn RetryPolicy>>,
+    pub fan_out: Option<FanOut>,
+    pub database: Option<Database>,
+    pub resource: Option<Resource>,
+    pub policy: Option<Policy>,
+    pub mitigations: Option<Mitigations>,
+    pub chaos: Option<Chaos>,
+    pub closed_loop: Option<ClosedLoop>,
+    pub request_sizes: Option<RequestSizes>,
+    pub timeouts: Option<Timeouts>,
+    pub stalls: Option<Stalls>,
+    pub network: Option<Network>,
+    pub client_pool: Option<ClientPool>,
+    pub telemetry: Option<Telemetry>,
+    pub timeline: Option<Timeline>,
+    pub observers: Vec<Box<dyn Observer>>,
+    /// Utilization of every worker, across the pools.
+    pub worker_times: Vec<WorkerTime>,
+}
+
+impl<'a> Simulation<'a> {
+    /// Sets up the simulation, creating the output files and starting the processes and servers the options
+    /// call for.
+    pub fn new(opt: &'a Opt) -> Result<Simulation<'a>, ConfigError> {
+        let classes = opt.classes();
+        let pool_specs = opt.pools();
+        let class_pools = pool::route_classes(&pool_specs, &classes).unwrap();
+        let pool_specs = match opt.queues {
+            Some(queues) => pool_specs[0].split(queues),
+            None if !opt.regions.is_empty() => {
+                opt.regions.iter().map(|r| r.pool(&classes)).collect()
+            }
+            None => pool_specs,
+        };
+        let seed = opt.seed.unwrap_or_else(|| thread_rng().gen());
+        let mut rng = RngStreams::new(opt, seed);
+        let resumed = if opt.resume {
+            let path = opt.checkpoint_file.as_ref().unwrap();
+            let checkpoint = Checkpoint::read(path, opt)
+                .map_err(|e| ConfigError::io("resume from checkpoint", path, e))?;
+            Some(checkpoint)
+        } else {
+            None
+        };
+        let mut observers: Vec<Box<dyn Observer>> = Vec::new();
+        if opt.explain {
+            let pool_names = pool_specs.iter().map(|p| p.name.clone()).collect();
+            observers.push(Box::new(Narrator::new(
+                pool_names,
+                opt.request_timeout as u64,
+            )));
+        }
+        if !opt.discipline_switches.is_empty() {
+            observers.push(Box::new(DisciplineSwitches::new(
+                &opt.discipline_switches,
+                opt.request_timeout as u64,
+            )));
+        }
+        if opt.queue_distribution {
+            observers.push(Box::new(QueueLengths::new(opt)));
+        }
+        if !opt.alerts.is_empty() {
+            observers.push(Box::new(Alerts::new(
+                &opt.alerts,
+                opt.alert_command.clone(),
+                opt.alert_cooldown,
+            )));
+        }
+        if opt.littles_law {
+            observers.push(Box::new(LittlesLaw::new(opt.warmup_ticks)));
+        }
+        if opt.detect_collapse {
+            let capacity = pool_specs.iter().map(|p| p.queue_size).sum();
+            observers.push(Box::new(CollapseDetector::new(
+                opt.warmup_ticks,
+                opt.collapse_window,
+                capacity,
+                opt.collapse_goodput,
+            )));
+        }
+        if opt.detect_retry_storms {
+            observers.push(Box::new(RetryStormDetector::new(
+                opt.warmup_ticks,
+                opt.retry_storm_window,
+                opt.retry_storm_share,
+            )));
+        }
+        if let Some(path) = opt.event_log.as_ref() {
+            let class_names = classes.iter().map(|c| c.name.clone()).collect();
+            let pool_names = pool_specs.iter().map(|p| p.name.clone()).collect();
+            let output = Output::create(path, opt.resume)
+                .map_err(|e| ConfigError::io("create event log", path, e))?;
+            observers.push(Box::new(EventLog::new(output, class_names, pool_names)));
+        }
+        if let Some(path) = opt.chrome_trace.as_ref() {
+            let pool_names: Vec<String> = pool_specs.iter().map(|p| p.name.clone()).collect();
+            let chrome_trace = Output::create(path, opt.resume)
+                .and_then(|output| ChromeTrace::new(output, &pool_names))
+                .map_err(|e| ConfigError::io("create Chrome trace", path, e))?;
+            observers.push(Box::new(chrome_trace));
+        }
+        if let Some(erlang_c) = ErlangC::new(opt) {
+            observers.push(Box::new(erlang_c));
+        }
+        let pools = pool_specs
+            .iter()
+            .enumerate()
+            .map(|(index, spec)| {
+                let autoscaler = if opt.autoscale_max_workers > 0 {
+                    let failure_seed = opt.failure_seed.unwrap_or(seed);
+                    Some(Autoscaler::new(
+                        opt,
+                        failure_seed.wrapping_add(1 + index as u64),
+                    ))
+                } else {
+                    None
+                };
+                let queue_depth_penalty = if opt.queue_latency_penalty > 0.0 {
+                    Some(QueueDepthPenalty::new(
+                        opt.queue_latency_curve,
+                        opt.queue_latency_penalty,
+                    ))
+                } else {
+                    None
+                };
+                Pool::new(
+                    spec,
+                    queue::new(
+                        opt.discipline(),
+                        spec.queue_size,
+                        RngStreams::queue_stream(seed, index),
+                    ),
+                    opt.late_shedding_interval > 0,
+                    opt.cull_timed_out,
+                    autoscaler,
+                    queue_depth_penalty,
+                    opt.straggler,
+                )
+            })
+            .collect();
+
+        Ok(Simulation {
+            opt,
+            pools,
+            class_distribution: if classes.len() > 1 {
+                Some(WeightedIndex::new(classes.iter().map(|c| c.weight)).unwrap())
+            } else {
+                None
+            },
+            class_pools,
+            balancer: opt
+                .queues
+                .map(|queues| Balancer::new(opt.routing, queues as usize)),
+            regions: if opt.regions.is_empty() {
+                None
+            } else {
+                Some(Regions::new(
+                    &opt.regions,
+                    opt.failover,
+                    opt.cross_region_latency,
+                ))
+            },
+            keys: if opt.keyed() {
+                Some(Keys::new(opt.keys, opt.key_skew))
+            } else {
+                None
+            },
+            cache: match (opt.cache_hit_ratio, opt.cache_size) {
+                (Some(hit_ratio), _) => Some(Cache::with_hit_ratio(
+                    hit_ratio,
+                    opt.cache_warmup_ticks,
+                    &opt.cache_flushes,
+                    opt.report_every(),
+                )),
+                (None, Some(size)) => Some(Cache::with_capacity(
+                    size,
+                    &opt.cache_flushes,
+                    opt.report_every(),
+                )),
+                (None, None) => None,
+            },
+            coalescer: if opt.coalesce {
+                Some(Coalescer::new())
+            } else {
+                None
+            },
+            quotas: if opt.quotas.is_empty() {
+                None
+            } else {
+                Some(Quotas::new(&opt.quotas, &classes).unwrap())
+            },
+            shedding: if opt.shed_thresholds.is_empty() {
+                None
+            } else {
+                Some(Shedding::new(&opt.shed_thresholds, &classes).unwrap())
+            },
+            retry_policies: retrypolicy::policies(
+                &opt.retry_policies,
+                &classes,
+                opt.retry_probabilities(),
+            )
+            .unwrap(),
+            fan_out: if opt.fan_out > 1 {
+                Some(FanOut::new(opt.fan_out, opt.fan_out_quorum()))
+            } else {
+                None
+            },
+            database: if opt.db_query_latency > 0.0 {
+                Some(Database::new(
+                    opt.db_query_latency,
+                    opt.db_contention_curve,
+                    opt.db_contention,
+                ))
+            } else {
+                None
+            },
+            resource: if opt.resource_slots > 0 {
+                Some(
+                    Resource::new(
+                        opt.resource_slots,
+                        opt.resource_queue_size,
+                        &opt.resource_classes,
+                        &classes,
+                    )
+                    .unwrap(),
+                )
+            } else {
+                None
+            },
+            policy: opt
+                .policy
+                .as_ref()
+                .map(|command| {
+                    Policy::spawn(command).map_err(|e| ConfigError::io("start policy", command, e))
+                })
+                .transpose()?,
+            mitigations: if opt.mitigations.is_empty() {
+                None
+            } else {
+                Some(Mitigations::new(&opt.mitigations, opt.mitigation_delay))
+            },
+            chaos: if opt.chaos.is_empty() {
+                None
+            } else {
+                Some(Chaos::new(&opt.chaos))
+            },
+            retries_cut: false,
+            mitigated_lifo: false,
+            telemetry: if opt.telemetry {
+                Some(Telemetry::new(opt.simulation_ticks))
+            } else {
+                None
+            },
+            timeline: if opt.html_report.is_some() || opt.plot.is_some() {
+                Some(Timeline::new(opt.report_every()))
+            } else {
+                None
+            },
+            time_series: opt
+                .time_series
+                .as_ref()
+                .map(|path| {
+                    Output::create(path, opt.resume)
+                        .and_then(|output| {
+                            TimeSeries::new(
+                                output,
+                                opt.report_every(),
+                                &metadata::comment_lines(opt),
+                            )
+                        })
+                        .map_err(|e| ConfigError::io("create time series", path, e))
+                })
+                .transpose()?,
+            snapshots: opt
+                .snapshots
+                .as_ref()
+                .map(|path| {
+                    Output::create(path, opt.resume)
+                        .map(|output| Snapshots::new(output, opt.snapshot_every, classes.len()))
+                        .map_err(|e| ConfigError::io("create snapshots", path, e))
+                })
+                .transpose()?,
+            progress: if opt.progress {
+                Some(Progress::new(opt.simulation_ticks as u64))
+            } else {
+                None
+            },
+            windows: if opt.run_forever {
+                Some(WindowedMetrics::new(opt.report_every()))
+            } else {
+                None
+            },
+            interrupted: if opt.run_forever {
+                Some(windows::on_interrupt().map_err(|e| ConfigError::Io {
+                    action: "handle interrupts".to_string(),
+                    error: e.to_string(),
+                })?)
+            } else {
+                None
+            },
+            metrics_server: opt
+                .metrics_address
+                .as_ref()
+                .map(|address| {
+                    MetricsServer::start(address)
+                        .map_err(|e| ConfigError::io("serve metrics on", address, e))
+                })
+                .transpose()?,
+            pacer: opt.speed.map(Pacer::new),
+            #[cfg(not(target_arch = "wasm32"))]
+            dashboard: if opt.tui {
+                let workers = pool_specs.iter().map(|p| p.workers as usize).sum::<usize>();
+                let dashboard = Dashboard::new(
+                    opt.simulation_ticks as u64,
+                    workers.max(opt.autoscale_max_workers as usize),
+                    opt.tui_speed
+                        .unwrap_or((opt.simulation_ticks as f64 / 30.0).max(1.0)),
+                )
+                .map_err(|e| ConfigError::Io {
+                    action: "start the TUI".to_string(),
+                    error: e.to_string(),
+                })?;
+                Some(dashboard)
+            } else {
+                None
+            },
+            observers,
+            events: Vec::new(),
+            arrival_schedule: {
+                let segments = opt.arrival_schedule().unwrap();
+                if segments.is_empty() {
+                    None
+                } else {
+                    Some(ArrivalSchedule::new(segments))
+                }
+            },
+            arrival_trace: opt.arrival_trace(&classes).unwrap(),
+            generator: opt
+                .generator
+                .as_ref()
+                .map(|command| {
+                    Generator::spawn(command, &classes)
+                        .map_err(|e| ConfigError::io("start generator", command, e))
+                })
+                .transpose()?,
+            request_sizes: opt.request_size.map(RequestSizes::new),
+            timeouts: if opt.timeout_distribution.is_some()
+                || classes.iter().any(|c| c.timeout.is_some())
+            {
+                Some(Timeouts::new(
+                    &classes,
+                    opt.request_timeout,
+                    opt.timeout_distribution,
+                ))
+            } else {
+                None
+            },
+            closed_loop: opt
+                .clients
+                .map(|clients| ClosedLoop::new(clients, opt.think_time, &mut rng.other)),
+            client_pool: opt.client_connections.map(|connections| {
+                let queue_timeout = opt.client_queue_timeout.unwrap_or(opt.request_timeout);
+                ClientPool::new(connections, queue_timeout as u64)
+            }),
+            network: opt
+                .network_latency
+                .map(|latency| Network::new(opt.network_latency_distribution, latency)),
+            stalls: opt
+                .stall_interval
+                .map(|interval| Stalls::new(interval, opt.stall_duration, &mut rng.failures)),
+            arrival_distribution: Normal::new(1.0, 0.25).unwrap(),
+            service_time: opt.service_time().unwrap(),
+            patience: opt
+                .patience
+                .map(|patience| ServiceTime::new(opt.patience_distribution, patience)),
+            spike_class: opt
+                .spike_class
+                .as_ref()
+                .map(|name| classes.iter().position(|c| &c.name == name).unwrap()),
+            spikes: opt.spikes(),
+            spike_multiplier: 1.0,
+            incoming_requests: 0.0,
+            retries: Retries::default(),
+            current_tick: 0,
+            rng,
+            stats: Stats::new(&classes),
+            resumed,
+        })
+    }
+
+    /// Adds an observer of the events of the run, on top of the ones the options call for.
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    /// Runs the simulation for the configured number of ticks, or until interrupted if it runs forever.
+    pub fn run(mut self) -> Summary {
+        let mut first_tick = 0;
+        if let Some(checkpoint) = self.resumed.take() {
+            first_tick = checkpoint.next_tick;
+            self.resume(checkpoint.state).unwrap_or_else(|e| {
+                let path = self.opt.checkpoint_file.as_deref().unwrap_or_default();
+                ConfigError::io("resume from checkpoint", path, e).exit()
+            });
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(dashboard) = self.dashboard.as_mut() {
+                dashboard.resume(&self.stats);
+            }
+        }
+        let end = if self.opt.run_forever {
+            u64::MAX
+        } else {
+            self.opt.simulation_ticks as u64
+        };
+        let mut ticks = end;
+        for tick in first_tick..end {
+            self.current_tick = tick;
+            if tick == self.opt.warmup_ticks && tick > 0 {
+                for pool in self.pools.iter_mut() {
+                    pool.reset_worker_times();
+                }
+            }
+            self.tick();
+            if let Some(mitigations) = self.mitigations.as_mut() {
+                let rejections = self
+                    .events
+                    .iter()
+                    .filter(|e| matches!(e, Event::Rejected { .. } | Event::Shed { .. }))
+                    .count();
+                let state = system_state(&self.pools, self.resource.as_ref());
+                mitigations.check(tick, &state, rejections as u64);
+            }
+            self.dispatch_events();
+            if let Some(closed_loop) = self.closed_loop.as_mut() {
+                if self.current_tick >= self.opt.warmup_ticks {
+                    closed_loop.tick_end();
+                }
+            }
+            if let Some(telemetry) = self.telemetry.as_mut() {
+                let queue_len = self.pools.iter().map(Pool::queue_len).sum();
+                let queue_capacity = self.pools.iter().map(Pool::queue_capacity).sum();
+                telemetry.tick(tick, queue_len, queue_capacity);
+            }
+            if let Some(timeline) = self.timeline.as_mut() {
+                timeline.tick(
+                    tick,
+                    self.pools.iter().map(Pool::queue_len).sum(),
+                    &self.stats,
+                );
+            }
+            if let Some(time_series) = self.time_series.as_mut() {
+                time_series
+                    .tick(
+                        tick,
+                        self.pools.iter().map(Pool::queue_len).sum(),
+                        self.pools.iter().map(Pool::busy_workers).sum(),
+                        self.stats.total_requests,
+                        self.stats.failed_requests,
+                    )
+                    .unwrap_or_else(|e| {
+                        let path = self.opt.time_series.as_deref().unwrap_or_default();
+                        ConfigError::io("write time series", path, e).exit()
+                    });
+            }
+            if let Some(snapshots) = self.snapshots.as_mut() {
+                snapshots
+                    .tick(tick, &self.pools, &self.stats)
+                    .unwrap_or_else(|e| {
+                        let path = self.opt.snapshots.as_deref().unwrap_or_default();
+                        ConfigError::io("write snapshots", path, e).exit()
+                    });
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(dashboard) = self.dashboard.as_mut() {
+                dashboard.tick(
+                    tick,
+                    self.pools.iter().map(Pool::queue_len).sum(),
+                    self.pools.iter().map(Pool::busy_workers).sum(),
+                    &self.stats,
+                );
+            }
+            if let Some(metrics_server) = self.metrics_server.as_mut() {
+                metrics_server.tick(
+                    tick,
+                    self.pools.iter().map(Pool::queue_len).sum(),
+                    self.pools.iter().map(Pool::busy_workers).sum(),
+                    self.pools.iter().map(Pool::workers).sum(),
+                    &self.stats,
+                );
+            }
+            if let Some(pacer) = self.pacer.as_ref() {
+                pacer.tick(tick);
+            }
+            if let Some(progress) = self.progress.as_mut() {
+                progress.tick(
+                    tick,
+                    self.pools.iter().map(Pool::queue_len).sum(),
+                    self.stats.total_requests,
+                    self.stats.failed_requests,
+                );
+            }
+            if let Some(windows) = self.windows.as_mut() {
+                windows.tick(
+                    tick,
+                    self.pools.iter().map(Pool::queue_len).sum(),
+                    &self.stats,
+                );
+            }
+            if let Some(every) = self.opt.checkpoint_every {
+                if (tick + 1) % every == 0 {
+                    self.checkpoint(tick + 1);
+                }
+            }
+            if self
+                .interrupted
+                .as_ref()
+                .is_some_and(|interrupted| interrupted.load(Ordering::Relaxed))
+            {
+                ticks = tick + 1;
+                break;
+            }
+        }
+        if let Some(windows) = self.windows.as_mut() {
+            windows.finish(self.current_tick, &self.stats);
+        }
+        if let Some(progress) = self.progress.as_ref() {
+            progress.finish();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(dashboard) = self.dashboard.as_mut() {
+            dashboard.finish();
+        }
+        if let Some(timeline) = self.timeline.as_mut() {
+            timeline.finish(&self.stats);
+        }
+        if let Some(time_series) = self.time_series.as_mut() {
+            time_series
+                .finish(
+                    self.current_tick,
+                    self.stats.total_requests,
+                    self.stats.failed_requests,
+                )
+                .unwrap_or_else(|e| {
+                    let path = self.opt.time_series.as_deref().unwrap_or_default();
+                    ConfigError::io("write time series", path, e).exit()
+                });
+        }
+        if let Some(snapshots) = self.snapshots.as_mut() {
+            snapshots.finish().unwrap_or_else(|e| {
+                let path = self.opt.snapshots.as_deref().unwrap_or_default();
+                ConfigError::io("write snapshots", path, e).exit()
+            });
+        }
+
+        let worker_times: Vec<WorkerTime> =
+            self.pools.iter().flat_map(Pool::worker_times).collect();
+        self.stats.worker_ticks = worker_times.iter().map(|w| w.available).sum();
+        Summary {
+            ticks,
+            worker_times,
+            stats: self.stats,
+            pools: self.pools,
+            class_pools: self.class_pools,
+            balancer: self.balancer,
+            regions: self.regions,
+            keys: self.keys,
+            cache: self.cache,
+            coalescer: self.coalescer,
+            quotas: self.quotas,
+            shedding: self.shedding,
+            retry_policies: self.retry_policies,
+            fan_out: self.fan_out,
+            database: self.database,
+            resource: self.resource,
+            policy: self.policy,
+            mitigations: self.mitigations,
+            chaos: self.chaos,
+            closed_loop: self.closed_loop,
+            request_sizes: self.request_sizes,
+            timeouts: self.timeouts,
+            stalls: self.stalls,
+            network: self.network,
+            client_pool: self.client_pool,
+            telemetry: self.telemetry,
+            timeline: self.timeline,
+            observers: self.observers,
+        }
+    }
+
+    /// Writes the state of the simulation to the checkpoint file, to resume at the specified tick from.
+    fn checkpoint(&mut self, next_tick: u64) {
+        let opt = self.opt;
+        let path = opt.checkpoint_file.as_ref().unwrap();
+        let written = self.save().and_then(|state| {
+            let checkpoint = Checkpoint::new(opt, next_tick, state);
+            let written = checkpoint.write(path);
+            self.resume(checkpoint.state).and(written)
+        });
+        written.unwrap_or_else(|e| ConfigError::io("write checkpoint", path, e).exit());
+    }
+
+    /// Takes the state of the simulation out, for a checkpoint. The outputs written as the run goes are flushed,
+    /// so that the checkpoint can record how far they got.
+    fn save(&mut self) -> Result<SimulationState, String> {
+        let retry_policies = self
+            .retry_policies
+            .iter()
+            .map(|policy| policy.checkpoint())
+            .collect::<Result<_, _>>()?;
+        let observers = self
+            .observers
+            .iter_mut()
+            .map(|observer| observer.checkpoint())
+            .collect::<Result<_, _>>()?;
+        let time_series = self
+            .time_series
+            .as_mut()
+            .map(TimeSeries::checkpoint)
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let snapshots = self
+            .snapshots
+            .as_mut()
+            .map(Snapshots::checkpoint)
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        Ok(SimulationState {
+            rng: self.rng.clone(),
+            incoming_requests: self.incoming_requests,
+            retries: mem::take(&mut self.retries),
+            pools: self.pools.iter_mut().map(Pool::checkpoint).collect(),
+            stats: mem::replace(&mut self.stats, Stats::new(&[])),
+            balancer: self.balancer.take(),
+            regions: self.regions.take(),
+            cache: self.cache.take(),
+            coalescer: self.coalescer.take(),
+            quotas: self.quotas.take(),
+            shedding: self.shedding.take(),
+            retry_policies,
+            fan_out: self.fan_out.take(),
+            database: self.database.take(),
+            resource: self.resource.take(),
+            mitigations: self.mitigations.take(),
+            chaos: self.chaos.take(),
+            retries_cut: self.retries_cut,
+            mitigated_lifo: self.mitigated_lifo,
+            telemetry: self.telemetry.take(),
+            timeline: self.timeline.take(),
+            time_series,
+            snapshots,
+            windows: self.windows.take(),
+            observers,
+            arrival_trace: self.arrival_trace.as_ref().map(ArrivalTrace::checkpoint),
+            closed_loop: self.closed_loop.take(),
+            request_sizes: self.request_sizes.take(),
+            timeouts: self.timeouts.take(),
+            stalls: self.stalls.take(),
+            network: self.network.take(),
+            client_pool: self.client_pool.take(),
+        })
+    }
+
+    /// Restores the state of the simulation from a checkpoint, cutting the outputs written as the run goes back to
+    /// where the checkpoint left them off.
+    fn resume(&mut self, state: SimulationState) -> Result<(), String> {
+        self.rng = state.rng;
+        self.incoming_requests = state.incoming_requests;
+        self.retries = state.retries;
+        for (pool, pool_checkpoint) in self.pools.iter_mut().zip(state.pools) {
+            pool.resume(pool_checkpoint);
+        }
+        self.stats = state.stats;
+        self.balancer = state.balancer;
+        self.regions = state.regions;
+        self.cache = state.cache;
+        self.coalescer = state.coalescer;
+        self.quotas = state.quotas;
+        self.shedding = state.shedding;
+        for (policy, policy_state) in self.retry_policies.iter_mut().zip(state.retry_policies) {
+            policy.resume(policy_state)?;
+        }
+        self.fan_out = state.fan_out;
+        self.database = state.database;
+        self.resource = state.resource;
+        self.mitigations = state.mitigations;
+        self.chaos = state.chaos;
+        self.retries_cut = state.retries_cut;
+        self.mitigated_lifo = state.mitigated_lifo;
+        self.telemetry = state.telemetry;
+        self.timeline = state.timeline;
+        if let (Some(time_series), Some(checkpoint)) =
+            (self.time_series.as_mut(), state.time_series)
+        {
+            time_series.resume(checkpoint).map_err(|e| e.to_string())?;
+        }
+        if let (Some(snapshots), Some(checkpoint)) = (self.snapshots.as_mut(), state.snapshots) {
+            snapshots.resume(checkpoint).map_err(|e| e.to_string())?;
+        }
+        self.windows = state.windows;
+        for (observer, observer_state) in self.observers.iter_mut().zip(state.observers) {
+            observer.resume(observer_state)?;
+        }
+        if let (Some(trace), Some(next)) = (self.arrival_trace.as_mut(), state.arrival_trace) {
+            trace.resume(next);
+        }
+        self.closed_loop = state.closed_loop;
+        self.request_sizes = state.request_sizes;
+        self.timeouts = state.timeouts;
+        self.stalls = state.stalls;
+        self.network = state.network;
+        self.client_pool = state.client_pool;
+        Ok(())
+    }
+
+    /// Hands the events of the current tick to the observers.
+    fn dispatch_events(&mut self) {
+        if self.observers.is_empty() {
+            self.events.clear();
+            return;
+        }
+
+        let state = system_state(&self.pools, self.resource.as_ref());
+        for observer in self.observers.iter_mut() {
+            for event in self.events.iter() {
+                observer.on_event(self.current_tick, event);
+            }
+            observer.on_tick_end(self.current_tick, &state);
+        }
+        self.events.clear();
+    }
+
+    fn tick(&mut self) {
+        if let Some(quotas) = self.quotas.as_mut() {
+            quotas.tick();
+        }
+        self.spike_tick();
+        self.inject_chaos();
+        self.mitigate();
+        let measured = self.is_warmed_up();
+        if let Some(cache) = self.cache.as_mut() {
+            cache.tick(self.current_tick, measured);
+        }
+        if self
+            .opt
+            .discipline_switches
+            .iter()
+            .any(|s| s.at == self.current_tick)
+        {
+            self.events.push(Event::DisciplineSwitched {
+                lifo: discipline::lifo_at(
+                    self.opt.discipline() == Discipline::Lifo,
+                    &self.opt.discipline_switches,
+                    self.current_tick,
+                ),
+            });
+        }
+        self.admit_waiting();
+        self.shed_late();
+        self.abandon();
+        if let Some(network) = self.network.as_mut() {
+            for request in network.arrivals_at(self.current_tick) {
+                self.reach(request);
+            }
+        }
+        if let Some(regions) = self.regions.as_mut() {
+            for request in regions.arrivals_at(self.current_tick) {
+                self.deliver(request);
+            }
+        }
+
+        let mut arrivals = Vec::new();
+        let replayed = match (self.arrival_trace.as_mut(), self.generator.as_mut()) {
+            (Some(trace), _) => Some(trace.arrivals_at(self.current_tick)),
+            (None, Some(generator)) => Some(generator.arrivals_at(self.current_tick)),
+            (None, None) => self
+                .closed_loop
+                .as_mut()
+                .map(|closed_loop| closed_loop.arrivals_at(self.current_tick)),
+        };
+        match replayed {
+            Some(replayed) => {
+                for arrival in replayed {
+                    let class = match (arrival.class, self.class_distribution.as_ref()) {
+                        (Some(class), _) => class,
+                        (None, Some(distribution)) => distribution.sample(&mut self.rng.arrivals),
+                        (None, None) => 0,
+                    };
+                    arrivals.push((arrival.offset, class));
+                }
+            }
+            None => {
+                // Compounding arrived requests, so that decimal portions don't get lost (since we can only
+                // create even number of requests on each try).
+                let arrival_rate = match self.arrival_schedule.as_ref() {
+                    Some(schedule) => {
+                        schedule.rate_at(self.opt.request_arrival_rate(), self.current_tick)
+                    }
+                    None => self.opt.request_arrival_rate(),
+                };
+                self.incoming_requests += match self.opt.arrival_distribution {
+                    ArrivalDistribution::Normal => {
+                        arrival_rate * self.arrival_distribution.sample(&mut self.rng.arrivals)
+                    }
+                    ArrivalDistribution::Poisson if arrival_rate > 0.0 => {
+                        Poisson::new(arrival_rate)
+                            .unwrap()
+                            .sample(&mut self.rng.arrivals)
+                    }
+                    ArrivalDistribution::Poisson => 0.0,
+                };
+
+                while self.incoming_requests > 0.0 {
+                    self.incoming_requests -= 1.0;
+                    let class = match self.class_distribution.as_ref() {
+                        Some(distribution) => distribution.sample(&mut self.rng.arrivals),
+                        None => 0,
+                    };
+                    arrivals.push((None, class));
+                }
+            }
+        }
+        self.start_waiting();
+        // Retries of the requests that timed out during the previous tick, and the ones held back until now.
+        if let Some(due) = self.retries.delayed.remove(&self.current_tick) {
+            self.retries.pending.extend(due);
+        }
+        let retries = mem::take(&mut self.retries.pending);
+
+        let mut arrivals: Vec<(f64, usize, Attempt)> = if self.opt.sub_tick_arrivals {
+            // Spread the arrivals over the tick, so that the order they reach the queue in (and their latency)
+            // doesn't depend on the order they were generated in. Traced and generated arrivals keep their offset.
+            // The retries draw theirs from their own stream, so that their number doesn't shift the others.
+            let fresh = arrivals.into_iter().map(|(offset, class)| {
+                (
+                    offset.unwrap_or_else(|| self.rng.arrivals.gen()),
+                    class,
+                    Attempt::first(),
+                )
+            });
+            let retried = retries
+                .into_iter()
+                .map(|(class, attempt)| (self.rng.retries.gen(), class, attempt));
+            fresh.chain(retried).collect()
+        } else {
+            arrivals
+                .into_iter()
+                .map(|(_, class)| (0.0, class, Attempt::first()))
+                .chain(
+                    retries
+                        .into_iter()
+                        .map(|(class, attempt)| (0.0, class, attempt)),
+                )
+                .collect()
+        };
+        arrivals.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        for (offset, class, attempt) in arrivals {
+            self.arrive(class, attempt, offset);
+            // Retries may fail right away, and be retried again at the same point within the tick.
+            while let Some((class, attempt)) = self.retries.pending.pop() {
+                self.arrive(class, attempt, offset);
+            }
+        }
+
+        let query_speed = match self.database.as_mut() {
+            Some(database) => {
+                database.tick(self.pools.iter().map(Pool::queries_in_flight).sum())
+                    / self.chaos.as_ref().map_or(1.0, Chaos::query_slowdown)
+            }
+            None => 1.0,
+        };
+        let measured = self.is_warmed_up();
+        if let Some((index, fraction)) = self.chaos.as_ref().and_then(Chaos::query_failure) {
+            let rng = &mut self.rng.failures;
+            let failed: usize = self
+                .pools
+                .iter_mut()
+                .map(|pool| pool.fail_queries(|| rng.gen_bool(fraction)))
+                .sum();
+            self.chaos.as_mut().unwrap().failed(index, failed, measured);
+        }
+        if let Some(stalls) = self.stalls.as_mut() {
+            if stalls.tick(self.current_tick, measured, &mut self.rng.failures) {
+                // Nothing gets done while the workers are frozen, but the queued requests keep aging.
+                for pool in self.pools.iter_mut() {
+                    pool.stalled_tick();
+                }
+                return;
+            }
+        }
+        let mut finished = Vec::new();
+        // Requests the workers found over their queue-wait budget, with their pools.
+        let mut expired = Vec::new();
+        for (index, pool) in self.pools.iter_mut().enumerate() {
+            let lifo = match self.policy.as_mut() {
+                Some(policy) if pool.queue_len() > 0 && pool.has_free_worker() => {
+                    policy.dequeue_from_back(&pool.state(self.current_tick))
+                }
+                _ => {
+                    self.mitigated_lifo
+                        || discipline::lifo_at(
+                            self.opt.discipline() == Discipline::Lifo,
+                            &self.opt.discipline_switches,
+                            self.current_tick,
+                        )
+                }
+            };
+            pool.switch_lifo(lifo);
+            let finished_before = finished.len();
+            let pool_expired = pool.working_tick(
+                self.current_tick,
+                index,
+                query_speed,
+                &mut finished,
+                &mut self.events,
+            );
+            expired.extend(pool_expired.into_iter().map(|request| (request, index)));
+            if let Some(balancer) = self.balancer.as_mut() {
+                for request in finished[finished_before..].iter() {
+                    if request.is_timed_out(self.current_tick) {
+                        balancer.failed(index, measured);
+                    }
+                }
+            }
+        }
+        for (request, pool) in expired {
+            if self.pools[pool].culls_timed_out() && request.is_timed_out(self.current_tick) {
+                self.cull(request, pool);
+            } else {
+                self.expire(request, pool);
+            }
+        }
+        for request in finished {
+            self.complete(request);
+        }
+    }
+
+    /// A new request arrived from a client (either a fresh one, or the specified attempt of a failed one).
+    fn arrive(&mut self, class: usize, mut attempt: Attempt, arrival_offset: f64) {
+        if self.is_warmed_up() {
+            self.stats.total_requests += 1;
+            self.stats.classes[class].total_requests += 1;
+            self.stats.record_attempt(attempt.number);
+        }
+        let id = self.request_id();
+        let measured = self.is_warmed_up();
+        if attempt.number == 1 {
+            self.retry_policies[class].requested();
+        }
+        // With multiple regions, the attempt of every request in flight has its route.
+        if let Some(regions) = self.regions.as_mut() {
+            if attempt.number == 1 {
+                attempt.route = regions.first_route(&mut self.rng.arrivals);
+            }
+            regions.sent(attempt.route, measured);
+        }
+        if attempt.number > 1 || self.regions.is_some() {
+            self.retries.attempts.insert(id, attempt);
+        }
+        self.events.push(Event::Arrived { request: id, class });
+        if let Some(client_pool) = self.client_pool.as_mut() {
+            if !client_pool.acquire(id, class, self.current_tick, measured) {
+                return;
+            }
+        }
+        self.start(id, class, arrival_offset);
+    }
+
+    /// Hands out the id of a new request or sub-request.
+    fn request_id(&mut self) -> u64 {
+        let id = self.retries.next_request_id;
+        self.retries.next_request_id += 1;
+        id
+    }
+
+    /// Sends a request the client has a connection for.
+    fn start(&mut self, id: u64, class: usize, arrival_offset: f64) {
+        if let Some(balking_queue_length) = self.opt.balking_queue_length {
+            // The longer the queue the client sees, the likelier it is to go elsewhere.
+            let queue_len = match (self.balancer.as_ref(), self.regions.as_ref()) {
+                (Some(_), _) => self.pools.iter().map(Pool::queue_len).sum(),
+                (None, Some(_)) => self.pools[self.route(id).region].queue_len(),
+                (None, None) => self.pools[self.class_pools[class]].queue_len(),
+            };
+            let probability = (queue_len as f64 / balking_queue_length as f64).min(1.0);
+            if self.rng.other.gen_bool(probability) {
+                self.events.push(Event::Balked { request: id });
+                self.fail(id, class, Failure::Balked);
+                return;
+            }
+        }
+
+        let patience = self
+            .patience
+            .as_ref()
+            .map(|patience| patience.sample(&mut self.rng.other).round() as u64);
+        let fan_out = match self.fan_out.as_mut() {
+            Some(fan_out) => fan_out,
+            None => {
+                let size = self.sample_size();
+                let mut request = Request::new(
+                    self.sample_execution_time(class, size),
+                    self.sample_timeout(class),
+                    self.current_tick,
+                    class,
+                );
+                request.set_id(id);
+                request.set_key(self.sample_key());
+                if let Some(budget) = self.opt.queue_timeout {
+                    request.set_queue_wait_budget(budget);
+                }
+                request.set_size(size);
+                request.set_arrival_offset(arrival_offset);
+                if let Some(patience) = patience {
+                    request.set_patience(patience);
+                }
+                if let Some(database) = self.database.as_ref() {
+                    request.set_query_time(database.sample_query_time(&mut self.rng.service));
+                }
+                self.send(request);
+                return;
+            }
+        };
+
+        fan_out.start(id);
+        for _ in 0..fan_out.degree() {
+            let size = self.sample_size();
+            let mut request = Request::sub_request(
+                self.sample_execution_time(class, size),
+                self.sample_timeout(class),
+                self.current_tick,
+                class,
+                id,
+            );
+            request.set_id(self.request_id());
+            request.set_key(self.sample_key());
+            if let Some(budget) = self.opt.queue_timeout {
+                request.set_queue_wait_budget(budget);
+            }
+            request.set_size(size);
+            request.set_arrival_offset(arrival_offset);
+            if let Some(patience) = patience {
+                request.set_patience(patience);
+            }
+            if let Some(database) = self.database.as_ref() {
+                request.set_query_time(database.sample_query_time(&mut self.rng.service));
+            }
+            self.send(request);
+        }
+    }
+
+    /// Fails the requests that waited for a client connection for too long, and sends the ones that got a
+    /// connection freed up during the previous tick.
+    fn start_waiting(&mut self) {
+        let measured = self.is_warmed_up();
+        let client_pool = match self.client_pool.as_mut() {
+            Some(client_pool) => client_pool,
+            None => return,
+        };
+        let expired = client_pool.expire(self.current_tick, measured);
+        let mut started = Vec::new();
+        while let Some(request) = client_pool.next(self.current_tick, measured) {
+            started.push(request);
+        }
+        for (id, class) in expired {
+            // Never sent, so there is no connection to release.
+            self.client_failed(id, class, Failure::TimedOut);
+        }
+        for (id, class) in started {
+            self.start(id, class, 0.0);
+        }
+    }
+
+    /// Sends the request over the network, delivering it to the server once it gets there.
+    fn send(&mut self, request: Request) {
+        let measured = self.is_warmed_up();
+        let request = match self.network.as_mut() {
+            Some(network) => {
+                network.send(request, self.current_tick, measured, &mut self.rng.other)
+            }
+            None => Some(request),
+        };
+        if let Some(request) = request {
+            self.reach(request);
+        }
+    }
+
+    /// The request crossed the network from its client. One sent to another region than its client's has yet to
+    /// cross over to it.
+    fn reach(&mut self, request: Request) {
+        let route = self.route(request.fan_out_parent().unwrap_or(request.id()));
+        let request = match self.regions.as_mut() {
+            Some(regions) => regions.send(request, route, self.current_tick),
+            None => Some(request),
+        };
+        if let Some(request) = request {
+            self.deliver(request);
+        }
+    }
+
+    /// Route of the request with the specified id, sent by a client, with multiple regions.
+    fn route(&self, id: u64) -> Route {
+        self.retries
+            .attempts
+            .get(&id)
+            .map_or_else(Route::default, |attempt| attempt.route)
+    }
+
+    /// The request reached the server, which either answers it from the cache, has it wait on an in-flight
+    /// request with the same key, admits it or rejects it.
+    fn deliver(&mut self, request: Request) {
+        let (id, class, parent) = (request.id(), request.class(), request.fan_out_parent());
+        let measured = self.is_warmed_up();
+        if let Some(cache) = self.cache.as_mut() {
+            if cache.lookup(
+                request.key(),
+                self.current_tick,
+                measured,
+                &mut self.rng.other,
+            ) {
+                self.events.push(Event::CacheHit { request: id });
+                let response_tick = self.current_tick + self.opt.cache_latency as u64 - 1
+                    + request.response_delay();
+                let result = if request.is_timed_out(response_tick) {
+                    Err(Failure::TimedOut)
+                } else {
+                    Ok(request.latency(response_tick))
+                };
+                if let Ok(latency) = result {
+                    self.completed_in_time(&request, latency);
+                }
+                match (parent, result) {
+                    (Some(parent), result) => self.sub_request_finished(parent, class, result),
+                    (None, Ok(latency)) => {
+                        if measured {
+                            self.stats.free_responses += 1;
+                        }
+                        self.succeed(id, class, latency);
+                    }
+                    (None, Err(failure)) => self.fail(id, class, failure),
+                }
+                return;
+            }
+        }
+        if let Some(coalescer) = self.coalescer.as_mut() {
+            if coalescer.is_in_flight(request.key()) {
+                coalescer.join(request, measured);
+                self.events.push(Event::Coalesced { request: id });
+                return;
+            }
+        }
+        let key = request.key();
+        if !self.admit(request) {
+            match parent {
+                Some(parent) => self.sub_request_finished(parent, class, Err(Failure::Rejected)),
+                None => self.fail(id, class, Failure::Rejected),
+            }
+        } else if let Some(coalescer) = self.coalescer.as_mut() {
+            coalescer.admitted(key);
+        }
+    }
+
+    /// Routes the request to the pool serving its class, unless it is over its class quota. Returns false if
+    /// the request had to be rejected.
+    fn admit(&mut self, mut request: Request) -> bool {
+        let (id, class) = (request.id(), request.class());
+        if let Some(quotas) = self.quotas.as_mut() {
+            if !quotas.allows(class) {
+                return false;
+            }
+        }
+
+        let measured = self.is_warmed_up();
+        let pool = match self.balancer.as_mut() {
+            Some(balancer) => {
+                balancer.route(&self.pools, request.key(), measured, &mut self.rng.other)
+            }
+            None if self.regions.is_some() => {
+                self.route(request.fan_out_parent().unwrap_or(id)).region
+            }
+            None => self.class_pools[class],
+        };
+        if let Some(policy) = self.policy.as_mut() {
+            let class_name = &self.stats.classes[class].name;
+            if !policy.admit(class_name, &self.pools[pool].state(self.current_tick)) {
+                self.events.push(Event::Rejected { request: id, pool });
+                self.failed_in_pool(pool);
+                return false;
+            }
+        }
+        if let Some(shedding) = self.shedding.as_mut() {
+            let target = &self.pools[pool];
+            if !target.has_free_worker()
+                && shedding.sheds(class, target.queue_len(), target.queue_size(), measured)
+            {
+                self.events.push(Event::Rejected { request: id, pool });
+                self.failed_in_pool(pool);
+                return false;
+            }
+        }
+        let mut holds_slot = false;
+        if let Some(resource) = self.resource.as_mut() {
+            if resource.is_needed_by(class) {
+                holds_slot = resource.try_acquire();
+                if !holds_slot {
+                    if !resource.enqueue(request) {
+                        self.events.push(Event::Rejected { request: id, pool });
+                        self.failed_in_pool(pool);
+                        return false;
+                    }
+                    self.admitted(id, class, pool, true);
+                    return true;
+                }
+            }
+        }
+        let queue_len = self.pools[pool].queue_len();
+        if self
+            .opt
+            .brownout_queue_length
+            .is_some_and(|brownout_queue_length| queue_len >= brownout_queue_length)
+            && self.rng.other.gen_bool(self.opt.brownout_fraction)
+        {
+            request.degrade(self.opt.brownout_service_fraction);
+        }
+        if !self.pools[pool].admit(request) {
+            self.events.push(Event::Rejected { request: id, pool });
+            self.failed_in_pool(pool);
+            if holds_slot {
+                self.resource.as_mut().unwrap().release();
+            }
+            return false;
+        }
+        let queued = self.pools[pool].queue_len() > queue_len;
+        self.admitted(id, class, pool, queued);
+        true
+    }
+
+    /// Counts a request that failed in the specified pool, for the load balancer's report on the queues.
+    fn failed_in_pool(&mut self, pool: usize) {
+        let measured = self.is_warmed_up();
+        if let Some(balancer) = self.balancer.as_mut() {
+            balancer.failed(pool, measured);
+        }
+    }
+
+    /// Counts a request admitted to the pool, `queued` if it has to wait for a worker.
+    fn admitted(&mut self, id: u64, class: usize, pool: usize, queued: bool) {
+        self.events.push(Event::Admitted {
+            request: id,
+            pool,
+            queued,
+        });
+        if let Some(quotas) = self.quotas.as_mut() {
+            quotas.admitted(class);
+        }
+    }
+
+    /// Hands the slots of the second resource freed up during the previous tick to the requests waiting for
+    /// them, which then go on to their pool. Those the pool has no room for are rejected.
+    fn admit_waiting(&mut self) {
+        let measured = self.is_warmed_up();
+        let resource = match self.resource.as_mut() {
+            Some(resource) => resource,
+            None => return,
+        };
+        let mut rejected = Vec::new();
+        while let Some(request) = resource.next_waiting() {
+            let (id, class, parent, key) = (
+                request.id(),
+                request.class(),
+                request.fan_out_parent(),
+                request.key(),
+            );
+            let pool = match self.balancer.as_mut() {
+                Some(balancer) => {
+                    balancer.route(&self.pools, request.key(), measured, &mut self.rng.other)
+                }
+                None => self.class_pools[class],
+            };
+            if !self.pools[pool].admit(request) {
+                resource.release();
+                self.events.push(Event::Rejected { request: id, pool });
+                rejected.push((id, class, parent, key, pool));
+            }
+        }
+        for (id, class, parent, key, pool) in rejected {
+            self.failed_in_pool(pool);
+            self.reject_admitted(id, class, parent, key);
+        }
+    }
+
+    /// Sheds the requests over the queue size from the queues, if it's time for the periodic late shedding.
+    fn shed_late(&mut self) {
+        let interval = self.opt.late_shedding_interval;
+        if interval == 0 || !self.current_tick.is_multiple_of(interval) {
+            return;
+        }
+
+        self.shed_excess();
+    }
+
+    /// Withdraws the requests whose clients ran out of patience by the end of the previous tick from the queues.
+    fn abandon(&mut self) {
+        if self.patience.is_none() || self.current_tick == 0 {
+            return;
+        }
+        for pool in 0..self.pools.len() {
+            for request in self.pools[pool].abandon(self.current_tick - 1) {
+                self.events.push(Event::Abandoned {
+                    request: request.id(),
+                    time_in_system: request.time_in_server(self.current_tick - 1),
+                });
+                self.failed_in_pool(pool);
+                if let Some(quotas) = self.quotas.as_mut() {
+                    quotas.released(request.class());
+                }
+                if let Some(resource) = self.resource.as_mut() {
+                    if resource.is_needed_by(request.class()) {
+                        resource.release();
+                    }
+                }
+                match request.fan_out_parent() {
+                    Some(parent) => {
+                        self.sub_request_finished(parent, request.class(), Err(Failure::Abandoned))
+                    }
+                    None => self.fail(request.id(), request.class(), Failure::Abandoned),
+                }
+                self.release_coalesced(request.key(), 0, false);
+            }
+        }
+    }
+
+    /// Fails a request a worker of the pool found over its queue-wait budget, without working on it.
+    fn expire(&mut self, request: Request, pool: usize) {
+        // The worker got to it at the start of the tick, so the current tick isn't part of the wait.
+        let time_in_system = request.time_in_server(self.current_tick - 1);
+        self.events.push(Event::Shed {
+            request: request.id(),
+            pool,
+            time_in_system,
+        });
+        if self.is_warmed_up() {
+            self.stats.queue_wait_expired += 1;
+        }
+        self.failed_in_pool(pool);
+        if let Some(resource) = self.resource.as_mut() {
+            if resource.is_needed_by(request.class()) {
+                resource.release();
+            }
+        }
+        self.reject_admitted(
+            request.id(),
+            request.class(),
+            request.fan_out_parent(),
+            request.key(),
+        );
+    }
+
+    /// Drops a request a worker of the pool found in the queue after its client gave up on it, instead of
+    /// wasting the worker's time on it. The client timed out.
+    fn cull(&mut self, request: Request, pool: usize) {
+        // The worker got to it at the start of the tick, so the current tick isn't part of the wait.
+        let time_in_system = request.time_in_server(self.current_tick - 1);
+        self.events.push(Event::Shed {
+            request: request.id(),
+            pool,
+            time_in_system,
+        });
+        if self.is_warmed_up() {
+            self.stats.culled_requests += 1;
+        }
+        self.failed_in_pool(pool);
+        if let Some(resource) = self.resource.as_mut() {
+            if resource.is_needed_by(request.class()) {
+                resource.release();
+            }
+        }
+        self.fail_admitted(
+            request.id(),
+            request.class(),
+            request.fan_out_parent(),
+            request.key(),
+            Failure::TimedOut,
+        );
+    }
+
+    /// Sheds the requests over the queue size of every pool.
+    fn shed_excess(&mut self) {
+        for pool in 0..self.pools.len() {
+            for request in self.pools[pool].shed_excess() {
+                // Shed at the start of the tick, so the current tick isn't part of the wait.
+                let time_in_system = request.time_in_server(self.current_tick - 1);
+                self.events.push(Event::Shed {
+                    request: request.id(),
+                    pool,
+                    time_in_system,
+                });
+                if self.is_warmed_up() {
+                    self.stats.shed_wait.record(time_in_system);
+                }
+                self.failed_in_pool(pool);
+                if let Some(resource) = self.resource.as_mut() {
+                    if resource.is_needed_by(request.class()) {
+                        resource.release();
+                    }
+                }
+                self.reject_admitted(
+                    request.id(),
+                    request.class(),
+                    request.fan_out_parent(),
+                    request.key(),
+                );
+            }
+        }
+    }
+
+    /// Fails a request with the specified key that was admitted, but had to be rejected later on.
+    fn reject_admitted(&mut self, id: u64, class: usize, parent: Option<u64>, key: u64) {
+        self.fail_admitted(id, class, parent, key, Failure::Rejected);
+    }
+
+    /// Fails an admitted request (or sub-request) that never got a response, with the specified failure.
+    fn fail_admitted(
+        &mut self,
+        id: u64,
+        class: usize,
+        parent: Option<u64>,
+        key: u64,
+        failure: Failure,
+    ) {
+        if let Some(quotas) = self.quotas.as_mut() {
+            quotas.released(class);
+        }
+        match parent {
+            Some(parent) => self.sub_request_finished(parent, class, Err(failure)),
+            None => self.fail(id, class, failure),
+        }
+        self.release_coalesced(key, 0, false);
+    }
+
+    /// The in-flight request with the specified key finished after the specified number of ticks of work, with
+    /// a response or without one, and the requests that waited on it get the same. The clients of those that
+    /// waited for longer than their timeout already gave up.
+    fn release_coalesced(&mut self, key: u64, worked_ticks: u32, responded: bool) {
+        let measured = self.is_warmed_up();
+        let waiting = match self.coalescer.as_mut() {
+            Some(coalescer) => coalescer.finished(key, worked_ticks, measured),
+            None => return,
+        };
+        for request in waiting {
+            let response_tick = self.current_tick + request.response_delay();
+            let result = if !responded {
+                Err(Failure::Rejected)
+            } else if request.is_timed_out(response_tick) {
+                Err(Failure::TimedOut)
+            } else {
+                Ok(request.latency(response_tick))
+            };
+            if let Ok(latency) = result {
+                self.completed_in_time(&request, latency);
+            }
+            match (request.fan_out_parent(), result) {
+                (Some(parent), result) => {
+                    self.sub_request_finished(parent, request.class(), result)
+                }
+                (None, Ok(latency)) => {
+                    if measured {
+                        self.stats.free_responses += 1;
+                    }
+                    self.succeed(request.id(), request.class(), latency);
+                }
+                (None, Err(failure)) => self.fail(request.id(), request.class(), failure),
+            }
+        }
+    }
+
+    /// A worker finished processing the request.
+    fn complete(&mut self, request: Request) {
+        if let Some(quotas) = self.quotas.as_mut() {
+            quotas.released(request.class());
+        }
+        if let Some(resource) = self.resource.as_mut() {
+            if resource.is_needed_by(request.class()) {
+                resource.release();
+            }
+        }
+        if self.is_warmed_up() {
+            self.stats.processed_requests += 1;
+        }
+        // A failed database query makes the response an error, which is nothing to cache.
+        let query_failed = request.has_failed_query();
+        if let Some(cache) = self.cache.as_mut().filter(|_| !query_failed) {
+            cache.fill(request.key());
+        }
+        // The client only gets the response once it crossed the network back.
+        let response_tick = self.current_tick + request.response_delay();
+        let timed_out = request.is_timed_out(response_tick);
+        let latency = request.latency(response_tick);
+        if self.is_warmed_up() {
+            let service_time = request.worked_ticks() as u64;
+            if timed_out {
+                self.stats.wasted_ticks += service_time as f64;
+            } else if !query_failed {
+                self.stats
+                    .queue_wait
+                    .record(latency.saturating_sub(service_time + request.network_delay()));
+                self.stats.service_time.record(service_time);
+                self.completed_in_time(&request, latency);
+                if request.is_degraded() {
+                    self.stats.degraded_responses += 1;
+                }
+            }
+        }
+        self.events.push(Event::Completed {
+            request: request.id(),
+            time_in_system: request.time_in_server(self.current_tick),
+            timed_out,
+        });
+        if let Some(parent) = request.fan_out_parent() {
+            let result = if timed_out {
+                Err(Failure::TimedOut)
+            } else if query_failed {
+                Err(Failure::Rejected)
+            } else {
+                Ok(latency)
+            };
+            self.sub_request_finished(parent, request.class(), result);
+        } else if timed_out {
+            // During this tick, a request finished but ended up timing out. This is the case where
+            // the client went away, but the server was still processing the request - the worst possible
+            // case for a synchronous queueing system.
+            self.fail(request.id(), request.class(), Failure::TimedOut);
+        } else if query_failed {
+            // The server passes the error of the database on, and the client sees it much like a rejection.
+            self.fail(request.id(), request.class(), Failure::Rejected);
+        } else {
+            self.succeed(request.id(), request.class(), latency);
+        }
+        self.release_coalesced(request.key(), request.worked_ticks(), true);
+    }
+
+    /// Records the latency of a request (or sub-request) that got its response in time, by its size and
+    /// timeout, once warmed up.
+    fn completed_in_time(&mut self, request: &Request, latency: u64) {
+        if !self.is_warmed_up() {
+            return;
+        }
+        if let Some(request_sizes) = self.request_sizes.as_mut() {
+            request_sizes.completed(request.size(), latency);
+        }
+        if let Some(timeouts) = self.timeouts.as_mut() {
+            timeouts.completed(request.timeout(), latency);
+        }
+    }
+
+    /// A sub-request of the specified parent request finished, with its latency if it succeeded. A parent request
+    /// can only fail on a failed sub-request, so the parent failure is of the same kind.
+    fn sub_request_finished(&mut self, parent: u64, class: usize, result: Result<u64, Failure>) {
+        let fan_out = self.fan_out.as_mut().unwrap();
+        match fan_out.sub_request_finished(parent, result.ok()) {
+            Some(Outcome::Succeeded(latency)) => self.succeed(parent, class, latency),
+            Some(Outcome::Failed) => self.fail(parent, class, result.unwrap_err()),
+            None => {}
+        }
+    }
+
+    /// Client received a successful response, after the specified number of ticks.
+    fn succeed(&mut self, id: u64, class: usize, latency: u64) {
+        self.retries.attempts.remove(&id);
+        if let Some(client_pool) = self.client_pool.as_mut() {
+            client_pool.release();
+        }
+        if self.is_warmed_up() {
+            self.stats.latency.record(latency);
+            self.stats.classes[class].latency.record(latency);
+        }
+        if let Some(closed_loop) = self.closed_loop.as_mut() {
+            closed_loop.finished(self.current_tick, &mut self.rng.other);
+        }
+    }
+
+    /// Client observed a failed request, either because it was rejected or it timed out.
+    fn fail(&mut self, id: u64, class: usize, failure: Failure) {
+        if let Some(client_pool) = self.client_pool.as_mut() {
+            client_pool.release();
+        }
+        self.client_failed(id, class, failure);
+    }
+
+    /// Client gave up on a request, either failed or never sent, and decides whether to retry it.
+    fn client_failed(&mut self, id: u64, class: usize, failure: Failure) {
+        // The retry policy of the class decides whether the client retries the failed request, and how long it
+        // waits before retrying.
+        let attempt = self
+            .retries
+            .attempts
+            .remove(&id)
+            .unwrap_or_else(Attempt::first);
+        let measured = self.is_warmed_up();
+        if let Some(regions) = self.regions.as_mut() {
+            regions.failed(attempt.route, measured);
+        }
+        let backoff = match failure {
+            _ if self.retries_cut => None,
+            Failure::Balked => None,
+            _ => self.retry_policies[class].backoff(&attempt, failure, &mut self.rng.retries),
+        };
+        if let Some(mut backoff) = backoff {
+            // Rejections carry the retry-after hint, which the polite clients wait out before retrying.
+            if let Some(retry_after) = self.opt.retry_after {
+                if matches!(failure, Failure::Rejected)
+                    && self.rng.retries.gen_bool(self.opt.retry_after_compliance)
+                {
+                    if self.is_warmed_up() {
+                        self.stats.deferred_retries += 1;
+                    }
+                    backoff = backoff.max(retry_after);
+                }
+            }
+            let mut retry = attempt.retry(backoff);
+            if let Some(regions) = self.regions.as_mut() {
+                retry.route = regions.retry_route(attempt.route, failure, measured);
+            }
+            let retry = (class, retry);
+            match backoff {
+                0 => self.retries.pending.push(retry),
+                _ => self
+                    .retries
+                    .delayed
+                    .entry(self.current_tick + backoff)
+                    .or_default()
+                    .push(retry),
+            }
+            self.events.push(Event::Retried { request: id });
+        } else if let Some(closed_loop) = self.closed_loop.as_mut() {
+            closed_loop.finished(self.current_tick, &mut self.rng.other);
+        }
+        if self.is_warmed_up() {
+            self.stats
+                .record_failure(class, attempt.number, failure, backoff.is_some());
+        }
+    }
+
+    /// Whether the warm-up period is over, and the outcomes of the requests count towards the statistics.
+    fn is_warmed_up(&self) -> bool {
+        self.current_tick >= self.opt.warmup_ticks
+    }
+
+    /// Begins and ends the faults of the chaos schedule due at the start of the current tick.
+    fn inject_chaos(&mut self) {
+        let actions = match self.chaos.as_mut() {
+            Some(chaos) => chaos.due(self.current_tick),
+            None => return,
+        };
+        for action in actions {
+            match action {
+                ChaosAction::Begin(index, Fault::Kill(workers)) => {
+                    for pool in 0..self.pools.len() {
+                        let killed = self.pools[pool].kill_workers(workers);
+                        let measured = self.is_warmed_up();
+                        self.chaos
+                            .as_mut()
+                            .unwrap()
+                            .failed(index, killed.len(), measured);
+                        for request in killed {
+                            self.kill(request, pool);
+                        }
+                    }
+                }
+                ChaosAction::End(Fault::Kill(workers)) => {
+                    for pool in self.pools.iter_mut() {
+                        pool.restore_workers(workers, self.opt.worker_startup_ticks);
+                    }
+                }
+                // The schedule keeps track of the database faults in effect, which the queries check every tick.
+                ChaosAction::Begin(_, Fault::DatabaseOutage(_) | Fault::DatabaseSlowdown(_))
+                | ChaosAction::End(Fault::DatabaseOutage(_) | Fault::DatabaseSlowdown(_)) => {}
+            }
+        }
+    }
+
+    /// Fails the request a killed worker of the pool was processing. The client sees its connection reset,
+    /// much like a rejection.
+    fn kill(&mut self, request: Request, pool: usize) {
+        self.events.push(Event::Killed {
+            request: request.id(),
+            pool,
+            time_in_system: request.time_in_server(self.current_tick),
+        });
+        self.failed_in_pool(pool);
+        if let Some(resource) = self.resource.as_mut() {
+            if resource.is_needed_by(request.class()) {
+                resource.release();
+            }
+        }
+        self.reject_admitted(
+            request.id(),
+            request.class(),
+            request.fan_out_parent(),
+            request.key(),
+        );
+    }
+
+    /// Takes the actions of the mitigations taking effect at the start of the current tick.
+    fn mitigate(&mut self) {
+        let actions = match self.mitigations.as_mut() {
+            Some(mitigations) => mitigations.due(self.current_tick),
+            None => return,
+        };
+        for action in actions {
+            match action {
+                Action::AddWorkers(workers) => {
+                    for pool in self.pools.iter_mut() {
+                        pool.add_workers(workers, self.opt.worker_startup_ticks);
+                    }
+                }
+                Action::Shed(queue_size) => {
+                    for pool in self.pools.iter_mut() {
+                        pool.set_queue_size(queue_size);
+                    }
+                    self.shed_excess();
+                }
+                Action::CutRetries => self.retries_cut = true,
+                Action::Lifo => self.mitigated_lifo = true,
+            }
+        }
+    }
+
+    /// Works out the latency multiplier of the current tick, from the spikes active during it.
+    fn spike_tick(&mut self) {
+        let tick = self.current_tick;
+        let mut multiplier = 1.0;
+        for spike in self.spikes.iter() {
+            if spike.start == tick {
+                self.events.push(Event::SpikeStarted {
+                    multiplier: spike.multiplier,
+                });
+            } else if spike.end() == tick {
+                self.events.push(Event::SpikeEnded);
+            }
+            if spike.is_active(tick) {
+                multiplier *= spike.multiplier;
+            }
+        }
+        self.spike_multiplier = multiplier;
+    }
+
+    /// Samples the key of a new request (or sub-request), if the requests have keys.
+    fn sample_key(&mut self) -> u64 {
+        match self.keys.as_ref() {
+            Some(keys) => keys.sample(&mut self.rng.other),
+            None => 0,
+        }
+    }
+
+    /// Samples the timeout of a new request (or sub-request) of the class, and counts it towards the requests
+    /// with its timeout.
+    fn sample_timeout(&mut self, class: usize) -> u32 {
+        let warmed_up = self.is_warmed_up();
+        match self.timeouts.as_mut() {
+            Some(timeouts) => {
+                let timeout = timeouts.sample(class, &mut self.rng.other);
+                if warmed_up {
+                    timeouts.arrived(timeout);
+                }
+                timeout
+            }
+            None => self.opt.request_timeout,
+        }
+    }
+
+    /// Samples the size of a new request (or sub-request), and counts it towards the requests of its size.
+    fn sample_size(&mut self) -> f64 {
+        let warmed_up = self.is_warmed_up();
+        match self.request_sizes.as_mut() {
+            Some(request_sizes) => {
+                let size = request_sizes.sample(&mut self.rng.service);
+                if warmed_up {
+                    request_sizes.arrived(size);
+                }
+                size
+            }
+            None => 1.0,
+        }
+    }
+
+    /// Samples the execution time of a request of the specified size. The service time distribution adds the
+    /// noise around the time proportional to the size.
+    fn sample_execution_time(&mut self, class: usize, size: f64) -> u32 {
+        let mut execution_time = self.service_time.sample(&mut self.rng.service) * size;
+        if self.spike_class.is_none_or(|c| c == class) {
+            // If we are simulating a short term latency spike, increase the latency of each request.
+            execution_time *= self.spike_multiplier;
+        }
+        if let Some(cap) = self.opt.service_time_cap {
+            execution_time = execution_time.min(cap);
+        }
+
+        execution_time as u32
+    }
+}
+
+impl Stats {
+    pub fn new(classes: &[ClassSpec]) -> Stats {
+        Stats {
+            total_requests: 0,
+            failed_requests: 0,
+            rejected_requests: 0,
+            timed_out_requests: 0,
+            retried_rejections: 0,
+            retried_timeouts: 0,
+            abandoned_requests: 0,
+            retried_abandonments: 0,
+            balked_requests: 0,
+            processed_requests: 0,
+            wasted_ticks: 0.0,
+            worker_ticks: 0.0,
+            latency: Histogram::new(),
+            queue_wait: Histogram::new(),
+            service_time: Histogram::new(),
+            shed_wait: Histogram::new(),
+            queue_wait_expired: 0,
+            culled_requests: 0,
+            degraded_responses: 0,
+            free_responses: 0,
+            deferred_retries: 0,
+            attempts: Vec::new(),
+            classes: classes
+                .iter()
+                .map(|c| ClassStats {
+                    name: c.name.clone(),
+                    total_requests: 0,
+                    failed_requests: 0,
+                    latency: Histogram::new(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Adds the statistics of another run of the same configuration, e.g. another replication.
+    pub fn merge(&mut self, other: &Stats) {
+        self.total_requests += other.total_requests;
+        self.failed_requests += other.failed_requests;
+        self.rejected_requests += other.rejected_requests;
+        self.timed_out_requests += other.timed_out_requests;
+        self.retried_rejections += other.retried_rejections;
+        self.retried_timeouts += other.retried_timeouts;
+        self.abandoned_requests += other.abandoned_requests;
+        self.retried_abandonments += other.retried_abandonments;
+        self.balked_requests += other.balked_requests;
+        self.processed_requests += other.processed_requests;
+        self.wasted_ticks += other.wasted_ticks;
+        self.worker_ticks += other.worker_ticks;
+        self.latency.merge(&other.latency);
+        self.queue_wait.merge(&other.queue_wait);
+        self.service_time.merge(&other.service_time);
+        self.shed_wait.merge(&other.shed_wait);
+        self.queue_wait_expired += other.queue_wait_expired;
+        self.culled_requests += other.culled_requests;
+        self.degraded_responses += other.degraded_responses;
+        self.free_responses += other.free_responses;
+        self.deferred_retries += other.deferred_retries;
+        for (attempt, other_attempt) in other.attempts.iter().enumerate() {
+            if attempt == self.attempts.len() {
+                self.attempts.push(AttemptStats::default());
+            }
+            self.attempts[attempt].total += other_attempt.total;
+            self.attempts[attempt].failed += other_attempt.failed;
+        }
+        for (class, other_class) in self.classes.iter_mut().zip(other.classes.iter()) {
+            class.total_requests += other_class.total_requests;
+            class.failed_requests += other_class.failed_requests;
+            class.latency.merge(&other_class.latency);
+        }
+    }
+
+    /// Counts a failed request of the class, and whether the client retried it.
+    pub fn record_failure(&mut self, class: usize, attempt: u32, failure: Failure, retried: bool) {
+        self.failed_requests += 1;
+        self.classes[class].failed_requests += 1;
+        if let Some(attempt) = self.attempts.get_mut(attempt as usize - 1) {
+            attempt.failed += 1;
+        }
+        let retried = retried as u64;
+        match failure {
+            Failure::Rejected => {
+                self.rejected_requests += 1;
+                self.retried_rejections += retried;
+            }
+            Failure::TimedOut => {
+                self.timed_out_requests += 1;
+                self.retried_timeouts += retried;
+            }
+            Failure::Abandoned => {
+                self.abandoned_requests += 1;
+                self.retried_abandonments += retried;
+            }
+            Failure::Balked => self.balked_requests += 1,
+        }
+    }
+
+    /// Counts a request with the specified attempt number, 1 for the original request.
+    pub fn record_attempt(&mut self, attempt: u32) {
+        let attempt = attempt as usize;
+        if self.attempts.len() < attempt {
+            self.attempts.resize(attempt, AttemptStats::default());
+        }
+        self.attempts[attempt - 1].total += 1;
+    }
+
+    pub fn failure_rate(&self) -> f64 {
+        failure_rate(self.failed_requests, self.total_requests)
+    }
+
+    /// Prints how many requests were rejected and how many timed out, and how many of each were retried.
+    pub fn print_failure_summary(&self) {
+        if self.failed_requests == 0 {
+            return;
+        }
+
+        println!(
+            "Rejected: {} ({} retried), timed out: {} ({} retried)",
+            self.rejected_requests,
+            self.retried_rejections,
+            self.timed_out_requests,
+            self.retried_timeouts
+        );
+        if self.abandoned_requests > 0 {
+            println!(
+                "Abandoned: {} ({:.2}% of the requests, {} retried)",
+                self.abandoned_requests,
+                failure_rate(self.abandoned_requests, self.total_requests),
+                self.retried_abandonments
+            );
+        }
+        if self.balked_requests > 0 {
+            println!(
+                "Balked: {} ({:.2}% of the requests)",
+                self.balked_requests,
+                failure_rate(self.balked_requests, self.total_requests)
+            );
+        }
+    }
+}
+
+impl Stats {
+    /// Prints the distributions of the time the requests spent in the queue and being processed, and how much
+    /// of their mean latency each of the two accounts for. Latency dominated by queueing calls for shedding
+    /// load, latency dominated by service time calls for more capacity or faster processing.
+    pub fn print_latency_breakdown(&self) {
+        if self.service_time.total() == 0 {
+            return;
+        }
+
+        let queue_wait = self.queue_wait.mean();
+        let service_time = self.service_time.mean();
+        println!(
+            "Time in queue: mean={:.2} {} ({:.2}% of the latency)",
+            queue_wait,
+            self.queue_wait.format_percentiles(),
+            queue_wait / (queue_wait + service_time) * 100.0
+        );
+        println!(
+            "Time in service: mean={:.2} {} ({:.2}% of the latency)",
+            service_time,
+            self.service_time.format_percentiles(),
+            service_time / (queue_wait + service_time) * 100.0
+        );
+    }
+
+    /// Prints the latency as the clients intended to experience it, with every failed request counted as taking
+    /// the full timeout, rather than only the latency of the successful ones. Once the system is saturated, the
+    /// successful requests are the lucky few, and their latency understates what the clients go through, the
+    /// same way a load tester that waits for responses before sending more understates it (coordinated
+    /// omission).
+    pub fn print_intended_latency(&self, timeout: u32) {
+        let mut intended = self.latency.clone();
+        intended.record_many(timeout as u64, self.failed_requests);
+        if intended.total() == 0 {
+            return;
+        }
+
+        println!(
+            "Intended latency: mean={:.2} {} (failures counted at the timeout)",
+            intended.mean(),
+            intended.format_percentiles()
+        );
+    }
+
+    /// Prints the rate at which the workers processed requests (throughput), and the rate at which they
+    /// processed them in time for the client to still be waiting (goodput), over the specified number of
+    /// measured ticks. A collapsed system keeps its workers busy while its goodput approaches zero. The
+    /// responses of the cache and the coalesced requests don't count, since the workers never saw them.
+    /// Requests that completed successfully with the work of a worker of their own.
+    pub fn goodput(&self) -> u64 {
+        self.latency.total().saturating_sub(self.free_responses)
+    }
+
+    pub fn print_throughput_summary(&self, ticks: u64) {
+        if ticks == 0 || self.processed_requests == 0 {
+            return;
+        }
+
+        let goodput = self.goodput();
+        println!(
+            "Throughput: {:.4} requests/tick, goodput: {:.4} requests/tick ({:.2}% of the processed requests)",
+            self.processed_requests as f64 / ticks as f64,
+            goodput as f64 / ticks as f64,
+            goodput as f64 / self.processed_requests as f64 * 100.0
+        );
+    }
+
+    /// Prints how the requests were answered: with a full response, a degraded one served by a browned out pool,
+    /// or not at all.
+    pub fn print_brownout_summary(&self) {
+        if self.degraded_responses == 0 {
+            return;
+        }
+
+        let total = self.total_requests as f64 / 100.0;
+        let full = self.latency.total() - self.degraded_responses;
+        println!(
+            "Responses: full {} ({:.2}%), degraded {} ({:.2}%), failed {} ({:.2}%)",
+            full,
+            full as f64 / total,
+            self.degraded_responses,
+            self.degraded_responses as f64 / total,
+            self.failed_requests,
+            self.failed_requests as f64 / total
+        );
+    }
+
+    /// Prints how many attempts the clients made per original request, and how often the attempts with each
+    /// number failed. The attempts from the fifth on are reported together.
+    pub fn print_retry_amplification_summary(&self) {
+        if self.attempts.len() < 2 {
+            return;
+        }
+
+        let original_requests = self.attempts[0].total;
+        println!(
+            "Retry amplification: {:.2} attempts per original request ({} attempts, {} original requests)",
+            self.total_requests as f64 / original_requests.max(1) as f64,
+            self.total_requests,
+            original_requests
+        );
+        for (attempt, stats) in self.attempts.iter().enumerate().take(5) {
+            let (label, total, failed) = if attempt == 4 && self.attempts.len() > 5 {
+                let rest = &self.attempts[4..];
+                (
+                    "5+".to_string(),
+                    rest.iter().map(|a| a.total).sum(),
+                    rest.iter().map(|a| a.failed).sum(),
+                )
+            } else {
+                ((attempt + 1).to_string(), stats.total, stats.failed)
+            };
+            println!(
+                "Attempt {}: {} requests, failure rate: {:.2}%",
+                label,
+                total,
+                failure_rate(failed, total.max(1))
+            );
+        }
+    }
+
+    /// Prints how many of the retried rejections waited out the specified retry-after hint.
+    pub fn print_retry_after_summary(&self, retry_after: u64) {
+        println!(
+            "Retry-after: {} of {} retried rejections ({:.2}%) waited out the {} tick hint",
+            self.deferred_retries,
+            self.retried_rejections,
+            self.deferred_retries as f64 / self.retried_rejections.max(1) as f64 * 100.0,
+            retry_after
+        );
+    }
+
+    /// Prints how many requests the workers dropped from the queues, since their clients already gave up.
+    pub fn print_culling_summary(&self) {
+        println!(
+            "Culling: {} requests ({:.2}%) dropped from the queue after their clients gave up, without taking a worker",
+            self.culled_requests,
+            self.culled_requests as f64 / self.total_requests.max(1) as f64 * 100.0
+        );
+    }
+
+    /// Prints how many requests were failed for waiting over the specified queue-wait budget.
+    pub fn print_queue_wait_budget_summary(&self, queue_wait_budget: u64) {
+        println!(
+            "Queue-wait budget: {} requests ({:.2}%) failed after waiting over {} ticks, without taking a worker",
+            self.queue_wait_expired,
+            self.queue_wait_expired as f64 / self.total_requests.max(1) as f64 * 100.0,
+            queue_wait_budget
+        );
+    }
+
+    /// Prints how many requests the late shedding rejected, and how long they waited for nothing.
+    pub fn print_shedding_summary(&self) {
+        if self.shed_wait.total() == 0 {
+            return;
+        }
+
+        println!(
+            "Late shedding: {} requests shed after waiting, wasted wait: mean={:.2} {}",
+            self.shed_wait.total(),
+            self.shed_wait.mean(),
+            self.shed_wait.format_percentiles()
+        );
+    }
+}
+
+impl Summary {
+    /// Prints the share of the capacity of the workers spent on requests that timed out before they completed.
+    /// Unlike a rejection, which costs the server next to nothing, such a request takes the full work of a
+    /// successful one, and calls for a different mitigation, such as dropping timed out requests from the queue.
+    pub fn print_wasted_work_summary(&self) {
+        if self.stats.wasted_ticks == 0.0 {
+            return;
+        }
+
+        let available: f64 = self.worker_times.iter().map(|w| w.available).sum();
+        let busy: f64 = self.worker_times.iter().map(|w| w.busy).sum();
+        println!(
+            "Wasted work: {:.2}% of the capacity ({:.2}% of the busy time) spent on requests that timed out",
+            self.stats.wasted_ticks / available * 100.0,
+            self.stats.wasted_ticks / busy * 100.0
+        );
+    }
+
+    /// Prints failure rates and latencies of the individual classes. Only interesting when there is more
+    /// than one.
+    pub fn print_class_summary(&self) {
+        if self.stats.classes.len() < 2 {
+            return;
+        }
+
+        for class in self.stats.classes.iter() {
+            println!(
+                "Class {}: {} requests, failure rate: {:.2}%, latency: {}",
+                class.name,
+                class.total_requests,
+                failure_rate(class.failed_requests, class.total_requests),
+                class.latency.format_percentiles()
+            );
+        }
+    }
+
+    /// Prints the retry policy of every class, and what it did during the run.
+    pub fn print_retry_policy_summary(&self) {
+        if let [policy] = self.retry_policies.as_slice() {
+            println!("Retry policy: {}", policy.describe());
+            return;
+        }
+
+        for (class, policy) in self.stats.classes.iter().zip(self.retry_policies.iter()) {
+            println!("Class {}: {}", class.name, policy.describe());
+        }
+    }
+
+    /// Prints failure rates of the individual pools. Only interesting when there is more than one.
+    pub fn print_pool_summary(&self) {
+        if let Some(balancer) = self.balancer.as_ref() {
+            balancer.print_summary(&self.pools);
+            return;
+        }
+        if let Some(regions) = self.regions.as_ref() {
+            regions.print_summary(&self.pools);
+            return;
+        }
+        if self.pools.len() < 2 {
+            return;
+        }
+
+        for (index, pool) in self.pools.iter().enumerate() {
+            let (mut total_requests, mut failed_requests) = (0, 0);
+            for (class, class_stats) in self.stats.classes.iter().enumerate() {
+                if self.class_pools[class] == index {
+                    total_requests += class_stats.total_requests;
+                    failed_requests += class_stats.failed_requests;
+                }
+            }
+            println!(
+                "Pool {}: {} requests, failure rate: {:.2}%, utilization: {:.2}%",
+                pool.name(),
+                total_requests,
+                failure_rate(failed_requests, total_requests),
+                utilization::aggregate(&pool.worker_times()) * 100.0
+            );
+        }
+    }
+}
+
+/// Percentage of the requests that failed.
+fn failure_rate(failed_requests: u64, total_requests: u64) -> f64 {
+    failed_requests as f64 / total_requests as f64 * 100.0
+}
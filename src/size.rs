@@ -0,0 +1,132 @@
+use crate::histogram::Histogram;
+use rand::distributions::Distribution;
+use rand::Rng;
+use rand_distr::{LogNormal, Pareto};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Lowest and highest power of two bounding the size buckets of the report, relative to the mean size.
+const MIN_BUCKET: i32 = -2;
+const MAX_BUCKET: i32 = 4;
+
+/// Distribution of the payload size of the requests, relative to the mean size, so that the mean is always 1.
+/// The service time of a request is proportional to its size.
+#[derive(Debug, Clone, Copy)]
+pub enum SizeDistribution {
+    /// Log-normal with the specified standard deviation of the logarithm of the size.
+    LogNormal(f64),
+    /// Pareto with the specified shape, which has to be greater than 1 for the mean to exist. The lower the
+    /// shape, the heavier the tail: a few huge requests among many small ones.
+    Pareto(f64),
+}
+
+impl FromStr for SizeDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, parameter) = s.split_once(':').ok_or_else(|| {
+            format!(
+                "Expected request size distribution as lognormal:SIGMA or pareto:SHAPE, got '{}'",
+                s
+            )
+        })?;
+        let parameter: f64 = parameter
+            .parse()
+            .map_err(|_| format!("Invalid parameter of request size distribution '{}'", s))?;
+        match name {
+            "lognormal" if parameter > 0.0 => Ok(SizeDistribution::LogNormal(parameter)),
+            "pareto" if parameter > 1.0 => Ok(SizeDistribution::Pareto(parameter)),
+            "lognormal" => Err("Log-normal sigma has to be greater than 0".to_string()),
+            "pareto" => Err("Pareto shape has to be greater than 1".to_string()),
+            _ => Err(format!(
+                "Unknown request size distribution '{}', expected lognormal or pareto",
+                name
+            )),
+        }
+    }
+}
+
+/// Outcomes of the requests of a range of sizes.
+#[derive(Default, Serialize, Deserialize)]
+struct Bucket {
+    total_requests: u64,
+    /// Latency of the requests that completed in time.
+    latency: Histogram,
+}
+
+/// Samples the sizes of the requests, and tracks how the requests of each range of sizes fared, in buckets
+/// doubling in size, so that the few huge requests clogging the queue can be told from the many small ones
+/// stuck behind them.
+#[derive(Serialize, Deserialize)]
+pub struct RequestSizes {
+    distribution: Sampler,
+    buckets: Vec<Bucket>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Sampler {
+    LogNormal(LogNormal<f64>),
+    Pareto(Pareto<f64>),
+}
+
+impl RequestSizes {
+    pub fn new(distribution: SizeDistribution) -> RequestSizes {
+        RequestSizes {
+            distribution: match distribution {
+                SizeDistribution::LogNormal(sigma) => {
+                    Sampler::LogNormal(LogNormal::new(-sigma * sigma / 2.0, sigma).unwrap())
+                }
+                SizeDistribution::Pareto(shape) => {
+                    Sampler::Pareto(Pareto::new((shape - 1.0) / shape, shape).unwrap())
+                }
+            },
+            buckets: (MIN_BUCKET..=MAX_BUCKET)
+                .map(|_| Bucket::default())
+                .collect(),
+        }
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        match &self.distribution {
+            Sampler::LogNormal(distribution) => distribution.sample(rng),
+            Sampler::Pareto(distribution) => distribution.sample(rng),
+        }
+    }
+
+    /// Counts a request of the specified size.
+    pub fn arrived(&mut self, size: f64) {
+        self.bucket(size).total_requests += 1;
+    }
+
+    /// Records the latency of a request of the specified size that completed in time.
+    pub fn completed(&mut self, size: f64, latency: u64) {
+        self.bucket(size).latency.record(latency);
+    }
+
+    pub fn print_summary(&self) {
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            if bucket.total_requests == 0 {
+                continue;
+            }
+            let power = MIN_BUCKET + index as i32;
+            let range = match power {
+                MIN_BUCKET => format!("<{}x", 2f64.powi(power + 1)),
+                MAX_BUCKET => format!(">={}x", 2f64.powi(power)),
+                _ => format!("{}-{}x", 2f64.powi(power), 2f64.powi(power + 1)),
+            };
+            let failed = bucket.total_requests.saturating_sub(bucket.latency.total());
+            println!(
+                "Size {}: {} requests, failure rate: {:.2}%, latency: {}",
+                range,
+                bucket.total_requests,
+                failed as f64 / bucket.total_requests as f64 * 100.0,
+                bucket.latency.format_percentiles()
+            );
+        }
+    }
+
+    fn bucket(&mut self, size: f64) -> &mut Bucket {
+        let power = (size.log2().floor() as i32).clamp(MIN_BUCKET, MAX_BUCKET);
+        &mut self.buckets[(power - MIN_BUCKET) as usize]
+    }
+}
@@ -0,0 +1,36 @@
+use crate::simulation::Stats;
+
+/// Latency service level objective: the share of the requests that have to complete successfully within the
+/// specified latency. Unlike a latency percentile of the successful requests, the failed requests count against
+/// it, so that failing fast doesn't look like meeting the objective.
+pub struct Slo {
+    latency: u64,
+    /// Target share of the requests, in percent, e.g. 99.9.
+    target: f64,
+}
+
+impl Slo {
+    pub fn new(latency: u64, target: f64) -> Slo {
+        Slo { latency, target }
+    }
+
+    /// Percentage of the requests that completed successfully within the latency of the objective.
+    pub fn attainment(&self, stats: &Stats) -> f64 {
+        stats.latency.count_at_most(self.latency) as f64 / stats.total_requests.max(1) as f64
+            * 100.0
+    }
+
+    pub fn is_met(&self, stats: &Stats) -> bool {
+        self.attainment(stats) >= self.target
+    }
+
+    pub fn print_summary(&self, stats: &Stats) {
+        println!(
+            "SLO: {:.3}% of the requests completed within {} ticks, target of {}% {}",
+            self.attainment(stats),
+            self.latency,
+            self.target,
+            if self.is_met(stats) { "met" } else { "missed" }
+        );
+    }
+}
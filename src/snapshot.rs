@@ -0,0 +1,137 @@
+use crate::output::Output;
+use crate::pool::Pool;
+use crate::simulation::Stats;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// Writes snapshots of the state of the simulated system as NDJSON, one line every `every` ticks. Unlike the
+/// time series, which aggregates the state into a few scalars, a snapshot keeps its shape: how long the queue
+/// was during the interval, how old the requests in the system are, and how each class fared, so that the way
+/// the state evolved can be analyzed after the fact.
+pub struct Snapshots {
+    every: u64,
+    writer: Output,
+    /// Ticks the queues were of each length during the current interval.
+    queue_lengths: BTreeMap<usize, u64>,
+    /// Cumulative requests, failures and requests completed in time of every class, at the last snapshot.
+    last_classes: Vec<(u64, u64, u64)>,
+}
+
+/// Where a checkpoint left the snapshots off: the length of the output, and the interval being sampled.
+#[derive(Serialize, Deserialize)]
+pub struct SnapshotsCheckpoint {
+    length: u64,
+    queue_lengths: BTreeMap<usize, u64>,
+    last_classes: Vec<(u64, u64, u64)>,
+}
+
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    /// Last tick of the interval.
+    tick: u64,
+    /// Length of the queues, and requests in the system (waiting or being processed), at the end of the interval.
+    queue_len: usize,
+    in_flight: usize,
+    /// Pairs of a queue length and the number of ticks of the interval the queues were of that length.
+    queue_lengths: Vec<(usize, u64)>,
+    /// Pairs of an age in ticks, a power of two, and the number of requests in the system at most that old, and
+    /// older than half of it.
+    in_flight_ages: Vec<(u64, u64)>,
+    classes: Vec<ClassSnapshot<'a>>,
+}
+
+#[derive(Serialize)]
+struct ClassSnapshot<'a> {
+    name: &'a str,
+    /// Requests of the class that arrived, failed, and completed in time during the interval.
+    requests: u64,
+    failures: u64,
+    completed: u64,
+    in_flight: usize,
+}
+
+impl Snapshots {
+    /// Creates the snapshots writing to the specified output.
+    pub fn new(writer: Output, every: u64, classes: usize) -> Snapshots {
+        Snapshots {
+            every,
+            writer,
+            queue_lengths: BTreeMap::new(),
+            last_classes: vec![(0, 0, 0); classes],
+        }
+    }
+
+    /// Samples the state of the system at the end of the specified tick, and writes a snapshot at the end of
+    /// every interval.
+    pub fn tick(&mut self, tick: u64, pools: &[Pool], stats: &Stats) -> io::Result<()> {
+        let queue_len = pools.iter().map(Pool::queue_len).sum();
+        *self.queue_lengths.entry(queue_len).or_default() += 1;
+        if !(tick + 1).is_multiple_of(self.every) {
+            return Ok(());
+        }
+
+        let mut ages: BTreeMap<u64, u64> = BTreeMap::new();
+        let mut class_in_flight = vec![0; self.last_classes.len()];
+        for request in pools.iter().flat_map(Pool::requests) {
+            let age = (tick + 1).saturating_sub(request.arrival_tick());
+            *ages.entry(age.max(1).next_power_of_two()).or_default() += 1;
+            class_in_flight[request.class()] += 1;
+        }
+        let classes = stats
+            .classes
+            .iter()
+            .zip(self.last_classes.iter_mut())
+            .zip(class_in_flight)
+            .map(|((class, last), in_flight)| {
+                let totals = (
+                    class.total_requests,
+                    class.failed_requests,
+                    class.latency.total(),
+                );
+                let snapshot = ClassSnapshot {
+                    name: &class.name,
+                    requests: totals.0 - last.0,
+                    failures: totals.1 - last.1,
+                    completed: totals.2 - last.2,
+                    in_flight,
+                };
+                *last = totals;
+                snapshot
+            })
+            .collect();
+        let snapshot = Snapshot {
+            tick,
+            queue_len,
+            in_flight: ages.values().sum::<u64>() as usize,
+            queue_lengths: std::mem::take(&mut self.queue_lengths)
+                .into_iter()
+                .collect(),
+            in_flight_ages: ages.into_iter().collect(),
+            classes,
+        };
+        serde_json::to_writer(&mut self.writer, &snapshot)?;
+        writeln!(self.writer)
+    }
+
+    /// Takes the interval being sampled out for a checkpoint, after flushing the output.
+    pub fn checkpoint(&mut self) -> io::Result<SnapshotsCheckpoint> {
+        Ok(SnapshotsCheckpoint {
+            length: self.writer.checkpoint()?,
+            queue_lengths: std::mem::take(&mut self.queue_lengths),
+            last_classes: self.last_classes.clone(),
+        })
+    }
+
+    /// Cuts the output back to where the checkpoint left it off, and carries on sampling its interval.
+    pub fn resume(&mut self, checkpoint: SnapshotsCheckpoint) -> io::Result<()> {
+        self.writer.resume(checkpoint.length)?;
+        self.queue_lengths = checkpoint.queue_lengths;
+        self.last_classes = checkpoint.last_classes;
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,23 @@
+/// Duration of a latency spike without a --spike_duration, in ticks: 1% of the default simulation time. Fixed
+/// rather than a share of the simulation time, so that extending a run doesn't move the spike.
+pub const DEFAULT_DURATION: u64 = 10_000;
+
+/// A temporary spike in the request processing latency, such as when a server has a temporary slow down.
+/// This tends to be the condition that triggers the congestion collapse.
+#[derive(Debug, Clone, Copy)]
+pub struct Spike {
+    pub start: u64,
+    pub duration: u64,
+    /// Factor the latency of requests arriving during the spike gets multiplied by.
+    pub multiplier: f64,
+}
+
+impl Spike {
+    pub fn is_active(&self, tick: u64) -> bool {
+        tick >= self.start && tick - self.start < self.duration
+    }
+
+    pub fn end(&self) -> u64 {
+        self.start + self.duration
+    }
+}
@@ -0,0 +1,73 @@
+use rand::distributions::Distribution;
+use rand::Rng;
+use rand_distr::Normal;
+use serde::{Deserialize, Serialize};
+
+/// Pauses freezing all the workers at once, such as garbage collection or compaction pauses. Unlike the
+/// independent noise of the service times, the slowness is correlated: nothing gets done during a pause, while
+/// the requests keep arriving and aging in the queue, so every pause ends with a burst of queued up work.
+#[derive(Serialize, Deserialize)]
+pub struct Stalls {
+    /// Distribution of the ticks between the end of a pause and the start of the next one.
+    interval: Normal<f64>,
+    /// Distribution of the length of a pause, in ticks.
+    duration: Normal<f64>,
+    /// Tick the current or next pause starts at, and the tick it ends at.
+    start: u64,
+    end: u64,
+    /// Pauses that started, and ticks stalled, after the warm-up.
+    stalls: u64,
+    stalled_ticks: u64,
+}
+
+impl Stalls {
+    /// Pauses of about the specified mean duration, with about the specified mean interval between them. Both
+    /// are normally distributed, with a standard deviation of a quarter of the mean.
+    pub fn new<R: Rng>(mean_interval: f64, mean_duration: f64, rng: &mut R) -> Stalls {
+        let mut stalls = Stalls {
+            interval: Normal::new(mean_interval, mean_interval / 4.0).unwrap(),
+            duration: Normal::new(mean_duration, mean_duration / 4.0).unwrap(),
+            start: 0,
+            end: 0,
+            stalls: 0,
+            stalled_ticks: 0,
+        };
+        stalls.schedule(0, rng);
+        stalls
+    }
+
+    /// Whether the workers are frozen during the specified tick. Counts the pause if `measured`.
+    pub fn tick<R: Rng>(&mut self, tick: u64, measured: bool, rng: &mut R) -> bool {
+        if tick >= self.end {
+            self.schedule(self.end, rng);
+        }
+        let stalled = tick >= self.start;
+        if stalled && measured {
+            if tick == self.start {
+                self.stalls += 1;
+            }
+            self.stalled_ticks += 1;
+        }
+        stalled
+    }
+
+    /// Prints the pauses, out of the specified number of measured ticks.
+    pub fn print_summary(&self, ticks: u64) {
+        println!(
+            "Stalls: {} ({} ticks, {:.2}% of the time with all the workers frozen)",
+            self.stalls,
+            self.stalled_ticks,
+            self.stalled_ticks as f64 / ticks.max(1) as f64 * 100.0
+        );
+    }
+
+    /// Schedules the next pause after the specified tick.
+    fn schedule<R: Rng>(&mut self, after: u64, rng: &mut R) {
+        self.start = after + sample_ticks(&self.interval, rng);
+        self.end = self.start + sample_ticks(&self.duration, rng);
+    }
+}
+
+fn sample_ticks<R: Rng>(distribution: &Normal<f64>, rng: &mut R) -> u64 {
+    distribution.sample(rng).round().max(1.0) as u64
+}
@@ -0,0 +1,127 @@
+use crate::checkpoint;
+use crate::events::{Event, Observer, SystemState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Most storms printed one by one; the summary only counts the rest.
+const MAX_PRINTED_STORMS: usize = 10;
+
+/// Detects retry storms: stretches of consecutive windows of ticks during which retries made up more than a
+/// share of the arriving requests. A storm is the clients amplifying the load on a system that is already
+/// failing them, and it often keeps going long after whatever failed the first requests went away.
+#[derive(Serialize, Deserialize)]
+pub struct RetryStormDetector {
+    warmup_ticks: u64,
+    window: u64,
+    /// Share of the arrivals that have to be retries for the window to count as part of a storm.
+    min_share: f64,
+    /// First tick of the current window, and the requests that arrived and were retried during it.
+    window_start: u64,
+    arrived: u64,
+    retried: u64,
+    /// The ongoing storm, if there is one.
+    ongoing: Option<Storm>,
+    storms: Vec<Storm>,
+}
+
+/// A retry storm, from the first tick of the first window it was detected in to the first tick of the first
+/// window without it.
+#[derive(Serialize, Deserialize)]
+struct Storm {
+    start: u64,
+    /// First tick of the window with the highest share of retries, and the share.
+    peak: u64,
+    peak_share: f64,
+    end: Option<u64>,
+}
+
+impl RetryStormDetector {
+    pub fn new(warmup_ticks: u64, window: u64, min_share: f64) -> RetryStormDetector {
+        RetryStormDetector {
+            warmup_ticks,
+            window,
+            min_share,
+            window_start: warmup_ticks,
+            arrived: 0,
+            retried: 0,
+            ongoing: None,
+            storms: Vec::new(),
+        }
+    }
+}
+
+impl Observer for RetryStormDetector {
+    fn on_event(&mut self, tick: u64, event: &Event) {
+        if tick < self.warmup_ticks {
+            return;
+        }
+        match *event {
+            Event::Arrived { .. } => self.arrived += 1,
+            Event::Retried { .. } => self.retried += 1,
+            _ => {}
+        }
+    }
+
+    fn on_tick_end(&mut self, tick: u64, _state: &SystemState) {
+        if tick < self.warmup_ticks || tick + 1 - self.window_start < self.window {
+            return;
+        }
+
+        let share = self.retried as f64 / self.arrived.max(1) as f64;
+        if share > self.min_share {
+            let storm = self.ongoing.get_or_insert(Storm {
+                start: self.window_start,
+                peak: self.window_start,
+                peak_share: share,
+                end: None,
+            });
+            if share > storm.peak_share {
+                storm.peak = self.window_start;
+                storm.peak_share = share;
+            }
+        } else if let Some(mut storm) = self.ongoing.take() {
+            storm.end = Some(self.window_start);
+            self.storms.push(storm);
+        }
+        self.window_start = tick + 1;
+        self.arrived = 0;
+        self.retried = 0;
+    }
+
+    fn print_summary(&self) {
+        let storms: Vec<&Storm> = self.storms.iter().chain(self.ongoing.as_ref()).collect();
+        if storms.is_empty() {
+            println!(
+                "Retry storms: none detected (no {} tick window with retries over {:.0}% of the arrivals)",
+                self.window,
+                self.min_share * 100.0
+            );
+            return;
+        }
+
+        for storm in storms.iter().take(MAX_PRINTED_STORMS) {
+            let end = match storm.end {
+                Some(end) => format!("subsided at tick {}", end),
+                None => "never subsided".to_string(),
+            };
+            println!(
+                "Retry storm: started at tick {}, peaked at tick {} (retries {:.2}% of the arrivals), {}",
+                storm.start,
+                storm.peak,
+                storm.peak_share * 100.0,
+                end
+            );
+        }
+        if storms.len() > MAX_PRINTED_STORMS {
+            println!("Retry storms: {} more", storms.len() - MAX_PRINTED_STORMS);
+        }
+    }
+
+    fn checkpoint(&mut self) -> Result<Value, String> {
+        checkpoint::save(self)
+    }
+
+    fn resume(&mut self, state: Value) -> Result<(), String> {
+        checkpoint::restore(self, state)
+    }
+}
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Workers turning slow for a window of the run, like a single bad host in a fleet. Specified on the command
+/// line as `workers:start:duration:multiplier`, the first `workers` workers of every pool being the
+/// stragglers. Since the idle workers are picked in order, the stragglers are the first to get the work.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Straggler {
+    workers: u16,
+    start: u64,
+    duration: u64,
+    /// Factor the service time of the requests a straggler starts during the window gets multiplied by.
+    multiplier: f64,
+    /// Requests started during the window, by the stragglers and by all the workers.
+    straggler_requests: u64,
+    total_requests: u64,
+}
+
+impl FromStr for Straggler {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let invalid = || format!("Invalid straggler '{}'", s);
+        match parts.as_slice() {
+            [workers, start, duration, multiplier] => {
+                let straggler = Straggler {
+                    workers: workers.parse().map_err(|_| invalid())?,
+                    start: start.parse().map_err(|_| invalid())?,
+                    duration: duration.parse().map_err(|_| invalid())?,
+                    multiplier: multiplier.parse().map_err(|_| invalid())?,
+                    straggler_requests: 0,
+                    total_requests: 0,
+                };
+                if straggler.workers == 0 || straggler.duration == 0 || straggler.multiplier <= 0.0
+                {
+                    return Err(format!(
+                        "Invalid straggler '{}', workers, duration and multiplier have to be greater than 0",
+                        s
+                    ));
+                }
+                Ok(straggler)
+            }
+            _ => Err(format!(
+                "Expected straggler as workers:start:duration:multiplier, got '{}'",
+                s
+            )),
+        }
+    }
+}
+
+impl Straggler {
+    /// Number of straggling workers.
+    pub fn workers(&self) -> u16 {
+        self.workers
+    }
+
+    /// The specified worker started a request during the specified tick. Returns the factor its service time
+    /// gets multiplied by.
+    pub fn started(&mut self, worker: usize, tick: u64) -> f64 {
+        if tick < self.start || tick - self.start >= self.duration {
+            return 1.0;
+        }
+        self.total_requests += 1;
+        if worker < self.workers as usize {
+            self.straggler_requests += 1;
+            self.multiplier
+        } else {
+            1.0
+        }
+    }
+
+    /// Prints the share of the requests started during the window the stragglers took, next to their fair
+    /// share among the specified number of workers, naming their pool if specified.
+    pub fn print_summary(&self, pool: Option<&str>, workers: usize) {
+        println!(
+            "Stragglers{}: {} of {} workers {}x slower during ticks {}-{} started {:.2}% of the requests (fair share: {:.2}%)",
+            pool.map(|name| format!(" of pool {}", name))
+                .unwrap_or_default(),
+            self.workers,
+            workers,
+            self.multiplier,
+            self.start,
+            self.start + self.duration,
+            self.straggler_requests as f64 / self.total_requests.max(1) as f64 * 100.0,
+            self.workers as f64 / workers.max(1) as f64 * 100.0
+        );
+    }
+}
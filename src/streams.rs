@@ -0,0 +1,59 @@
+use crate::Opt;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+/// Stream of the random order queue of the first pool, the next pools taking the next ones, after the streams
+/// of the run.
+const QUEUE_STREAMS: u64 = 5;
+
+/// Independent streams of random numbers, one for each source of randomness of a run. A change affecting how
+/// many numbers one of them draws, e.g. a different retry probability, leaves the sequences of the others alone,
+/// so that two configurations compared with the same seed see the same arrivals and service times (common
+/// random numbers), and the difference in their outcomes comes from the change rather than from the noise.
+///
+/// The streams are derived from the seed of the run, and the first four can be seeded on their own, e.g. to try
+/// other arrivals while keeping everything else the same.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RngStreams {
+    /// Number of requests arriving during every tick, the points within the tick they arrive at, and their
+    /// classes.
+    pub arrivals: ChaCha12Rng,
+    /// Service times of the requests, and their sizes and database query times.
+    pub service: ChaCha12Rng,
+    /// Clients' decisions whether to retry a failed request.
+    pub retries: ChaCha12Rng,
+    /// Injected failures: the stalls freezing the workers. The autoscaler's workers failing to start, or starting
+    /// slowly, draw from generators of their own, derived from the same seed.
+    pub failures: ChaCha12Rng,
+    /// Everything else: the clients' balking, patience and think times, network delays, load balancing,
+    /// and which requests get a degraded response in a brownout. The random order queues draw from generators
+    /// of their own, on the streams after this one.
+    pub other: ChaCha12Rng,
+}
+
+impl RngStreams {
+    /// Streams of a run with the specified seed, the ones with a seed of their own in the options aside.
+    pub fn new(opt: &Opt, seed: u64) -> RngStreams {
+        let stream = |index, own_seed: Option<u64>| {
+            let mut rng = ChaCha12Rng::seed_from_u64(own_seed.unwrap_or(seed));
+            rng.set_stream(index);
+            rng
+        };
+        RngStreams {
+            arrivals: stream(0, opt.arrival_seed),
+            service: stream(1, opt.service_seed),
+            retries: stream(2, opt.retry_seed),
+            failures: stream(3, opt.failure_seed),
+            other: stream(4, None),
+        }
+    }
+
+    /// Generator of its own for the random order queue of the pool with the specified index, in a run with the
+    /// specified seed.
+    pub fn queue_stream(seed: u64, pool: usize) -> ChaCha12Rng {
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        rng.set_stream(QUEUE_STREAMS + pool as u64);
+        rng
+    }
+}
@@ -0,0 +1,103 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time a process gets to exit once its standard input is closed, before it's killed.
+const EXIT_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// External process the simulator talks to with a line-based protocol: every request is a single line written
+/// to its standard input, answered by a single line on its standard output.
+pub struct Subprocess {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Subprocess {
+    /// Starts the process, using the shell to run the specified command.
+    pub fn spawn(command: &str) -> Result<Subprocess, String> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| e.to_string())?;
+        let stdin = child.stdin.take();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        Ok(Subprocess {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    /// Sends the request line, and waits for the response line.
+    pub fn request(&mut self, request: &str) -> Result<String, String> {
+        let stdin = self.stdin.as_mut().unwrap();
+        writeln!(stdin, "{}", request)
+            .and_then(|_| stdin.flush())
+            .map_err(|e| format!("Failed to send request: {}", e))?;
+
+        let mut response = String::new();
+        match self.stdout.read_line(&mut response) {
+            Ok(0) => Err("Process exited".to_string()),
+            Ok(_) => Ok(response),
+            Err(e) => Err(format!("Failed to read response: {}", e)),
+        }
+    }
+}
+
+impl Drop for Subprocess {
+    fn drop(&mut self) {
+        // Closing the standard input tells the process there won't be any more requests. A process that ignores
+        // it, or keeps writing to its standard output no one reads any more, is killed after a grace period.
+        self.stdin.take();
+        let deadline = Instant::now() + EXIT_GRACE_PERIOD;
+        while Instant::now() < deadline {
+            match self.child.try_wait() {
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                _ => return,
+            }
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_answered_line_by_line() {
+        let mut process =
+            Subprocess::spawn("while read line; do echo \"re: $line\"; done").unwrap();
+        assert_eq!(process.request("one").unwrap(), "re: one\n");
+        assert_eq!(process.request("two").unwrap(), "re: two\n");
+    }
+
+    #[test]
+    fn exited_process_fails_the_request() {
+        let mut process = Subprocess::spawn("read line; echo done").unwrap();
+        assert_eq!(process.request("first").unwrap(), "done\n");
+        // Either the request or its response, depending on how far the process got with exiting.
+        assert!(process.request("second").is_err());
+    }
+
+    #[test]
+    fn process_ignoring_the_end_of_its_input_is_killed() {
+        for command in ["yes garbage", "sleep 60"] {
+            let started = Instant::now();
+            drop(Subprocess::spawn(command).unwrap());
+            assert!(
+                started.elapsed() < EXIT_GRACE_PERIOD * 5,
+                "{} took {:?}",
+                command,
+                started.elapsed()
+            );
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use crate::metadata;
+use crate::simulation::{self, Stats};
+use crate::validation::{self, ConfigError};
+use crate::Opt;
+use rand::{thread_rng, Rng};
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{self, Write};
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// Parameters that can be swept, named after their command line flags.
+const PARAMETERS: &[&str] = &[
+    "arrival_rate",
+    "workers",
+    "timeout",
+    "mean_latency",
+    "queue_size",
+    "retry_probability",
+    "fan_out",
+    "spike_multiplier",
+    "queue_latency_penalty",
+    "db_query_latency",
+    "db_contention",
+];
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct SweepOpt {
+    /// Parameter to vary, named after its command line flag, e.g. arrival_rate.
+    #[structopt(long = "parameter")]
+    parameter: String,
+
+    /// First value of the parameter.
+    #[structopt(long = "from")]
+    from: f64,
+
+    /// Last value of the parameter.
+    #[structopt(long = "to")]
+    to: f64,
+
+    /// Difference between two consecutive values of the parameter.
+    #[structopt(long = "step")]
+    step: f64,
+
+    /// File to write the results to, as CSV. Defaults to stdout.
+    #[structopt(long = "output")]
+    output: Option<String>,
+
+    /// Number of times to run the simulation for each value, each time with a different seed. The results of
+    /// the replications are merged.
+    #[structopt(long = "replications", default_value = "1")]
+    replications: u32,
+}
+
+/// Range of values of a parameter, specified as `from:to:step`.
+#[derive(Debug, Clone, Copy)]
+pub struct Range {
+    from: f64,
+    to: f64,
+    step: f64,
+}
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct PhaseOpt {
+    /// Range of client timeouts, as from:to:step.
+    #[structopt(long = "timeout", default_value = "100:1000:100")]
+    timeouts: Range,
+
+    /// Range of server queue sizes, as from:to:step.
+    #[structopt(long = "queue_size", default_value = "0:1000:100")]
+    queue_sizes: Range,
+
+    /// File to write the results to, as CSV. Defaults to stdout.
+    #[structopt(long = "output")]
+    output: Option<String>,
+
+    /// Number of times to run the simulation for each combination, each time with a different seed. The
+    /// results of the replications are merged.
+    #[structopt(long = "replications", default_value = "1")]
+    replications: u32,
+}
+
+impl FromStr for Range {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<f64> = s
+            .split(':')
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("Expected range as from:to:step, got '{}'", s))?;
+        match parts[..] {
+            [from, to, step] => Range::new(from, to, step),
+            _ => Err(format!("Expected range as from:to:step, got '{}'", s)),
+        }
+    }
+}
+
+impl Range {
+    fn new(from: f64, to: f64, step: f64) -> Result<Range, String> {
+        if step <= 0.0 || to < from {
+            return Err(
+                "Sweep step has to be greater than 0, and the last value can't be lower than the first"
+                    .to_string(),
+            );
+        }
+        Ok(Range { from, to, step })
+    }
+
+    fn values(&self) -> Vec<f64> {
+        let points = ((self.to - self.from) / self.step + 1e-9).floor() as u64;
+        (0..=points)
+            // Rounded, so that the accumulated floating point error doesn't end up in the output.
+            .map(|point| ((self.from + point as f64 * self.step) * 1e9).round() / 1e9)
+            .collect()
+    }
+}
+
+/// Runs a simulation for every value of the swept parameter, with the rest of the options as specified, and
+/// writes a CSV row with the results of each.
+pub fn run(opt: &Opt, sweep: &SweepOpt) {
+    validate(opt, sweep.replications).unwrap_or_else(|error| error.exit());
+    if !PARAMETERS.contains(&sweep.parameter.as_str()) {
+        ConfigError::Invalid(format!(
+            "Can't sweep '{}', expected one of {}",
+            sweep.parameter,
+            PARAMETERS.join(", ")
+        ))
+        .exit();
+    }
+    let range = Range::new(sweep.from, sweep.to, sweep.step)
+        .unwrap_or_else(|e| ConfigError::Invalid(e).exit());
+
+    let mut out = create_output(sweep.output.as_ref());
+    let cost = opt.cost();
+    write!(out, "{}", metadata::comment_lines(opt)).unwrap_or_else(write_failed);
+    writeln!(
+        out,
+        "{},requests,failure_rate,latency_mean,latency_p50,latency_p90,latency_p99,latency_p999,latency_max{}",
+        sweep.parameter,
+        if cost.is_some() {
+            ",cost,worker_cost,failure_cost"
+        } else {
+            ""
+        }
+    )
+    .unwrap_or_else(write_failed);
+
+    let values = range.values();
+    let points = values
+        .iter()
+        .map(|&value| {
+            let mut point_opt = opt.clone();
+            set_parameter(&mut point_opt, &sweep.parameter, value);
+            validation::validate_or_exit(&point_opt);
+            point_opt
+        })
+        .collect();
+    let results = run_points(points, sweep.replications);
+
+    for (value, stats) in values.iter().zip(results.iter()) {
+        let costs = match cost.as_ref() {
+            Some(cost) => {
+                let (workers, failures) = cost.split(stats);
+                format!(",{:.2},{:.2},{:.2}", workers + failures, workers, failures)
+            }
+            None => String::new(),
+        };
+        writeln!(
+            out,
+            "{},{},{:.4},{:.2},{},{},{},{},{}{}",
+            value,
+            stats.total_requests,
+            stats.failure_rate(),
+            stats.latency.mean(),
+            stats.latency.percentile(50.0),
+            stats.latency.percentile(90.0),
+            stats.latency.percentile(99.0),
+            stats.latency.percentile(99.9),
+            stats.latency.max(),
+            costs
+        )
+        .and_then(|_| out.flush())
+        .unwrap_or_else(write_failed);
+    }
+}
+
+/// Runs a simulation for every combination of client timeout and server queue size, and writes the goodput
+/// (successful requests per tick) of each as a grid, with a row per queue size and a column per timeout. The
+/// two interact, and are the most commonly mis-tuned pair of knobs.
+pub fn run_phase(opt: &Opt, phase: &PhaseOpt) {
+    validate(opt, phase.replications).unwrap_or_else(|error| error.exit());
+    let timeouts = phase.timeouts.values();
+    let queue_sizes = phase.queue_sizes.values();
+    let mut out = create_output(phase.output.as_ref());
+    let header: Vec<String> = timeouts.iter().map(f64::to_string).collect();
+    write!(out, "{}", metadata::comment_lines(opt)).unwrap_or_else(write_failed);
+    writeln!(out, "queue_size/timeout,{}", header.join(",")).unwrap_or_else(write_failed);
+
+    let mut points = Vec::new();
+    for &queue_size in queue_sizes.iter() {
+        for &timeout in timeouts.iter() {
+            let mut point_opt = opt.clone();
+            set_parameter(&mut point_opt, "queue_size", queue_size);
+            set_parameter(&mut point_opt, "timeout", timeout);
+            validation::validate_or_exit(&point_opt);
+            points.push(point_opt);
+        }
+    }
+    let results = run_points(points, phase.replications);
+
+    let measured_ticks =
+        (opt.simulation_ticks as u64 - opt.warmup_ticks) * phase.replications as u64;
+    for (queue_size, row_results) in queue_sizes.iter().zip(results.chunks(timeouts.len())) {
+        let mut row = vec![queue_size.to_string()];
+        for stats in row_results {
+            let goodput = stats.latency.total() as f64 / measured_ticks as f64;
+            row.push(format!("{:.4}", goodput));
+        }
+        writeln!(out, "{}", row.join(","))
+            .and_then(|_| out.flush())
+            .unwrap_or_else(write_failed);
+    }
+}
+
+/// Exits with an explanation unless there is at least one replication.
+/// Checks the replications of a sweep or phase, and that its runs don't write files of their own, which the runs
+/// in parallel would overwrite each other's.
+fn validate(opt: &Opt, replications: u32) -> Result<(), ConfigError> {
+    if replications == 0 {
+        return Err(ConfigError::OutOfRange {
+            parameter: "replications",
+            expected: "at least 1",
+        });
+    }
+    let outputs = [
+        (opt.time_series.is_some(), "timeseries"),
+        (opt.snapshots.is_some(), "snapshots"),
+        (opt.event_log.is_some(), "event_log"),
+        (opt.chrome_trace.is_some(), "chrome_trace"),
+        (opt.checkpoint_file.is_some(), "checkpoint_file"),
+    ];
+    match outputs.iter().find(|(set, _)| *set) {
+        Some((_, name)) => Err(ConfigError::Invalid(format!(
+            "Sweep and phase runs can't write --{}, since the runs in parallel would overwrite each other's. \
+             Leave it out, or write it from a single run",
+            name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Runs the simulation the specified number of times for every point, on all cores, and merges the results of
+/// each point. Every replication has its own seed, derived from the configured one (or a random one). The
+/// points share the seeds, so they all see the same arrivals.
+fn run_points(points: Vec<Opt>, replications: u32) -> Vec<Stats> {
+    let seed = points
+        .first()
+        .and_then(|opt| opt.seed)
+        .unwrap_or_else(|| thread_rng().gen());
+    let runs: Vec<(usize, u32)> = (0..points.len())
+        .flat_map(|point| (0..replications).map(move |replication| (point, replication)))
+        .collect();
+    let results: Vec<(usize, Stats)> = runs
+        .par_iter()
+        .map(|&(point, replication)| {
+            let mut run_opt = points[point].clone();
+            run_opt.seed = Some(seed.wrapping_add(replication as u64));
+            run_opt.progress = false;
+            run_opt.tui = false;
+            run_opt.metrics_address = None;
+            run_opt.speed = None;
+            (point, simulation::run(&run_opt).stats)
+        })
+        .collect();
+
+    let mut merged: Vec<Option<Stats>> = (0..points.len()).map(|_| None).collect();
+    for (point, stats) in results {
+        match merged[point].as_mut() {
+            Some(merged) => merged.merge(&stats),
+            None => merged[point] = Some(stats),
+        }
+    }
+    merged.into_iter().map(Option::unwrap).collect()
+}
+
+fn create_output(path: Option<&String>) -> Box<dyn Write> {
+    match path {
+        Some(path) => Box::new(
+            File::create(path)
+                .unwrap_or_else(|e| ConfigError::io("create sweep output", path, e).exit()),
+        ),
+        None => Box::new(io::stdout()),
+    }
+}
+
+fn write_failed(error: io::Error) {
+    ConfigError::Io {
+        action: "write sweep results".to_string(),
+        error: error.to_string(),
+    }
+    .exit()
+}
+
+fn set_parameter(opt: &mut Opt, parameter: &str, value: f64) {
+    match parameter {
+        "arrival_rate" => opt.request_arrival_rate = Some(value),
+        "workers" => opt.num_workers = value.round() as u16,
+        "timeout" => opt.request_timeout = value.round() as u32,
+        "mean_latency" => opt.mean_request_latency = value,
+        "queue_size" => opt.queue_size = value.round() as usize,
+        "retry_probability" => opt.retry_probability = value,
+        "fan_out" => opt.fan_out = value.round() as u32,
+        "spike_multiplier" => opt.spike_multipliers = vec![value],
+        "queue_latency_penalty" => opt.queue_latency_penalty = value,
+        "db_query_latency" => opt.db_query_latency = value,
+        "db_contention" => opt.db_contention = value,
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opt(args: &str) -> Opt {
+        Opt::from_iter(std::iter::once("queueingsimulator").chain(args.split_whitespace()))
+    }
+
+    #[test]
+    fn ranges_include_both_ends_without_accumulating_errors() {
+        let range: Range = "0.1:0.5:0.1".parse().unwrap();
+        assert_eq!(range.values(), [0.1, 0.2, 0.3, 0.4, 0.5]);
+        assert_eq!("5:5:1".parse::<Range>().unwrap().values(), [5.0]);
+        assert!("1:0:1".parse::<Range>().is_err());
+        assert!("0:1:0".parse::<Range>().is_err());
+        assert!("0:1".parse::<Range>().is_err());
+    }
+
+    #[test]
+    fn every_parameter_can_be_set() {
+        for parameter in PARAMETERS {
+            let mut point = opt("-r 0.1");
+            set_parameter(&mut point, parameter, 2.0);
+        }
+        let mut point = opt("-r 0.1");
+        set_parameter(&mut point, "workers", 14.6);
+        assert_eq!(point.num_workers, 15);
+    }
+
+    #[test]
+    fn runs_writing_files_of_their_own_are_refused() {
+        assert!(validate(&opt("-r 0.1"), 3).is_ok());
+        assert!(validate(&opt("-r 0.1"), 0).is_err());
+        for output in [
+            "--timeseries -",
+            "--snapshots snapshots.ndjson",
+            "--event_log events.ndjson",
+            "--chrome_trace trace.json",
+            "--checkpoint_file checkpoint.json",
+        ] {
+            assert!(
+                validate(&opt(&format!("-r 0.1 {}", output)), 1).is_err(),
+                "{}",
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn points_see_the_same_arrivals_and_merge_their_replications() {
+        let points: Vec<Opt> = [10, 20]
+            .iter()
+            .map(|workers| {
+                opt(&format!(
+                    "-r 0.3 --seed 4 --simulation_time 20000 --workers {}",
+                    workers
+                ))
+            })
+            .collect();
+        let single = run_points(points.clone(), 1);
+        let replicated = run_points(points, 3);
+        // Arrivals don't depend on the number of workers, and the retries aren't counted as requests.
+        let originals = |stats: &Stats| stats.attempts[0].total;
+        assert_eq!(originals(&single[0]), originals(&single[1]));
+        assert!(single[0].failure_rate() > single[1].failure_rate());
+        assert!(originals(&replicated[0]) > 2 * originals(&single[0]));
+        // With the same seed, the replications are the same every time.
+        let again = run_points(
+            vec![opt("-r 0.3 --seed 4 --simulation_time 20000 --workers 10")],
+            3,
+        );
+        assert_eq!(again[0].total_requests, replicated[0].total_requests);
+        assert_eq!(again[0].failed_requests, replicated[0].failed_requests);
+    }
+}
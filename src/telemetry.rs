@@ -0,0 +1,85 @@
+use crate::request::Request;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::mem;
+use std::time::Instant;
+
+/// Number of intervals the throughput of the simulation engine is reported for.
+const THROUGHPUT_INTERVALS: u64 = 10;
+
+/// Telemetry about the simulation engine itself, rather than the simulated system. Helps to tell apart
+/// the scenarios that hit the limits of the engine (e.g. huge queues slowing every tick down) from the ones
+/// that model genuine behavior.
+#[derive(Serialize, Deserialize)]
+pub struct Telemetry {
+    /// When the run started, or resumed: the engine's own speed is measured afresh.
+    #[serde(skip, default = "Instant::now")]
+    started: Instant,
+    #[serde(skip, default = "Instant::now")]
+    interval_started: Instant,
+    interval_ticks: u64,
+    ticks_per_second: Vec<f64>,
+    peak_queue_len: usize,
+    peak_queue_capacity: usize,
+}
+
+impl Telemetry {
+    pub fn new(simulation_ticks: u32) -> Telemetry {
+        let now = Instant::now();
+        Telemetry {
+            started: now,
+            interval_started: now,
+            interval_ticks: (simulation_ticks as u64 / THROUGHPUT_INTERVALS).max(1),
+            ticks_per_second: Vec::new(),
+            peak_queue_len: 0,
+            peak_queue_capacity: 0,
+        }
+    }
+
+    /// Records the state of the engine after the specified tick completed.
+    pub fn tick(&mut self, tick: u64, queue_len: usize, queue_capacity: usize) {
+        self.peak_queue_len = self.peak_queue_len.max(queue_len);
+        self.peak_queue_capacity = self.peak_queue_capacity.max(queue_capacity);
+
+        if (tick + 1).is_multiple_of(self.interval_ticks) {
+            let now = Instant::now();
+            let elapsed = now.duration_since(self.interval_started).as_secs_f64();
+            self.ticks_per_second
+                .push(self.interval_ticks as f64 / elapsed.max(f64::EPSILON));
+            self.interval_started = now;
+        }
+    }
+
+    pub fn print_summary(&self, simulation_ticks: u32) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        println!(
+            "Engine: {} ticks in {:.2}s ({:.0} ticks/s).",
+            simulation_ticks,
+            elapsed,
+            simulation_ticks as f64 / elapsed.max(f64::EPSILON)
+        );
+        let over_time: Vec<String> = self
+            .ticks_per_second
+            .iter()
+            .map(|rate| format!("{:.0}", rate))
+            .collect();
+        println!("Engine ticks/s over time: {}", over_time.join(" "));
+        println!(
+            "Engine peak queue length: {}, peak queue allocation: {} ({} KiB).",
+            self.peak_queue_len,
+            self.peak_queue_capacity,
+            self.peak_queue_capacity * mem::size_of::<Request>() / 1024
+        );
+        match memory_high_water_mark_kib() {
+            Some(kib) => println!("Engine memory high-water mark: {} KiB.", kib),
+            None => println!("Engine memory high-water mark: unavailable."),
+        }
+    }
+}
+
+/// Peak resident set size of this process. Only available on Linux, where it is read from procfs.
+fn memory_high_water_mark_kib() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
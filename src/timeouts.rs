@@ -0,0 +1,95 @@
+use crate::class::ClassSpec;
+use crate::histogram::Histogram;
+use crate::service::{LatencyDistribution, ServiceTime};
+use rand::distributions::Distribution;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Outcomes of the requests with a range of timeouts.
+#[derive(Default, Serialize, Deserialize)]
+struct Bucket {
+    total_requests: u64,
+    /// Latency of the requests that completed in time.
+    latency: Histogram,
+}
+
+/// Timeouts of the requests, when not all of the clients use the same one: either fixed for every class, or
+/// drawn from a distribution around the timeout of the class. Tracks how the requests with each timeout
+/// fared, since the clients with short timeouts give up (and retry) long before the others notice anything.
+#[derive(Serialize, Deserialize)]
+pub struct Timeouts {
+    /// Timeout of the requests of each class, or the mean of the distribution it's drawn from.
+    class_timeouts: Vec<u32>,
+    /// Distributions of the timeouts of each class, if they vary from request to request.
+    distributions: Option<Vec<ServiceTime>>,
+    /// Outcomes by the timeout, or by the power of two bounding it if the timeouts are drawn from distributions.
+    buckets: BTreeMap<u32, Bucket>,
+}
+
+impl Timeouts {
+    /// Timeouts of the specified classes, the ones without a timeout of their own using the default one.
+    pub fn new(
+        classes: &[ClassSpec],
+        default_timeout: u32,
+        distribution: Option<LatencyDistribution>,
+    ) -> Timeouts {
+        let class_timeouts: Vec<u32> = classes
+            .iter()
+            .map(|c| c.timeout.unwrap_or(default_timeout))
+            .collect();
+        Timeouts {
+            distributions: distribution.map(|distribution| {
+                class_timeouts
+                    .iter()
+                    .map(|&timeout| ServiceTime::new(distribution, timeout as f64))
+                    .collect()
+            }),
+            class_timeouts,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    /// Samples the timeout of a new request of the class, at least a tick.
+    pub fn sample<R: Rng>(&self, class: usize, rng: &mut R) -> u32 {
+        match self.distributions.as_ref() {
+            Some(distributions) => (distributions[class].sample(rng).round() as u32).max(1),
+            None => self.class_timeouts[class],
+        }
+    }
+
+    /// Counts a request with the specified timeout.
+    pub fn arrived(&mut self, timeout: u32) {
+        self.bucket(timeout).total_requests += 1;
+    }
+
+    /// Records the latency of a request with the specified timeout that completed in time.
+    pub fn completed(&mut self, timeout: u32, latency: u64) {
+        self.bucket(timeout).latency.record(latency);
+    }
+
+    pub fn print_summary(&self) {
+        for (&timeout, bucket) in self.buckets.iter() {
+            let range = match self.distributions {
+                Some(_) => format!("{}-{}", timeout, timeout.saturating_mul(2) - 1),
+                None => timeout.to_string(),
+            };
+            let failed = bucket.total_requests.saturating_sub(bucket.latency.total());
+            println!(
+                "Timeout {}: {} requests, failure rate: {:.2}%, latency: {}",
+                range,
+                bucket.total_requests,
+                failed as f64 / bucket.total_requests.max(1) as f64 * 100.0,
+                bucket.latency.format_percentiles()
+            );
+        }
+    }
+
+    fn bucket(&mut self, timeout: u32) -> &mut Bucket {
+        let key = match self.distributions {
+            Some(_) => 1 << timeout.ilog2(),
+            None => timeout,
+        };
+        self.buckets.entry(key).or_default()
+    }
+}
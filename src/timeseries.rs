@@ -0,0 +1,157 @@
+use crate::output::Output;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+/// Writes time series of the simulated system state as CSV. The state is sampled on every tick, but
+/// only emitted once per bucket of `bucket_ticks` ticks, aggregated to min/mean/max. This keeps output of
+/// very long runs plottable, without losing the extremes.
+pub struct TimeSeries {
+    bucket_ticks: u64,
+    writer: Output,
+    bucket: Bucket,
+    last_total_requests: u64,
+    last_failed_requests: u64,
+}
+
+/// Where a checkpoint left the time series off: the length of the output, and the bucket being aggregated.
+#[derive(Serialize, Deserialize)]
+pub struct TimeSeriesCheckpoint {
+    length: u64,
+    bucket: Bucket,
+    last_total_requests: u64,
+    last_failed_requests: u64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Bucket {
+    ticks: u64,
+    queue_len: MinMeanMax,
+    busy_workers: MinMeanMax,
+}
+
+/// Running min/mean/max aggregate of a sampled value.
+#[derive(Default, Serialize, Deserialize)]
+struct MinMeanMax {
+    min: u64,
+    max: u64,
+    sum: u64,
+    samples: u64,
+}
+
+impl TimeSeries {
+    /// Creates a time series writing to the specified output. `comments` are written before the header, unless the
+    /// output carries on from a resumed run.
+    pub fn new(writer: Output, bucket_ticks: u64, comments: &str) -> io::Result<TimeSeries> {
+        let mut time_series = TimeSeries {
+            bucket_ticks,
+            writer,
+            bucket: Bucket::default(),
+            last_total_requests: 0,
+            last_failed_requests: 0,
+        };
+        if time_series.writer.is_resumed() {
+            return Ok(time_series);
+        }
+        write!(time_series.writer, "{}", comments)?;
+        writeln!(
+            time_series.writer,
+            "tick,queue_min,queue_mean,queue_max,busy_min,busy_mean,busy_max,requests,failures,failure_rate"
+        )?;
+        Ok(time_series)
+    }
+
+    /// Samples the state of the system at the end of the specified tick. Total and failed requests are
+    /// cumulative counts since the beginning of the simulation.
+    pub fn tick(
+        &mut self,
+        tick: u64,
+        queue_len: usize,
+        busy_workers: usize,
+        total_requests: u64,
+        failed_requests: u64,
+    ) -> io::Result<()> {
+        self.bucket.ticks += 1;
+        self.bucket.queue_len.sample(queue_len as u64);
+        self.bucket.busy_workers.sample(busy_workers as u64);
+        if self.bucket.ticks < self.bucket_ticks {
+            return Ok(());
+        }
+
+        self.emit(tick, total_requests, failed_requests)
+    }
+
+    /// Takes the bucket being aggregated out for a checkpoint, after flushing the output.
+    pub fn checkpoint(&mut self) -> io::Result<TimeSeriesCheckpoint> {
+        Ok(TimeSeriesCheckpoint {
+            length: self.writer.checkpoint()?,
+            bucket: std::mem::take(&mut self.bucket),
+            last_total_requests: self.last_total_requests,
+            last_failed_requests: self.last_failed_requests,
+        })
+    }
+
+    /// Cuts the output back to where the checkpoint left it off, and carries on aggregating its bucket.
+    pub fn resume(&mut self, checkpoint: TimeSeriesCheckpoint) -> io::Result<()> {
+        self.writer.resume(checkpoint.length)?;
+        self.bucket = checkpoint.bucket;
+        self.last_total_requests = checkpoint.last_total_requests;
+        self.last_failed_requests = checkpoint.last_failed_requests;
+        Ok(())
+    }
+
+    /// Emits the last, possibly partial, bucket and flushes the output.
+    pub fn finish(
+        &mut self,
+        tick: u64,
+        total_requests: u64,
+        failed_requests: u64,
+    ) -> io::Result<()> {
+        if self.bucket.ticks > 0 {
+            self.emit(tick, total_requests, failed_requests)?;
+        }
+        self.writer.flush()
+    }
+
+    fn emit(&mut self, tick: u64, total_requests: u64, failed_requests: u64) -> io::Result<()> {
+        let requests = total_requests - self.last_total_requests;
+        let failures = failed_requests - self.last_failed_requests;
+        let failure_rate = if requests > 0 {
+            failures as f64 / requests as f64 * 100.0
+        } else {
+            0.0
+        };
+        let bucket = std::mem::take(&mut self.bucket);
+        writeln!(
+            self.writer,
+            "{},{},{:.2},{},{},{:.2},{},{},{},{:.2}",
+            tick + 1 - bucket.ticks,
+            bucket.queue_len.min,
+            bucket.queue_len.mean(),
+            bucket.queue_len.max,
+            bucket.busy_workers.min,
+            bucket.busy_workers.mean(),
+            bucket.busy_workers.max,
+            requests,
+            failures,
+            failure_rate
+        )?;
+        self.last_total_requests = total_requests;
+        self.last_failed_requests = failed_requests;
+        Ok(())
+    }
+}
+
+impl MinMeanMax {
+    fn sample(&mut self, value: u64) {
+        if self.samples == 0 || value < self.min {
+            self.min = value;
+        }
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.samples += 1;
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum as f64 / self.samples as f64
+    }
+}
@@ -0,0 +1,204 @@
+use crate::class::ClassSpec;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// A single arrival replayed from a trace.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TracedArrival {
+    pub tick: u64,
+    /// Point within the tick the request arrived at, if the trace recorded it.
+    pub offset: Option<f64>,
+    /// Class of the request, if the trace recorded it.
+    pub class: Option<usize>,
+}
+
+/// Arrivals recorded from real traffic, replayed instead of the synthetic arrival process. Times are in ticks,
+/// counted from the start of the simulation.
+///
+/// Traces are either CSV files with a header, or NDJSON files (recognized by the `.ndjson` or `.jsonl`
+/// extension), with one of two kinds of records:
+///
+/// * `tick,count` (or `{"tick": 5, "count": 3}`) - the number of requests that arrived during a tick.
+/// * `time` (or `{"time": 5.25}`) - a single request arriving at the specified, possibly fractional, tick.
+///
+/// Both can have an optional `class` column (or field) naming the class of the requests. Requests without one
+/// are assigned a class at random, according to the class weights.
+pub struct ArrivalTrace {
+    arrivals: Vec<TracedArrival>,
+    /// Index of the first arrival that hasn't been replayed yet.
+    next: usize,
+}
+
+/// A record of an NDJSON trace, or of a generator response.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Record {
+    tick: Option<u64>,
+    count: Option<u64>,
+    time: Option<f64>,
+    class: Option<String>,
+}
+
+impl Record {
+    /// Appends the arrivals the record stands for.
+    pub fn push_arrivals(
+        self,
+        classes: &[ClassSpec],
+        arrivals: &mut Vec<TracedArrival>,
+    ) -> Result<(), String> {
+        let class = match self.class {
+            Some(name) => Some(
+                classes
+                    .iter()
+                    .position(|c| c.name == name)
+                    .ok_or_else(|| format!("Unknown class '{}'", name))?,
+            ),
+            None => None,
+        };
+        match (self.tick, self.count, self.time) {
+            (Some(tick), Some(count), None) => arrivals.extend((0..count).map(|_| TracedArrival {
+                tick,
+                offset: None,
+                class,
+            })),
+            (None, None, Some(time)) if time >= 0.0 => arrivals.push(TracedArrival {
+                tick: time as u64,
+                offset: Some(time.fract()),
+                class,
+            }),
+            _ => return Err("Expected either tick and count, or a time".to_string()),
+        }
+        Ok(())
+    }
+}
+
+impl ArrivalTrace {
+    pub fn new(mut arrivals: Vec<TracedArrival>) -> ArrivalTrace {
+        arrivals.sort_by(|a, b| {
+            a.tick
+                .cmp(&b.tick)
+                .then(a.offset.unwrap_or(0.0).total_cmp(&b.offset.unwrap_or(0.0)))
+        });
+        ArrivalTrace { arrivals, next: 0 }
+    }
+
+    pub fn read(path: &str, classes: &[ClassSpec]) -> Result<ArrivalTrace, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read arrival trace '{}': {}", path, e))?;
+        let extension = Path::new(path).extension().and_then(|e| e.to_str());
+        let records = match extension {
+            Some("ndjson") | Some("jsonl") => parse_ndjson(&contents),
+            _ => parse_csv(&contents),
+        }
+        .map_err(|e| format!("Invalid arrival trace '{}': {}", path, e))?;
+
+        let mut arrivals = Vec::new();
+        for (line, record) in records {
+            record
+                .push_arrivals(classes, &mut arrivals)
+                .map_err(|e| format!("{} on line {} of '{}'", e, line, path))?;
+        }
+        Ok(ArrivalTrace::new(arrivals))
+    }
+
+    /// Index of the first arrival not replayed yet, for a checkpoint.
+    pub fn checkpoint(&self) -> usize {
+        self.next
+    }
+
+    /// Carries on replaying the trace from the arrival a checkpoint left off at.
+    pub fn resume(&mut self, next: usize) {
+        self.next = next.min(self.arrivals.len());
+    }
+
+    /// Arrivals recorded during the specified tick. Ticks have to be replayed in order.
+    pub fn arrivals_at(&mut self, tick: u64) -> &[TracedArrival] {
+        let start = self.next;
+        while self.next < self.arrivals.len() && self.arrivals[self.next].tick <= tick {
+            self.next += 1;
+        }
+        &self.arrivals[start..self.next]
+    }
+
+    /// Mean number of arrivals per tick over the specified number of ticks.
+    pub fn mean_rate(&self, ticks: u32) -> f64 {
+        let arrivals = self
+            .arrivals
+            .iter()
+            .filter(|a| a.tick < ticks as u64)
+            .count();
+        arrivals as f64 / ticks as f64
+    }
+}
+
+/// Parses a CSV trace into records, each with its line number.
+fn parse_csv(contents: &str) -> Result<Vec<(usize, Record)>, String> {
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+    let header: Vec<&str> = match lines.next() {
+        Some((_, header)) => header.split(',').map(str::trim).collect(),
+        None => return Ok(Vec::new()),
+    };
+    let column = |name: &str| header.iter().position(|&c| c == name);
+    let (tick, count, time, class) = (
+        column("tick"),
+        column("count"),
+        column("time"),
+        column("class"),
+    );
+    if let Some(unknown) = header
+        .iter()
+        .find(|c| !["tick", "count", "time", "class"].contains(c))
+    {
+        return Err(format!(
+            "Unknown column '{}', expected tick, count, time or class",
+            unknown
+        ));
+    }
+
+    lines
+        .map(|(line, contents)| {
+            let fields: Vec<&str> = contents.split(',').map(str::trim).collect();
+            if fields.len() != header.len() {
+                return Err(format!("Expected {} fields on line {}", header.len(), line));
+            }
+            let invalid = || format!("Invalid number on line {}", line);
+            Ok((
+                line,
+                Record {
+                    tick: tick
+                        .map(|i| fields[i].parse())
+                        .transpose()
+                        .map_err(|_| invalid())?,
+                    count: count
+                        .map(|i| fields[i].parse())
+                        .transpose()
+                        .map_err(|_| invalid())?,
+                    time: time
+                        .map(|i| fields[i].parse())
+                        .transpose()
+                        .map_err(|_| invalid())?,
+                    class: class.map(|i| fields[i].to_string()),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Parses an NDJSON trace into records, each with its line number.
+fn parse_ndjson(contents: &str) -> Result<Vec<(usize, Record)>, String> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .map(|record| (index + 1, record))
+                .map_err(|e| format!("{} on line {}", e, index + 1))
+        })
+        .collect()
+}
@@ -0,0 +1,48 @@
+use crate::simulation::{self, Summary};
+use crate::Opt;
+
+/// Reports how much of the failure rate is driven by the tail of the service time distribution. Reruns the
+/// simulation with the same seed for each of the configured caps on the service time, as if no request ever took
+/// longer than the cap, and compares the outcomes. A large share points at the tail (and at hedging or timeouts
+/// as the fix), a small one at the overall capacity.
+pub fn print_report(opt: &Opt, summary: &Summary) {
+    let failure_rate = summary.stats.failure_rate();
+    for &cap in opt.service_time_caps.iter() {
+        let mut capped = opt.clone();
+        capped.service_time_caps.clear();
+        capped.service_time_cap = Some(cap);
+        capped.batching_report = false;
+        capped.explain = false;
+        capped.queue_distribution = false;
+        capped.littles_law = false;
+        capped.telemetry = false;
+        capped.tui = false;
+        capped.metrics_address = None;
+        capped.speed = None;
+        capped.time_series = None;
+        capped.snapshots = None;
+        capped.event_log = None;
+        capped.chrome_trace = None;
+        capped.checkpoint_every = None;
+        capped.resume = false;
+        capped.html_report = None;
+        capped.alerts.clear();
+        let capped_failure_rate = simulation::run(&capped).stats.failure_rate();
+
+        let share = if failure_rate > 0.0 {
+            format!(
+                ", {:.0}% of the failure rate",
+                (failure_rate - capped_failure_rate) / failure_rate * 100.0
+            )
+        } else {
+            String::new()
+        };
+        println!(
+            "Service time capped at {} ticks: failure rate {:.2}% ({:+.2} points{})",
+            cap,
+            capped_failure_rate,
+            capped_failure_rate - failure_rate,
+            share
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use crate::simulation;
+use crate::validation;
+use crate::{print_summary, Opt};
+use structopt::StructOpt;
+
+/// A single step of the tutorial: a simulation to run, and what to look for in its results.
+struct Step {
+    title: &'static str,
+    args: &'static [&'static str],
+    explanation: &'static str,
+}
+
+const STEPS: &[Step] = &[
+    Step {
+        title: "Baseline",
+        args: &["-r", "0.1"],
+        explanation: "10 workers taking 50 ticks per request can serve 0.2 requests per tick, so at 0.1 the \
+            system is only half busy. Requests rarely wait in the queue, and none of them fail.",
+    },
+    Step {
+        title: "A latency spike",
+        args: &["-r", "0.1", "--simulate_spike", "--retry_policy", "none"],
+        explanation: "For a short while at the start, requests take 10x longer. The queue fills up, requests \
+            wait in it past their timeout, and fail. Look at the timeline: once the spike is over, the workers \
+            chew through the backlog and the system recovers on its own.",
+    },
+    Step {
+        title: "A latency spike, with retries",
+        args: &["-r", "0.1", "--simulate_spike"],
+        explanation: "Same spike, but now clients retry half of the failed requests. The retries add to the \
+            load exactly when the system can least afford it, so the queue stays full long after the spike is \
+            gone. Workers keep picking up requests whose clients already gave up - this is congestion collapse. \
+            Compare the tick at which the queue drained with the previous run.",
+    },
+    Step {
+        title: "A latency spike, with retries and load shedding",
+        args: &["-r", "0.1", "--simulate_spike", "--queue_size", "100"],
+        explanation: "Same spike and retries, but the queue only holds 100 requests. Excess requests get \
+            rejected right away instead of waiting past their timeout, so whatever the workers pick up can \
+            still succeed. Rejecting early sounds worse, yet the failure rate is a fraction of the previous run.",
+    },
+    Step {
+        title: "A latency spike, with retries and a LIFO queue",
+        args: &["-r", "0.1", "--simulate_spike", "--discipline", "lifo"],
+        explanation: "Back to the large queue, but serving the newest requests first. Fresh requests are \
+            likely to still be within their timeout, while the stale ones languish at the back of the queue. \
+            Try the other parameters described in the README next.",
+    },
+];
+
+/// Runs all tutorial steps one after another, printing what to look for in each of them.
+pub fn run() {
+    for (index, step) in STEPS.iter().enumerate() {
+        let mut args = vec!["queueingsimulator"];
+        args.extend_from_slice(step.args);
+        args.push("--explain");
+        let opt = Opt::from_iter(args.iter());
+        validation::validate_or_exit(&opt);
+
+        println!(
+            "=== Step {} of {}: {} ===",
+            index + 1,
+            STEPS.len(),
+            step.title
+        );
+        println!("$ {}", args.join(" "));
+        println!("{}", step.explanation);
+        println!();
+        let summary = simulation::run(&opt);
+        print_summary(&opt, &summary);
+        println!();
+    }
+}
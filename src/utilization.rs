@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+/// Time a single worker spent processing requests, out of the time it was available to, both in ticks and
+/// counted after the warm-up only.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct WorkerTime {
+    pub busy: f64,
+    pub available: f64,
+}
+
+impl WorkerTime {
+    /// Share of its available time the worker was busy.
+    pub fn utilization(&self) -> f64 {
+        if self.available == 0.0 {
+            0.0
+        } else {
+            self.busy / self.available
+        }
+    }
+}
+
+/// Share of the time the workers spent processing requests, in aggregate. Utilization close to 1 is the leading
+/// indicator of a collapse: the queue has no spare capacity left to drain into once anything goes wrong.
+pub fn aggregate(workers: &[WorkerTime]) -> f64 {
+    let busy: f64 = workers.iter().map(|w| w.busy).sum();
+    let available: f64 = workers.iter().map(|w| w.available).sum();
+    if available == 0.0 {
+        0.0
+    } else {
+        busy / available
+    }
+}
+
+/// Prints the aggregate utilization and its distribution across the workers. The distribution is uneven when
+/// idle workers are picked in a fixed order, the first ones getting the bulk of the work at low load.
+pub fn print_summary(workers: &[WorkerTime]) {
+    let mut utilizations: Vec<f64> = workers.iter().map(WorkerTime::utilization).collect();
+    if utilizations.is_empty() {
+        return;
+    }
+    utilizations.sort_by(f64::total_cmp);
+    println!(
+        "Utilization: {:.2}% (per worker: min={:.2}% p50={:.2}% max={:.2}%)",
+        aggregate(workers) * 100.0,
+        utilizations[0] * 100.0,
+        utilizations[utilizations.len() / 2] * 100.0,
+        utilizations[utilizations.len() - 1] * 100.0
+    );
+}
@@ -0,0 +1,713 @@
+use crate::balancer::Routing;
+use crate::checkpoint;
+use crate::eventdriven::{self, Engine};
+use crate::pool;
+use crate::queue::Discipline;
+use crate::quota::Quotas;
+use crate::region::Failover;
+use crate::resource::Resource;
+use crate::retrypolicy;
+use crate::shedding::Shedding;
+use crate::Opt;
+use std::fmt;
+use std::process;
+
+/// Exit status of a run with parameters that don't make sense, telling it apart from a run that went over one
+/// of its thresholds.
+const EXIT_STATUS: i32 = 2;
+
+/// Parameters that don't make sense, on their own or in combination with each other. Every error says what to
+/// change to fix it.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Neither an arrival rate, nor any other source of arrivals.
+    MissingArrivals,
+    /// Value of a parameter out of the range it has to be in.
+    OutOfRange {
+        parameter: &'static str,
+        expected: &'static str,
+    },
+    /// Parameter that only makes sense with another one.
+    Requires {
+        parameter: &'static str,
+        required: &'static str,
+    },
+    /// Parameters that can't be combined, and what to do instead.
+    Conflict {
+        problem: &'static str,
+        fix: &'static str,
+    },
+    /// Feature the event engine doesn't support.
+    UnsupportedByEngine(&'static str),
+    /// Feature the checkpoints don't cover.
+    UnsupportedByCheckpoints(&'static str),
+    /// Value a parameter rejected on its own, such as an unreadable trace or an unknown class, with the reason.
+    Invalid(String),
+    /// File, process or address of a parameter that couldn't be used, such as an output file in a directory
+    /// that doesn't exist, with what the run tried to do with it.
+    Io { action: String, error: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MissingArrivals => write!(
+                f,
+                "No arrivals to simulate. Set --arrival_rate, or replay an --arrival_trace, run a --generator \
+                 or simulate closed-loop --clients instead"
+            ),
+            ConfigError::OutOfRange {
+                parameter,
+                expected,
+            } => write!(f, "--{} has to be {}", parameter, expected),
+            ConfigError::Requires {
+                parameter,
+                required,
+            } => write!(f, "--{} requires --{}, set it too", parameter, required),
+            ConfigError::Conflict { problem, fix } => write!(f, "{}. {}", problem, fix),
+            ConfigError::UnsupportedByEngine(feature) => write!(
+                f,
+                "The event engine doesn't support {}. Use --engine tick, or leave it out",
+                feature
+            ),
+            ConfigError::UnsupportedByCheckpoints(feature) => write!(
+                f,
+                "Checkpoints don't support {}. Leave out either it, or --checkpoint_every and --resume",
+                feature
+            ),
+            ConfigError::Invalid(reason) => write!(f, "{}", reason),
+            ConfigError::Io { action, error } => write!(f, "Failed to {}: {}", action, error),
+        }
+    }
+}
+
+impl ConfigError {
+    /// Failure to do something with the file, process or address of a parameter, e.g. `io("write plot", path,
+    /// error)`.
+    pub fn io(action: &str, target: &str, error: impl fmt::Display) -> ConfigError {
+        ConfigError::Io {
+            action: format!("{} '{}'", action, target),
+            error: error.to_string(),
+        }
+    }
+
+    /// Prints the error, and exits.
+    pub fn exit(&self) -> ! {
+        eprintln!("error: {}", self);
+        process::exit(EXIT_STATUS);
+    }
+}
+
+fn out_of_range(parameter: &'static str, expected: &'static str) -> ConfigError {
+    ConfigError::OutOfRange {
+        parameter,
+        expected,
+    }
+}
+
+fn conflict(problem: &'static str, fix: &'static str) -> ConfigError {
+    ConfigError::Conflict { problem, fix }
+}
+
+/// Checks that the simulation parameters make sense, on their own and in combination, including the
+/// combinations of the features that can't be used together.
+pub fn validate(opt: &Opt) -> Result<(), ConfigError> {
+    validate_arrivals(opt)?;
+    validate_service(opt)?;
+    validate_clients(opt)?;
+    validate_outputs(opt)?;
+    validate_topology(opt)
+}
+
+/// Validates the parameters and exits with an explanation if they don't make sense.
+pub fn validate_or_exit(opt: &Opt) {
+    if let Err(error) = validate(opt) {
+        error.exit();
+    }
+}
+
+/// Prints warnings about parameters that are valid, but unlikely to be what was meant.
+pub fn print_warnings(opt: &Opt) {
+    let mean_latency = match opt.service_time() {
+        Ok(service_time) => service_time.mean(),
+        Err(_) => return,
+    };
+    if opt.request_timeout as f64 <= mean_latency {
+        eprintln!(
+            "warning: the --timeout of {} is no longer than the mean latency of {:.2}, so most requests will time \
+             out even with idle workers. Raise the timeout above the mean latency for a meaningful simulation",
+            opt.request_timeout, mean_latency
+        );
+    }
+    if !opt.retry_policies.is_empty() && opt.sets_retry_probabilities() {
+        eprintln!(
+            "warning: the retry probability options are deprecated, and only apply to the classes without a \
+             --retry_policy. Use --retry_policy probability:P instead"
+        );
+    }
+}
+
+fn validate_arrivals(opt: &Opt) -> Result<(), ConfigError> {
+    let sources = [
+        opt.arrival_trace.is_some(),
+        opt.generator.is_some(),
+        opt.clients.is_some(),
+    ];
+    if sources.contains(&true) {
+        if opt.request_arrival_rate.is_some()
+            || !opt.arrival_schedule().unwrap_or_default().is_empty()
+            || sources.iter().filter(|&&s| s).count() > 1
+        {
+            return Err(conflict(
+                "Arrival trace, generator, closed-loop clients, and arrival rate or schedule can't be combined",
+                "Pick just one source of arrivals",
+            ));
+        }
+        opt.arrival_trace(&opt.classes())
+            .map_err(ConfigError::Invalid)?;
+        if opt.generator.is_some() && opt.export.is_some() {
+            return Err(conflict(
+                "Generated arrivals can't be exported",
+                "Record them into an --arrival_trace, and export that instead",
+            ));
+        }
+        if opt.clients.is_some() && opt.export.is_some() {
+            return Err(conflict(
+                "Closed-loop workloads can't be exported",
+                "Export an open-loop --arrival_rate instead",
+            ));
+        }
+        if opt.clients == Some(0) {
+            return Err(out_of_range("clients", "greater than 0"));
+        }
+        if opt.think_time < 0.0 {
+            return Err(out_of_range("think_time", "at least 0"));
+        }
+    } else {
+        match opt.request_arrival_rate {
+            None => return Err(ConfigError::MissingArrivals),
+            Some(rate) if rate <= 0.0 => {
+                return Err(out_of_range("arrival_rate", "greater than 0"))
+            }
+            Some(_) => {}
+        }
+        opt.arrival_schedule().map_err(ConfigError::Invalid)?;
+    }
+    if opt.simulation_ticks == 0 {
+        return Err(out_of_range("simulation_time", "greater than 0"));
+    }
+    if opt.warmup_ticks >= opt.simulation_ticks as u64 {
+        return Err(out_of_range(
+            "warmup_ticks",
+            "shorter than the --simulation_time",
+        ));
+    }
+    if opt.spike_multipliers.iter().any(|&m| m <= 0.0) {
+        return Err(out_of_range("spike_multiplier", "greater than 0"));
+    }
+    if let Some(spike_class) = opt.spike_class.as_ref() {
+        if !opt.classes().iter().any(|c| &c.name == spike_class) {
+            return Err(ConfigError::Invalid(format!(
+                "Spike class '{}' is not a known class. Define it with --class, or leave out --spike_class",
+                spike_class
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_service(opt: &Opt) -> Result<(), ConfigError> {
+    if opt.mean_request_latency <= 0.0 {
+        return Err(out_of_range("mean_latency", "greater than 0"));
+    }
+    opt.service_time().map_err(ConfigError::Invalid)?;
+    if opt.request_timeout == 0 {
+        return Err(out_of_range("timeout", "greater than 0"));
+    }
+    if opt.pools().iter().any(|p| p.workers == 0) {
+        return Err(out_of_range("workers", "greater than 0, in every pool"));
+    }
+    if opt.autoscale_max_workers != 0 && opt.autoscale_max_workers < opt.num_workers {
+        return Err(out_of_range(
+            "autoscale_max_workers",
+            "0 (disabled) or at least the number of --workers",
+        ));
+    }
+    if !(0.0..=1.0).contains(&opt.worker_start_failure_probability) {
+        return Err(out_of_range(
+            "worker_start_failure_probability",
+            "between 0 and 1",
+        ));
+    }
+    if !(0.0..=1.0).contains(&opt.worker_slow_start_probability) {
+        return Err(out_of_range(
+            "worker_slow_start_probability",
+            "between 0 and 1",
+        ));
+    }
+    if opt.fan_out == 0 {
+        return Err(out_of_range("fan_out", "at least 1"));
+    }
+    if opt.fan_out_quorum() == 0 || opt.fan_out_quorum() > opt.fan_out {
+        return Err(out_of_range(
+            "fan_out_quorum",
+            "between 1 and the --fan_out",
+        ));
+    }
+    if opt.queue_latency_penalty < 0.0 {
+        return Err(out_of_range("queue_latency_penalty", "at least 0"));
+    }
+    if opt.db_query_latency < 0.0 {
+        return Err(out_of_range("db_query_latency", "at least 0"));
+    }
+    if opt.db_contention < 0.0 {
+        return Err(out_of_range("db_contention", "at least 0"));
+    }
+    if opt.db_query_latency == 0.0 && opt.chaos.iter().any(|spec| spec.fault().is_database()) {
+        return Err(ConfigError::Requires {
+            parameter: "chaos",
+            required: "db_query_latency",
+        });
+    }
+    if opt.stall_interval.is_some_and(|i| i <= 0.0) {
+        return Err(out_of_range("stall_interval", "greater than 0"));
+    }
+    if opt.stall_duration <= 0.0 {
+        return Err(out_of_range("stall_duration", "greater than 0"));
+    }
+    if opt.service_time_caps.iter().any(|&cap| cap <= 0.0) {
+        return Err(out_of_range("truncate_service_time", "greater than 0"));
+    }
+    if opt.queue_timeout == Some(0) {
+        return Err(out_of_range("queue_timeout", "greater than 0"));
+    }
+    if opt.brownout_queue_length.is_some() {
+        if opt.brownout_fraction <= 0.0 || opt.brownout_fraction > 1.0 {
+            return Err(out_of_range(
+                "brownout_fraction",
+                "greater than 0 and at most 1",
+            ));
+        }
+        if opt.brownout_service_fraction <= 0.0 || opt.brownout_service_fraction > 1.0 {
+            return Err(out_of_range(
+                "brownout_service_fraction",
+                "greater than 0 and at most 1",
+            ));
+        }
+        if opt.fan_out > 1 {
+            return Err(conflict(
+                "Brownouts are not supported with fan-out",
+                "Leave out either --brownout_queue_length or --fan_out",
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_clients(opt: &Opt) -> Result<(), ConfigError> {
+    if opt.balking_queue_length == Some(0) {
+        return Err(out_of_range("balking_queue_length", "greater than 0"));
+    }
+    if opt.client_connections == Some(0) {
+        return Err(out_of_range("client_connections", "greater than 0"));
+    }
+    if opt.client_queue_timeout == Some(0) {
+        return Err(out_of_range("client_queue_timeout", "greater than 0"));
+    }
+    if opt.network_latency.is_some_and(|l| l <= 0.0) {
+        return Err(out_of_range("network_latency", "greater than 0"));
+    }
+    if opt.retry_after == Some(0) {
+        return Err(out_of_range("retry_after", "greater than 0"));
+    }
+    if opt.patience.is_some_and(|p| p <= 0.0) {
+        return Err(out_of_range("patience", "greater than 0"));
+    }
+    for (parameter, probability) in [
+        ("retry_probability", opt.retry_probability),
+        (
+            "connection_retry_probability",
+            opt.retry_probabilities().rejected,
+        ),
+        (
+            "timeout_retry_probability",
+            opt.retry_probabilities().timed_out,
+        ),
+        (
+            "abandonment_retry_probability",
+            opt.abandonment_retry_probability,
+        ),
+        ("retry_after_compliance", opt.retry_after_compliance),
+    ] {
+        if !(0.0..=1.0).contains(&probability) {
+            return Err(out_of_range(parameter, "between 0 and 1"));
+        }
+    }
+    Ok(())
+}
+
+fn validate_outputs(opt: &Opt) -> Result<(), ConfigError> {
+    if opt.checkpoint_every.is_some() || opt.resume {
+        if opt.checkpoint_every == Some(0) {
+            return Err(out_of_range("checkpoint_every", "at least 1 tick"));
+        }
+        if opt.checkpoint_file.is_none() {
+            let parameter = if opt.resume {
+                "resume"
+            } else {
+                "checkpoint_every"
+            };
+            return Err(ConfigError::Requires {
+                parameter,
+                required: "checkpoint_file",
+            });
+        }
+        if let Some(feature) = checkpoint::unsupported_feature(opt) {
+            return Err(ConfigError::UnsupportedByCheckpoints(feature));
+        }
+    }
+    if opt.collapse_window == 0 {
+        return Err(out_of_range("collapse_window", "greater than 0"));
+    }
+    if !(0.0..=1.0).contains(&opt.collapse_goodput) {
+        return Err(out_of_range("collapse_goodput", "between 0 and 1"));
+    }
+    if opt.retry_storm_window == 0 {
+        return Err(out_of_range("retry_storm_window", "greater than 0"));
+    }
+    if !(0.0..1.0).contains(&opt.retry_storm_share) {
+        return Err(out_of_range(
+            "retry_storm_share",
+            "at least 0 and less than 1",
+        ));
+    }
+    if opt.slo_target <= 0.0 || opt.slo_target > 100.0 {
+        return Err(out_of_range(
+            "slo_target",
+            "a percentage greater than 0 and at most 100",
+        ));
+    }
+    if opt.worker_cost.is_some_and(|c| c < 0.0) {
+        return Err(out_of_range("worker_cost", "at least 0"));
+    }
+    if opt.failure_cost.is_some_and(|c| c < 0.0) {
+        return Err(out_of_range("failure_cost", "at least 0"));
+    }
+    if opt.slo_exit_code && opt.slo_latency.is_none() {
+        return Err(ConfigError::Requires {
+            parameter: "slo_exit_code",
+            required: "slo_latency",
+        });
+    }
+    if opt
+        .fail_if_failure_rate_above
+        .is_some_and(|threshold| !(0.0..100.0).contains(&threshold))
+    {
+        return Err(out_of_range(
+            "fail_if_failure_rate_above",
+            "a percentage of at least 0 and below 100",
+        ));
+    }
+    if opt.alert_command.is_some() && opt.alerts.is_empty() {
+        return Err(ConfigError::Requires {
+            parameter: "alert_command",
+            required: "alert",
+        });
+    }
+    if !opt.headroom_objectives.is_empty()
+        && (opt.request_arrival_rate.is_none()
+            || !opt.arrival_schedule().unwrap_or_default().is_empty())
+    {
+        return Err(conflict(
+            "Headroom can only be reported for a constant arrival rate",
+            "Leave out the arrival schedule, trace, generator or clients",
+        ));
+    }
+    if opt.headroom_ticks == Some(0) {
+        return Err(out_of_range("headroom_ticks", "greater than 0"));
+    }
+    if opt.speed.is_some_and(|speed| speed <= 0.0) {
+        return Err(out_of_range("speed", "greater than 0"));
+    }
+    if opt.tui {
+        if opt.speed.is_some() {
+            return Err(conflict(
+                "The TUI has its own speed",
+                "Set it with --tui_speed instead of --speed",
+            ));
+        }
+        if opt.progress {
+            return Err(conflict(
+                "Progress reporting and the TUI can't be combined",
+                "Leave out --progress, the TUI shows the progress already",
+            ));
+        }
+        let stdout_outputs = [
+            (opt.time_series.as_deref(), "Time series"),
+            (opt.snapshots.as_deref(), "Snapshots"),
+            (opt.event_log.as_deref(), "The event log"),
+        ];
+        if stdout_outputs.iter().any(|(path, _)| *path == Some("-")) {
+            return Err(conflict(
+                "Nothing can be written to stdout while the TUI is showing",
+                "Write the time series, snapshots and event log to files instead",
+            ));
+        }
+        if opt.tui_speed.is_some_and(|speed| speed < 1.0) {
+            return Err(out_of_range("tui_speed", "at least 1 tick per second"));
+        }
+    }
+    if opt.run_forever {
+        if opt.command.is_some() {
+            return Err(conflict(
+                "Only a single run can run forever",
+                "Leave out either --run_forever or the subcommand",
+            ));
+        }
+        if opt.progress || opt.tui {
+            return Err(conflict(
+                "A run that runs forever reports its progress in windows already",
+                "Leave out --progress and --tui",
+            ));
+        }
+        if opt.html_report.is_some() || opt.plot.is_some() {
+            return Err(conflict(
+                "The timeline of a run that runs forever would grow without bound",
+                "Leave out --html_report and --plot",
+            ));
+        }
+        if opt.batching_report
+            || opt.queues.is_some()
+            || !opt.service_time_caps.is_empty()
+            || !opt.headroom_objectives.is_empty()
+        {
+            return Err(conflict(
+                "The reports rerunning the simulation can't rerun one that runs forever",
+                "Leave out --batching_report, --queues, --truncate_service_time and --headroom",
+            ));
+        }
+    }
+    if opt.engine == Engine::Event {
+        if let Some(feature) = eventdriven::unsupported_feature(opt) {
+            return Err(ConfigError::UnsupportedByEngine(feature));
+        }
+    }
+    if opt.snapshot_every == 0 {
+        return Err(out_of_range("snapshot_every", "at least 1 tick"));
+    }
+    if opt.report_every == Some(0) {
+        return Err(out_of_range("report_every", "at least 1 tick"));
+    }
+    Ok(())
+}
+
+fn validate_regions(opt: &Opt) -> Result<(), ConfigError> {
+    if opt.regions.is_empty() {
+        if opt.failover != Failover::None {
+            return Err(ConfigError::Requires {
+                parameter: "failover",
+                required: "region",
+            });
+        }
+        if opt.cross_region_latency > 0 {
+            return Err(ConfigError::Requires {
+                parameter: "cross_region_latency",
+                required: "region",
+            });
+        }
+        return Ok(());
+    }
+    if opt.regions.len() < 2 {
+        return Err(out_of_range(
+            "region",
+            "given at least twice, for two regions or more",
+        ));
+    }
+    for region in opt.regions.iter() {
+        if opt.regions.iter().filter(|r| r.name == region.name).count() > 1 {
+            return Err(ConfigError::Invalid(format!(
+                "Region '{}' is defined more than once. Give every --region a name of its own",
+                region.name
+            )));
+        }
+        if region.workers == 0 {
+            return Err(out_of_range(
+                "region",
+                "given with at least 1 worker, in every region",
+            ));
+        }
+    }
+    if !opt.pools.is_empty() || opt.queues.is_some() {
+        return Err(conflict(
+            "Regions are not supported with bulkhead pools or multiple queues",
+            "Leave out either the --region definitions, or --pool and --queues",
+        ));
+    }
+    if opt.autoscale_max_workers > 0 {
+        return Err(conflict(
+            "Autoscaling is not supported with multiple regions",
+            "Leave out either --autoscale_max_workers or the --region definitions",
+        ));
+    }
+    Ok(())
+}
+
+fn validate_topology(opt: &Opt) -> Result<(), ConfigError> {
+    let classes = opt.classes();
+    for class in classes.iter() {
+        if classes.iter().filter(|c| c.name == class.name).count() > 1 {
+            return Err(ConfigError::Invalid(format!(
+                "Class '{}' is defined more than once. Give every --class a name of its own",
+                class.name
+            )));
+        }
+    }
+    pool::route_classes(&opt.pools(), &classes).map_err(ConfigError::Invalid)?;
+    Quotas::new(&opt.quotas, &classes).map_err(ConfigError::Invalid)?;
+    Shedding::new(&opt.shed_thresholds, &classes).map_err(ConfigError::Invalid)?;
+    retrypolicy::policies(&opt.retry_policies, &classes, opt.retry_probabilities())
+        .map_err(ConfigError::Invalid)?;
+    if !opt.pools.is_empty() && opt.autoscale_max_workers > 0 {
+        return Err(conflict(
+            "Autoscaling is not supported with bulkhead pools",
+            "Leave out either --autoscale_max_workers or the --pool definitions",
+        ));
+    }
+    validate_regions(opt)?;
+    if opt.lifo && opt.discipline != Discipline::Fifo {
+        return Err(conflict(
+            "--lifo is a deprecated alias of --discipline lifo",
+            "Leave out --lifo",
+        ));
+    }
+    if !opt.discipline().is_arrival_order() && !opt.discipline_switches.is_empty() {
+        return Err(conflict(
+            "Only the FIFO and LIFO queues serve the requests by the order they arrived in, and can switch",
+            "Leave out either --discipline_switch, or --discipline edf or siro",
+        ));
+    }
+    Resource::new(
+        opt.resource_slots,
+        opt.resource_queue_size,
+        &opt.resource_classes,
+        &classes,
+    )
+    .map_err(ConfigError::Invalid)?;
+    if opt.keyed() {
+        if opt.keys == 0 {
+            return Err(out_of_range("keys", "greater than 0"));
+        }
+        if opt.key_skew < 0.0 {
+            return Err(out_of_range("key_skew", "at least 0"));
+        }
+    }
+    if opt.cache_hit_ratio.is_some() || opt.cache_size.is_some() {
+        if opt.cache_hit_ratio.is_some() && opt.cache_size.is_some() {
+            return Err(conflict(
+                "The cache either has a hit ratio or a size",
+                "Leave out either --cache_hit_ratio or --cache_size",
+            ));
+        }
+        if opt
+            .cache_hit_ratio
+            .is_some_and(|hit_ratio| !(0.0..=1.0).contains(&hit_ratio))
+        {
+            return Err(out_of_range("cache_hit_ratio", "between 0 and 1"));
+        }
+        if opt.cache_size == Some(0) {
+            return Err(out_of_range("cache_size", "greater than 0"));
+        }
+        if opt.cache_latency == 0 || opt.cache_latency >= opt.request_timeout {
+            return Err(out_of_range(
+                "cache_latency",
+                "at least 1 tick, and below the --timeout",
+            ));
+        }
+    }
+    if let Some(queues) = opt.queues {
+        if !opt.pools.is_empty() {
+            return Err(conflict(
+                "Multiple queues are not supported with bulkhead pools",
+                "Leave out either --queues or the --pool definitions",
+            ));
+        }
+        if queues == 0 || queues > opt.num_workers {
+            return Err(out_of_range(
+                "queues",
+                "between 1 and the number of --workers",
+            ));
+        }
+        if opt.autoscale_max_workers > 0 {
+            return Err(conflict(
+                "Autoscaling is not supported with multiple queues",
+                "Leave out either --autoscale_max_workers or --queues",
+            ));
+        }
+        if let Routing::Choices(choices) = opt.routing {
+            if choices > queues as usize {
+                return Err(out_of_range(
+                    "routing",
+                    "choices:D with at most as many choices as --queues",
+                ));
+            }
+        }
+    }
+    if let Some(straggler) = opt.straggler.as_ref() {
+        let pools = match opt.queues {
+            Some(queues) => opt.pools()[0].split(queues),
+            None if !opt.regions.is_empty() => {
+                let classes = opt.classes();
+                opt.regions.iter().map(|r| r.pool(&classes)).collect()
+            }
+            None => opt.pools(),
+        };
+        if pools.iter().any(|p| straggler.workers() > p.workers) {
+            return Err(out_of_range(
+                "straggler",
+                "on at most as many workers as every pool has",
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    fn opt(args: &str) -> Opt {
+        Opt::from_iter(std::iter::once("queueingsimulator").chain(args.split_whitespace()))
+    }
+
+    #[test]
+    fn lifo_is_a_deprecated_alias_of_the_lifo_discipline() {
+        assert_eq!(opt("-r 0.1 --lifo").discipline(), Discipline::Lifo);
+        assert!(validate(&opt("-r 0.1 --lifo")).is_ok());
+        assert!(validate(&opt("-r 0.1 --lifo --discipline lifo")).is_err());
+        assert!(validate(&opt("-r 0.1 --lifo --discipline edf")).is_err());
+    }
+
+    #[test]
+    fn simulation_time_is_checked_before_the_warmup() {
+        let error = validate(&opt("-r 0.1 --simulation_time 0")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "--simulation_time has to be greater than 0"
+        );
+        let error = validate(&opt("-r 0.1 --simulation_time 10 --warmup_ticks 10")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "--warmup_ticks has to be shorter than the --simulation_time"
+        );
+    }
+
+    #[test]
+    fn io_errors_name_what_failed() {
+        let error = ConfigError::io("write plot", "out/plot.svg", "No such file or directory");
+        assert_eq!(
+            error.to_string(),
+            "Failed to write plot 'out/plot.svg': No such file or directory"
+        );
+    }
+}
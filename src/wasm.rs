@@ -0,0 +1,22 @@
+use crate::server;
+use crate::simulation;
+use serde_json::json;
+use wasm_bindgen::prelude::*;
+
+/// Runs a scenario in the browser, for the interactive page in web/. The scenario is a JSON object of
+/// parameters, refused and validated like the ones sent to the serve subcommand, and the results are the same
+/// JSON as the server answers with. The telemetry is refused too, since it reads the wall clock, which
+/// WebAssembly doesn't have.
+#[wasm_bindgen]
+pub fn simulate(scenario: &str) -> String {
+    let results = server::parse_scenario(scenario.as_bytes())
+        .and_then(|opt| match opt.telemetry {
+            true => Err("Parameter 'telemetry' can't be set in the browser".to_string()),
+            false => Ok(opt),
+        })
+        .map(|opt| server::results(&opt, &simulation::run(&opt)));
+    match results {
+        Ok(results) => results.to_string(),
+        Err(error) => json!({ "error": error }).to_string(),
+    }
+}
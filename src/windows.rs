@@ -0,0 +1,88 @@
+use crate::histogram::Histogram;
+use crate::simulation::Stats;
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use signal_hook::{consts::SIGINT, flag};
+use std::io;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+/// Metrics of a run that never ends, printed for every window of ticks as it goes, since there is no end to
+/// print a summary at. Every window stands on its own, unlike the cumulative summary, so that the latest state
+/// of the system doesn't get drowned in its history.
+#[derive(Serialize, Deserialize)]
+pub struct WindowedMetrics {
+    every: u64,
+    /// First tick of the current window, and the longest the queue got during it.
+    start: u64,
+    max_queue_len: usize,
+    /// Cumulative requests, failures and latencies at the start of the current window.
+    last_requests: u64,
+    last_failures: u64,
+    last_latency: Histogram,
+}
+
+impl WindowedMetrics {
+    pub fn new(every: u64) -> WindowedMetrics {
+        WindowedMetrics {
+            every,
+            start: 0,
+            max_queue_len: 0,
+            last_requests: 0,
+            last_failures: 0,
+            last_latency: Histogram::new(),
+        }
+    }
+
+    /// Records the state of the system at the end of the specified tick, and prints the metrics of the window
+    /// at the end of every window.
+    pub fn tick(&mut self, tick: u64, queue_len: usize, stats: &Stats) {
+        self.max_queue_len = self.max_queue_len.max(queue_len);
+        if (tick + 1).is_multiple_of(self.every) {
+            self.print_window(tick, stats);
+        }
+    }
+
+    /// Prints the metrics of the last window, if the run ended in the middle of it.
+    pub fn finish(&mut self, last_tick: u64, stats: &Stats) {
+        if last_tick >= self.start {
+            self.print_window(last_tick, stats);
+        }
+    }
+
+    fn print_window(&mut self, last_tick: u64, stats: &Stats) {
+        let requests = stats.total_requests - self.last_requests;
+        let failures = stats.failed_requests - self.last_failures;
+        let latency = stats.latency.since(&self.last_latency);
+        println!(
+            "Ticks {}-{}: {} requests, failure rate: {:.2}%, max queue length: {}, latency: {}",
+            self.start,
+            last_tick,
+            requests,
+            failures as f64 / requests.max(1) as f64 * 100.0,
+            self.max_queue_len,
+            latency.format_percentiles()
+        );
+        self.start = last_tick + 1;
+        self.max_queue_len = 0;
+        self.last_requests = stats.total_requests;
+        self.last_failures = stats.failed_requests;
+        self.last_latency = stats.latency.clone();
+    }
+}
+
+/// Flag raised by the first interrupt (Ctrl-C), for a run to stop at the end of its tick and still print its
+/// summary. A second interrupt exits right away, in case the run doesn't stop.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn on_interrupt() -> io::Result<Arc<AtomicBool>> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&interrupted))?;
+    flag::register(SIGINT, Arc::clone(&interrupted))?;
+    Ok(interrupted)
+}
+
+/// A flag nothing raises, since WebAssembly has no interrupts to handle.
+#[cfg(target_arch = "wasm32")]
+pub fn on_interrupt() -> io::Result<Arc<AtomicBool>> {
+    Ok(Arc::new(AtomicBool::new(false)))
+}
@@ -0,0 +1,151 @@
+use crate::queue::QueueDiscipline;
+use crate::request::Request;
+use crate::utilization::WorkerTime;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct Worker {
+    current_request: Option<Request>,
+    startup_ticks: u32,
+    /// Whether the worker was killed, and does nothing until it's restored.
+    killed: bool,
+    time: WorkerTime,
+}
+
+impl Worker {
+    pub fn new() -> Worker {
+        Worker::starting(0)
+    }
+
+    /// New worker that will only start taking requests after the specified number of ticks.
+    pub fn starting(startup_ticks: u32) -> Worker {
+        Worker {
+            current_request: None,
+            startup_ticks,
+            killed: false,
+            time: WorkerTime::default(),
+        }
+    }
+
+    /// Spends the specified tick. If there is current request, works on it. If there isn't one, tries
+    /// to pick up a new request from the queue, skipping the ones over their queue-wait budget into `expired`,
+    /// along with the ones whose clients already gave up, if `cull_timed_out`.
+    ///
+    /// Returns previous request, if it was finished on this tick.
+    pub fn tick(
+        &mut self,
+        tick: u64,
+        queue: &mut dyn QueueDiscipline,
+        cull_timed_out: bool,
+        query_speed: f64,
+        expired: &mut Vec<Request>,
+    ) -> Option<Request> {
+        if self.killed {
+            return None;
+        }
+        if self.startup_ticks > 0 {
+            // Still starting up, so can't do any work yet.
+            self.startup_ticks -= 1;
+            return None;
+        }
+
+        self.time.available += 1.0;
+        if self.current_request.is_some() {
+            self.time.busy += 1.0;
+        }
+        let current_option = &mut self.current_request;
+
+        if let Some(current) = current_option {
+            current.working_tick(query_speed);
+            if current.is_done() {
+                return self.current_request.take();
+            }
+        } else {
+            // No need to tick here, because that request was already ticked while it was in the queue.
+            loop {
+                match queue.pop() {
+                    Some(request)
+                        if request.is_over_queue_wait_budget(tick)
+                            || (cull_timed_out && request.is_timed_out(tick)) =>
+                    {
+                        expired.push(request)
+                    }
+                    next => {
+                        self.current_request = next;
+                        break;
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Spends one tick frozen, e.g. in a garbage collection pause. The time counts as available, and as busy
+    /// if the worker holds a request, since the request is delayed all the same.
+    pub fn stalled_tick(&mut self) {
+        if self.killed {
+            return;
+        }
+        if self.startup_ticks > 0 {
+            self.startup_ticks -= 1;
+            return;
+        }
+        self.time.available += 1.0;
+        if self.current_request.is_some() {
+            self.time.busy += 1.0;
+        }
+    }
+
+    /// Time the worker spent processing requests since it started, or since the last reset.
+    pub fn time(&self) -> WorkerTime {
+        self.time
+    }
+
+    pub fn reset_time(&mut self) {
+        self.time = WorkerTime::default();
+    }
+
+    pub fn is_free(&self) -> bool {
+        !self.killed && self.startup_ticks == 0 && self.current_request.is_none()
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed
+    }
+
+    /// Kills the worker, which stops working until restored. Returns the request it was processing, if any.
+    pub fn kill(&mut self) -> Option<Request> {
+        self.killed = true;
+        self.current_request.take()
+    }
+
+    /// Restores a killed worker, which takes the specified number of ticks to start again.
+    pub fn restore(&mut self, startup_ticks: u32) {
+        self.killed = false;
+        self.startup_ticks = startup_ticks;
+    }
+
+    pub fn current_request(&self) -> Option<&Request> {
+        self.current_request.as_ref()
+    }
+
+    pub fn current_request_mut(&mut self) -> Option<&mut Request> {
+        self.current_request.as_mut()
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.current_request.is_some()
+    }
+
+    /// Whether the worker is waiting for the database query of its current request.
+    pub fn is_querying(&self) -> bool {
+        self.current_request
+            .as_ref()
+            .is_some_and(Request::is_querying)
+    }
+
+    pub fn take(&mut self, request: Request) {
+        self.current_request = Some(request);
+    }
+}